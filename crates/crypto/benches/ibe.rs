@@ -0,0 +1,151 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use crypto::ibe::{
+    decrypt_batched_deterministic_with_gid, decrypt_deterministic, encrypt_batched_deterministic,
+    extract_with_gid, generate_key_pair, hash_id, verify_user_secret_key_with_gid,
+    verify_user_secret_keys_batched_with_gid, Randomness,
+};
+use crypto::{
+    create_full_id, seal_decrypt, seal_encrypt, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys,
+    ObjectID, KEY_SIZE,
+};
+use fastcrypto::groups::Scalar;
+use rand::thread_rng;
+
+/// Compare decrypting all shares one by one, rehashing the id every time, with the batched API.
+fn decrypt_all_shares(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decrypt_all_shares");
+    let mut rng = thread_rng();
+    let id = create_full_id(&ObjectID::random(), b"id");
+
+    for n in [3, 5, 10] {
+        let public_keys = (0..n)
+            .map(|_| generate_key_pair(&mut rng).1)
+            .collect::<Vec<_>>();
+        let infos = (0..n)
+            .map(|i| (ObjectID::random(), i as u8))
+            .collect::<Vec<_>>();
+        let plaintexts = vec![[0u8; KEY_SIZE]; n];
+        let randomness = Randomness::rand(&mut rng);
+        let (_, ciphertexts) =
+            encrypt_batched_deterministic(&randomness, &plaintexts, &public_keys, &id, &infos)
+                .unwrap();
+
+        group.bench_with_input(BenchmarkId::new("individual", n), &n, |b, _| {
+            b.iter(|| {
+                ciphertexts
+                    .iter()
+                    .zip(&public_keys)
+                    .zip(&infos)
+                    .map(|((c, pk), info)| {
+                        decrypt_deterministic(&randomness, c, pk, &id, info).unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batched", n), &n, |b, _| {
+            b.iter(|| {
+                decrypt_batched_deterministic_with_gid(
+                    &randomness,
+                    &ciphertexts,
+                    &public_keys,
+                    &hash_id(&id),
+                    &infos,
+                )
+                .unwrap()
+            })
+        });
+    }
+}
+
+/// Compare verifying user secret keys for the same id one by one with the batched multi-pairing check.
+fn verify_user_secret_keys(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_user_secret_keys");
+    let mut rng = thread_rng();
+    let gid = hash_id(&create_full_id(&ObjectID::random(), b"id"));
+
+    for n in [3, 5, 10] {
+        let keypairs = (0..n)
+            .map(|_| generate_key_pair(&mut rng))
+            .collect::<Vec<_>>();
+        let user_secret_keys = keypairs
+            .iter()
+            .map(|(mk, _)| extract_with_gid(mk, &gid))
+            .collect::<Vec<_>>();
+        let user_secret_keys = user_secret_keys.iter().collect::<Vec<_>>();
+        let public_keys = keypairs.iter().map(|(_, pk)| pk).collect::<Vec<_>>();
+
+        group.bench_with_input(BenchmarkId::new("individual", n), &n, |b, _| {
+            b.iter(|| {
+                user_secret_keys
+                    .iter()
+                    .zip(&public_keys)
+                    .all(|(usk, pk)| verify_user_secret_key_with_gid(usk, &gid, pk).is_ok())
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("batched", n), &n, |b, _| {
+            b.iter(|| {
+                verify_user_secret_keys_batched_with_gid(&user_secret_keys, &gid, &public_keys)
+                    .unwrap()
+            })
+        });
+    }
+}
+
+/// Decrypt an encrypted object with and without the share consistency check.
+fn seal_decrypt_with_consistency_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("seal_decrypt");
+    let mut rng = thread_rng();
+    let package_id = ObjectID::random();
+    let id = b"id".to_vec();
+    let full_id = create_full_id(&package_id, &id);
+
+    let keypairs = (0..5)
+        .map(|_| generate_key_pair(&mut rng))
+        .collect::<Vec<_>>();
+    let services = keypairs
+        .iter()
+        .map(|_| ObjectID::random())
+        .collect::<Vec<_>>();
+    let public_keys =
+        IBEPublicKeys::BonehFranklinBLS12381(keypairs.iter().map(|(_, pk)| *pk).collect());
+    let (encrypted, _) = seal_encrypt(
+        package_id,
+        id,
+        services.clone(),
+        &public_keys,
+        3,
+        EncryptionInput::Aes256Gcm {
+            data: vec![0u8; 1024],
+            aad: None,
+        },
+    )
+    .unwrap();
+    let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+        services
+            .into_iter()
+            .zip(&keypairs)
+            .take(3)
+            .map(|(s, (mk, _))| (s, crypto::ibe::extract(mk, &full_id)))
+            .collect(),
+    );
+
+    group.bench_function("without_check", |b| {
+        b.iter(|| seal_decrypt(&encrypted, &user_secret_keys, None).unwrap())
+    });
+    group.bench_function("with_check", |b| {
+        b.iter(|| seal_decrypt(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    decrypt_all_shares,
+    verify_user_secret_keys,
+    seal_decrypt_with_consistency_check
+);
+criterion_main!(benches);
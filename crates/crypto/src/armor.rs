@@ -0,0 +1,158 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! ASCII armor for [EncryptedObject]s, suitable for pasting into emails, tickets and config files.
+//!
+//! The armor is modelled after OpenPGP (RFC 4880, section 6): The BCS serialization of the object is base64
+//! encoded, split into lines of [LINE_LENGTH] characters and followed by a line with `=` and the base64
+//! encoding of the CRC-24 checksum of the serialization:
+//! ```text
+//! -----BEGIN SEAL ENCRYPTED OBJECT-----
+//!
+//! AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABAECAwQDAAAAAAAAAAAA
+//! ...
+//! =njUN
+//! -----END SEAL ENCRYPTED OBJECT-----
+//! ```
+
+use crate::prelude::*;
+use crate::EncryptedObject;
+use fastcrypto::encoding::{Base64, Encoding};
+use fastcrypto::error::FastCryptoError::{GeneralError, InvalidInput};
+use fastcrypto::error::FastCryptoResult;
+
+pub const ARMOR_HEADER: &str = "-----BEGIN SEAL ENCRYPTED OBJECT-----";
+pub const ARMOR_FOOTER: &str = "-----END SEAL ENCRYPTED OBJECT-----";
+
+/// The number of base64 characters per line. This is a multiple of 4, so no line but the last can contain padding.
+pub const LINE_LENGTH: usize = 64;
+
+const CRC24_INIT: u32 = 0xB704CE;
+const CRC24_POLY: u32 = 0x1864CFB;
+
+impl EncryptedObject {
+    /// Encode this object as ASCII armor.
+    pub fn to_armor(&self) -> FastCryptoResult<String> {
+        let bytes = bcs::to_bytes(self).map_err(|_| InvalidInput)?;
+        let body = Base64::encode(&bytes);
+        let mut armor = format!("{}\n\n", ARMOR_HEADER);
+        // The base64 alphabet is ASCII, so splitting into byte chunks gives valid strings.
+        for line in body.as_bytes().chunks(LINE_LENGTH) {
+            armor.push_str(core::str::from_utf8(line).expect("Base64 is ASCII"));
+            armor.push('\n');
+        }
+        armor.push('=');
+        armor.push_str(&Base64::encode(&crc24(&bytes).to_be_bytes()[1..]));
+        armor.push('\n');
+        armor.push_str(ARMOR_FOOTER);
+        Ok(armor)
+    }
+
+    /// Decode an object from ASCII armor. Whitespace around lines and text before the header and after the footer
+    /// is ignored. Fails if the checksum is missing or does not match.
+    pub fn from_armor(armor: &str) -> FastCryptoResult<Self> {
+        let mut lines = armor
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| *line != ARMOR_HEADER)
+            .skip(1);
+
+        let mut body = String::new();
+        let mut checksum = None;
+        loop {
+            match lines.next() {
+                None => return Err(GeneralError("Missing armor footer".to_string())),
+                Some(ARMOR_FOOTER) => break,
+                Some("") => continue,
+                Some(_) if checksum.is_some() => {
+                    return Err(GeneralError("Unexpected line after checksum".to_string()))
+                }
+                Some(line) => match line.strip_prefix('=') {
+                    Some(encoded) => checksum = Some(encoded),
+                    None => body.push_str(line),
+                },
+            }
+        }
+
+        let bytes = Base64::decode(&body)?;
+        let checksum = checksum.ok_or(GeneralError("Missing armor checksum".to_string()))?;
+        if Base64::decode(checksum)? != crc24(&bytes).to_be_bytes()[1..] {
+            return Err(GeneralError("Invalid armor checksum".to_string()));
+        }
+        bcs::from_bytes(&bytes).map_err(|_| InvalidInput)
+    }
+}
+
+/// The CRC-24 checksum used by OpenPGP (RFC 4880, section 6.1).
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = CRC24_INIT;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= CRC24_POLY;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ibe, seal_encrypt, EncryptionInput, IBEPublicKeys, ObjectID};
+
+    #[test]
+    fn test_crc24() {
+        // Check value from the CRC catalogue for CRC-24/OPENPGP.
+        assert_eq!(crc24(b"123456789"), 0x21CF02);
+        assert_eq!(crc24(b""), CRC24_INIT);
+    }
+
+    #[test]
+    fn test_armor_round_trip() {
+        let (_, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let (object, _) = seal_encrypt(
+            ObjectID::random(),
+            vec![1, 2, 3],
+            vec![ObjectID::random()],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]),
+            1,
+            EncryptionInput::Aes256Gcm {
+                data: vec![7u8; 100],
+                aad: None,
+            },
+        )
+        .unwrap();
+        let bytes = bcs::to_bytes(&object).unwrap();
+
+        let armor = object.to_armor().unwrap();
+        assert!(armor.starts_with(ARMOR_HEADER));
+        assert!(armor.ends_with(ARMOR_FOOTER));
+        assert!(armor.lines().all(|line| line.len() <= LINE_LENGTH));
+        let parsed = EncryptedObject::from_armor(&armor).unwrap();
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        // Surrounding text, indentation and CRLF line endings are accepted.
+        let pasted = format!(
+            "Hi,\r\nplease find the object below.\r\n\r\n{}\r\n\r\nThanks",
+            armor.replace('\n', "\r\n  ")
+        );
+        let parsed = EncryptedObject::from_armor(&pasted).unwrap();
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        // A modified body is detected by the checksum.
+        let line = armor.lines().nth(2).unwrap();
+        let mut modified_line = line.to_string();
+        let c = modified_line.remove(0);
+        modified_line.insert(0, if c == 'A' { 'B' } else { 'A' });
+        assert!(EncryptedObject::from_armor(&armor.replace(line, &modified_line)).is_err());
+
+        // Missing checksum or footer.
+        let checksum_line = armor.lines().rev().nth(1).unwrap();
+        assert!(checksum_line.starts_with('='));
+        assert!(EncryptedObject::from_armor(&armor.replace(checksum_line, "")).is_err());
+        assert!(EncryptedObject::from_armor(&armor.replace(ARMOR_FOOTER, "")).is_err());
+    }
+}
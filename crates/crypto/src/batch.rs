@@ -0,0 +1,225 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Batch versions of [seal_encrypt](crate::seal_encrypt) and [seal_decrypt](crate::seal_decrypt)
+//! which process many objects in parallel.
+//!
+//! The key servers and public keys are shared by all objects in a batch and the hash-to-curve of
+//! a full id is only computed once, even if it is used by several objects. The results are
+//! returned in the same order as the inputs, and an error for one object does not affect the
+//! others.
+
+use crate::{
+    create_full_id, ibe, seal_decrypt_with_gid, seal_encrypt_with_gid, EncryptedObject,
+    EncryptionInput, IBEPublicKeys, IBEUserSecretKeys, ObjectID, SymmetricKey,
+    DEFAULT_MAX_DECOMPRESSED_SIZE,
+};
+use fastcrypto::error::FastCryptoResult;
+use fastcrypto::groups::bls12381::G1Element;
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// A single message to encrypt as part of a batch.
+#[derive(Clone, Debug)]
+pub struct BatchEncryptionInput {
+    pub package_id: ObjectID,
+    pub id: Vec<u8>,
+    pub encryption_input: EncryptionInput,
+}
+
+/// Encrypt a batch of messages in parallel using the same key servers, public keys and threshold.
+///
+/// @param key_servers The seal key services to use for all encryptions.
+/// @param public_keys The public keys of the key servers.
+/// @param threshold The threshold for the TSS.
+/// @param inputs The messages to encrypt.
+/// @return For each input, in the same order, the encrypted object and the derived symmetric key or an error.
+pub fn seal_encrypt_batch(
+    key_servers: &[ObjectID],
+    public_keys: &IBEPublicKeys,
+    threshold: u8,
+    inputs: Vec<BatchEncryptionInput>,
+) -> Vec<FastCryptoResult<(EncryptedObject, SymmetricKey)>> {
+    let gids = hash_full_ids(inputs.iter().map(|input| (&input.package_id, &input.id)));
+    inputs
+        .into_par_iter()
+        .map(|input| {
+            let gid = &gids[&create_full_id(&input.package_id, &input.id)];
+            seal_encrypt_with_gid(
+                input.package_id,
+                input.id,
+                key_servers.to_vec(),
+                public_keys,
+                threshold,
+                input.encryption_input,
+                None,
+                None,
+                gid,
+                &mut rand::thread_rng(),
+            )
+        })
+        .collect()
+}
+
+/// Decrypt a batch of encrypted objects in parallel.
+///
+/// @param encrypted_objects The objects to decrypt, each with the user secret keys to use for it.
+/// @param public_keys The public keys of the key servers. If given, they are used to check the consistency of the
+///                    shares of every object and must be given in the same order as the services in each object.
+/// @return For each encrypted object, in the same order, the decrypted message or an error.
+pub fn seal_decrypt_batch(
+    encrypted_objects: &[(&EncryptedObject, &IBEUserSecretKeys)],
+    public_keys: Option<&IBEPublicKeys>,
+) -> Vec<FastCryptoResult<Vec<u8>>> {
+    let gids = hash_full_ids(
+        encrypted_objects
+            .iter()
+            .map(|(object, _)| (&object.package_id, &object.id)),
+    );
+    encrypted_objects
+        .par_iter()
+        .map(|(object, user_secret_keys)| {
+            let gid = &gids[&create_full_id(&object.package_id, &object.id)];
+            seal_decrypt_with_gid(
+                object,
+                user_secret_keys,
+                public_keys,
+                gid,
+                DEFAULT_MAX_DECOMPRESSED_SIZE,
+            )
+        })
+        .collect()
+}
+
+/// Hash all distinct full ids to G1 in parallel.
+fn hash_full_ids<'a>(
+    ids: impl Iterator<Item = (&'a ObjectID, &'a Vec<u8>)>,
+) -> HashMap<Vec<u8>, G1Element> {
+    let mut full_ids = ids
+        .map(|(package_id, id)| create_full_id(package_id, id))
+        .collect::<Vec<_>>();
+    full_ids.sort();
+    full_ids.dedup();
+    full_ids
+        .into_par_iter()
+        .map(|full_id| {
+            let gid = ibe::hash_id(&full_id);
+            (full_id, gid)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seal_decrypt;
+    use fastcrypto::error::FastCryptoError::InvalidInput;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_batch_round_trip() {
+        let mut rng = rand::thread_rng();
+        let keypairs = (0..3)
+            .map(|_| ibe::generate_key_pair(&mut rng))
+            .collect_vec();
+        let services = keypairs.iter().map(|_| ObjectID::random()).collect_vec();
+        let public_keys =
+            IBEPublicKeys::BonehFranklinBLS12381(keypairs.iter().map(|(_, pk)| *pk).collect_vec());
+
+        // Two packages with repeated ids to exercise the hash cache.
+        let package_ids = [ObjectID::random(), ObjectID::random()];
+        let inputs = (0..8u8)
+            .map(|i| BatchEncryptionInput {
+                package_id: package_ids[(i % 2) as usize],
+                id: vec![i % 3],
+                encryption_input: EncryptionInput::Aes256Gcm {
+                    data: vec![i; 10],
+                    aad: None,
+                },
+            })
+            .collect_vec();
+
+        let encrypted = seal_encrypt_batch(&services, &public_keys, 2, inputs)
+            .into_iter()
+            .map(|r| r.unwrap().0)
+            .collect_vec();
+
+        let user_secret_keys = encrypted
+            .iter()
+            .map(|object| {
+                let full_id = create_full_id(&object.package_id, &object.id);
+                IBEUserSecretKeys::BonehFranklinBLS12381(
+                    services
+                        .iter()
+                        .zip(&keypairs)
+                        .map(|(s, (mk, _))| (*s, ibe::extract(mk, &full_id)))
+                        .collect(),
+                )
+            })
+            .collect_vec();
+        let batch = encrypted.iter().zip(&user_secret_keys).collect_vec();
+
+        let decrypted = seal_decrypt_batch(&batch, Some(&public_keys));
+        for (i, (result, (object, usks))) in decrypted.into_iter().zip(&batch).enumerate() {
+            let expected = vec![i as u8; 10];
+            assert_eq!(result.unwrap(), expected);
+            assert_eq!(seal_decrypt(object, usks, None).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_batch_errors_are_per_item() {
+        let mut rng = rand::thread_rng();
+        let (master_key, public_key) = ibe::generate_key_pair(&mut rng);
+        let service = ObjectID::random();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let package_id = ObjectID::random();
+
+        let inputs = (0..3u8)
+            .map(|i| BatchEncryptionInput {
+                package_id,
+                id: vec![i],
+                encryption_input: EncryptionInput::Hmac256Ctr {
+                    data: vec![i],
+                    aad: None,
+                },
+            })
+            .collect_vec();
+        let encrypted = seal_encrypt_batch(&[service], &public_keys, 1, inputs)
+            .into_iter()
+            .map(|r| r.unwrap().0)
+            .collect_vec();
+
+        // Only give a valid key for the first and last object.
+        let keys = encrypted
+            .iter()
+            .enumerate()
+            .map(|(i, object)| {
+                let id = if i == 1 { vec![42] } else { object.id.clone() };
+                IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([(
+                    service,
+                    ibe::extract(&master_key, &create_full_id(&package_id, &id)),
+                )]))
+            })
+            .collect_vec();
+        let batch = encrypted.iter().zip(&keys).collect_vec();
+        let results = seal_decrypt_batch(&batch, None);
+        assert_eq!(results[0].as_ref().unwrap(), &vec![0]);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &vec![2]);
+
+        // An invalid threshold fails every item.
+        let results = seal_encrypt_batch(
+            &[service],
+            &public_keys,
+            2,
+            vec![BatchEncryptionInput {
+                package_id,
+                id: vec![],
+                encryption_input: EncryptionInput::Plain,
+            }],
+        );
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Err(InvalidInput)));
+    }
+}
@@ -0,0 +1,348 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! An [EncryptedBundle] protects many payloads under a single id with one key encapsulation.
+//!
+//! The header is an [EncryptedObject] in `Plain` mode, so the shares are encrypted exactly as in
+//! [seal_encrypt] and the bundle key is what [seal_decrypt] returns for it. Each entry is encrypted
+//! with a DEM under a key derived from the bundle key, a random salt and the label of the entry, so
+//! entries can be decrypted independently and anyone holding the bundle key can add entries later.
+//! The salt makes sure that no two entries are encrypted under the same key, even if a label is
+//! reused after its entry has been removed, since AES-GCM uses a fixed nonce. Decrypting a
+//! bundle needs a single round of key fetching for the id of the header.
+
+use crate::prelude::*;
+#[cfg(any(feature = "std", test))]
+use crate::seal_encrypt;
+use crate::utils::generate_random_bytes;
+use crate::{
+    decrypt_with_dem, encrypt_with_dem, seal_decrypt, Ciphertext, EncryptedObject, EncryptionInput,
+    IBEPublicKeys, IBEUserSecretKeys, ObjectID, SymmetricKey, KEY_SIZE,
+};
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+use fastcrypto::hmac::{hmac_sha3_256, HmacKey};
+use fastcrypto::traits::{AllowedRng, ToFromBytes};
+#[cfg(any(feature = "std", test))]
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
+
+/// The domain separation tag for deriving entry keys from a bundle key.
+pub const BUNDLE_ENTRY_DST: &[u8] = b"SUI-SEAL-BUNDLE-ENTRY-00";
+
+/// The length of the random salt of each entry.
+pub const BUNDLE_ENTRY_SALT_LENGTH: usize = 32;
+
+/// A header shared by all entries and a list of entries, each encrypted under its own key.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EncryptedBundle {
+    pub version: u8,
+    pub header: EncryptedObject,
+    pub entries: Vec<BundleEntry>,
+}
+
+/// A single payload of a bundle. Labels are unique within a bundle.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub label: Vec<u8>,
+    pub salt: [u8; BUNDLE_ENTRY_SALT_LENGTH],
+    pub ciphertext: Ciphertext,
+}
+
+/// Encrypt a set of labelled payloads as a bundle for the given id.
+///
+/// @param package_id The package id.
+/// @param id The id.
+/// @param key_servers The seal key services to use for the encryption.
+/// @param public_keys The public keys of the key servers.
+/// @param threshold The threshold for the TSS.
+/// @param entries The labels and payloads. `Plain` mode is not allowed for entries.
+/// @return The bundle and the bundle key which can be used to add more entries. The key is zeroized when dropped.
+#[cfg(any(feature = "std", test))]
+pub fn seal_encrypt_bundle(
+    package_id: ObjectID,
+    id: Vec<u8>,
+    key_servers: Vec<ObjectID>,
+    public_keys: &IBEPublicKeys,
+    threshold: u8,
+    entries: Vec<(Vec<u8>, EncryptionInput)>,
+) -> FastCryptoResult<(EncryptedBundle, SymmetricKey)> {
+    let (header, bundle_key) = seal_encrypt(
+        package_id,
+        id,
+        key_servers,
+        public_keys,
+        threshold,
+        EncryptionInput::Plain,
+    )?;
+    let mut bundle = EncryptedBundle {
+        version: 0,
+        header,
+        entries: Vec::with_capacity(entries.len()),
+    };
+    let mut rng = thread_rng();
+    for (label, encryption_input) in entries {
+        bundle.add_entry(&bundle_key, label, encryption_input, &mut rng)?;
+    }
+    Ok((bundle, bundle_key))
+}
+
+/// Decrypt the bundle key from the header of a bundle. See [seal_decrypt] for the parameters.
+pub fn seal_decrypt_bundle_key(
+    bundle: &EncryptedBundle,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: Option<&IBEPublicKeys>,
+) -> FastCryptoResult<SymmetricKey> {
+    bundle.check_version()?;
+    let key: [u8; KEY_SIZE] = seal_decrypt(&bundle.header, user_secret_keys, public_keys)?
+        .try_into()
+        .map_err(|_| InvalidInput)?;
+    Ok(key.into())
+}
+
+impl EncryptedBundle {
+    /// Encrypt a new entry under the bundle key with a fresh salt. Fails if the label is already used or if `Plain`
+    /// mode is given.
+    pub fn add_entry<R: AllowedRng>(
+        &mut self,
+        bundle_key: &SymmetricKey,
+        label: Vec<u8>,
+        encryption_input: EncryptionInput,
+        rng: &mut R,
+    ) -> FastCryptoResult<()> {
+        self.check_version()?;
+        if matches!(encryption_input, EncryptionInput::Plain) || self.entry(&label).is_some() {
+            return Err(InvalidInput);
+        }
+        let salt = generate_random_bytes(rng);
+        let ciphertext = encrypt_with_dem(
+            encryption_input,
+            &derive_entry_key(bundle_key, &salt, &label),
+        )?;
+        self.entries.push(BundleEntry {
+            label,
+            salt,
+            ciphertext,
+        });
+        Ok(())
+    }
+
+    /// Decrypt the entry with the given label.
+    pub fn decrypt_entry(
+        &self,
+        bundle_key: &SymmetricKey,
+        label: &[u8],
+    ) -> FastCryptoResult<Vec<u8>> {
+        self.check_version()?;
+        let entry = self.entry(label).ok_or(InvalidInput)?;
+        if matches!(entry.ciphertext, Ciphertext::Plain) {
+            return Err(InvalidInput);
+        }
+        decrypt_with_dem(
+            &entry.ciphertext,
+            &derive_entry_key(bundle_key, &entry.salt, label),
+        )
+    }
+
+    /// The labels of all entries in the order they were added.
+    pub fn labels(&self) -> impl Iterator<Item = &[u8]> {
+        self.entries.iter().map(|entry| entry.label.as_slice())
+    }
+
+    fn entry(&self, label: &[u8]) -> Option<&BundleEntry> {
+        self.entries.iter().find(|entry| entry.label == label)
+    }
+
+    fn check_version(&self) -> FastCryptoResult<()> {
+        if self.version != 0 || !matches!(self.header.ciphertext, Ciphertext::Plain) {
+            return Err(InvalidInput);
+        }
+        Ok(())
+    }
+}
+
+/// Derive the key for an entry from the bundle key and the salt and label of the entry.
+fn derive_entry_key(
+    bundle_key: &SymmetricKey,
+    salt: &[u8; BUNDLE_ENTRY_SALT_LENGTH],
+    label: &[u8],
+) -> SymmetricKey {
+    let hmac_key = HmacKey::from_bytes(bundle_key.as_bytes()).expect("Fixed length");
+    let mut input = BUNDLE_ENTRY_DST.to_vec();
+    input.extend_from_slice(salt); // 32 bytes
+    input.extend_from_slice(label);
+    hmac_sha3_256(&hmac_key, &input).digest.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_full_id, ibe};
+    use itertools::Itertools;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let mut rng = rand::thread_rng();
+        let keypairs = (0..3)
+            .map(|_| ibe::generate_key_pair(&mut rng))
+            .collect_vec();
+        let services = keypairs.iter().map(|_| ObjectID::random()).collect_vec();
+        let public_keys =
+            IBEPublicKeys::BonehFranklinBLS12381(keypairs.iter().map(|(_, pk)| *pk).collect_vec());
+        let package_id = ObjectID::random();
+        let id = vec![1, 2, 3];
+
+        let (bundle, bundle_key) = seal_encrypt_bundle(
+            package_id,
+            id.clone(),
+            services.clone(),
+            &public_keys,
+            2,
+            vec![
+                (
+                    b"a".to_vec(),
+                    EncryptionInput::Aes256Gcm {
+                        data: b"first".to_vec(),
+                        aad: None,
+                    },
+                ),
+                (
+                    b"b".to_vec(),
+                    EncryptionInput::Hmac256Ctr {
+                        data: b"second".to_vec(),
+                        aad: Some(b"aad".to_vec()),
+                    },
+                ),
+            ],
+        )
+        .unwrap();
+
+        // Add an entry later using only the bundle key, and round trip through BCS.
+        let mut bundle: EncryptedBundle =
+            bcs::from_bytes(&bcs::to_bytes(&bundle).unwrap()).unwrap();
+        bundle
+            .add_entry(
+                &bundle_key,
+                b"c".to_vec(),
+                EncryptionInput::Aes256Gcm {
+                    data: b"third".to_vec(),
+                    aad: None,
+                },
+                &mut rng,
+            )
+            .unwrap();
+        assert!(bundle
+            .add_entry(&bundle_key, b"a".to_vec(), EncryptionInput::Plain, &mut rng)
+            .is_err());
+        assert_eq!(
+            bundle.labels().collect_vec(),
+            vec![b"a".as_slice(), b"b".as_slice(), b"c".as_slice()]
+        );
+
+        let full_id = create_full_id(&package_id, &id);
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+            services
+                .into_iter()
+                .zip(keypairs)
+                .take(2)
+                .map(|(s, (mk, _))| (s, ibe::extract(&mk, &full_id)))
+                .collect(),
+        );
+        let decrypted_key =
+            seal_decrypt_bundle_key(&bundle, &user_secret_keys, Some(&public_keys)).unwrap();
+        assert_eq!(decrypted_key, bundle_key);
+
+        assert_eq!(
+            bundle.decrypt_entry(&decrypted_key, b"a").unwrap(),
+            b"first"
+        );
+        assert_eq!(
+            bundle.decrypt_entry(&decrypted_key, b"b").unwrap(),
+            b"second"
+        );
+        assert_eq!(
+            bundle.decrypt_entry(&decrypted_key, b"c").unwrap(),
+            b"third"
+        );
+        assert!(bundle.decrypt_entry(&decrypted_key, b"d").is_err());
+    }
+
+    #[test]
+    fn test_entries_are_bound_to_labels() {
+        let bundle_key = SymmetricKey::from([7u8; KEY_SIZE]);
+        let salt = [1u8; BUNDLE_ENTRY_SALT_LENGTH];
+        assert_ne!(
+            derive_entry_key(&bundle_key, &salt, b"a"),
+            derive_entry_key(&bundle_key, &salt, b"b")
+        );
+
+        let (_, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let (mut bundle, bundle_key) = seal_encrypt_bundle(
+            ObjectID::random(),
+            vec![],
+            vec![ObjectID::random()],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]),
+            1,
+            vec![
+                (
+                    b"a".to_vec(),
+                    EncryptionInput::Aes256Gcm {
+                        data: b"first".to_vec(),
+                        aad: None,
+                    },
+                ),
+                (
+                    b"b".to_vec(),
+                    EncryptionInput::Aes256Gcm {
+                        data: b"second".to_vec(),
+                        aad: None,
+                    },
+                ),
+            ],
+        )
+        .unwrap();
+
+        // Swapping the labels of two entries makes both fail to decrypt.
+        let label = bundle.entries[0].label.clone();
+        bundle.entries[0].label = bundle.entries[1].label.clone();
+        bundle.entries[1].label = label;
+        assert!(bundle.decrypt_entry(&bundle_key, b"a").is_err());
+        assert!(bundle.decrypt_entry(&bundle_key, b"b").is_err());
+    }
+
+    #[test]
+    fn test_reused_labels_get_fresh_keys() {
+        let mut rng = rand::thread_rng();
+        let (_, public_key) = ibe::generate_key_pair(&mut rng);
+        let (mut bundle, bundle_key) = seal_encrypt_bundle(
+            ObjectID::random(),
+            vec![],
+            vec![ObjectID::random()],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]),
+            1,
+            vec![],
+        )
+        .unwrap();
+        let input = || EncryptionInput::Aes256Gcm {
+            data: b"payload".to_vec(),
+            aad: None,
+        };
+
+        // Add the same label and payload twice, removing the first entry in between.
+        bundle
+            .add_entry(&bundle_key, b"a".to_vec(), input(), &mut rng)
+            .unwrap();
+        let first = bundle.entries.pop().unwrap();
+        bundle
+            .add_entry(&bundle_key, b"a".to_vec(), input(), &mut rng)
+            .unwrap();
+        let second = &bundle.entries[0];
+
+        assert_ne!(first.salt, second.salt);
+        assert_ne!(
+            bcs::to_bytes(&first.ciphertext).unwrap(),
+            bcs::to_bytes(&second.ciphertext).unwrap()
+        );
+        assert_eq!(bundle.decrypt_entry(&bundle_key, b"a").unwrap(), b"payload");
+    }
+}
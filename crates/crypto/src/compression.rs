@@ -0,0 +1,155 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compression of DEM payloads before encryption.
+//!
+//! Decompression is bounded by a maximum output size to protect against decompression bombs.
+//! Zstandard is only available if the `zstd` feature is enabled, which it is by default, and Deflate only if the
+//! `std` feature is enabled.
+
+use crate::prelude::*;
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use fastcrypto::error::FastCryptoError::GeneralError;
+#[cfg(feature = "std")]
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+/// The default maximum size of a decompressed payload used by `seal_decrypt`.
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    Zstd,
+    Deflate,
+}
+
+impl Compression {
+    pub fn compress(&self, data: &[u8]) -> FastCryptoResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::encode_all(data, 0).map_err(|_| InvalidInput),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(zstd_not_enabled()),
+            #[cfg(feature = "std")]
+            Compression::Deflate => {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).map_err(|_| InvalidInput)?;
+                encoder.finish().map_err(|_| InvalidInput)
+            }
+            #[cfg(not(feature = "std"))]
+            Compression::Deflate => Err(deflate_not_enabled()),
+        }
+    }
+
+    /// Decompress a payload. Fails if the decompressed payload is larger than `max_size` bytes.
+    pub fn decompress(&self, data: &[u8], max_size: usize) -> FastCryptoResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => read_bounded(
+                zstd::stream::read::Decoder::new(data).map_err(|_| InvalidInput)?,
+                max_size,
+            ),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(zstd_not_enabled()),
+            #[cfg(feature = "std")]
+            Compression::Deflate => read_bounded(flate2::read::DeflateDecoder::new(data), max_size),
+            #[cfg(not(feature = "std"))]
+            Compression::Deflate => Err(deflate_not_enabled()),
+        }
+    }
+}
+
+/// Read at most `max_size` bytes and fail if there are more.
+#[cfg(feature = "std")]
+fn read_bounded<R: Read>(reader: R, max_size: usize) -> FastCryptoResult<Vec<u8>> {
+    let mut output = Vec::new();
+    reader
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut output)
+        .map_err(|_| InvalidInput)?;
+    if output.len() > max_size {
+        return Err(GeneralError(
+            "Decompressed payload exceeds the maximum size".to_string(),
+        ));
+    }
+    Ok(output)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_not_enabled() -> fastcrypto::error::FastCryptoError {
+    GeneralError("Zstandard support is not enabled".to_string())
+}
+
+#[cfg(not(feature = "std"))]
+fn deflate_not_enabled() -> fastcrypto::error::FastCryptoError {
+    GeneralError("Deflate support requires the std feature".to_string())
+}
+
+/// Parse a compression codec from either "zstd" or "deflate".
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zstd" => Ok(Compression::Zstd),
+            "deflate" => Ok(Compression::Deflate),
+            _ => Err(format!("Invalid compression codec: {}", s)),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Deflate => write!(f, "deflate"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_round_trip_and_limit() {
+        let data = b"{\"level\":\"info\",\"message\":\"hello\"}\n".repeat(100);
+        for compression in [
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            Compression::Deflate,
+        ] {
+            let compressed = compression.compress(&data).unwrap();
+            assert!(compressed.len() < data.len() / 5);
+            assert_eq!(
+                compression.decompress(&compressed, data.len()).unwrap(),
+                data
+            );
+            assert!(compression.decompress(&compressed, data.len() - 1).is_err());
+        }
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_not_enabled() {
+        assert!(Compression::Zstd.compress(b"data").is_err());
+        assert!(Compression::Zstd.decompress(b"data", 100).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        for compression in [Compression::Zstd, Compression::Deflate] {
+            assert_eq!(
+                Compression::from_str(&compression.to_string()).unwrap(),
+                compression
+            );
+        }
+        assert!(Compression::from_str("gzip").is_err());
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::utils::xor_unchecked;
+use crate::prelude::*;
+use crate::utils::{xor, xor_unchecked};
 use crate::KEY_SIZE;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes256;
 use fastcrypto::error::FastCryptoError;
 use fastcrypto::hmac::HmacKey;
 use fastcrypto::{
@@ -75,6 +79,66 @@ impl Hmac256Ctr {
     }
 }
 
+/// AES key wrap as defined in RFC 3394 using AES-256 as the block cipher, used to protect a caller-supplied key.
+/// The wrapped key is 8 bytes longer than the key and the integrity of the key is checked when unwrapping.
+pub struct AesKeyWrap;
+
+impl AesKeyWrap {
+    /// The default initial value from RFC 3394, section 2.2.3.1.
+    const IV: [u8; 8] = [0xA6; 8];
+
+    pub fn wrap(key: &[u8; KEY_SIZE], kek: &[u8; KEY_SIZE]) -> Vec<u8> {
+        let cipher = Aes256::new(GenericArray::from_slice(kek));
+        let mut a = Self::IV;
+        let mut r = key.chunks_exact(8).map(to_block).collect::<Vec<_>>();
+        let n = r.len();
+        for j in 0..6 {
+            for (i, r_i) in r.iter_mut().enumerate() {
+                let mut block = GenericArray::clone_from_slice(&[a, *r_i].concat());
+                cipher.encrypt_block(&mut block);
+                a = xor(
+                    &to_block(&block[..8]),
+                    &((n * j + i + 1) as u64).to_be_bytes(),
+                );
+                *r_i = to_block(&block[8..]);
+            }
+        }
+        [a].into_iter().chain(r).flatten().collect()
+    }
+
+    pub fn unwrap(wrapped_key: &[u8], kek: &[u8; KEY_SIZE]) -> FastCryptoResult<[u8; KEY_SIZE]> {
+        if wrapped_key.len() != KEY_SIZE + 8 {
+            return Err(FastCryptoError::InvalidInput);
+        }
+        let cipher = Aes256::new(GenericArray::from_slice(kek));
+        let mut a = to_block(&wrapped_key[..8]);
+        let mut r = wrapped_key[8..]
+            .chunks_exact(8)
+            .map(to_block)
+            .collect::<Vec<_>>();
+        let n = r.len();
+        for j in (0..6).rev() {
+            for (i, r_i) in r.iter_mut().enumerate().rev() {
+                let t = xor(&a, &((n * j + i + 1) as u64).to_be_bytes());
+                let mut block = GenericArray::clone_from_slice(&[t, *r_i].concat());
+                cipher.decrypt_block(&mut block);
+                a = to_block(&block[..8]);
+                *r_i = to_block(&block[8..]);
+            }
+        }
+        if a != Self::IV {
+            return Err(FastCryptoError::GeneralError(
+                "Invalid wrapped key".to_string(),
+            ));
+        }
+        Ok(r.concat().try_into().expect("fixed length"))
+    }
+}
+
+fn to_block(bytes: &[u8]) -> [u8; 8] {
+    bytes.try_into().expect("fixed length")
+}
+
 /// Encrypts the message in CTR mode using hmac_sha3_256 as a PRF.
 fn encrypt_in_ctr_mode(key: &[u8; KEY_SIZE], msg: &[u8]) -> Vec<u8> {
     // Derive encryption key
@@ -109,7 +173,7 @@ fn to_bytes(n: usize) -> Vec<u8> {
 
 #[cfg(test)]
 mod tests {
-    use crate::dem::{Aes256Gcm, Hmac256Ctr};
+    use crate::dem::{Aes256Gcm, AesKeyWrap, Hmac256Ctr};
     use crate::{utils::generate_random_bytes, KEY_SIZE};
     use rand::thread_rng;
 
@@ -208,4 +272,30 @@ mod tests {
             (ciphertext, mac)
         );
     }
+
+    #[test]
+    fn test_aes_key_wrap() {
+        // Test vector from RFC 3394, section 4.6.
+        let kek: [u8; KEY_SIZE] =
+            hex::decode("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let key: [u8; KEY_SIZE] =
+            hex::decode("00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let wrapped = hex::decode(
+            "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21",
+        )
+        .unwrap();
+        assert_eq!(AesKeyWrap::wrap(&key, &kek), wrapped);
+        assert_eq!(AesKeyWrap::unwrap(&wrapped, &kek).unwrap(), key);
+
+        let mut tampered = wrapped.clone();
+        tampered[10] ^= 1;
+        assert!(AesKeyWrap::unwrap(&tampered, &kek).is_err());
+        assert!(AesKeyWrap::unwrap(&wrapped[1..], &kek).is_err());
+    }
 }
@@ -1,13 +1,37 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::utils::overwrite_volatile;
+use core::fmt::{Debug, Formatter};
 use fastcrypto::groups::{GroupElement, Scalar};
 use fastcrypto::traits::AllowedRng;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// An ElGamal secret key. The key is zeroized when dropped and is redacted in [Debug] output.
 #[derive(Serialize, Deserialize)]
 pub struct SecretKey<G: GroupElement>(G::ScalarType);
 
+impl<G: GroupElement> Zeroize for SecretKey<G> {
+    fn zeroize(&mut self) {
+        overwrite_volatile(&mut self.0, G::ScalarType::zero());
+    }
+}
+
+impl<G: GroupElement> Drop for SecretKey<G> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<G: GroupElement> ZeroizeOnDrop for SecretKey<G> {}
+
+impl<G: GroupElement> Debug for SecretKey<G> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("SecretKey([REDACTED])")
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PublicKey<G: GroupElement>(G);
 
@@ -1,14 +1,14 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use fastcrypto::error::FastCryptoResult;
-use fastcrypto::{error::FastCryptoError::InvalidInput, traits::AllowedRng};
-use rand::Rng;
-use std::ops::{AddAssign, Neg};
-use std::{
+use core::ops::{AddAssign, Neg};
+use core::{
     iter::{Product, Sum},
     ops::{Add, Div, Mul, Sub},
 };
+use fastcrypto::error::FastCryptoResult;
+use fastcrypto::{error::FastCryptoError::InvalidInput, traits::AllowedRng};
+use rand::Rng;
 
 /// This represents an element in the Galois field of order 2⁸ represented as F₂(x) / <x⁸ + x⁴ + x³ + x + 1>, also known as Rinjdael's finite field.
 #[derive(Clone, Debug, Copy, Eq, PartialEq, Hash)]
@@ -4,21 +4,24 @@
 //! Implementation of a the Boneh-Franklin Identity-based encryption scheme from https://eprint.iacr.org/2001/090 over the BLS12-381 curve construction.
 //! It enables a symmetric key to be derived from the identity + the public key of a user and used to encrypt a fixed size message of length [KEY_LENGTH].
 
-use crate::utils::xor;
-use crate::{DST_POP, KEY_SIZE};
+use crate::prelude::*;
+use crate::utils::{overwrite_volatile, xor};
+use crate::{ObjectID, DST_POP, DST_USK_BATCH, DST_USK_POK, KEY_SIZE};
+use core::fmt::{Debug, Formatter};
 use fastcrypto::error::FastCryptoError::{GeneralError, InvalidInput};
 use fastcrypto::error::FastCryptoResult;
-use fastcrypto::groups::bls12381::{G1Element, G2Element, GTElement, Scalar};
+use fastcrypto::groups::bls12381::{
+    G1Element, G2Element, GTElement, Scalar, G1_ELEMENT_BYTE_LENGTH, SCALAR_LENGTH,
+};
 use fastcrypto::groups::{GroupElement, HashToGroupElement, Pairing, Scalar as GenericScalar};
 use fastcrypto::hmac::{hkdf_sha3_256, HkdfIkm};
 use fastcrypto::serde_helpers::ToFromByteArray;
 use fastcrypto::traits::AllowedRng;
 use fastcrypto::traits::ToFromBytes;
-use sui_types::base_types::ObjectID;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-pub type MasterKey = Scalar;
 pub type PublicKey = G2Element;
-pub type UserSecretKey = G1Element;
 pub type Nonce = G2Element;
 pub type Plaintext = [u8; KEY_SIZE];
 pub type Ciphertext = [u8; KEY_SIZE];
@@ -27,20 +30,129 @@ pub type Randomness = Scalar;
 // Additional info for the key derivation. Contains the object id for the key server and the share index.
 pub type Info = (ObjectID, u8);
 
+/// The master key of a key server.
+/// The key is zeroized when dropped and is redacted in [Debug] output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MasterKey(Scalar);
+
+/// A user secret key derived from a master key for a specific id.
+/// The key is zeroized when dropped and is redacted in [Debug] output.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserSecretKey(G1Element);
+
+impl MasterKey {
+    /// Get the underlying scalar. Callers should avoid keeping copies of it.
+    pub fn expose(&self) -> &Scalar {
+        &self.0
+    }
+}
+
+impl UserSecretKey {
+    /// Get the underlying group element. Callers should avoid keeping copies of it.
+    pub fn expose(&self) -> &G1Element {
+        &self.0
+    }
+}
+
+impl From<Scalar> for MasterKey {
+    fn from(scalar: Scalar) -> Self {
+        Self(scalar)
+    }
+}
+
+impl From<G1Element> for UserSecretKey {
+    fn from(element: G1Element) -> Self {
+        Self(element)
+    }
+}
+
+impl ToFromByteArray<SCALAR_LENGTH> for MasterKey {
+    fn from_byte_array(bytes: &[u8; SCALAR_LENGTH]) -> FastCryptoResult<Self> {
+        Scalar::from_byte_array(bytes).map(Self)
+    }
+
+    fn to_byte_array(&self) -> [u8; SCALAR_LENGTH] {
+        self.0.to_byte_array()
+    }
+}
+
+impl ToFromByteArray<G1_ELEMENT_BYTE_LENGTH> for UserSecretKey {
+    fn from_byte_array(bytes: &[u8; G1_ELEMENT_BYTE_LENGTH]) -> FastCryptoResult<Self> {
+        G1Element::from_byte_array(bytes).map(Self)
+    }
+
+    fn to_byte_array(&self) -> [u8; G1_ELEMENT_BYTE_LENGTH] {
+        self.0.to_byte_array()
+    }
+}
+
+impl Zeroize for MasterKey {
+    fn zeroize(&mut self) {
+        overwrite_volatile(&mut self.0, Scalar::zero());
+    }
+}
+
+impl Zeroize for UserSecretKey {
+    fn zeroize(&mut self) {
+        overwrite_volatile(&mut self.0, G1Element::zero());
+    }
+}
+
+impl Drop for MasterKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Drop for UserSecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for MasterKey {}
+
+impl ZeroizeOnDrop for UserSecretKey {}
+
+impl Debug for MasterKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("MasterKey([REDACTED])")
+    }
+}
+
+impl Debug for UserSecretKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("UserSecretKey([REDACTED])")
+    }
+}
+
 /// Generate a key pair consisting of a master key and a public key.
 pub fn generate_key_pair<R: AllowedRng>(rng: &mut R) -> (MasterKey, PublicKey) {
-    let sk = Scalar::rand(rng);
-    (sk, public_key_from_master_key(&sk))
+    let sk = MasterKey(Scalar::rand(rng));
+    let pk = public_key_from_master_key(&sk);
+    (sk, pk)
 }
 
 /// Derive a public key from a master key.
 pub fn public_key_from_master_key(master_key: &MasterKey) -> PublicKey {
-    G2Element::generator() * master_key
+    G2Element::generator() * master_key.0
+}
+
+/// Hash an id to G1. The result can be passed to the `*_with_gid` functions to avoid hashing the same id more than once.
+pub fn hash_id(id: &[u8]) -> G1Element {
+    G1Element::hash_to_group_element(id)
 }
 
 /// Extract a user secret key from a master key and an id.
 pub fn extract(master_key: &MasterKey, id: &[u8]) -> UserSecretKey {
-    G1Element::hash_to_group_element(id) * master_key
+    extract_with_gid(master_key, &hash_id(id))
+}
+
+/// Same as [extract] but with the id already hashed to G1 using [hash_id].
+pub fn extract_with_gid(master_key: &MasterKey, gid: &G1Element) -> UserSecretKey {
+    UserSecretKey(*gid * master_key.0)
 }
 
 /// Verify that a user secret key is valid for a given public key and id.
@@ -49,8 +161,20 @@ pub fn verify_user_secret_key(
     id: &[u8],
     public_key: &PublicKey,
 ) -> FastCryptoResult<()> {
-    if user_secret_key.pairing(&G2Element::generator())
-        == G1Element::hash_to_group_element(id).pairing(public_key)
+    verify_user_secret_key_with_gid(user_secret_key, &hash_id(id), public_key)
+}
+
+/// Same as [verify_user_secret_key] but with the id already hashed to G1 using [hash_id].
+pub fn verify_user_secret_key_with_gid(
+    user_secret_key: &UserSecretKey,
+    gid: &G1Element,
+    public_key: &PublicKey,
+) -> FastCryptoResult<()> {
+    // e(usk, g2) = e(gid, pk) is checked as e(usk, g2) * e(-gid, pk) = 1 with a single final exponentiation.
+    if G1Element::multi_pairing(
+        &[user_secret_key.0, -*gid],
+        &[G2Element::generator(), *public_key],
+    )? == GTElement::zero()
     {
         Ok(())
     } else {
@@ -58,6 +182,63 @@ pub fn verify_user_secret_key(
     }
 }
 
+/// Verify user secret keys for the same id against the public keys of the key servers they were extracted from.
+///
+/// With random coefficients c_i, the keys are valid iff e(sum c_i * usk_i, g2) = e(gid, sum c_i * pk_i) except with
+/// negligible probability, so the check takes a single multi-pairing of two pairs regardless of the number of keys.
+/// The coefficients are derived by hashing all inputs, so they cannot be chosen by whoever created the keys.
+/// If the check fails, the keys are verified one by one to return the index of the first invalid key. If the number
+/// of keys and public keys differ, the index of the first key without a counterpart is returned.
+pub fn verify_user_secret_keys_batched_with_gid(
+    user_secret_keys: &[&UserSecretKey],
+    gid: &G1Element,
+    public_keys: &[&PublicKey],
+) -> Result<(), usize> {
+    if user_secret_keys.len() != public_keys.len() {
+        return Err(user_secret_keys.len().min(public_keys.len()));
+    }
+    let coefficients = batch_verification_coefficients(user_secret_keys, gid, public_keys);
+    let combined_key = user_secret_keys
+        .iter()
+        .zip(&coefficients)
+        .fold(G1Element::zero(), |acc, (usk, c)| acc + usk.0 * *c);
+    let combined_public_key = public_keys
+        .iter()
+        .zip(&coefficients)
+        .fold(G2Element::zero(), |acc, (pk, c)| acc + **pk * *c);
+    if verify_user_secret_key_with_gid(&UserSecretKey(combined_key), gid, &combined_public_key)
+        .is_ok()
+    {
+        return Ok(());
+    }
+    Err(user_secret_keys
+        .iter()
+        .zip(public_keys)
+        .position(|(usk, pk)| verify_user_secret_key_with_gid(usk, gid, pk).is_err())
+        .unwrap_or(0))
+}
+
+/// The coefficients for [verify_user_secret_keys_batched_with_gid], derived from all keys and the id.
+fn batch_verification_coefficients(
+    user_secret_keys: &[&UserSecretKey],
+    gid: &G1Element,
+    public_keys: &[&PublicKey],
+) -> Vec<Scalar> {
+    let mut input = DST_USK_BATCH.to_vec();
+    input.extend_from_slice(&gid.to_byte_array()); // 48 bytes
+    for (usk, pk) in user_secret_keys.iter().zip(public_keys) {
+        input.extend_from_slice(&usk.0.to_byte_array()); // 48 bytes
+        input.extend_from_slice(&pk.to_byte_array()); // 96 bytes
+    }
+    (0..user_secret_keys.len() as u64)
+        .map(|i| {
+            let mut input = input.clone();
+            input.extend_from_slice(&i.to_le_bytes());
+            Scalar::hash_to_group_element(&input)
+        })
+        .collect()
+}
+
 /// Encrypt a set of messages for a given identity but different public keys.
 /// The infos are used to derive the symmetric keys for the encryption.
 pub fn encrypt_batched_deterministic(
@@ -66,21 +247,31 @@ pub fn encrypt_batched_deterministic(
     public_keys: &[PublicKey],
     id: &[u8],
     infos: &[Info],
+) -> FastCryptoResult<(Nonce, Vec<Ciphertext>)> {
+    encrypt_batched_deterministic_with_gid(randomness, plaintexts, public_keys, &hash_id(id), infos)
+}
+
+/// Same as [encrypt_batched_deterministic] but with the id already hashed to G1 using [hash_id].
+pub fn encrypt_batched_deterministic_with_gid(
+    randomness: &Randomness,
+    plaintexts: &[Plaintext],
+    public_keys: &[PublicKey],
+    gid: &G1Element,
+    infos: &[Info],
 ) -> FastCryptoResult<(Nonce, Vec<Ciphertext>)> {
     let batch_size = plaintexts.len();
     if batch_size != public_keys.len() || batch_size != infos.len() {
         return Err(InvalidInput);
     }
 
-    let gid = G1Element::hash_to_group_element(id);
-    let gid_r = gid * randomness;
+    let gid_r = *gid * randomness;
     let nonce = G2Element::generator() * randomness;
     Ok((
         nonce,
         (0..batch_size)
             .map(|i| {
                 xor(
-                    &kdf(&gid_r.pairing(&public_keys[i]), &nonce, &gid, &infos[i]),
+                    &kdf(&gid_r.pairing(&public_keys[i]), &nonce, gid, &infos[i]),
                     &plaintexts[i],
                 )
             })
@@ -97,10 +288,20 @@ pub fn decrypt(
     id: &[u8],
     info: &Info,
 ) -> Plaintext {
-    let gid = G1Element::hash_to_group_element(id);
+    decrypt_with_gid(nonce, ciphertext, secret_key, &hash_id(id), info)
+}
+
+/// Same as [decrypt] but with the id already hashed to G1 using [hash_id].
+pub fn decrypt_with_gid(
+    nonce: &Nonce,
+    ciphertext: &Ciphertext,
+    secret_key: &UserSecretKey,
+    gid: &G1Element,
+    info: &Info,
+) -> Plaintext {
     xor(
         ciphertext,
-        &kdf(&secret_key.pairing(nonce), nonce, &gid, info),
+        &kdf(&secret_key.0.pairing(nonce), nonce, gid, info),
     )
 }
 
@@ -121,13 +322,55 @@ pub fn decrypt_deterministic(
     id: &[u8],
     info: &Info,
 ) -> FastCryptoResult<Plaintext> {
-    let gid = G1Element::hash_to_group_element(id);
-    let gid_r = gid * randomness;
+    decrypt_deterministic_with_gid(randomness, ciphertext, public_key, &hash_id(id), info)
+}
+
+/// Same as [decrypt_deterministic] but with the id already hashed to G1 using [hash_id].
+pub fn decrypt_deterministic_with_gid(
+    randomness: &Randomness,
+    ciphertext: &Ciphertext,
+    public_key: &PublicKey,
+    gid: &G1Element,
+    info: &Info,
+) -> FastCryptoResult<Plaintext> {
+    decrypt_batched_deterministic_with_gid(
+        randomness,
+        core::slice::from_ref(ciphertext),
+        core::slice::from_ref(public_key),
+        gid,
+        core::slice::from_ref(info),
+    )
+    .map(|plaintexts| plaintexts[0])
+}
+
+/// Decrypt a set of messages encrypted with [encrypt_batched_deterministic] using the randomness, e.g., to check the
+/// consistency of all shares of an encrypted object. The nonce and the G1 side of the pairing are computed once and
+/// shared by all ciphertexts, so only one pairing per ciphertext is needed.
+pub fn decrypt_batched_deterministic_with_gid(
+    randomness: &Randomness,
+    ciphertexts: &[Ciphertext],
+    public_keys: &[PublicKey],
+    gid: &G1Element,
+    infos: &[Info],
+) -> FastCryptoResult<Vec<Plaintext>> {
+    let batch_size = ciphertexts.len();
+    if batch_size != public_keys.len() || batch_size != infos.len() {
+        return Err(InvalidInput);
+    }
+
+    let gid_r = *gid * randomness;
     let nonce = G2Element::generator() * randomness;
-    Ok(xor(
-        ciphertext,
-        &kdf(&gid_r.pairing(public_key), &nonce, &gid, info),
-    ))
+    Ok(ciphertexts
+        .iter()
+        .zip(public_keys)
+        .zip(infos)
+        .map(|((ciphertext, public_key), info)| {
+            xor(
+                ciphertext,
+                &kdf(&gid_r.pairing(public_key), &nonce, gid, info),
+            )
+        })
+        .collect())
 }
 
 /// Derive a random key from public inputs.
@@ -179,7 +422,103 @@ pub fn create_proof_of_possession(master_key: &MasterKey, message: &[u8]) -> Pro
     let mut full_msg = DST_POP.to_vec();
     full_msg.extend(bcs::to_bytes(&public_key).expect("valid pk"));
     full_msg.extend(message);
-    G1Element::hash_to_group_element(&full_msg) * master_key
+    G1Element::hash_to_group_element(&full_msg) * master_key.0
+}
+
+/// Verify a proof-of-possession created by [create_proof_of_possession] for the given public key and message.
+pub fn verify_proof_of_possession(
+    public_key: &PublicKey,
+    message: &[u8],
+    pop: &ProofOfPossession,
+) -> FastCryptoResult<()> {
+    let mut full_msg = DST_POP.to_vec();
+    full_msg.extend(bcs::to_bytes(public_key).expect("valid pk"));
+    full_msg.extend(message);
+    if G1Element::multi_pairing(
+        &[*pop, -G1Element::hash_to_group_element(&full_msg)],
+        &[G2Element::generator(), *public_key],
+    )? == GTElement::zero()
+    {
+        Ok(())
+    } else {
+        Err(InvalidInput)
+    }
+}
+
+/// A non-interactive zero-knowledge proof of knowledge of a user secret key for an id and a public key, i.e., of
+/// `usk` such that `e(usk, g2) = e(H(id), pk)`. It convinces a verifier that a user was granted access to an id
+/// without revealing the key, so the verifier cannot decrypt anything.
+///
+/// This is a Schnorr proof for the homomorphism `x -> e(x, g2)` made non-interactive with the Fiat-Shamir
+/// transform. The proof is bound to a challenge chosen by the verifier, so it cannot be replayed to other verifiers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserSecretKeyProof {
+    challenge: Scalar,
+    response: G1Element,
+}
+
+/// Create a proof of knowledge of a user secret key for an id and a public key.
+///
+/// @param user_secret_key The user secret key for the id.
+/// @param id The full id, see [crate::create_full_id].
+/// @param public_key The public key of the key server that the user secret key was extracted from.
+/// @param verifier_challenge A challenge chosen by the verifier, e.g., a random nonce or a session identifier.
+/// @param rng The source of randomness.
+/// @return The proof.
+pub fn create_user_secret_key_proof<R: AllowedRng>(
+    user_secret_key: &UserSecretKey,
+    id: &[u8],
+    public_key: &PublicKey,
+    verifier_challenge: &[u8],
+    rng: &mut R,
+) -> UserSecretKeyProof {
+    let gid = hash_id(id);
+    let r = G1Element::generator() * Scalar::rand(rng);
+    let commitment = r.pairing(&G2Element::generator());
+    let challenge =
+        user_secret_key_proof_challenge(&gid, public_key, &commitment, verifier_challenge);
+    UserSecretKeyProof {
+        challenge,
+        response: r + user_secret_key.0 * challenge,
+    }
+}
+
+/// Verify a proof created by [create_user_secret_key_proof]. Only the public key and the id are needed.
+pub fn verify_user_secret_key_proof(
+    proof: &UserSecretKeyProof,
+    id: &[u8],
+    public_key: &PublicKey,
+    verifier_challenge: &[u8],
+) -> FastCryptoResult<()> {
+    let gid = hash_id(id);
+    // If the proof is valid, this equals the commitment e(r, g2) since e(usk, g2) = e(H(id), pk).
+    // It is computed as e(response, g2) * e(-challenge * H(id), pk) with a single final exponentiation.
+    let commitment = G1Element::multi_pairing(
+        &[proof.response, gid * -proof.challenge],
+        &[G2Element::generator(), *public_key],
+    )?;
+    if user_secret_key_proof_challenge(&gid, public_key, &commitment, verifier_challenge)
+        == proof.challenge
+    {
+        Ok(())
+    } else {
+        Err(InvalidInput)
+    }
+}
+
+/// The Fiat-Shamir challenge for a [UserSecretKeyProof].
+fn user_secret_key_proof_challenge(
+    gid: &G1Element,
+    public_key: &PublicKey,
+    commitment: &GTElement,
+    verifier_challenge: &[u8],
+) -> Scalar {
+    let mut input = DST_USK_POK.to_vec();
+    input.extend_from_slice(&gid.to_byte_array()); // 48 bytes
+    input.extend_from_slice(&public_key.to_byte_array()); // 96 bytes
+    input.extend_from_slice(&commitment.to_byte_array()); // 576 bytes
+    input.extend_from_slice(verifier_challenge);
+    Scalar::hash_to_group_element(&input)
 }
 
 #[cfg(test)]
@@ -202,4 +541,185 @@ mod tests {
                 .unwrap();
         assert_eq!(expected, derived_key);
     }
+
+    #[test]
+    fn test_proof_of_possession() {
+        let (master_key, public_key) = generate_key_pair(&mut rand::thread_rng());
+        let pop = create_proof_of_possession(&master_key, b"message");
+        assert!(verify_proof_of_possession(&public_key, b"message", &pop).is_ok());
+        assert!(verify_proof_of_possession(&public_key, b"other message", &pop).is_err());
+
+        let (_, other_public_key) = generate_key_pair(&mut rand::thread_rng());
+        assert!(verify_proof_of_possession(&other_public_key, b"message", &pop).is_err());
+    }
+
+    #[test]
+    fn test_secret_keys_are_redacted_and_zeroized() {
+        let (mut master_key, _) = generate_key_pair(&mut rand::thread_rng());
+        let mut usk = extract(&master_key, b"id");
+        assert_eq!(format!("{:?}", master_key), "MasterKey([REDACTED])");
+        assert_eq!(format!("{:?}", usk), "UserSecretKey([REDACTED])");
+
+        master_key.zeroize();
+        usk.zeroize();
+        assert_eq!(*master_key.expose(), Scalar::zero());
+        assert_eq!(*usk.expose(), G1Element::zero());
+    }
+
+    #[test]
+    fn test_gid_functions_match() {
+        let mut rng = rand::thread_rng();
+        let id = b"some id";
+        let gid = hash_id(id);
+        let keypairs = (0..4)
+            .map(|_| generate_key_pair(&mut rng))
+            .collect::<Vec<_>>();
+        let public_keys = keypairs.iter().map(|(_, pk)| *pk).collect::<Vec<_>>();
+        let infos = (0..4u8)
+            .map(|i| (ObjectID::random(), i))
+            .collect::<Vec<_>>();
+        let plaintexts = (0..4u8).map(|i| [i; KEY_SIZE]).collect::<Vec<_>>();
+        let randomness = Randomness::rand(&mut rng);
+
+        let (nonce, ciphertexts) =
+            encrypt_batched_deterministic(&randomness, &plaintexts, &public_keys, id, &infos)
+                .unwrap();
+        assert_eq!(
+            encrypt_batched_deterministic_with_gid(
+                &randomness,
+                &plaintexts,
+                &public_keys,
+                &gid,
+                &infos
+            )
+            .unwrap(),
+            (nonce, ciphertexts.clone())
+        );
+
+        let batched = decrypt_batched_deterministic_with_gid(
+            &randomness,
+            &ciphertexts,
+            &public_keys,
+            &gid,
+            &infos,
+        )
+        .unwrap();
+        assert_eq!(batched, plaintexts);
+
+        for (i, (master_key, public_key)) in keypairs.iter().enumerate() {
+            let usk = extract(master_key, id);
+            assert_eq!(usk, extract_with_gid(master_key, &gid));
+            assert!(verify_user_secret_key_with_gid(&usk, &gid, public_key).is_ok());
+            assert_eq!(
+                decrypt(&nonce, &ciphertexts[i], &usk, id, &infos[i]),
+                plaintexts[i]
+            );
+            assert_eq!(
+                decrypt_deterministic(&randomness, &ciphertexts[i], public_key, id, &infos[i])
+                    .unwrap(),
+                plaintexts[i]
+            );
+        }
+
+        assert!(decrypt_batched_deterministic_with_gid(
+            &randomness,
+            &ciphertexts[1..],
+            &public_keys,
+            &gid,
+            &infos,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_batched_user_secret_key_verification() {
+        let mut rng = rand::thread_rng();
+        let id = b"some id";
+        let gid = hash_id(id);
+        let keypairs = (0..4)
+            .map(|_| generate_key_pair(&mut rng))
+            .collect::<Vec<_>>();
+        let public_keys = keypairs.iter().map(|(_, pk)| pk).collect::<Vec<_>>();
+        let mut usks = keypairs
+            .iter()
+            .map(|(mk, _)| extract(mk, id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verify_user_secret_keys_batched_with_gid(
+                &usks.iter().collect::<Vec<_>>(),
+                &gid,
+                &public_keys
+            ),
+            Ok(())
+        );
+
+        // The keys of two servers are swapped.
+        usks.swap(1, 2);
+        assert_eq!(
+            verify_user_secret_keys_batched_with_gid(
+                &usks.iter().collect::<Vec<_>>(),
+                &gid,
+                &public_keys
+            ),
+            Err(1)
+        );
+
+        // Invalid keys that cancel out in an unweighted sum are detected.
+        usks.swap(1, 2);
+        let delta = G1Element::generator() * Scalar::rand(&mut rng);
+        usks[0] = UserSecretKey(usks[0].0 + delta);
+        usks[3] = UserSecretKey(usks[3].0 - delta);
+        assert_eq!(
+            verify_user_secret_keys_batched_with_gid(
+                &usks.iter().collect::<Vec<_>>(),
+                &gid,
+                &public_keys
+            ),
+            Err(0)
+        );
+        assert!(verify_user_secret_keys_batched_with_gid(
+            &usks.iter().collect::<Vec<_>>(),
+            &gid,
+            &public_keys[1..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_user_secret_key_proof() {
+        let mut rng = rand::thread_rng();
+        let (master_key, public_key) = generate_key_pair(&mut rng);
+        let (_, other_public_key) = generate_key_pair(&mut rng);
+        let id = b"some id";
+        let usk = extract(&master_key, id);
+
+        let proof = create_user_secret_key_proof(&usk, id, &public_key, b"challenge", &mut rng);
+        assert!(verify_user_secret_key_proof(&proof, id, &public_key, b"challenge").is_ok());
+
+        // The proof does not contain the key and is randomized.
+        let bytes = bcs::to_bytes(&proof).unwrap();
+        let usk_bytes = bcs::to_bytes(&usk).unwrap();
+        assert!(!bytes.windows(usk_bytes.len()).any(|w| w == usk_bytes));
+        assert_ne!(
+            create_user_secret_key_proof(&usk, id, &public_key, b"challenge", &mut rng),
+            proof
+        );
+        assert_eq!(
+            bcs::from_bytes::<UserSecretKeyProof>(&bytes).unwrap(),
+            proof
+        );
+
+        // Wrong challenge, id or public key.
+        assert!(verify_user_secret_key_proof(&proof, id, &public_key, b"other").is_err());
+        assert!(
+            verify_user_secret_key_proof(&proof, b"other id", &public_key, b"challenge").is_err()
+        );
+        assert!(verify_user_secret_key_proof(&proof, id, &other_public_key, b"challenge").is_err());
+
+        // A key for another id does not give a valid proof.
+        let other_usk = extract(&master_key, b"other id");
+        let proof =
+            create_user_secret_key_proof(&other_usk, id, &public_key, b"challenge", &mut rng);
+        assert!(verify_user_secret_key_proof(&proof, id, &public_key, b"challenge").is_err());
+    }
 }
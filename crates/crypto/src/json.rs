@@ -0,0 +1,468 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A stable, human-readable JSON encoding of [EncryptedObject].
+//!
+//! The derived serde implementations encode byte arrays as lists of integers and depend on the names of Rust
+//! types, so they are not suited for sharing objects. In this encoding, object ids are `0x`-prefixed hex strings,
+//! the id is hex encoded and all other binary fields are base64 encoded. Enums are tagged with a `type` field:
+//! ```json
+//! {
+//!   "version": 0,
+//!   "package_id": "0x0000000000000000000000000000000000000000000000000000000000000001",
+//!   "id": "010203",
+//!   "services": [{ "object_id": "0x...", "share_index": 1 }],
+//!   "threshold": 1,
+//!   "encrypted_shares": {
+//!     "type": "BonehFranklinBLS12381",
+//!     "nonce": "...",
+//!     "encrypted_shares": ["..."],
+//!     "encrypted_randomness": "..."
+//!   },
+//!   "ciphertext": { "type": "Aes256Gcm", "blob": "...", "aad": null },
+//!   "metadata": null
+//! }
+//! ```
+//! For version 2 objects, each service also has a `key_version` field.
+//!
+//! Converting between this encoding and BCS is lossless.
+
+use crate::prelude::*;
+use crate::{Ciphertext, DemHeader, EncryptedObject, IBEEncryptions, ObjectID, KEY_SIZE};
+use core::str::FromStr;
+use fastcrypto::encoding::{Base64, Encoding, Hex};
+use fastcrypto::error::FastCryptoError::{GeneralError, InvalidInput};
+use fastcrypto::error::FastCryptoResult;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EncryptedObjectJson {
+    version: u8,
+    package_id: ObjectID,
+    id: String,
+    services: Vec<ServiceJson>,
+    threshold: u8,
+    encrypted_shares: IBEEncryptionsJson,
+    ciphertext: CiphertextJson,
+    metadata: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ServiceJson {
+    object_id: ObjectID,
+    share_index: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_version: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum IBEEncryptionsJson {
+    BonehFranklinBLS12381 {
+        nonce: String,
+        encrypted_shares: Vec<String>,
+        encrypted_randomness: String,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+enum CiphertextJson {
+    Aes256Gcm {
+        blob: String,
+        aad: Option<String>,
+    },
+    Hmac256Ctr {
+        blob: String,
+        aad: Option<String>,
+        mac: String,
+    },
+    Plain,
+    KeyWrap {
+        wrapped_key: String,
+    },
+    Aes256GcmWithHeader {
+        header: DemHeaderJson,
+        blob: String,
+        aad: Option<String>,
+    },
+    Hmac256CtrWithHeader {
+        header: DemHeaderJson,
+        blob: String,
+        mac: String,
+        aad: Option<String>,
+    },
+}
+
+/// Padding and compression are given by their names, e.g. "padme" or "zstd".
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DemHeaderJson {
+    padding: Option<String>,
+    compression: Option<String>,
+}
+
+impl EncryptedObject {
+    /// Encode this object as pretty-printed JSON.
+    pub fn to_json(&self) -> FastCryptoResult<String> {
+        serde_json::to_string_pretty(&EncryptedObjectJson::try_from(self)?)
+            .map_err(|_| InvalidInput)
+    }
+
+    /// Decode an object from its JSON encoding.
+    pub fn from_json(json: &str) -> FastCryptoResult<Self> {
+        serde_json::from_str::<EncryptedObjectJson>(json)
+            .map_err(|e| GeneralError(format!("Invalid JSON: {}", e)))?
+            .try_into()
+    }
+}
+
+impl TryFrom<&EncryptedObject> for EncryptedObjectJson {
+    type Error = fastcrypto::error::FastCryptoError;
+
+    fn try_from(object: &EncryptedObject) -> FastCryptoResult<Self> {
+        Ok(EncryptedObjectJson {
+            version: object.version,
+            package_id: object.package_id,
+            id: Hex::encode(&object.id),
+            services: object
+                .services
+                .iter()
+                .enumerate()
+                .map(|(i, (object_id, share_index))| ServiceJson {
+                    object_id: *object_id,
+                    share_index: *share_index,
+                    key_version: object
+                        .key_versions
+                        .as_ref()
+                        .and_then(|key_versions| key_versions.get(i).copied()),
+                })
+                .collect(),
+            threshold: object.threshold,
+            encrypted_shares: match &object.encrypted_shares {
+                IBEEncryptions::BonehFranklinBLS12381 {
+                    nonce,
+                    encrypted_shares,
+                    encrypted_randomness,
+                } => IBEEncryptionsJson::BonehFranklinBLS12381 {
+                    nonce: Base64::encode(bcs::to_bytes(nonce).map_err(|_| InvalidInput)?),
+                    encrypted_shares: encrypted_shares.iter().map(Base64::encode).collect(),
+                    encrypted_randomness: Base64::encode(encrypted_randomness),
+                },
+            },
+            ciphertext: match &object.ciphertext {
+                Ciphertext::Aes256Gcm { blob, aad } => CiphertextJson::Aes256Gcm {
+                    blob: Base64::encode(blob),
+                    aad: aad.as_ref().map(Base64::encode),
+                },
+                Ciphertext::Hmac256Ctr { blob, aad, mac } => CiphertextJson::Hmac256Ctr {
+                    blob: Base64::encode(blob),
+                    aad: aad.as_ref().map(Base64::encode),
+                    mac: Base64::encode(mac),
+                },
+                Ciphertext::Plain => CiphertextJson::Plain,
+                Ciphertext::KeyWrap { wrapped_key } => CiphertextJson::KeyWrap {
+                    wrapped_key: Base64::encode(wrapped_key),
+                },
+                Ciphertext::Aes256GcmWithHeader { header, blob, aad } => {
+                    CiphertextJson::Aes256GcmWithHeader {
+                        header: header.into(),
+                        blob: Base64::encode(blob),
+                        aad: aad.as_ref().map(Base64::encode),
+                    }
+                }
+                Ciphertext::Hmac256CtrWithHeader {
+                    header,
+                    blob,
+                    mac,
+                    aad,
+                } => CiphertextJson::Hmac256CtrWithHeader {
+                    header: header.into(),
+                    blob: Base64::encode(blob),
+                    mac: Base64::encode(mac),
+                    aad: aad.as_ref().map(Base64::encode),
+                },
+            },
+            metadata: object.metadata.as_ref().map(Base64::encode),
+        })
+    }
+}
+
+impl TryFrom<EncryptedObjectJson> for EncryptedObject {
+    type Error = fastcrypto::error::FastCryptoError;
+
+    fn try_from(json: EncryptedObjectJson) -> FastCryptoResult<Self> {
+        // Either all or none of the services have a key version.
+        let key_versions = match json
+            .services
+            .iter()
+            .map(|service| service.key_version)
+            .collect::<Option<Vec<_>>>()
+        {
+            Some(key_versions) if !key_versions.is_empty() => Some(key_versions),
+            _ if json
+                .services
+                .iter()
+                .all(|service| service.key_version.is_none()) =>
+            {
+                None
+            }
+            _ => return Err(InvalidInput),
+        };
+        let object = EncryptedObject {
+            version: json.version,
+            package_id: json.package_id,
+            id: Hex::decode(&json.id)?,
+            services: json
+                .services
+                .into_iter()
+                .map(|service| (service.object_id, service.share_index))
+                .collect(),
+            threshold: json.threshold,
+            encrypted_shares: match json.encrypted_shares {
+                IBEEncryptionsJson::BonehFranklinBLS12381 {
+                    nonce,
+                    encrypted_shares,
+                    encrypted_randomness,
+                } => IBEEncryptions::BonehFranklinBLS12381 {
+                    nonce: bcs::from_bytes(&Base64::decode(&nonce)?).map_err(|_| InvalidInput)?,
+                    encrypted_shares: encrypted_shares
+                        .iter()
+                        .map(|share| decode_key(share))
+                        .collect::<FastCryptoResult<_>>()?,
+                    encrypted_randomness: decode_key(&encrypted_randomness)?,
+                },
+            },
+            ciphertext: match json.ciphertext {
+                CiphertextJson::Aes256Gcm { blob, aad } => Ciphertext::Aes256Gcm {
+                    blob: Base64::decode(&blob)?,
+                    aad: decode_optional(aad)?,
+                },
+                CiphertextJson::Hmac256Ctr { blob, aad, mac } => Ciphertext::Hmac256Ctr {
+                    blob: Base64::decode(&blob)?,
+                    aad: decode_optional(aad)?,
+                    mac: decode_key(&mac)?,
+                },
+                CiphertextJson::Plain => Ciphertext::Plain,
+                CiphertextJson::KeyWrap { wrapped_key } => Ciphertext::KeyWrap {
+                    wrapped_key: Base64::decode(&wrapped_key)?,
+                },
+                CiphertextJson::Aes256GcmWithHeader { header, blob, aad } => {
+                    Ciphertext::Aes256GcmWithHeader {
+                        header: header.try_into()?,
+                        blob: Base64::decode(&blob)?,
+                        aad: decode_optional(aad)?,
+                    }
+                }
+                CiphertextJson::Hmac256CtrWithHeader {
+                    header,
+                    blob,
+                    mac,
+                    aad,
+                } => Ciphertext::Hmac256CtrWithHeader {
+                    header: header.try_into()?,
+                    blob: Base64::decode(&blob)?,
+                    mac: decode_key(&mac)?,
+                    aad: decode_optional(aad)?,
+                },
+            },
+            metadata: decode_optional(json.metadata)?,
+            key_versions,
+        };
+
+        // Round trip through BCS to apply the same checks as when parsing BCS, e.g. of the version.
+        bcs::to_bytes(&object)
+            .and_then(|bytes| bcs::from_bytes(&bytes))
+            .map_err(|_| InvalidInput)
+    }
+}
+
+impl From<&DemHeader> for DemHeaderJson {
+    fn from(header: &DemHeader) -> Self {
+        DemHeaderJson {
+            padding: header.padding.map(|padding| padding.to_string()),
+            compression: header
+                .compression
+                .map(|compression| compression.to_string()),
+        }
+    }
+}
+
+impl TryFrom<DemHeaderJson> for DemHeader {
+    type Error = fastcrypto::error::FastCryptoError;
+
+    fn try_from(json: DemHeaderJson) -> FastCryptoResult<Self> {
+        Ok(DemHeader {
+            padding: json
+                .padding
+                .map(|padding| FromStr::from_str(&padding).map_err(GeneralError))
+                .transpose()?,
+            compression: json
+                .compression
+                .map(|compression| FromStr::from_str(&compression).map_err(GeneralError))
+                .transpose()?,
+        })
+    }
+}
+
+fn decode_key(encoded: &str) -> FastCryptoResult<[u8; KEY_SIZE]> {
+    Base64::decode(encoded)?
+        .try_into()
+        .map_err(|_| InvalidInput)
+}
+
+fn decode_optional(encoded: Option<String>) -> FastCryptoResult<Option<Vec<u8>>> {
+    encoded.map(|encoded| Base64::decode(&encoded)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ibe, seal_encrypt, seal_encrypt_with_key_versions, seal_encrypt_with_metadata, Compression,
+        EncryptionInput, IBEPublicKeys, Metadata, Padding, SymmetricKey,
+    };
+    use fastcrypto::groups::GroupElement;
+
+    #[test]
+    fn test_json_round_trip() {
+        let (_, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let header = DemHeader {
+            padding: Some(Padding::Block(32)),
+            compression: Some(Compression::Deflate),
+        };
+        let inputs = [
+            EncryptionInput::Aes256Gcm {
+                data: b"data".to_vec(),
+                aad: Some(b"aad".to_vec()),
+            },
+            EncryptionInput::Hmac256Ctr {
+                data: b"data".to_vec(),
+                aad: None,
+            },
+            EncryptionInput::Plain,
+            EncryptionInput::KeyWrap {
+                key: SymmetricKey::from([3u8; KEY_SIZE]),
+            },
+            EncryptionInput::Aes256GcmWithHeader {
+                data: b"data".to_vec(),
+                aad: None,
+                header: header.clone(),
+            },
+            EncryptionInput::Hmac256CtrWithHeader {
+                data: b"data".to_vec(),
+                aad: Some(b"aad".to_vec()),
+                header,
+            },
+        ];
+
+        for input in inputs {
+            let (object, _) = seal_encrypt(
+                ObjectID::random(),
+                vec![1, 2, 3],
+                vec![ObjectID::random()],
+                &public_keys,
+                1,
+                input,
+            )
+            .unwrap();
+            let json = object.to_json().unwrap();
+            let parsed = EncryptedObject::from_json(&json).unwrap();
+            assert_eq!(
+                bcs::to_bytes(&parsed).unwrap(),
+                bcs::to_bytes(&object).unwrap()
+            );
+            assert_eq!(parsed.to_json().unwrap(), json);
+        }
+
+        let (object, _) = seal_encrypt_with_metadata(
+            ObjectID::random(),
+            vec![],
+            vec![ObjectID::random()],
+            &public_keys,
+            1,
+            EncryptionInput::Plain,
+            &Metadata::default(),
+        )
+        .unwrap();
+        let parsed = EncryptedObject::from_json(&object.to_json().unwrap()).unwrap();
+        assert_eq!(
+            bcs::to_bytes(&parsed).unwrap(),
+            bcs::to_bytes(&object).unwrap()
+        );
+
+        let (object, _) = seal_encrypt_with_key_versions(
+            ObjectID::random(),
+            vec![],
+            vec![ObjectID::random(), ObjectID::random()],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![public_key, public_key]),
+            1,
+            EncryptionInput::Plain,
+            None,
+            vec![0, 3],
+        )
+        .unwrap();
+        let json = object.to_json().unwrap();
+        let parsed = EncryptedObject::from_json(&json).unwrap();
+        assert_eq!(parsed.key_versions, Some(vec![0, 3]));
+        assert_eq!(
+            bcs::to_bytes(&parsed).unwrap(),
+            bcs::to_bytes(&object).unwrap()
+        );
+
+        // Key versions must be given for all services or none.
+        let mut modified: serde_json::Value = serde_json::from_str(&json).unwrap();
+        modified["services"][0]
+            .as_object_mut()
+            .unwrap()
+            .remove("key_version");
+        assert!(EncryptedObject::from_json(&modified.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_json_encoding() {
+        let object = EncryptedObject {
+            version: 0,
+            package_id: ObjectID::from_str("0x1").unwrap(),
+            id: vec![1, 2, 3],
+            services: vec![(ObjectID::from_str("0x2").unwrap(), 1)],
+            threshold: 1,
+            encrypted_shares: IBEEncryptions::BonehFranklinBLS12381 {
+                nonce: ibe::Nonce::generator(),
+                encrypted_shares: vec![[0u8; KEY_SIZE]],
+                encrypted_randomness: [0u8; KEY_SIZE],
+            },
+            ciphertext: Ciphertext::Aes256Gcm {
+                blob: b"blob".to_vec(),
+                aad: None,
+            },
+            metadata: None,
+            key_versions: None,
+        };
+        let json: serde_json::Value = serde_json::from_str(&object.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["package_id"],
+            "0x0000000000000000000000000000000000000000000000000000000000000001"
+        );
+        assert_eq!(json["id"], "010203");
+        assert_eq!(json["services"][0]["share_index"], 1);
+        assert_eq!(json["encrypted_shares"]["type"], "BonehFranklinBLS12381");
+        assert_eq!(json["ciphertext"]["type"], "Aes256Gcm");
+        assert_eq!(json["ciphertext"]["blob"], "YmxvYg==");
+        assert!(json["ciphertext"]["aad"].is_null());
+
+        // Unknown fields, unsupported versions and invalid encodings are rejected.
+        let mut modified = json.clone();
+        modified["unknown"] = 1.into();
+        assert!(EncryptedObject::from_json(&modified.to_string()).is_err());
+        let mut modified = json.clone();
+        modified["version"] = 2.into();
+        assert!(EncryptedObject::from_json(&modified.to_string()).is_err());
+        let mut modified = json.clone();
+        modified["ciphertext"]["blob"] = "not base64!".into();
+        assert!(EncryptedObject::from_json(&modified.to_string()).is_err());
+    }
+}
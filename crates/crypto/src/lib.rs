@@ -1,29 +1,63 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::dem::Hmac256Ctr;
-use crate::ibe::{decrypt_deterministic, encrypt_batched_deterministic};
+//! Without the default `std` feature, the code of this crate is `no_std` and only uses `core` and `alloc`. Its
+//! dependencies, e.g. `fastcrypto` and `serde_json`, are not gated by the feature, so whether the crate builds for a
+//! target without `std` depends on them. The batch APIs, Deflate compression and the functions using the thread local
+//! RNG, e.g. `seal_encrypt`, are only available with `std`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use crate::dem::{AesKeyWrap, Hmac256Ctr};
+use crate::ibe::{
+    decrypt_batched_deterministic_with_gid, decrypt_with_gid,
+    encrypt_batched_deterministic_with_gid,
+};
 use crate::tss::{combine, interpolate, SecretSharing};
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+pub use compression::{Compression, DEFAULT_MAX_DECOMPRESSED_SIZE};
+use core::fmt::{Debug, Formatter};
+use core::ops::Deref;
 use dem::Aes256Gcm;
 use fastcrypto::error::FastCryptoError::{GeneralError, InvalidInput};
 use fastcrypto::error::FastCryptoResult;
+use fastcrypto::groups::bls12381::G1Element;
 use fastcrypto::groups::Scalar;
 use fastcrypto::hmac::{hmac_sha3_256, HmacKey};
+use fastcrypto::traits::{AllowedRng, ToFromBytes};
 use itertools::Itertools;
+pub use object_id::ObjectID;
+pub use padding::Padding;
+use prelude::*;
+#[cfg(any(feature = "std", test))]
 use rand::thread_rng;
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
-pub use sui_types::base_types::ObjectID;
-use sui_types::crypto::ToFromBytes;
 use tss::split;
 use utils::generate_random_bytes;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
 
+pub mod armor;
+#[cfg(feature = "std")]
+pub mod batch;
+pub mod bundle;
+pub mod compression;
 pub mod dem;
 pub mod elgamal;
 pub mod gf256;
 pub mod ibe;
+mod json;
+pub mod object_id;
+pub mod onchain;
+pub mod padding;
+pub mod patterns;
 mod polynomial;
+mod prelude;
+mod serialization;
 pub mod tss;
 mod utils;
 
@@ -33,12 +67,52 @@ pub const DST: &[u8] = b"SUI-SEAL-IBE-BLS12381-00";
 /// The domain separation tag for the hash-to-group function.
 pub const DST_POP: &[u8] = b"SUI-SEAL-IBE-BLS12381-POP-00";
 
+/// The domain separation tag for proofs of knowledge of user secret keys.
+pub const DST_USK_POK: &[u8] = b"SUI-SEAL-IBE-BLS12381-USK-POK-00";
+
+/// The domain separation tag for the coefficients of batched user secret key verification.
+pub const DST_USK_BATCH: &[u8] = b"SUI-SEAL-IBE-BLS12381-USK-BATCH-00";
+
 pub const KEY_SIZE: usize = 32;
 
+/// A symmetric key, e.g. the base key of an encryption or a key derived from it.
+/// The key is zeroized when dropped and is redacted in [Debug] output.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SymmetricKey([u8; KEY_SIZE]);
+
+impl SymmetricKey {
+    pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
+        &self.0
+    }
+}
+
+impl From<[u8; KEY_SIZE]> for SymmetricKey {
+    fn from(bytes: [u8; KEY_SIZE]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl Deref for SymmetricKey {
+    type Target = [u8; KEY_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Debug for SymmetricKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("SymmetricKey([REDACTED])")
+    }
+}
+
 /// This represents an encrypted object.
 /// Must be aligned with TypeScript type.
-#[serde_as]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Version 0 objects do not have the `metadata` field in their serialization. Version 1 objects have an optional
+/// encrypted [Metadata] block as the last field. Version 2 objects also record the version of the master key of each
+/// service. See the `serialization` module.
+#[derive(Clone, Debug)]
 pub struct EncryptedObject {
     pub version: u8,
     pub package_id: ObjectID,
@@ -48,6 +122,32 @@ pub struct EncryptedObject {
     pub threshold: u8,
     pub encrypted_shares: IBEEncryptions,
     pub ciphertext: Ciphertext,
+    /// The BCS serialization of a [Metadata] encrypted with AES-256-GCM under the key for [KeyPurpose::Metadata].
+    pub metadata: Option<Vec<u8>>,
+    /// The version of the master key of each service, in the same order as `services`. Only set for version 2
+    /// objects. Older objects are encrypted to version 0 of every key, see [EncryptedObject::key_version].
+    pub key_versions: Option<Vec<u32>>,
+}
+
+impl EncryptedObject {
+    /// The version of the master key the share of the service with the given index is encrypted to.
+    pub fn key_version(&self, index: usize) -> u32 {
+        self.key_versions
+            .as_ref()
+            .and_then(|key_versions| key_versions.get(index).copied())
+            .unwrap_or(0)
+    }
+}
+
+/// Information about the encrypted content which can be decrypted without downloading or decrypting the content itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
+    /// The creation time in milliseconds since the Unix epoch.
+    pub created_at: Option<u64>,
+    /// Application specific key-value pairs.
+    pub tags: Vec<(String, String)>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -62,6 +162,64 @@ pub enum Ciphertext {
         mac: [u8; KEY_SIZE],
     },
     Plain,
+    /// A caller-supplied key wrapped with AES key wrap (RFC 3394) under the DEM key.
+    KeyWrap {
+        wrapped_key: Vec<u8>,
+    },
+    /// Same as `Aes256Gcm` but the payload is encoded as described by the header before encryption.
+    Aes256GcmWithHeader {
+        header: DemHeader,
+        blob: Vec<u8>,
+        aad: Option<Vec<u8>>,
+    },
+    /// Same as `Hmac256Ctr` but the payload is encoded as described by the header before encryption.
+    Hmac256CtrWithHeader {
+        header: DemHeader,
+        blob: Vec<u8>,
+        mac: [u8; KEY_SIZE],
+        aad: Option<Vec<u8>>,
+    },
+}
+
+/// Describes how a payload is encoded before it is encrypted by the DEM, e.g., how it is compressed and padded.
+/// The header is authenticated together with the additional authenticated data.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DemHeader {
+    pub padding: Option<Padding>,
+    pub compression: Option<Compression>,
+}
+
+impl DemHeader {
+    /// Encode a message before encryption. The message is first compressed and then padded.
+    fn encode(&self, data: &[u8]) -> FastCryptoResult<Vec<u8>> {
+        let payload = match &self.compression {
+            Some(compression) => compression.compress(data)?,
+            None => data.to_vec(),
+        };
+        match &self.padding {
+            Some(padding) => padding.pad(&payload),
+            None => Ok(payload),
+        }
+    }
+
+    /// Decode a decrypted payload. Fails if the decompressed message is larger than `max_decompressed_size`.
+    fn decode(&self, payload: Vec<u8>, max_decompressed_size: usize) -> FastCryptoResult<Vec<u8>> {
+        let payload = match &self.padding {
+            Some(padding) => padding.unpad(payload)?,
+            None => payload,
+        };
+        match &self.compression {
+            Some(compression) => compression.decompress(&payload, max_decompressed_size),
+            None => Ok(payload),
+        }
+    }
+
+    /// The data authenticated by the DEM: The serialized header followed by the additional authenticated data.
+    fn authenticated_data(&self, aad: &Option<Vec<u8>>) -> Vec<u8> {
+        let mut authenticated_data = bcs::to_bytes(self).expect("serialization should not fail");
+        authenticated_data.extend_from_slice(aad.as_ref().map_or(&[], |v| v));
+        authenticated_data
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -84,9 +242,31 @@ pub enum IBEUserSecretKeys {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum EncryptionInput {
-    Aes256Gcm { data: Vec<u8>, aad: Option<Vec<u8>> },
-    Hmac256Ctr { data: Vec<u8>, aad: Option<Vec<u8>> },
+    Aes256Gcm {
+        data: Vec<u8>,
+        aad: Option<Vec<u8>>,
+    },
+    Hmac256Ctr {
+        data: Vec<u8>,
+        aad: Option<Vec<u8>>,
+    },
     Plain,
+    /// Protect an existing key instead of a message. `seal_decrypt` returns the key.
+    /// This variant is never serialized, so the key cannot end up in a serialized input.
+    #[serde(skip)]
+    KeyWrap {
+        key: SymmetricKey,
+    },
+    Aes256GcmWithHeader {
+        data: Vec<u8>,
+        aad: Option<Vec<u8>>,
+        header: DemHeader,
+    },
+    Hmac256CtrWithHeader {
+        data: Vec<u8>,
+        aad: Option<Vec<u8>>,
+        header: DemHeader,
+    },
 }
 
 /// Encrypt the given plaintext. This is done as follows:
@@ -101,7 +281,8 @@ pub enum EncryptionInput {
 /// @param public_keys The public keys of the key servers.
 /// @param threshold The threshold for the TSS.
 /// @param encryption_input The encryption input.
-/// @return The encrypted object and the derived symmetric key used for the encryption.
+/// @return The encrypted object and the derived symmetric key used for the encryption. The key is zeroized when dropped.
+#[cfg(any(feature = "std", test))]
 pub fn seal_encrypt(
     package_id: ObjectID,
     id: Vec<u8>,
@@ -109,53 +290,145 @@ pub fn seal_encrypt(
     public_keys: &IBEPublicKeys,
     threshold: u8,
     encryption_input: EncryptionInput,
-) -> FastCryptoResult<(EncryptedObject, [u8; KEY_SIZE])> {
+) -> FastCryptoResult<(EncryptedObject, SymmetricKey)> {
+    let gid = ibe::hash_id(&create_full_id(&package_id, &id));
+    seal_encrypt_with_gid(
+        package_id,
+        id,
+        key_servers,
+        public_keys,
+        threshold,
+        encryption_input,
+        None,
+        None,
+        &gid,
+        &mut thread_rng(),
+    )
+}
+
+/// Same as [seal_encrypt] but also attaches an encrypted metadata block, which can be decrypted using
+/// [seal_decrypt_metadata]. The resulting object has version 1.
+#[cfg(any(feature = "std", test))]
+pub fn seal_encrypt_with_metadata(
+    package_id: ObjectID,
+    id: Vec<u8>,
+    key_servers: Vec<ObjectID>,
+    public_keys: &IBEPublicKeys,
+    threshold: u8,
+    encryption_input: EncryptionInput,
+    metadata: &Metadata,
+) -> FastCryptoResult<(EncryptedObject, SymmetricKey)> {
+    let gid = ibe::hash_id(&create_full_id(&package_id, &id));
+    seal_encrypt_with_gid(
+        package_id,
+        id,
+        key_servers,
+        public_keys,
+        threshold,
+        encryption_input,
+        Some(metadata),
+        None,
+        &gid,
+        &mut thread_rng(),
+    )
+}
+
+/// Same as [seal_encrypt] but records the version of the master key of each key server, so objects remain
+/// decryptable when key servers rotate their master keys. The public keys must be those of the given versions.
+/// An encrypted metadata block is attached if given. The resulting object has version 2.
+///
+/// @param key_versions The version of the master key of each key server, in the same order as `key_servers`.
+#[cfg(any(feature = "std", test))]
+#[allow(clippy::too_many_arguments)]
+pub fn seal_encrypt_with_key_versions(
+    package_id: ObjectID,
+    id: Vec<u8>,
+    key_servers: Vec<ObjectID>,
+    public_keys: &IBEPublicKeys,
+    threshold: u8,
+    encryption_input: EncryptionInput,
+    metadata: Option<&Metadata>,
+    key_versions: Vec<u32>,
+) -> FastCryptoResult<(EncryptedObject, SymmetricKey)> {
+    let gid = ibe::hash_id(&create_full_id(&package_id, &id));
+    seal_encrypt_with_gid(
+        package_id,
+        id,
+        key_servers,
+        public_keys,
+        threshold,
+        encryption_input,
+        metadata,
+        Some(key_versions),
+        &gid,
+        &mut thread_rng(),
+    )
+}
+
+/// Same as [seal_encrypt] but with the full id already hashed to G1.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn seal_encrypt_with_gid<R: AllowedRng>(
+    package_id: ObjectID,
+    id: Vec<u8>,
+    key_servers: Vec<ObjectID>,
+    public_keys: &IBEPublicKeys,
+    threshold: u8,
+    encryption_input: EncryptionInput,
+    metadata: Option<&Metadata>,
+    key_versions: Option<Vec<u32>>,
+    gid: &G1Element,
+    rng: &mut R,
+) -> FastCryptoResult<(EncryptedObject, SymmetricKey)> {
     let number_of_shares = key_servers.len() as u8;
     if threshold > number_of_shares || threshold == 0 {
         return Err(InvalidInput);
     }
-
-    let mut rng = thread_rng();
-    let full_id = create_full_id(&package_id, &id);
+    if key_versions
+        .as_ref()
+        .is_some_and(|key_versions| key_versions.len() != key_servers.len())
+    {
+        return Err(InvalidInput);
+    }
 
     // Generate a random base key
-    let base_key = generate_random_bytes(&mut rng);
+    let base_key = SymmetricKey(generate_random_bytes(rng));
 
     // Derive the key used by the DEM
     let dem_key = derive_key(KeyPurpose::DEM, &base_key);
-    let ciphertext = match encryption_input {
-        EncryptionInput::Aes256Gcm { data, aad } => Ciphertext::Aes256Gcm {
-            blob: Aes256Gcm::encrypt(&data, aad.as_ref().unwrap_or(&vec![]), &dem_key),
-            aad,
-        },
-        EncryptionInput::Hmac256Ctr { data, aad } => {
-            let (blob, mac) = Hmac256Ctr::encrypt(&data, aad.as_ref().unwrap_or(&vec![]), &dem_key);
-            Ciphertext::Hmac256Ctr { blob, mac, aad }
-        }
-        EncryptionInput::Plain => Ciphertext::Plain,
-    };
+    let ciphertext = encrypt_with_dem(encryption_input, &dem_key)?;
+
+    // Encrypt the metadata under its own key
+    let metadata = metadata.map(|metadata| {
+        Aes256Gcm::encrypt(
+            &bcs::to_bytes(metadata).expect("serialization should not fail"),
+            &[],
+            &derive_key(KeyPurpose::Metadata, &base_key),
+        )
+    });
 
-    // Secret share the derived key
-    let SecretSharing {
-        indices, shares, ..
-    } = split(&mut rng, base_key, threshold, number_of_shares)?;
+    // Secret share the derived key. The shares are zeroized when dropped.
+    let sharing: Zeroizing<SecretSharing<KEY_SIZE>> =
+        Zeroizing::new(split(rng, &*base_key, threshold, number_of_shares)?);
 
-    let services = key_servers.into_iter().zip(indices).collect::<Vec<_>>();
+    let services = key_servers
+        .into_iter()
+        .zip(sharing.indices().iter().copied())
+        .collect::<Vec<_>>();
 
     let encrypted_shares = match public_keys {
         IBEPublicKeys::BonehFranklinBLS12381(public_keys) => {
             if public_keys.len() != number_of_shares as usize {
                 return Err(InvalidInput);
             }
-            let randomness = ibe::Randomness::rand(&mut rng);
+            let randomness = ibe::Randomness::rand(rng);
 
             // Encrypt the shares using the IBE keys.
             // Use the share index as the `index` parameter for the IBE decryption, allowing to encrypt shares for the same identity to the same public key.
-            let (nonce, encrypted_shares) = encrypt_batched_deterministic(
+            let (nonce, encrypted_shares) = encrypt_batched_deterministic_with_gid(
                 &randomness,
-                &shares,
+                sharing.shares(),
                 public_keys,
-                &full_id,
+                gid,
                 &services,
             )?;
 
@@ -173,13 +446,19 @@ pub fn seal_encrypt(
 
     Ok((
         EncryptedObject {
-            version: 0,
+            version: match (&metadata, &key_versions) {
+                (_, Some(_)) => 2,
+                (Some(_), None) => 1,
+                (None, None) => 0,
+            },
             package_id,
             id,
             services,
             threshold,
             encrypted_shares,
             ciphertext,
+            metadata,
+            key_versions,
         },
         dem_key,
     ))
@@ -194,29 +473,103 @@ pub fn seal_encrypt(
 /// @param encrypted_object The encrypted object. See `seal_encrypt`.
 /// @param user_secret_keys The user secret keys. It's assumed that these are validated. Otherwise, the decryption will fail or, eg. in the case of using `Plain` mode, the derived key will be wrong.
 /// @param public_keys The public keys of the key servers. If provided, all shares will be decrypted and checked for consistency.
-/// @return The decrypted plaintext or, if `Plain` mode was used, the derived key.
+/// @return The decrypted plaintext or, if `Plain` mode was used, the derived key or, if `KeyWrap` mode was used, the wrapped key.
 pub fn seal_decrypt(
     encrypted_object: &EncryptedObject,
     user_secret_keys: &IBEUserSecretKeys,
     public_keys: Option<&IBEPublicKeys>,
 ) -> FastCryptoResult<Vec<u8>> {
+    seal_decrypt_with_max_decompressed_size(
+        encrypted_object,
+        user_secret_keys,
+        public_keys,
+        DEFAULT_MAX_DECOMPRESSED_SIZE,
+    )
+}
+
+/// Same as [seal_decrypt] but with a custom bound on the size of a decompressed message instead of
+/// [DEFAULT_MAX_DECOMPRESSED_SIZE]. The bound only applies if the message was compressed.
+pub fn seal_decrypt_with_max_decompressed_size(
+    encrypted_object: &EncryptedObject,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: Option<&IBEPublicKeys>,
+    max_decompressed_size: usize,
+) -> FastCryptoResult<Vec<u8>> {
+    let gid = ibe::hash_id(&create_full_id(
+        &encrypted_object.package_id,
+        &encrypted_object.id,
+    ));
+    seal_decrypt_with_gid(
+        encrypted_object,
+        user_secret_keys,
+        public_keys,
+        &gid,
+        max_decompressed_size,
+    )
+}
+
+/// Same as [seal_decrypt_with_max_decompressed_size] but with the full id already hashed to G1.
+pub(crate) fn seal_decrypt_with_gid(
+    encrypted_object: &EncryptedObject,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: Option<&IBEPublicKeys>,
+    gid: &G1Element,
+    max_decompressed_size: usize,
+) -> FastCryptoResult<Vec<u8>> {
+    // Derive symmetric key and decrypt the ciphertext
+    let base_key = decrypt_base_key(encrypted_object, user_secret_keys, public_keys, gid)?;
+    let dem_key = derive_key(KeyPurpose::DEM, &base_key);
+    decrypt_with_dem_bounded(
+        &encrypted_object.ciphertext,
+        &dem_key,
+        max_decompressed_size,
+    )
+}
+
+/// Decrypt the metadata block of an encrypted object without decrypting the content. Returns `None` if the object
+/// does not have a metadata block. See [seal_decrypt] for the parameters.
+pub fn seal_decrypt_metadata(
+    encrypted_object: &EncryptedObject,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: Option<&IBEPublicKeys>,
+) -> FastCryptoResult<Option<Metadata>> {
+    let Some(metadata) = &encrypted_object.metadata else {
+        return Ok(None);
+    };
+    let gid = ibe::hash_id(&create_full_id(
+        &encrypted_object.package_id,
+        &encrypted_object.id,
+    ));
+    let base_key = decrypt_base_key(encrypted_object, user_secret_keys, public_keys, &gid)?;
+    let metadata = Aes256Gcm::decrypt(metadata, &[], &derive_key(KeyPurpose::Metadata, &base_key))?;
+    bcs::from_bytes(&metadata)
+        .map(Some)
+        .map_err(|_| InvalidInput)
+}
+
+/// Reconstruct the base key of an encrypted object from the shares decrypted with the given user secret keys.
+fn decrypt_base_key(
+    encrypted_object: &EncryptedObject,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: Option<&IBEPublicKeys>,
+    gid: &G1Element,
+) -> FastCryptoResult<SymmetricKey> {
     let EncryptedObject {
         version,
-        package_id,
-        id,
         encrypted_shares,
         services,
         threshold,
-        ciphertext,
+        metadata,
+        key_versions,
         ..
     } = encrypted_object;
 
-    if *version != 0 {
-        return Err(InvalidInput);
+    match (version, metadata, key_versions) {
+        (0, None, None) | (1, _, None) => {}
+        (2, _, Some(key_versions)) if key_versions.len() == services.len() => {}
+        _ => return Err(InvalidInput),
     }
 
-    let full_id = create_full_id(package_id, id);
-
     // Decap IBE keys and decrypt shares
     let shares = match (&encrypted_shares, user_secret_keys) {
         (
@@ -248,13 +601,13 @@ pub fn seal_decrypt(
                 .into_iter()
                 .map(|i| {
                     let index = services[i].1;
-                    (index, ibe::decrypt(
+                    (index, decrypt_with_gid(
                         nonce,
                         &encrypted_shares[i],
                         user_secret_keys
                             .get(&services[i].0)
                             .expect("This shouldn't happen: It's checked above that this secret key is available"),
-                        &full_id,
+                        gid,
                         &services[i],
                     ))
                 })
@@ -263,29 +616,98 @@ pub fn seal_decrypt(
     };
 
     // Create the base key from the shares
-    let base_key = combine(&shares)?;
+    let base_key = SymmetricKey(combine(&shares)?);
 
     // If the public keys are given, we can decrypt all shares and check for consistency
     if let Some(public_keys) = public_keys {
-        encrypted_shares.check_share_consistency(
-            &shares,
-            &full_id,
-            services,
-            public_keys,
-            &base_key,
-        )?;
+        encrypted_shares.check_share_consistency(&shares, gid, services, public_keys, &base_key)?;
     }
+    Ok(base_key)
+}
 
-    // Derive symmetric key and decrypt the ciphertext
-    let dem_key = derive_key(KeyPurpose::DEM, &base_key);
+/// Encrypt the input using the DEM it specifies and the given key.
+pub(crate) fn encrypt_with_dem(
+    encryption_input: EncryptionInput,
+    dem_key: &[u8; KEY_SIZE],
+) -> FastCryptoResult<Ciphertext> {
+    Ok(match encryption_input {
+        EncryptionInput::Aes256Gcm { data, aad } => Ciphertext::Aes256Gcm {
+            blob: Aes256Gcm::encrypt(&data, aad.as_ref().unwrap_or(&vec![]), dem_key),
+            aad,
+        },
+        EncryptionInput::Hmac256Ctr { data, aad } => {
+            let (blob, mac) = Hmac256Ctr::encrypt(&data, aad.as_ref().unwrap_or(&vec![]), dem_key);
+            Ciphertext::Hmac256Ctr { blob, mac, aad }
+        }
+        EncryptionInput::Plain => Ciphertext::Plain,
+        EncryptionInput::KeyWrap { key } => Ciphertext::KeyWrap {
+            wrapped_key: AesKeyWrap::wrap(&key, dem_key),
+        },
+        EncryptionInput::Aes256GcmWithHeader { data, aad, header } => {
+            Ciphertext::Aes256GcmWithHeader {
+                blob: Aes256Gcm::encrypt(
+                    &header.encode(&data)?,
+                    &header.authenticated_data(&aad),
+                    dem_key,
+                ),
+                header,
+                aad,
+            }
+        }
+        EncryptionInput::Hmac256CtrWithHeader { data, aad, header } => {
+            let (blob, mac) = Hmac256Ctr::encrypt(
+                &header.encode(&data)?,
+                &header.authenticated_data(&aad),
+                dem_key,
+            );
+            Ciphertext::Hmac256CtrWithHeader {
+                header,
+                blob,
+                mac,
+                aad,
+            }
+        }
+    })
+}
+
+/// Decrypt a ciphertext using the given key. If `Plain` mode was used, the key itself is returned.
+/// Compressed messages are bounded by [DEFAULT_MAX_DECOMPRESSED_SIZE].
+pub fn decrypt_with_dem(
+    ciphertext: &Ciphertext,
+    dem_key: &[u8; KEY_SIZE],
+) -> FastCryptoResult<Vec<u8>> {
+    decrypt_with_dem_bounded(ciphertext, dem_key, DEFAULT_MAX_DECOMPRESSED_SIZE)
+}
+
+fn decrypt_with_dem_bounded(
+    ciphertext: &Ciphertext,
+    dem_key: &[u8; KEY_SIZE],
+    max_decompressed_size: usize,
+) -> FastCryptoResult<Vec<u8>> {
     match ciphertext {
         Ciphertext::Aes256Gcm { blob, aad } => {
-            Aes256Gcm::decrypt(blob, aad.as_ref().map_or(&[], |v| v), &dem_key)
+            Aes256Gcm::decrypt(blob, aad.as_ref().map_or(&[], |v| v), dem_key)
         }
         Ciphertext::Hmac256Ctr { blob, aad, mac } => {
-            Hmac256Ctr::decrypt(blob, mac, aad.as_ref().map_or(&[], |v| v), &dem_key)
+            Hmac256Ctr::decrypt(blob, mac, aad.as_ref().map_or(&[], |v| v), dem_key)
         }
         Ciphertext::Plain => Ok(dem_key.to_vec()),
+        Ciphertext::KeyWrap { wrapped_key } => {
+            AesKeyWrap::unwrap(wrapped_key, dem_key).map(|key| SymmetricKey(key).to_vec())
+        }
+        Ciphertext::Aes256GcmWithHeader { header, blob, aad } => header.decode(
+            Aes256Gcm::decrypt(blob, &header.authenticated_data(aad), dem_key)?,
+            max_decompressed_size,
+        ),
+        Ciphertext::Hmac256CtrWithHeader {
+            header,
+            blob,
+            mac,
+            aad,
+        } => header.decode(
+            Hmac256Ctr::decrypt(blob, mac, &header.authenticated_data(aad), dem_key)?,
+            max_decompressed_size,
+        ),
     }
 }
 
@@ -306,15 +728,18 @@ pub enum KeyPurpose {
     EncryptedRandomness,
     /// The key used by the DEM.
     DEM,
+    /// The key used to encrypt the metadata block.
+    Metadata,
 }
 
 /// Derive a key for a specific purpose from the base key.
-fn derive_key(purpose: KeyPurpose, derived_key: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+fn derive_key(purpose: KeyPurpose, derived_key: &[u8; KEY_SIZE]) -> SymmetricKey {
     let hmac_key = HmacKey::from_bytes(derived_key).expect("Fixed length");
-    match purpose {
+    SymmetricKey(match purpose {
         KeyPurpose::EncryptedRandomness => hmac_sha3_256(&hmac_key, &[0]).digest,
         KeyPurpose::DEM => hmac_sha3_256(&hmac_key, &[1]).digest,
-    }
+        KeyPurpose::Metadata => hmac_sha3_256(&hmac_key, &[2]).digest,
+    })
 }
 
 impl IBEEncryptions {
@@ -323,7 +748,7 @@ impl IBEEncryptions {
     fn check_share_consistency(
         &self,
         shares: &[(u8, [u8; KEY_SIZE])],
-        full_id: &[u8],
+        gid: &G1Element,
         services: &[(ObjectID, u8)],
         public_keys: &IBEPublicKeys,
         base_key: &[u8; KEY_SIZE],
@@ -332,7 +757,7 @@ impl IBEEncryptions {
         let polynomial = interpolate(shares)?;
 
         // Decrypt all shares using the derived key
-        let all_shares = self.decrypt_all_shares(full_id, services, public_keys, base_key)?;
+        let all_shares = self.decrypt_all_shares(gid, services, public_keys, base_key)?;
 
         // Check that all shares are points on the reconstructed polynomials
         if all_shares
@@ -347,7 +772,7 @@ impl IBEEncryptions {
     /// Given the derived key, decrypt all shares
     fn decrypt_all_shares(
         &self,
-        full_id: &[u8],
+        gid: &G1Element,
         services: &[(ObjectID, u8)],
         public_keys: &IBEPublicKeys,
         base_key: &[u8; KEY_SIZE],
@@ -368,18 +793,14 @@ impl IBEEncryptions {
                 // Decrypt all shares
                 match public_keys {
                     IBEPublicKeys::BonehFranklinBLS12381(public_keys) => {
-                        if public_keys.len() != encrypted_shares.len() {
-                            return Err(InvalidInput);
-                        }
-                        public_keys
-                            .iter()
-                            .zip(encrypted_shares)
-                            .zip(services)
-                            .map(|((pk, s), service)| {
-                                decrypt_deterministic(&nonce, s, pk, full_id, service)
-                                    .map(|s| (service.1, s))
-                            })
-                            .collect::<FastCryptoResult<_>>()
+                        let shares = decrypt_batched_deterministic_with_gid(
+                            &nonce,
+                            encrypted_shares,
+                            public_keys,
+                            gid,
+                            services,
+                        )?;
+                        Ok(services.iter().map(|(_, i)| *i).zip(shares).collect())
                     }
                 }
             }
@@ -390,15 +811,12 @@ impl IBEEncryptions {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::str::FromStr;
     use fastcrypto::{
         encoding::{Base64, Encoding},
-        groups::{
-            bls12381::{G1Element, Scalar},
-            HashToGroupElement,
-        },
+        groups::HashToGroupElement,
         serde_helpers::ToFromByteArray,
     };
-    use std::str::FromStr;
 
     #[test]
     fn test_hash_with_prefix_regression() {
@@ -564,6 +982,7 @@ mod tests {
             .map(|(s, kp)| (s, ibe::extract(&kp.0, &full_id)))
             .collect();
 
+        assert_eq!(format!("{:?}", key), "SymmetricKey([REDACTED])");
         assert_eq!(
             key.to_vec(),
             seal_decrypt(
@@ -575,6 +994,239 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_key_wrap_round_trip() {
+        let package_id = ObjectID::random();
+        let id = vec![1, 2, 3, 4];
+        let full_id = create_full_id(&package_id, &id);
+
+        let mut rng = rand::thread_rng();
+        let keypairs = (0..3)
+            .map(|_| ibe::generate_key_pair(&mut rng))
+            .collect_vec();
+
+        let services = keypairs.iter().map(|_| ObjectID::random()).collect_vec();
+
+        let public_keys =
+            IBEPublicKeys::BonehFranklinBLS12381(keypairs.iter().map(|(_, pk)| *pk).collect_vec());
+
+        let data_key = SymmetricKey::from(generate_random_bytes(&mut rng));
+        assert!(bcs::to_bytes(&EncryptionInput::KeyWrap {
+            key: data_key.clone()
+        })
+        .is_err());
+        let (encrypted, dem_key) = seal_encrypt(
+            package_id,
+            id,
+            services.clone(),
+            &public_keys,
+            2,
+            EncryptionInput::KeyWrap {
+                key: data_key.clone(),
+            },
+        )
+        .unwrap();
+        assert_ne!(dem_key, data_key);
+        match &encrypted.ciphertext {
+            Ciphertext::KeyWrap { wrapped_key } => assert_eq!(wrapped_key.len(), KEY_SIZE + 8),
+            _ => panic!("Expected a wrapped key"),
+        }
+
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+            services
+                .into_iter()
+                .zip(keypairs)
+                .map(|(s, kp)| (s, ibe::extract(&kp.0, &full_id)))
+                .collect(),
+        );
+        assert_eq!(
+            seal_decrypt(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap(),
+            data_key.to_vec()
+        );
+    }
+
+    #[test]
+    fn test_metadata_round_trip() {
+        let package_id = ObjectID::random();
+        let id = vec![1, 2, 3, 4];
+        let full_id = create_full_id(&package_id, &id);
+
+        let mut rng = rand::thread_rng();
+        let keypairs = (0..3)
+            .map(|_| ibe::generate_key_pair(&mut rng))
+            .collect_vec();
+
+        let services = keypairs.iter().map(|_| ObjectID::random()).collect_vec();
+
+        let public_keys =
+            IBEPublicKeys::BonehFranklinBLS12381(keypairs.iter().map(|(_, pk)| *pk).collect_vec());
+
+        let metadata = Metadata {
+            filename: Some("report.pdf".to_string()),
+            mime_type: Some("application/pdf".to_string()),
+            created_at: Some(1700000000000),
+            tags: vec![("department".to_string(), "cardiology".to_string())],
+        };
+        let (encrypted, _) = seal_encrypt_with_metadata(
+            package_id,
+            id,
+            services.clone(),
+            &public_keys,
+            2,
+            EncryptionInput::Aes256Gcm {
+                data: b"content".to_vec(),
+                aad: None,
+            },
+            &metadata,
+        )
+        .unwrap();
+        assert_eq!(encrypted.version, 1);
+
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+            services
+                .into_iter()
+                .zip(keypairs)
+                .map(|(s, kp)| (s, ibe::extract(&kp.0, &full_id)))
+                .collect(),
+        );
+        assert_eq!(
+            seal_decrypt_metadata(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap(),
+            Some(metadata)
+        );
+        assert_eq!(
+            seal_decrypt(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap(),
+            b"content"
+        );
+
+        // Metadata is not allowed for version 0 objects.
+        let mut invalid = encrypted.clone();
+        invalid.version = 0;
+        assert!(seal_decrypt(&invalid, &user_secret_keys, None).is_err());
+    }
+
+    #[test]
+    fn test_padding_round_trip() {
+        let package_id = ObjectID::random();
+        let id = vec![1, 2, 3, 4];
+        let full_id = create_full_id(&package_id, &id);
+
+        let mut rng = rand::thread_rng();
+        let (master_key, public_key) = ibe::generate_key_pair(&mut rng);
+        let service = ObjectID::random();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([(
+            service,
+            ibe::extract(&master_key, &full_id),
+        )]));
+
+        let header = DemHeader {
+            padding: Some(Padding::Block(256)),
+            compression: None,
+        };
+        let inputs = [
+            EncryptionInput::Aes256GcmWithHeader {
+                data: b"Hello, World!".to_vec(),
+                aad: Some(b"something".to_vec()),
+                header: header.clone(),
+            },
+            EncryptionInput::Hmac256CtrWithHeader {
+                data: b"Hello, World!".to_vec(),
+                aad: Some(b"something".to_vec()),
+                header: header.clone(),
+            },
+        ];
+        for input in inputs {
+            let (mut encrypted, _) = seal_encrypt(
+                package_id,
+                id.clone(),
+                vec![service],
+                &public_keys,
+                1,
+                input,
+            )
+            .unwrap();
+            match &encrypted.ciphertext {
+                Ciphertext::Aes256GcmWithHeader { blob, .. } => assert_eq!(blob.len(), 256 + 16),
+                Ciphertext::Hmac256CtrWithHeader { blob, .. } => assert_eq!(blob.len(), 256),
+                _ => panic!("Expected a ciphertext with a header"),
+            }
+            assert_eq!(
+                seal_decrypt(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap(),
+                b"Hello, World!"
+            );
+
+            // The header is authenticated.
+            match &mut encrypted.ciphertext {
+                Ciphertext::Aes256GcmWithHeader { header, .. }
+                | Ciphertext::Hmac256CtrWithHeader { header, .. } => {
+                    header.padding = Some(Padding::Block(128))
+                }
+                _ => unreachable!(),
+            }
+            assert!(seal_decrypt(&encrypted, &user_secret_keys, None).is_err());
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_compression_round_trip() {
+        let package_id = ObjectID::random();
+        let id = vec![1, 2, 3, 4];
+        let full_id = create_full_id(&package_id, &id);
+
+        let mut rng = rand::thread_rng();
+        let (master_key, public_key) = ibe::generate_key_pair(&mut rng);
+        let service = ObjectID::random();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([(
+            service,
+            ibe::extract(&master_key, &full_id),
+        )]));
+
+        let data = b"{\"level\":\"info\",\"message\":\"hello\"}\n".repeat(1000);
+        for compression in [
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+            Compression::Deflate,
+        ] {
+            let (encrypted, _) = seal_encrypt(
+                package_id,
+                id.clone(),
+                vec![service],
+                &public_keys,
+                1,
+                EncryptionInput::Aes256GcmWithHeader {
+                    data: data.clone(),
+                    aad: None,
+                    header: DemHeader {
+                        padding: Some(Padding::Padme),
+                        compression: Some(compression),
+                    },
+                },
+            )
+            .unwrap();
+            match &encrypted.ciphertext {
+                Ciphertext::Aes256GcmWithHeader { blob, .. } => {
+                    assert!(blob.len() < data.len() / 5)
+                }
+                _ => panic!("Expected a ciphertext with a header"),
+            }
+            assert_eq!(
+                seal_decrypt(&encrypted, &user_secret_keys, Some(&public_keys)).unwrap(),
+                data
+            );
+
+            // The decompressed size is bounded.
+            assert!(seal_decrypt_with_max_decompressed_size(
+                &encrypted,
+                &user_secret_keys,
+                None,
+                data.len() - 1
+            )
+            .is_err());
+        }
+    }
+
     #[test]
     fn typescript_test_vector() {
         let package_id = [0u8; 32];
@@ -587,7 +1239,8 @@ mod tests {
         ]
         .iter()
         .map(|key| {
-            Scalar::from_byte_array(&Base64::decode(key).unwrap().try_into().unwrap()).unwrap()
+            ibe::MasterKey::from_byte_array(&Base64::decode(key).unwrap().try_into().unwrap())
+                .unwrap()
         })
         .collect::<Vec<_>>();
         let public_keys = master_keys
@@ -685,13 +1338,16 @@ mod tests {
         // Decryption fails with all shares
         assert!(seal_decrypt(
             &encrypted,
-            &IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from(usks)),
+            &IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from(usks.clone())),
             None,
         )
         .is_err_and(|e| e == GeneralError("Invalid MAC".to_string())));
 
         // Consider only the first two shares
-        let usks = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([usks[0], usks[1]]));
+        let usks = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([
+            usks[0].clone(),
+            usks[1].clone(),
+        ]));
 
         // Decryption with the first two valid shares succeeds.
         assert_eq!(seal_decrypt(&encrypted, &usks, None,).unwrap(), data);
@@ -0,0 +1,157 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A minimal 32-byte object id which has the same BCS serialization as the `ObjectID` type used on Sui.
+//! It is used instead of the type from `sui_types` to allow this crate to be built for WebAssembly.
+
+use crate::prelude::*;
+use core::fmt::{Debug, Display, Formatter};
+use core::ops::Deref;
+use core::str::FromStr;
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// The length of an object id in bytes.
+pub const OBJECT_ID_LENGTH: usize = 32;
+
+/// The address of an object on Sui, e.g., a package or a key server object.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ObjectID([u8; OBJECT_ID_LENGTH]);
+
+impl ObjectID {
+    pub const ZERO: Self = Self([0u8; OBJECT_ID_LENGTH]);
+
+    pub const fn new(bytes: [u8; OBJECT_ID_LENGTH]) -> Self {
+        Self(bytes)
+    }
+
+    /// Create an object id from a byte slice. Returns an [InvalidInput] error if the slice does not have length [OBJECT_ID_LENGTH].
+    pub fn from_bytes<T: AsRef<[u8]>>(bytes: T) -> FastCryptoResult<Self> {
+        bytes
+            .as_ref()
+            .try_into()
+            .map(Self)
+            .map_err(|_| InvalidInput)
+    }
+
+    pub fn into_bytes(self) -> [u8; OBJECT_ID_LENGTH] {
+        self.0
+    }
+
+    /// Create a random object id.
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Return the hex encoding of this id prefixed with "0x" and including leading zeros.
+    pub fn to_hex_uncompressed(&self) -> String {
+        format!("0x{}", hex::encode(self.0))
+    }
+}
+
+impl From<[u8; OBJECT_ID_LENGTH]> for ObjectID {
+    fn from(bytes: [u8; OBJECT_ID_LENGTH]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<ObjectID> for [u8; OBJECT_ID_LENGTH] {
+    fn from(id: ObjectID) -> Self {
+        id.0
+    }
+}
+
+impl Deref for ObjectID {
+    type Target = [u8; OBJECT_ID_LENGTH];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for ObjectID {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Parse a hex encoded object id. The "0x" prefix is optional and leading zeros may be omitted, e.g., "0x2" is a valid id.
+impl FromStr for ObjectID {
+    type Err = fastcrypto::error::FastCryptoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+        if s.is_empty() || s.len() > 2 * OBJECT_ID_LENGTH {
+            return Err(InvalidInput);
+        }
+        let padded = format!("{:0>width$}", s, width = 2 * OBJECT_ID_LENGTH);
+        hex::decode(padded)
+            .map_err(|_| InvalidInput)
+            .and_then(Self::from_bytes)
+    }
+}
+
+impl Display for ObjectID {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.to_hex_uncompressed())
+    }
+}
+
+impl Debug for ObjectID {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.to_hex_uncompressed())
+    }
+}
+
+impl Serialize for ObjectID {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            self.to_hex_uncompressed().serialize(serializer)
+        } else {
+            self.0.serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectID {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let s = String::deserialize(deserializer)?;
+            Self::from_str(&s).map_err(|_| D::Error::custom("Invalid object id"))
+        } else {
+            <[u8; OBJECT_ID_LENGTH]>::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let id = ObjectID::from_str("0x2").unwrap();
+        let mut expected = [0u8; OBJECT_ID_LENGTH];
+        expected[31] = 2;
+        assert_eq!(id.into_bytes(), expected);
+        assert_eq!(
+            id.to_string(),
+            "0x0000000000000000000000000000000000000000000000000000000000000002"
+        );
+        assert_eq!(ObjectID::from_str(&id.to_string()).unwrap(), id);
+
+        assert!(ObjectID::from_str("").is_err());
+        assert!(ObjectID::from_str("0xzz").is_err());
+        assert!(ObjectID::from_str(&format!("0x{}", "0".repeat(65))).is_err());
+    }
+
+    #[test]
+    fn test_bcs_serialization() {
+        let id = ObjectID::random();
+        let bytes = bcs::to_bytes(&id).unwrap();
+        assert_eq!(bytes, id.into_bytes());
+        assert_eq!(bcs::from_bytes::<ObjectID>(&bytes).unwrap(), id);
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Arguments for decrypting an [EncryptedObject] on chain using the `seal::bf_hmac_encryption` Move module.
+//!
+//! A transaction decrypting on chain calls
+//! 1. `bf_hmac_encryption::parse_encrypted_object` on the BCS serialization of the object,
+//! 2. `sui::bls12381::g1_from_bytes` on each derived key,
+//! 3. `bf_hmac_encryption::get_public_key` on the key server objects,
+//! 4. `bf_hmac_encryption::verify_derived_keys` with the derived keys, the package id, the id and the public keys of
+//!    the key servers that the derived keys are from, and
+//! 5. `bf_hmac_encryption::decrypt` with the parsed object, the verified derived keys and the public keys of all
+//!    key servers of the object in the order they appear in the object.
+//!
+//! [OnchainDecryptionArguments] holds exactly these inputs, and the derived keys are verified before they are returned
+//! so the transaction will not abort because of an invalid key.
+
+use crate::prelude::*;
+use crate::{
+    create_full_id, ibe, Ciphertext, EncryptedObject, IBEPublicKeys, IBEUserSecretKeys, ObjectID,
+};
+use fastcrypto::error::FastCryptoError::GeneralError;
+use fastcrypto::error::FastCryptoResult;
+use fastcrypto::groups::bls12381::G1_ELEMENT_BYTE_LENGTH;
+use fastcrypto::serde_helpers::ToFromByteArray;
+use serde::{Deserialize, Serialize};
+
+/// The inputs for decrypting an object on chain. The BCS serialization of this struct is the bundle of all arguments
+/// in the order given here, and each field can also be used as a pure argument on its own.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnchainDecryptionArguments {
+    /// The BCS serialization of the encrypted object, for `parse_encrypted_object`.
+    pub encrypted_object: Vec<u8>,
+    /// The compressed derived keys, for `g1_from_bytes`.
+    pub derived_keys: Vec<Vec<u8>>,
+    /// The package id, for `verify_derived_keys`.
+    pub package_id: ObjectID,
+    /// The id, for `verify_derived_keys`.
+    pub id: Vec<u8>,
+    /// The key servers that the derived keys are from in the same order, for `get_public_key` and `verify_derived_keys`.
+    pub derived_key_servers: Vec<ObjectID>,
+    /// All key servers of the encrypted object in the order they appear in it, for `get_public_key` and `decrypt`.
+    pub key_servers: Vec<ObjectID>,
+}
+
+/// Prepare the arguments for decrypting an encrypted object on chain.
+///
+/// @param encrypted_object The encrypted object. Only version 0 objects in `Hmac256Ctr` mode can be decrypted on chain.
+/// @param user_secret_keys The user secret keys. At least threshold keys for key servers of the object must be given.
+/// @param public_keys The public keys of all key servers of the object in the order they appear in it.
+/// @return The arguments for the Move calls.
+pub fn prepare_onchain_decryption(
+    encrypted_object: &EncryptedObject,
+    user_secret_keys: &IBEUserSecretKeys,
+    public_keys: &IBEPublicKeys,
+) -> FastCryptoResult<OnchainDecryptionArguments> {
+    if encrypted_object.version != 0 {
+        return Err(GeneralError(
+            "Only version 0 objects can be decrypted on chain".to_string(),
+        ));
+    }
+    if !matches!(encrypted_object.ciphertext, Ciphertext::Hmac256Ctr { .. }) {
+        return Err(GeneralError(
+            "Only Hmac256Ctr objects can be decrypted on chain".to_string(),
+        ));
+    }
+
+    let (
+        IBEUserSecretKeys::BonehFranklinBLS12381(user_secret_keys),
+        IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+    ) = (user_secret_keys, public_keys);
+    if public_keys.len() != encrypted_object.services.len() {
+        return Err(GeneralError(
+            "A public key must be given for each key server".to_string(),
+        ));
+    }
+
+    let gid = ibe::hash_id(&create_full_id(
+        &encrypted_object.package_id,
+        &encrypted_object.id,
+    ));
+    let (derived_key_servers, (keys, keys_public_keys)): (Vec<_>, (Vec<_>, Vec<_>)) =
+        encrypted_object
+            .services
+            .iter()
+            .zip(public_keys)
+            .filter_map(|((service, _), public_key)| {
+                user_secret_keys
+                    .get(service)
+                    .map(|user_secret_key| (*service, (user_secret_key, public_key)))
+            })
+            .unzip();
+    ibe::verify_user_secret_keys_batched_with_gid(&keys, &gid, &keys_public_keys).map_err(|i| {
+        GeneralError(format!(
+            "Invalid user secret key for key server {}",
+            derived_key_servers[i]
+        ))
+    })?;
+    let derived_keys = keys
+        .into_iter()
+        .map(|user_secret_key| {
+            let bytes: [u8; G1_ELEMENT_BYTE_LENGTH] = user_secret_key.to_byte_array();
+            bytes.to_vec()
+        })
+        .collect::<Vec<_>>();
+    if derived_keys.len() < encrypted_object.threshold as usize {
+        return Err(GeneralError("Not enough user secret keys".to_string()));
+    }
+
+    Ok(OnchainDecryptionArguments {
+        encrypted_object: bcs::to_bytes(encrypted_object)
+            .map_err(|_| GeneralError("Serialization failed".to_string()))?,
+        derived_keys,
+        package_id: encrypted_object.package_id,
+        id: encrypted_object.id.clone(),
+        derived_key_servers,
+        key_servers: encrypted_object
+            .services
+            .iter()
+            .map(|(service, _)| *service)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMap;
+    use crate::{seal_decrypt, seal_encrypt, EncryptionInput};
+    use core::str::FromStr;
+
+    /// The test vectors from `test_seal_decrypt` in `move/seal/sources/bf_hmac_encryption.move`.
+    const ENCRYPTED_OBJECT: &str = "00000000000000000000000000000000000000000000000000000000000000000020381dd9078c322a4663c392761a0211b527c127b29583851217f948d62131f40903034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab9601d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d302dba72804cc9504a82bbaa13ed4a83a0e2c6219d7e45125cf57fd10cbab957a97030200b7f57f44e5302b684737612ebf4561ce4b4c5fea496731914f78d402db1c3c712fae396125a150e8eb1582e05a1f98140afc3214db2060c80471d6d97a173407c41fa4ca58396f6f879826e4f78b7f58282c8e48c664c9f8c953ab2e7a727125030fbf02ffa94172ae1a5c5b1be1b8bddb20ea698d49150aa361ed56504daa3c8f6f7bc1e58f024dff40892db134da0b61e58fa82317afa6884ae14f5d739b5e95fc1b56d645b75d60302775aac94d1bf52a103eefbad9cecd61fbbad37c9dbccceeb9007861ee3f34e4a546b7fe6b5b195ef1fee6ba8080d5d228bd721904b0d5010dab6e4eca9b82653721946aac8401200000000000000000000000000000000000000000000000000000000000000001a5fb3bfe499a0fa285e7129a88962e278fc65e821851d4234ada909ac72a77e5";
+    const PUBLIC_KEYS: [&str; 3] = [
+        "aeb258b9fb9a2f29f74eb0a1a895860bb1c6ba3f9ea7075366de159e4764413e9ec0597ac9c0dad409723935440a45f40eee4728630ae3ea40a68a819375bba1d78d7810f901d8a469d785d00cfed6bd28f01d41e49c5652d924e9d19fddcf62",
+        "b1076a26f4f82f39d0e767fcd2118659362afe40bce4e8d553258c86756bb74f888bca79f2d6b71edf6e25af89efa83713a223b48a19d2e551897ac92ac7458336cd489be3be025e348ca93f4c94d22594f96f0e08990e51a7de9da8ff29c98f",
+        "95fcb465af3791f31d53d80db6c8dcf9f83a419b2570614ecfbb068f47613da17cb9ffc66bb052b9546f17196929538f0bd2d38e1f515d9916e2db13dc43e0ccbd4cb3d7cbb13ffecc0b68b37481ebaaaa17cad18096a9c2c27a797f17d78623",
+    ];
+    const USER_SECRET_KEYS: [&str; 2] = [
+        "8244fcbe49870a4d4aa947b7034a873e168580e18b5834ea34940dc9f492eda03a9b20c3c3c120b1a462f1642575e0cc",
+        "a0f04b759ed2ff477f0fe5b672992235205d2af502f659d4bbb484b745e35fd7a9ff11e37e12111023a891c3fa98a2d3",
+    ];
+
+    /// The argument bundle used by `test_decrypt_with_onchain_decryption_arguments` in
+    /// `move/seal/sources/bf_hmac_encryption.move`.
+    const ARGUMENTS: &str = "da0300000000000000000000000000000000000000000000000000000000000000000020381dd9078c322a4663c392761a0211b527c127b29583851217f948d62131f40903034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab9601d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d302dba72804cc9504a82bbaa13ed4a83a0e2c6219d7e45125cf57fd10cbab957a97030200b7f57f44e5302b684737612ebf4561ce4b4c5fea496731914f78d402db1c3c712fae396125a150e8eb1582e05a1f98140afc3214db2060c80471d6d97a173407c41fa4ca58396f6f879826e4f78b7f58282c8e48c664c9f8c953ab2e7a727125030fbf02ffa94172ae1a5c5b1be1b8bddb20ea698d49150aa361ed56504daa3c8f6f7bc1e58f024dff40892db134da0b61e58fa82317afa6884ae14f5d739b5e95fc1b56d645b75d60302775aac94d1bf52a103eefbad9cecd61fbbad37c9dbccceeb9007861ee3f34e4a546b7fe6b5b195ef1fee6ba8080d5d228bd721904b0d5010dab6e4eca9b82653721946aac8401200000000000000000000000000000000000000000000000000000000000000001a5fb3bfe499a0fa285e7129a88962e278fc65e821851d4234ada909ac72a77e502308244fcbe49870a4d4aa947b7034a873e168580e18b5834ea34940dc9f492eda03a9b20c3c3c120b1a462f1642575e0cc30a0f04b759ed2ff477f0fe5b672992235205d2af502f659d4bbb484b745e35fd7a9ff11e37e12111023a891c3fa98a2d3000000000000000000000000000000000000000000000000000000000000000020381dd9078c322a4663c392761a0211b527c127b29583851217f948d62131f40902034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab96d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d303034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab96d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d3dba72804cc9504a82bbaa13ed4a83a0e2c6219d7e45125cf57fd10cbab957a97";
+
+    #[test]
+    fn test_arguments_match_move_test() {
+        let encrypted_object: EncryptedObject =
+            bcs::from_bytes(&hex::decode(ENCRYPTED_OBJECT).unwrap()).unwrap();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(
+            PUBLIC_KEYS
+                .iter()
+                .map(|pk| bcs::from_bytes(&hex::decode(pk).unwrap()).unwrap())
+                .collect(),
+        );
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+            encrypted_object
+                .services
+                .iter()
+                .zip(USER_SECRET_KEYS)
+                .map(|((service, _), usk)| {
+                    (
+                        *service,
+                        bcs::from_bytes(&hex::decode(usk).unwrap()).unwrap(),
+                    )
+                })
+                .collect(),
+        );
+
+        let arguments =
+            prepare_onchain_decryption(&encrypted_object, &user_secret_keys, &public_keys).unwrap();
+        assert_eq!(
+            arguments.encrypted_object,
+            hex::decode(ENCRYPTED_OBJECT).unwrap()
+        );
+        assert_eq!(
+            arguments.derived_key_servers,
+            arguments.key_servers[..2].to_vec()
+        );
+        assert_eq!(hex::encode(bcs::to_bytes(&arguments).unwrap()), ARGUMENTS);
+        assert_eq!(
+            bcs::from_bytes::<OnchainDecryptionArguments>(&hex::decode(ARGUMENTS).unwrap())
+                .unwrap(),
+            arguments
+        );
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let (master_key, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let service = ObjectID::from_str("0x1").unwrap();
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let encrypt = |input| {
+            seal_encrypt(
+                ObjectID::random(),
+                vec![1],
+                vec![service],
+                &public_keys,
+                1,
+                input,
+            )
+            .unwrap()
+            .0
+        };
+        let object = encrypt(EncryptionInput::Hmac256Ctr {
+            data: b"data".to_vec(),
+            aad: None,
+        });
+        let full_id = create_full_id(&object.package_id, &object.id);
+        let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([(
+            service,
+            ibe::extract(&master_key, &full_id),
+        )]));
+
+        let arguments =
+            prepare_onchain_decryption(&object, &user_secret_keys, &public_keys).unwrap();
+        assert_eq!(arguments.derived_key_servers, vec![service]);
+        assert_eq!(
+            seal_decrypt(&object, &user_secret_keys, Some(&public_keys)).unwrap(),
+            b"data"
+        );
+
+        // Only Hmac256Ctr is supported on chain.
+        let aes = encrypt(EncryptionInput::Aes256Gcm {
+            data: b"data".to_vec(),
+            aad: None,
+        });
+        assert!(prepare_onchain_decryption(&aes, &user_secret_keys, &public_keys).is_err());
+
+        // Invalid or missing keys.
+        let invalid = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::from([(
+            service,
+            ibe::extract(&master_key, b"other id"),
+        )]));
+        assert!(prepare_onchain_decryption(&object, &invalid, &public_keys).is_err());
+        let missing = IBEUserSecretKeys::BonehFranklinBLS12381(HashMap::new());
+        assert!(prepare_onchain_decryption(&object, &missing, &public_keys).is_err());
+        assert!(prepare_onchain_decryption(
+            &object,
+            &user_secret_keys,
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![])
+        )
+        .is_err());
+    }
+}
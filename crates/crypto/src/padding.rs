@@ -0,0 +1,158 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Length-hiding padding schemes for DEM payloads.
+//!
+//! A message is padded by appending a single `0x80` byte followed by zeros until the padded length is reached
+//! (ISO/IEC 7816-4 padding), so the padding can be removed without knowing the original length.
+
+use crate::prelude::*;
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+use serde::{Deserialize, Serialize};
+
+const MARKER: u8 = 0x80;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Padding {
+    /// Pad to the next power of two. The overhead is at most 100%.
+    PowerOfTwo,
+    /// Pad as described in the PADMÉ scheme from https://lbarman.ch/blog/padme/.
+    /// The overhead is at most 12% and decreases for larger messages.
+    Padme,
+    /// Pad to a multiple of the given block size.
+    Block(u32),
+}
+
+impl Padding {
+    /// The length of a padded message of the given length.
+    pub fn padded_length(&self, length: usize) -> FastCryptoResult<usize> {
+        // Room for the marker byte.
+        let length = length.checked_add(1).ok_or(InvalidInput)?;
+        match self {
+            Padding::PowerOfTwo => length.checked_next_power_of_two().ok_or(InvalidInput),
+            Padding::Padme => {
+                let e = length.ilog2();
+                if e == 0 {
+                    return Ok(length);
+                }
+                let s = e.ilog2() + 1;
+                let mask = (1usize << (e - s)) - 1;
+                length
+                    .checked_add(mask)
+                    .map(|l| l & !mask)
+                    .ok_or(InvalidInput)
+            }
+            Padding::Block(0) => Err(InvalidInput),
+            Padding::Block(size) => {
+                let size = *size as usize;
+                length.div_ceil(size).checked_mul(size).ok_or(InvalidInput)
+            }
+        }
+    }
+
+    /// Pad a message using this scheme.
+    pub fn pad(&self, data: &[u8]) -> FastCryptoResult<Vec<u8>> {
+        let padded_length = self.padded_length(data.len())?;
+        let mut padded = Vec::with_capacity(padded_length);
+        padded.extend_from_slice(data);
+        padded.push(MARKER);
+        padded.resize(padded_length, 0);
+        Ok(padded)
+    }
+
+    /// Remove the padding from a message. Fails if the message was not padded using this scheme.
+    pub fn unpad(&self, mut padded: Vec<u8>) -> FastCryptoResult<Vec<u8>> {
+        let padded_length = padded.len();
+        let marker = padded
+            .iter()
+            .rposition(|b| *b != 0)
+            .filter(|i| padded[*i] == MARKER)
+            .ok_or(InvalidInput)?;
+        if self.padded_length(marker)? != padded_length {
+            return Err(InvalidInput);
+        }
+        padded.truncate(marker);
+        Ok(padded)
+    }
+}
+
+/// Parse a padding scheme from either "pow2", "padme" or "block:<size>".
+impl FromStr for Padding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pow2" => Ok(Padding::PowerOfTwo),
+            "padme" => Ok(Padding::Padme),
+            _ => s
+                .strip_prefix("block:")
+                .and_then(|size| size.parse().ok())
+                .filter(|size| *size > 0)
+                .map(Padding::Block)
+                .ok_or_else(|| format!("Invalid padding scheme: {}", s)),
+        }
+    }
+}
+
+impl Display for Padding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Padding::PowerOfTwo => write!(f, "pow2"),
+            Padding::Padme => write!(f, "padme"),
+            Padding::Block(size) => write!(f, "block:{}", size),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padded_lengths() {
+        let lengths = [0, 1, 7, 8, 100, 1000, 9999];
+        let expected = [
+            (Padding::PowerOfTwo, [1, 2, 8, 16, 128, 1024, 16384]),
+            (Padding::Padme, [1, 2, 8, 10, 104, 1024, 10240]),
+            (Padding::Block(16), [16, 16, 16, 16, 112, 1008, 10000]),
+        ];
+        for (padding, expected) in expected {
+            for (length, expected) in lengths.iter().zip(expected) {
+                assert_eq!(padding.padded_length(*length).unwrap(), expected);
+            }
+        }
+        assert!(Padding::Block(0).padded_length(1).is_err());
+    }
+
+    #[test]
+    fn test_pad_and_unpad() {
+        for padding in [Padding::PowerOfTwo, Padding::Padme, Padding::Block(64)] {
+            for length in [0, 1, 31, 32, 33, 500] {
+                let data = vec![0u8; length];
+                let padded = padding.pad(&data).unwrap();
+                assert_eq!(padded.len(), padding.padded_length(length).unwrap());
+                assert_eq!(padding.unpad(padded).unwrap(), data);
+            }
+        }
+
+        // Missing marker or inconsistent length.
+        assert!(Padding::PowerOfTwo.unpad(vec![0; 8]).is_err());
+        assert!(Padding::PowerOfTwo.unpad(vec![1, MARKER, 0]).is_err());
+        assert!(Padding::Block(4)
+            .unpad(vec![1, MARKER, 0, 0, 0, 0, 0, 0])
+            .is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        for padding in [Padding::PowerOfTwo, Padding::Padme, Padding::Block(4096)] {
+            assert_eq!(Padding::from_str(&padding.to_string()).unwrap(), padding);
+        }
+        assert!(Padding::from_str("block:0").is_err());
+        assert!(Padding::from_str("block:").is_err());
+        assert!(Padding::from_str("none").is_err());
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Builders and parsers for the ids used by the access patterns in `move/patterns`.
+//!
+//! Each pattern's `check_policy` expects the inner id, i.e. the id without the package id prefix, to have a
+//! specific layout. A [PatternId] gives the id for a pattern and can be parsed back from an inner id. Sui addresses
+//! are represented as [ObjectID]s since both are 32 bytes and have the same BCS serialization.
+
+use crate::prelude::*;
+use crate::{create_full_id, ObjectID};
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+
+/// The access patterns defined in `move/patterns`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Whitelist,
+    Tle,
+    PrivateData,
+    AccountBased,
+    Subscription,
+    Voting,
+}
+
+/// An id for one of the access patterns in `move/patterns`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternId {
+    /// `whitelist`: The id of the whitelist object followed by an arbitrary suffix.
+    Whitelist {
+        whitelist: ObjectID,
+        suffix: Vec<u8>,
+    },
+    /// `tle`: The BCS serialization of the time in milliseconds since the Unix epoch after which the key can be fetched.
+    Tle { unlock_at: u64 },
+    /// `private_data`: The address of the creator of the `PrivateData` object followed by its nonce.
+    PrivateData { creator: ObjectID, nonce: Vec<u8> },
+    /// `account_based`: The BCS serialization of the address of the account that can fetch the key.
+    AccountBased { account: ObjectID },
+    /// `subscription`: The id of the service object followed by an arbitrary suffix.
+    Subscription { service: ObjectID, suffix: Vec<u8> },
+    /// `voting`: The id of the vote object.
+    Voting { vote: ObjectID },
+}
+
+impl PatternId {
+    /// The pattern this id is for.
+    pub fn pattern(&self) -> Pattern {
+        match self {
+            PatternId::Whitelist { .. } => Pattern::Whitelist,
+            PatternId::Tle { .. } => Pattern::Tle,
+            PatternId::PrivateData { .. } => Pattern::PrivateData,
+            PatternId::AccountBased { .. } => Pattern::AccountBased,
+            PatternId::Subscription { .. } => Pattern::Subscription,
+            PatternId::Voting { .. } => Pattern::Voting,
+        }
+    }
+
+    /// The inner id as expected by the `check_policy` function of the pattern.
+    pub fn to_inner_id(&self) -> Vec<u8> {
+        match self {
+            PatternId::Whitelist {
+                whitelist: prefix,
+                suffix,
+            }
+            | PatternId::Subscription {
+                service: prefix,
+                suffix,
+            }
+            | PatternId::PrivateData {
+                creator: prefix,
+                nonce: suffix,
+            } => [&prefix[..], &suffix[..]].concat(),
+            PatternId::Tle { unlock_at } => bcs::to_bytes(unlock_at).expect("Fixed length"),
+            PatternId::AccountBased { account } => bcs::to_bytes(account).expect("Fixed length"),
+            PatternId::Voting { vote } => vote.to_vec(),
+        }
+    }
+
+    /// The full id used for encryption and key extraction, see [create_full_id].
+    pub fn to_full_id(&self, package_id: &ObjectID) -> Vec<u8> {
+        create_full_id(package_id, &self.to_inner_id())
+    }
+
+    /// Parse an inner id for the given pattern. Fails if the id does not have the layout expected by the pattern.
+    pub fn parse(pattern: Pattern, inner_id: &[u8]) -> FastCryptoResult<Self> {
+        let split_object_id = || {
+            inner_id
+                .split_first_chunk()
+                .map(|(object_id, rest)| (ObjectID::new(*object_id), rest.to_vec()))
+                .ok_or(InvalidInput)
+        };
+        Ok(match pattern {
+            Pattern::Whitelist => {
+                let (whitelist, suffix) = split_object_id()?;
+                PatternId::Whitelist { whitelist, suffix }
+            }
+            Pattern::Subscription => {
+                let (service, suffix) = split_object_id()?;
+                PatternId::Subscription { service, suffix }
+            }
+            Pattern::PrivateData => {
+                let (creator, nonce) = split_object_id()?;
+                PatternId::PrivateData { creator, nonce }
+            }
+            Pattern::Tle => PatternId::Tle {
+                unlock_at: bcs::from_bytes(inner_id).map_err(|_| InvalidInput)?,
+            },
+            Pattern::AccountBased => PatternId::AccountBased {
+                account: bcs::from_bytes(inner_id).map_err(|_| InvalidInput)?,
+            },
+            Pattern::Voting => PatternId::Voting {
+                vote: ObjectID::from_bytes(inner_id)?,
+            },
+        })
+    }
+}
+
+/// Parse a pattern from the name of its Move module, e.g. "whitelist" or "tle".
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whitelist" => Ok(Pattern::Whitelist),
+            "tle" => Ok(Pattern::Tle),
+            "private_data" => Ok(Pattern::PrivateData),
+            "account_based" => Ok(Pattern::AccountBased),
+            "subscription" => Ok(Pattern::Subscription),
+            "voting" => Ok(Pattern::Voting),
+            _ => Err(format!("Unknown pattern: {}", s)),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Pattern::Whitelist => "whitelist",
+            Pattern::Tle => "tle",
+            Pattern::PrivateData => "private_data",
+            Pattern::AccountBased => "account_based",
+            Pattern::Subscription => "subscription",
+            Pattern::Voting => "voting",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts() {
+        let object_id = ObjectID::from_str("0xabc").unwrap();
+
+        // tle: bcs::to_bytes(&t) is the little-endian encoding of the u64.
+        assert_eq!(
+            PatternId::Tle { unlock_at: 1 }.to_inner_id(),
+            vec![1, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        // Addresses and object ids are serialized as their 32 bytes.
+        for id in [
+            PatternId::AccountBased { account: object_id },
+            PatternId::Voting { vote: object_id },
+        ] {
+            assert_eq!(id.to_inner_id(), object_id.to_vec());
+        }
+
+        // Prefix patterns.
+        let expected = [&object_id[..], b"suffix"].concat();
+        for id in [
+            PatternId::Whitelist {
+                whitelist: object_id,
+                suffix: b"suffix".to_vec(),
+            },
+            PatternId::Subscription {
+                service: object_id,
+                suffix: b"suffix".to_vec(),
+            },
+            PatternId::PrivateData {
+                creator: object_id,
+                nonce: b"suffix".to_vec(),
+            },
+        ] {
+            assert_eq!(id.to_inner_id(), expected);
+        }
+
+        let package_id = ObjectID::random();
+        let id = PatternId::Voting { vote: object_id };
+        assert_eq!(
+            id.to_full_id(&package_id),
+            create_full_id(&package_id, &object_id[..])
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let object_id = ObjectID::random();
+        let ids = [
+            PatternId::Whitelist {
+                whitelist: object_id,
+                suffix: vec![1, 2, 3],
+            },
+            PatternId::Tle {
+                unlock_at: 1_700_000_000_000,
+            },
+            PatternId::PrivateData {
+                creator: object_id,
+                nonce: vec![],
+            },
+            PatternId::AccountBased { account: object_id },
+            PatternId::Subscription {
+                service: object_id,
+                suffix: vec![4],
+            },
+            PatternId::Voting { vote: object_id },
+        ];
+        for id in ids {
+            let pattern = id.pattern();
+            assert_eq!(Pattern::from_str(&pattern.to_string()).unwrap(), pattern);
+            assert_eq!(PatternId::parse(pattern, &id.to_inner_id()).unwrap(), id);
+        }
+
+        // Invalid lengths.
+        assert!(PatternId::parse(Pattern::Tle, &[0; 9]).is_err());
+        assert!(PatternId::parse(Pattern::Tle, &[0; 7]).is_err());
+        assert!(PatternId::parse(Pattern::Voting, &[0; 33]).is_err());
+        assert!(PatternId::parse(Pattern::AccountBased, &[0; 31]).is_err());
+        assert!(PatternId::parse(Pattern::Whitelist, &[0; 31]).is_err());
+        assert!(Pattern::from_str("unknown").is_err());
+    }
+}
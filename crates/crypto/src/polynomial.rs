@@ -2,11 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::gf256::GF256;
+use crate::prelude::*;
+use core::iter::{Product, Sum};
+use core::ops::{Add, Div, Mul};
+use core::unreachable;
 use fastcrypto::error::FastCryptoResult;
 use itertools::Itertools;
-use std::iter::{Product, Sum};
-use std::ops::{Add, Div, Mul};
-use std::{unreachable, vec};
 
 /// This represents a polynomial over the Galois Field GF256.
 /// See [gf256](crate::gf256) for more details.
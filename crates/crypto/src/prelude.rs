@@ -0,0 +1,9 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The items of the std prelude which are in `alloc`, so modules compile the same with and without the `std` feature.
+
+pub use alloc::format;
+pub use alloc::string::{String, ToString};
+pub use alloc::vec;
+pub use alloc::vec::Vec;
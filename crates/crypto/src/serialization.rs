@@ -0,0 +1,319 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serialization of [EncryptedObject]. The fields depend on the version of the object:
+//!  - Version 0: `version, package_id, id, services, threshold, encrypted_shares, ciphertext`.
+//!  - Version 1: The fields of version 0 followed by `metadata`.
+//!  - Version 2: The fields of version 1 followed by `key_versions`.
+//!
+//! For BCS, version 0 objects are serialized exactly as before versioning was introduced.
+
+use crate::prelude::*;
+use crate::EncryptedObject;
+use core::fmt::Formatter;
+use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const FIELDS: &[&str] = &[
+    "version",
+    "package_id",
+    "id",
+    "services",
+    "threshold",
+    "encrypted_shares",
+    "ciphertext",
+    "metadata",
+    "key_versions",
+];
+
+/// The latest supported version.
+const MAX_VERSION: u8 = 2;
+
+/// Returns true if objects with the given version have a metadata field.
+fn has_metadata(version: u8) -> bool {
+    version >= 1
+}
+
+/// Returns true if objects with the given version have a key versions field.
+fn has_key_versions(version: u8) -> bool {
+    version >= 2
+}
+
+/// The number of serialized fields of objects with the given version.
+fn field_count(version: u8) -> usize {
+    FIELDS.len() - 2 + has_metadata(version) as usize + has_key_versions(version) as usize
+}
+
+impl Serialize for EncryptedObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let with_metadata = has_metadata(self.version);
+        if !with_metadata && self.metadata.is_some() {
+            return Err(<S::Error as serde::ser::Error>::custom(
+                "Metadata is not supported by this version",
+            ));
+        }
+        let with_key_versions = has_key_versions(self.version);
+        if with_key_versions != self.key_versions.is_some() {
+            return Err(<S::Error as serde::ser::Error>::custom(
+                "Key versions must be given for, and only for, version 2 objects",
+            ));
+        }
+        let mut state =
+            serializer.serialize_struct("EncryptedObject", field_count(self.version))?;
+        state.serialize_field("version", &self.version)?;
+        state.serialize_field("package_id", &self.package_id)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("services", &self.services)?;
+        state.serialize_field("threshold", &self.threshold)?;
+        state.serialize_field("encrypted_shares", &self.encrypted_shares)?;
+        state.serialize_field("ciphertext", &self.ciphertext)?;
+        if with_metadata {
+            state.serialize_field("metadata", &self.metadata)?;
+        }
+        if let Some(key_versions) = &self.key_versions {
+            state.serialize_field("key_versions", key_versions)?;
+        }
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for EncryptedObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("EncryptedObject", FIELDS, EncryptedObjectVisitor)
+    }
+}
+
+struct EncryptedObjectVisitor;
+
+impl<'de> Visitor<'de> for EncryptedObjectVisitor {
+    type Value = EncryptedObject;
+
+    fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str("an encrypted object")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let version: u8 = next(&mut seq, 0)?;
+        if version > MAX_VERSION {
+            return Err(A::Error::custom("Unsupported version"));
+        }
+        Ok(EncryptedObject {
+            version,
+            package_id: next(&mut seq, 1)?,
+            id: next(&mut seq, 2)?,
+            services: next(&mut seq, 3)?,
+            threshold: next(&mut seq, 4)?,
+            encrypted_shares: next(&mut seq, 5)?,
+            ciphertext: next(&mut seq, 6)?,
+            metadata: if has_metadata(version) {
+                next(&mut seq, 7)?
+            } else {
+                None
+            },
+            key_versions: if has_key_versions(version) {
+                Some(next(&mut seq, 8)?)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut version = None;
+        let mut package_id = None;
+        let mut id = None;
+        let mut services = None;
+        let mut threshold = None;
+        let mut encrypted_shares = None;
+        let mut ciphertext = None;
+        let mut metadata = None;
+        let mut key_versions = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => version = Some(map.next_value()?),
+                "package_id" => package_id = Some(map.next_value()?),
+                "id" => id = Some(map.next_value()?),
+                "services" => services = Some(map.next_value()?),
+                "threshold" => threshold = Some(map.next_value()?),
+                "encrypted_shares" => encrypted_shares = Some(map.next_value()?),
+                "ciphertext" => ciphertext = Some(map.next_value()?),
+                "metadata" => metadata = map.next_value()?,
+                "key_versions" => key_versions = Some(map.next_value()?),
+                _ => return Err(A::Error::unknown_field(&key, FIELDS)),
+            }
+        }
+        let version: u8 = version.ok_or_else(|| A::Error::missing_field("version"))?;
+        if version > MAX_VERSION
+            || (!has_metadata(version) && metadata.is_some())
+            || has_key_versions(version) != key_versions.is_some()
+        {
+            return Err(A::Error::custom("Unsupported version"));
+        }
+        Ok(EncryptedObject {
+            version,
+            package_id: package_id.ok_or_else(|| A::Error::missing_field("package_id"))?,
+            id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+            services: services.ok_or_else(|| A::Error::missing_field("services"))?,
+            threshold: threshold.ok_or_else(|| A::Error::missing_field("threshold"))?,
+            encrypted_shares: encrypted_shares
+                .ok_or_else(|| A::Error::missing_field("encrypted_shares"))?,
+            ciphertext: ciphertext.ok_or_else(|| A::Error::missing_field("ciphertext"))?,
+            metadata,
+            key_versions,
+        })
+    }
+}
+
+fn next<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+    seq: &mut A,
+    index: usize,
+) -> Result<T, A::Error> {
+    seq.next_element()?
+        .ok_or_else(|| A::Error::invalid_length(index, &EncryptedObjectVisitor))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ibe, seal_encrypt, seal_encrypt_with_key_versions, seal_encrypt_with_metadata,
+        EncryptedObject, EncryptionInput, IBEPublicKeys, Metadata, ObjectID,
+    };
+
+    fn encrypt(metadata: Option<&Metadata>) -> EncryptedObject {
+        let (_, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let public_keys = IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]);
+        let input = EncryptionInput::Aes256Gcm {
+            data: b"data".to_vec(),
+            aad: None,
+        };
+        match metadata {
+            None => seal_encrypt(
+                ObjectID::random(),
+                vec![1],
+                vec![ObjectID::random()],
+                &public_keys,
+                1,
+                input,
+            ),
+            Some(metadata) => seal_encrypt_with_metadata(
+                ObjectID::random(),
+                vec![1],
+                vec![ObjectID::random()],
+                &public_keys,
+                1,
+                input,
+                metadata,
+            ),
+        }
+        .unwrap()
+        .0
+    }
+
+    #[test]
+    fn test_version_0_has_no_metadata_field() {
+        let object = encrypt(None);
+        assert_eq!(object.version, 0);
+        let bytes = bcs::to_bytes(&object).unwrap();
+        let expected = bcs::to_bytes(&(
+            object.version,
+            object.package_id,
+            &object.id,
+            &object.services,
+            object.threshold,
+            &object.encrypted_shares,
+            &object.ciphertext,
+        ))
+        .unwrap();
+        assert_eq!(bytes, expected);
+
+        let parsed: EncryptedObject = bcs::from_bytes(&bytes).unwrap();
+        assert!(parsed.metadata.is_none());
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        // Trailing bytes are rejected.
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(bcs::from_bytes::<EncryptedObject>(&longer).is_err());
+    }
+
+    #[test]
+    fn test_version_1_round_trip() {
+        let object = encrypt(Some(&Metadata::default()));
+        assert_eq!(object.version, 1);
+
+        let bytes = bcs::to_bytes(&object).unwrap();
+        let parsed: EncryptedObject = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.metadata, object.metadata);
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        let json = serde_json::to_string(&object).unwrap();
+        let parsed: EncryptedObject = serde_json::from_str(&json).unwrap();
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        // Version 2 objects have an additional field, so parsing a version 1 object as version 2 fails.
+        let mut unknown = bytes.clone();
+        unknown[0] = 2;
+        assert!(bcs::from_bytes::<EncryptedObject>(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_version_2_round_trip() {
+        let (_, public_key) = ibe::generate_key_pair(&mut rand::thread_rng());
+        let (object, _) = seal_encrypt_with_key_versions(
+            ObjectID::random(),
+            vec![1],
+            vec![ObjectID::random()],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![public_key]),
+            1,
+            EncryptionInput::Aes256Gcm {
+                data: b"data".to_vec(),
+                aad: None,
+            },
+            None,
+            vec![7],
+        )
+        .unwrap();
+        assert_eq!(object.version, 2);
+        assert_eq!(object.key_version(0), 7);
+
+        let bytes = bcs::to_bytes(&object).unwrap();
+        let mut expected = bcs::to_bytes(&(
+            object.version,
+            object.package_id,
+            &object.id,
+            &object.services,
+            object.threshold,
+            &object.encrypted_shares,
+            &object.ciphertext,
+            &object.metadata,
+        ))
+        .unwrap();
+        expected.extend(bcs::to_bytes(&vec![7u32]).unwrap());
+        assert_eq!(bytes, expected);
+
+        let parsed: EncryptedObject = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(parsed.key_versions, Some(vec![7]));
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        let json = serde_json::to_string(&object).unwrap();
+        let parsed: EncryptedObject = serde_json::from_str(&json).unwrap();
+        assert_eq!(bcs::to_bytes(&parsed).unwrap(), bytes);
+
+        // Key versions are only allowed for version 2 objects.
+        let mut invalid = object.clone();
+        invalid.version = 1;
+        assert!(bcs::to_bytes(&invalid).is_err());
+        let mut invalid = object;
+        invalid.key_versions = None;
+        assert!(bcs::to_bytes(&invalid).is_err());
+
+        // Unknown versions are rejected.
+        let mut unknown = bytes;
+        unknown[0] = 3;
+        assert!(bcs::from_bytes::<EncryptedObject>(&unknown).is_err());
+
+        // Older objects use version 0 of every key.
+        assert_eq!(encrypt(None).key_version(0), 0);
+    }
+}
@@ -6,13 +6,16 @@
 
 use crate::gf256::GF256;
 use crate::polynomial::Polynomial;
+use crate::prelude::*;
 use crate::utils::transpose;
+use core::iter::repeat_with;
 use fastcrypto::error::FastCryptoError::InvalidInput;
 use fastcrypto::error::FastCryptoResult;
 use fastcrypto::traits::AllowedRng;
 use itertools::Itertools;
-use std::iter::repeat_with;
+use zeroize::Zeroize;
 
+#[derive(Zeroize)]
 pub struct SecretSharing<const N: usize> {
     pub(crate) secret: [u8; N],
     pub(crate) indices: Vec<u8>,
@@ -34,9 +37,10 @@ impl<const N: usize> SecretSharing<N> {
 }
 
 /// Split a secret into `num_shares` shares such that at least `threshold` shares are needed to reconstruct the secret.
+/// The secret is taken by reference so no copy of it is left on the stack of the caller.
 pub fn split<R: AllowedRng, const N: usize>(
     rng: &mut R,
-    secret: [u8; N],
+    secret: &[u8; N],
     threshold: u8,
     number_of_shares: u8,
 ) -> FastCryptoResult<SecretSharing<N>> {
@@ -56,7 +60,7 @@ pub fn split<R: AllowedRng, const N: usize>(
     let shares = transpose(&byte_shares)?;
 
     Ok(SecretSharing {
-        secret,
+        secret: *secret,
         indices,
         shares,
     })
@@ -258,7 +262,7 @@ mod tests {
 
         let SecretSharing {
             indices, shares, ..
-        } = split(&mut thread_rng(), secret, 3, 5).unwrap();
+        } = split(&mut thread_rng(), &secret, 3, 5).unwrap();
 
         assert_eq!(
             secret,
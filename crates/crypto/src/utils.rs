@@ -1,6 +1,7 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::prelude::*;
 use fastcrypto::error::FastCryptoError::InvalidInput;
 use fastcrypto::error::FastCryptoResult;
 use fastcrypto::traits::AllowedRng;
@@ -48,3 +49,11 @@ pub(crate) fn transpose<const N: usize>(matrix: &[Vec<u8>]) -> FastCryptoResult<
         })
         .collect())
 }
+
+/// Overwrite a value in place such that the write is not optimized away by the compiler.
+/// This is used to zeroize secret values of types that do not implement [zeroize::Zeroize].
+pub(crate) fn overwrite_volatile<T: Copy>(value: &mut T, with: T) {
+    // SAFETY: `value` is a valid and aligned mutable reference.
+    unsafe { core::ptr::write_volatile(value, with) };
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
@@ -0,0 +1,639 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! An append-only, tamper-evident log of the keys released by the key server.
+//!
+//! The log is a file of JSON lines, one per [AuditEntry]. Every entry contains the hash of the previous entry, so
+//! modifying, reordering or removing entries breaks the chain. Once per checkpoint interval, a checkpoint entry is
+//! appended with a signature on the hash of the chain by the master key of an identity, created like its proof of
+//! possession, so the chain cannot be recomputed after tampering without the master key.
+//!
+//! Removing entries from the end of the log leaves a valid chain. The sequence number and hash of every checkpoint
+//! are therefore also written to the server log, and `key-server audit verify --head <SEQ>:<HASH>` checks that the
+//! audit log still contains an entry noted earlier.
+//!
+//! Entries are written and synced to disk, and checkpoints are signed, by a dedicated thread, so neither blocks the
+//! async runtime. Each write is acknowledged once it is on disk.
+
+use crate::clock::Clock;
+use crate::types::{IbePublicKey, MasterKeyPOP};
+use anyhow::{anyhow, bail, Result};
+use crypto::ibe::verify_proof_of_possession;
+use fastcrypto::ed25519::Ed25519PublicKey;
+use fastcrypto::hash::{Blake2b256, HashFunction};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{mpsc, Arc};
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+use tokio::sync::oneshot;
+use tracing::info;
+
+const ENTRY_DST: &[u8] = b"SEAL-AUDIT-ENTRY";
+const CHECKPOINT_DST: &[u8] = b"SEAL-AUDIT-CHECKPOINT";
+
+type Hash = [u8; 32];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum AuditEvent {
+    /// Keys were released to a user.
+    Release {
+        key_server_object_id: ObjectID,
+        /// The requested version of the master key, if any.
+        key_version: Option<u32>,
+        user: SuiAddress,
+        /// The package called by the PTB.
+        package_id: ObjectID,
+        /// The hex encoded full ids of the released keys.
+        ids: Vec<String>,
+        session_vk: Ed25519PublicKey,
+        req_id: Option<String>,
+    },
+    /// A signature on all previous entries.
+    Checkpoint {
+        key_server_object_id: ObjectID,
+        key_version: u32,
+        signature: MasterKeyPOP,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct AuditEntry {
+    /// The position of the entry in the log, starting at 0.
+    pub seq: u64,
+    /// Unix time in milliseconds.
+    pub timestamp: u64,
+    pub event: AuditEvent,
+    /// The hex encoded hash of the previous entry, or zeros for the first entry.
+    pub prev_hash: String,
+    /// The hex encoded hash of this entry.
+    pub hash: String,
+}
+
+fn entry_hash(seq: u64, timestamp: u64, event: &AuditEvent, prev_hash: &Hash) -> Hash {
+    let mut data = ENTRY_DST.to_vec();
+    data.extend(prev_hash);
+    data.extend(bcs::to_bytes(&(seq, timestamp, event)).expect("serializable"));
+    Blake2b256::digest(&data).digest
+}
+
+/// The message signed by the checkpoint with the given sequence number and timestamp.
+fn checkpoint_message(seq: u64, timestamp: u64, prev_hash: &Hash) -> Vec<u8> {
+    let mut message = CHECKPOINT_DST.to_vec();
+    message.extend(bcs::to_bytes(&(seq, timestamp, prev_hash)).expect("serializable"));
+    message
+}
+
+fn decode_hash(seq: u64, hash: &str) -> Result<Hash> {
+    hex::decode(hash)
+        .ok()
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(|| anyhow!("Entry {} has an invalid hash", seq))
+}
+
+/// Read the entries of a log, checking that they form a hash chain, and call `visit` for each. Returns the sequence
+/// number of the next entry and the hash of the last entry.
+fn read_chain(
+    mut reader: impl BufRead,
+    mut visit: impl FnMut(&AuditEntry) -> Result<()>,
+) -> Result<(u64, Hash)> {
+    let (mut next_seq, mut head) = (0, [0; 32]);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok((next_seq, head));
+        }
+        let Some(json) = line.strip_suffix('\n') else {
+            bail!(
+                "Entry {} is incomplete, the log has been truncated",
+                next_seq
+            );
+        };
+        let entry: AuditEntry = serde_json::from_str(json)
+            .map_err(|e| anyhow!("Entry {} is malformed: {}", next_seq, e))?;
+        if entry.seq != next_seq {
+            bail!(
+                "Expected entry {} but found entry {}, entries have been removed or reordered",
+                next_seq,
+                entry.seq
+            );
+        }
+        if decode_hash(entry.seq, &entry.prev_hash)? != head {
+            bail!("Entry {} does not follow the previous entry", entry.seq);
+        }
+        let hash = entry_hash(entry.seq, entry.timestamp, &entry.event, &head);
+        if decode_hash(entry.seq, &entry.hash)? != hash {
+            bail!("Entry {} has been modified", entry.seq);
+        }
+        visit(&entry)?;
+        next_seq += 1;
+        head = hash;
+    }
+}
+
+/// Signs the message of a checkpoint with the master key of an identity.
+pub(crate) type CheckpointSigner = Box<dyn FnOnce(&[u8]) -> Result<MasterKeyPOP> + Send>;
+
+/// Acknowledges a request once it has been handled and any entry is on disk.
+type Ack = oneshot::Sender<Result<()>>;
+
+enum Request {
+    Record(AuditEvent),
+    Checkpoint {
+        key_server_object_id: ObjectID,
+        key_version: u32,
+        sign: CheckpointSigner,
+    },
+}
+
+struct State {
+    file: File,
+    next_seq: u64,
+    head: Hash,
+    /// The number of entries since the last checkpoint.
+    unsigned: u64,
+    /// Set if writing an entry failed, after which the end of the file may be corrupted.
+    broken: bool,
+}
+
+impl State {
+    fn append(&mut self, timestamp: u64, event: AuditEvent) -> Result<AuditEntry> {
+        if self.broken {
+            bail!("A previous write to the audit log failed");
+        }
+        let hash = entry_hash(self.next_seq, timestamp, &event, &self.head);
+        let entry = AuditEntry {
+            seq: self.next_seq,
+            timestamp,
+            event,
+            prev_hash: hex::encode(self.head),
+            hash: hex::encode(hash),
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        if let Err(e) = self
+            .file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.sync_data())
+        {
+            self.broken = true;
+            return Err(e.into());
+        }
+        self.next_seq += 1;
+        self.head = hash;
+        Ok(entry)
+    }
+
+    fn handle(&mut self, request: Request, timestamp: u64) -> Result<()> {
+        match request {
+            Request::Record(event) => {
+                self.append(timestamp, event)?;
+                self.unsigned += 1;
+            }
+            Request::Checkpoint {
+                key_server_object_id,
+                key_version,
+                sign,
+            } => {
+                if self.unsigned == 0 {
+                    return Ok(());
+                }
+                let signature = sign(&checkpoint_message(self.next_seq, timestamp, &self.head))?;
+                let entry = self.append(
+                    timestamp,
+                    AuditEvent::Checkpoint {
+                        key_server_object_id,
+                        key_version,
+                        signature,
+                    },
+                )?;
+                self.unsigned = 0;
+                info!("Audit log checkpoint {}:{}", entry.seq, entry.hash);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A log opened for appending. Entries are synced to disk before they are acknowledged.
+pub(crate) struct AuditLog {
+    /// Requests to the writer thread, which owns the file and stops once this is dropped.
+    requests: mpsc::Sender<(Request, Ack)>,
+}
+
+impl AuditLog {
+    /// Open the log at the given path, creating it if it does not exist. An existing log is verified first, and new
+    /// entries continue its chain.
+    pub fn open(path: &Path, clock: Arc<dyn Clock>) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut unsigned = 0;
+        let (next_seq, head) = read_chain(BufReader::new(&mut file), |entry| {
+            unsigned = match entry.event {
+                AuditEvent::Checkpoint { .. } => 0,
+                AuditEvent::Release { .. } => unsigned + 1,
+            };
+            Ok(())
+        })
+        .map_err(|e| anyhow!("Invalid audit log {}: {}", path.display(), e))?;
+        info!(
+            "Opened audit log {} with {} entries",
+            path.display(),
+            next_seq
+        );
+        let mut state = State {
+            file,
+            next_seq,
+            head,
+            unsigned,
+            broken: false,
+        };
+        let (requests, receiver) = mpsc::channel::<(Request, Ack)>();
+        std::thread::Builder::new()
+            .name("audit-log".into())
+            .spawn(move || {
+                for (request, ack) in receiver {
+                    // The requester may have given up waiting, but the entry is written regardless.
+                    let _ = ack.send(state.handle(request, clock.now()));
+                }
+            })?;
+        Ok(Self { requests })
+    }
+
+    async fn send(&self, request: Request) -> Result<()> {
+        let (ack, written) = oneshot::channel();
+        self.requests
+            .send((request, ack))
+            .map_err(|_| anyhow!("The audit log writer has stopped"))?;
+        written
+            .await
+            .map_err(|_| anyhow!("The audit log writer has stopped"))?
+    }
+
+    /// Append an event. Keys must not be released if this fails.
+    pub async fn record(&self, event: AuditEvent) -> Result<()> {
+        self.send(Request::Record(event)).await
+    }
+
+    /// Append a checkpoint signed by the master key with the given version of the given identity, unless no entries
+    /// have been added since the last checkpoint. The signer is called by the writer thread.
+    pub async fn checkpoint(
+        &self,
+        key_server_object_id: ObjectID,
+        key_version: u32,
+        sign: CheckpointSigner,
+    ) -> Result<()> {
+        self.send(Request::Checkpoint {
+            key_server_object_id,
+            key_version,
+            sign,
+        })
+        .await
+    }
+}
+
+/// The result of verifying a log.
+#[derive(Debug, PartialEq)]
+pub(crate) struct VerifyReport {
+    pub entries: u64,
+    pub releases: u64,
+    pub checkpoints: u64,
+    /// The number of entries after the last checkpoint, which are not covered by a signature.
+    pub unsigned: u64,
+    /// The hex encoded hash of the last entry, if any.
+    pub head: Option<String>,
+}
+
+/// Verify the hash chain of a log and that every checkpoint is signed by one of the given public keys. If an entry
+/// noted earlier is given by its sequence number and hash, the log must still contain it.
+pub(crate) fn verify(
+    reader: impl BufRead,
+    public_keys: &[IbePublicKey],
+    expected: Option<(u64, &str)>,
+) -> Result<VerifyReport> {
+    let mut report = VerifyReport {
+        entries: 0,
+        releases: 0,
+        checkpoints: 0,
+        unsigned: 0,
+        head: None,
+    };
+    read_chain(reader, |entry| {
+        match &entry.event {
+            AuditEvent::Release { .. } => {
+                report.releases += 1;
+                report.unsigned += 1;
+            }
+            AuditEvent::Checkpoint { signature, .. } => {
+                let message = checkpoint_message(
+                    entry.seq,
+                    entry.timestamp,
+                    &decode_hash(entry.seq, &entry.prev_hash)?,
+                );
+                if !public_keys.iter().any(|public_key| {
+                    verify_proof_of_possession(public_key, &message, signature).is_ok()
+                }) {
+                    bail!("Checkpoint {} has an invalid signature", entry.seq);
+                }
+                report.checkpoints += 1;
+                report.unsigned = 0;
+            }
+        }
+        if let Some((seq, hash)) = expected {
+            if entry.seq == seq && entry.hash != hash {
+                bail!("Entry {} does not match the expected hash", seq);
+            }
+        }
+        report.entries += 1;
+        report.head = Some(entry.hash.clone());
+        Ok(())
+    })?;
+    if let Some((seq, _)) = expected {
+        if seq >= report.entries {
+            bail!("Entry {} is missing, the log has been truncated", seq);
+        }
+    }
+    Ok(report)
+}
+
+/// Write the releases with a timestamp in `[from, to)` as JSON lines, after verifying the hash chain of the log.
+/// Returns the number of exported entries.
+pub(crate) fn export(
+    reader: impl BufRead,
+    from: u64,
+    to: u64,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut releases = vec![];
+    read_chain(reader, |entry| {
+        if matches!(entry.event, AuditEvent::Release { .. })
+            && (from..to).contains(&entry.timestamp)
+        {
+            releases.push(serde_json::to_string(entry)?);
+        }
+        Ok(())
+    })?;
+    for release in &releases {
+        writeln!(writer, "{}", release)?;
+    }
+    Ok(releases.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::identity::KeyIdentity;
+    use crate::master_key::LockedMasterKey;
+    use crypto::ibe;
+    use fastcrypto::ed25519::Ed25519KeyPair;
+    use fastcrypto::traits::KeyPair;
+    use rand::thread_rng;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    const START_TIME: u64 = 1_700_000_000_000;
+
+    struct Setup {
+        path: tempfile::TempPath,
+        clock: Arc<MockClock>,
+        identity: Arc<KeyIdentity>,
+        public_key: IbePublicKey,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+            let identity = KeyIdentity::new(
+                Box::new(LockedMasterKey::new(master_key)),
+                ObjectID::random(),
+                0,
+                None,
+            )
+            .unwrap();
+            Self {
+                path: tempfile::NamedTempFile::new().unwrap().into_temp_path(),
+                clock: Arc::new(MockClock::new(START_TIME)),
+                identity: Arc::new(identity),
+                public_key,
+            }
+        }
+
+        fn open(&self) -> AuditLog {
+            AuditLog::open(&self.path, self.clock.clone()).unwrap()
+        }
+
+        /// Record a release every second.
+        async fn record_releases(&self, log: &AuditLog, count: usize) {
+            for _ in 0..count {
+                log.record(release()).await.unwrap();
+                self.clock.advance(Duration::from_secs(1));
+            }
+        }
+
+        /// Checkpoint the log with the master key of the identity.
+        async fn checkpoint(&self, log: &AuditLog) {
+            let identity = self.identity.clone();
+            log.checkpoint(
+                identity.key_server_object_id,
+                identity.key_version,
+                Box::new(move |message| identity.master_key.create_proof_of_possession(message)),
+            )
+            .await
+            .unwrap();
+        }
+
+        fn verify(&self, expected: Option<(u64, &str)>) -> Result<VerifyReport> {
+            verify(
+                BufReader::new(File::open(&self.path).unwrap()),
+                &[self.public_key],
+                expected,
+            )
+        }
+
+        fn lines(&self) -> Vec<String> {
+            std::fs::read_to_string(&self.path)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+
+        fn write_lines(&self, lines: &[String]) {
+            std::fs::write(&self.path, lines.join("\n") + "\n").unwrap();
+        }
+    }
+
+    fn release() -> AuditEvent {
+        AuditEvent::Release {
+            key_server_object_id: ObjectID::random(),
+            key_version: None,
+            user: SuiAddress::random_for_testing_only(),
+            package_id: ObjectID::random(),
+            ids: vec![hex::encode(b"id")],
+            session_vk: Ed25519KeyPair::generate(&mut thread_rng()).public().clone(),
+            req_id: Some("request".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_and_verify() {
+        let setup = Setup::new();
+        let log = setup.open();
+        setup.record_releases(&log, 3).await;
+        setup.checkpoint(&log).await;
+        // Without new entries, no checkpoint is added.
+        setup.checkpoint(&log).await;
+        setup.record_releases(&log, 1).await;
+
+        let report = setup.verify(None).unwrap();
+        assert_eq!(
+            report,
+            VerifyReport {
+                entries: 5,
+                releases: 4,
+                checkpoints: 1,
+                unsigned: 1,
+                head: Some(
+                    serde_json::from_str::<AuditEntry>(&setup.lines()[4])
+                        .unwrap()
+                        .hash
+                ),
+            }
+        );
+
+        // Reopening the log continues the chain, and entries written before are covered by the next checkpoint.
+        drop(log);
+        let log = setup.open();
+        setup.checkpoint(&log).await;
+        setup.record_releases(&log, 1).await;
+        setup.checkpoint(&log).await;
+        let report = setup.verify(None).unwrap();
+        assert_eq!(
+            (report.entries, report.checkpoints, report.unsigned),
+            (8, 3, 0)
+        );
+
+        // Checkpoints are only accepted with the public key of the identity.
+        let (_, other_public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        assert!(verify(reader, &[], None).is_err());
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        assert!(verify(reader, &[other_public_key], None).is_err());
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        assert!(verify(reader, &[other_public_key, setup.public_key], None).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tampering_is_detected() {
+        let setup = Setup::new();
+        let log = setup.open();
+        setup.record_releases(&log, 3).await;
+        setup.checkpoint(&log).await;
+        setup.record_releases(&log, 2).await;
+        drop(log);
+        let lines = setup.lines();
+        let check_error = |lines: Vec<String>, expected: &str| {
+            setup.write_lines(&lines);
+            let error = setup.verify(None).unwrap_err().to_string();
+            assert!(error.contains(expected), "{}", error);
+            assert!(AuditLog::open(&setup.path, setup.clock.clone()).is_err());
+        };
+
+        // A modified entry.
+        let mut modified = lines.clone();
+        let mut entry: Value = serde_json::from_str(&lines[1]).unwrap();
+        entry["event"]["req_id"] = "other request".into();
+        modified[1] = entry.to_string();
+        check_error(modified, "Entry 1 has been modified");
+
+        // A removed entry.
+        let mut removed = lines.clone();
+        removed.remove(2);
+        check_error(removed, "Expected entry 2 but found entry 3");
+
+        // The first entry removed.
+        check_error(lines[1..].to_vec(), "Expected entry 0 but found entry 1");
+
+        // A partially written entry.
+        setup.write_lines(&lines);
+        let contents = std::fs::read_to_string(&setup.path).unwrap();
+        std::fs::write(&setup.path, &contents[..contents.len() - 10]).unwrap();
+        let error = setup.verify(None).unwrap_err().to_string();
+        assert!(error.contains("Entry 5 is incomplete"), "{}", error);
+
+        // A recomputed chain after modifying an entry is only detected by the signature of the checkpoint.
+        let mut recomputed = vec![];
+        let mut head = [0; 32];
+        for (i, line) in lines.iter().enumerate() {
+            let mut entry: AuditEntry = serde_json::from_str(line).unwrap();
+            if let (1, AuditEvent::Release { req_id, .. }) = (i, &mut entry.event) {
+                *req_id = None;
+            }
+            entry.prev_hash = hex::encode(head);
+            head = entry_hash(entry.seq, entry.timestamp, &entry.event, &head);
+            entry.hash = hex::encode(head);
+            recomputed.push(serde_json::to_string(&entry).unwrap());
+        }
+        setup.write_lines(&recomputed);
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        assert!(read_chain(reader, |_| Ok(())).is_ok());
+        let error = setup.verify(None).unwrap_err().to_string();
+        assert!(
+            error.contains("Checkpoint 3 has an invalid signature"),
+            "{}",
+            error
+        );
+
+        // Entries removed from the end are detected with a head noted earlier.
+        setup.write_lines(&lines[..4]);
+        let head: AuditEntry = serde_json::from_str(&lines[5]).unwrap();
+        assert!(setup.verify(None).is_ok());
+        let error = setup
+            .verify(Some((head.seq, &head.hash)))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Entry 5 is missing"), "{}", error);
+        setup.write_lines(&lines);
+        assert!(setup.verify(Some((head.seq, &head.hash))).is_ok());
+        assert!(setup
+            .verify(Some((head.seq, &hex::encode([0; 32]))))
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let setup = Setup::new();
+        let log = setup.open();
+        setup.record_releases(&log, 3).await;
+        setup.checkpoint(&log).await;
+        setup.record_releases(&log, 3).await;
+
+        let mut output = vec![];
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        let exported = export(reader, START_TIME + 1_000, START_TIME + 5_000, &mut output).unwrap();
+        assert_eq!(exported, 4);
+        let entries: Vec<AuditEntry> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            entries.iter().map(|entry| entry.seq).collect::<Vec<_>>(),
+            vec![1, 2, 4, 5]
+        );
+
+        // Nothing is exported from a tampered log.
+        let mut lines = setup.lines();
+        lines.remove(0);
+        setup.write_lines(&lines);
+        let reader = BufReader::new(File::open(&setup.path).unwrap());
+        assert!(export(reader, 0, u64::MAX, std::io::sink()).is_err());
+    }
+}
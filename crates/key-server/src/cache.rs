@@ -1,11 +1,13 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::externals::current_epoch_time;
+use crate::clock::Clock;
 use lru::LruCache;
 use parking_lot::Mutex;
+use serde::Serialize;
 use std::hash::Hash;
 use std::num::NonZero;
+use std::sync::Arc;
 
 pub(crate) const CACHE_SIZE: usize = 1000;
 pub(crate) const CACHE_TTL: u64 = 3 * 60 * 1000; // 3 minutes
@@ -17,18 +19,28 @@ struct CacheEntry<V> {
     pub expiry: u64,
 }
 
+/// The number of entries in a cache, including expired entries not yet evicted.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub(crate) struct CacheStatus {
+    pub entries: usize,
+    pub capacity: usize,
+}
+
 pub(crate) struct Cache<K, V> {
     ttl: u64,
+    /// The clock used to expire entries.
+    clock: Arc<dyn Clock>,
     cache: Mutex<LruCache<K, CacheEntry<V>>>,
 }
 
 impl<K: Hash + Eq, V: Copy> Cache<K, V> {
-    /// Create a new cache with a given TTL and size.
+    /// Create a new cache with a given TTL and size, expiring entries according to the given clock.
     /// Panics if ttl or size is 0.
-    pub fn new(ttl: u64, size: usize) -> Self {
+    pub fn new(ttl: u64, size: usize, clock: Arc<dyn Clock>) -> Self {
         assert!(size > 0 && ttl > 0, "TTL and size must be greater than 0");
         Self {
             ttl,
+            clock,
             cache: Mutex::new(LruCache::new(NonZero::new(size).expect("fixed value"))),
         }
     }
@@ -37,7 +49,7 @@ impl<K: Hash + Eq, V: Copy> Cache<K, V> {
         let mut cache = self.cache.lock();
         match cache.get(key) {
             Some(entry) => {
-                if entry.expiry < current_epoch_time() {
+                if entry.expiry < self.clock.now() {
                     cache.pop(key);
                     None
                 } else {
@@ -54,36 +66,67 @@ impl<K: Hash + Eq, V: Copy> Cache<K, V> {
             key,
             CacheEntry {
                 value,
-                expiry: current_epoch_time() + self.ttl,
+                expiry: self.clock.now() + self.ttl,
             },
         );
     }
+
+    pub fn status(&self) -> CacheStatus {
+        let cache = self.cache.lock();
+        CacheStatus {
+            entries: cache.len(),
+            capacity: cache.cap().get(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread::sleep;
+    use crate::clock::MockClock;
     use std::time::Duration;
 
+    fn cache(ttl: u64, size: usize) -> (Cache<u32, &'static str>, Arc<MockClock>) {
+        let clock = Arc::new(MockClock::new(0));
+        (Cache::new(ttl, size, clock.clone()), clock)
+    }
+
     #[test]
     fn test_cache_insert_and_get() {
-        let cache = Cache::new(1000, 10);
+        let (cache, _) = cache(1000, 10);
         cache.insert(1, "value1");
         assert_eq!(cache.get(&1), Some("value1"));
     }
 
+    #[test]
+    fn test_cache_status() {
+        let (cache, _) = cache(1000, 2);
+        cache.insert(1, "value1");
+        assert_eq!(
+            cache.status(),
+            CacheStatus {
+                entries: 1,
+                capacity: 2
+            }
+        );
+        cache.insert(2, "value2");
+        cache.insert(3, "value3");
+        assert_eq!(cache.status().entries, 2);
+    }
+
     #[test]
     fn test_cache_expiry() {
-        let cache = Cache::new(1000, 10);
+        let (cache, clock) = cache(1000, 10);
         cache.insert(1, "value1");
-        sleep(Duration::from_millis(1100));
+        clock.advance(Duration::from_millis(1000));
+        assert_eq!(cache.get(&1), Some("value1"));
+        clock.advance(Duration::from_millis(1));
         assert_eq!(cache.get(&1), None);
     }
 
     #[test]
     fn test_cache_overwrite() {
-        let cache = Cache::new(1000, 10);
+        let (cache, _) = cache(1000, 10);
         cache.insert(1, "value1");
         cache.insert(1, "value2");
         assert_eq!(cache.get(&1), Some("value2"));
@@ -91,7 +134,7 @@ mod tests {
 
     #[test]
     fn test_cache_lru_eviction() {
-        let cache = Cache::new(1000, 2);
+        let (cache, _) = cache(1000, 2);
         cache.insert(1, "value1");
         cache.insert(2, "value2");
         cache.insert(3, "value3");
@@ -0,0 +1,640 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Access to the chain. [SuiChain] uses full nodes and GraphQL endpoints, while tests can use the
+//! in-memory [mock::MockChain] to script dry run outcomes, package upgrades and checkpoint times.
+
+use crate::cache::{Cache, CacheStatus};
+use crate::clock::Clock;
+use crate::config::PackageResolverKind;
+use crate::endpoints::{post_json, EndpointPool, EndpointStatus, ENDPOINT_TIMEOUT};
+use crate::errors::InternalError;
+use crate::externals::cached_first_and_last_pkg_id;
+use crate::metrics::Metrics;
+use crate::package_resolver::{resolve_with_fallback, resolvers, PackageResolver};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::DateTime;
+use futures::future::join;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use sui_sdk::rpc_types::SuiTransactionBlockEffectsAPI;
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+use sui_sdk::types::signature::GenericSignature;
+use sui_sdk::types::transaction::{ProgrammableTransaction, TransactionKind};
+use sui_sdk::verify_personal_message_signature::verify_personal_message_signature;
+use sui_sdk::{SuiClient, SuiClientBuilder};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+#[async_trait]
+pub(crate) trait ChainBackend: Send + Sync {
+    /// Dry run the PTB with the given sender. Returns whether the transaction executed successfully, or an error if
+    /// the dry run could not be performed.
+    async fn dry_run(
+        &self,
+        sender: SuiAddress,
+        ptb: &ProgrammableTransaction,
+        gas_budget: u64,
+        gas_price: u64,
+    ) -> Result<bool>;
+
+    /// The timestamp in milliseconds of the latest checkpoint.
+    async fn latest_checkpoint_timestamp(&self) -> Result<u64>;
+
+    async fn reference_gas_price(&self) -> Result<u64>;
+
+    /// The ids of the first and the latest version of the given package.
+    async fn first_and_last_pkg_id(
+        &self,
+        pkg_id: &ObjectID,
+    ) -> Result<(ObjectID, ObjectID), InternalError>;
+
+    /// Verify a signature on a personal message. zkLogin signatures are verified against the chain state.
+    async fn verify_personal_message_signature(
+        &self,
+        signature: GenericSignature,
+        message: &[u8],
+        signer: SuiAddress,
+    ) -> Result<()>;
+
+    /// The health of the endpoints used, if any.
+    fn endpoints_status(&self) -> EndpointsStatus {
+        EndpointsStatus::default()
+    }
+
+    /// The status of the cache of package versions, if any.
+    fn package_cache_status(&self) -> Option<CacheStatus> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub(crate) struct EndpointsStatus {
+    pub full_nodes: Vec<EndpointStatus>,
+    pub graphql: Vec<EndpointStatus>,
+}
+
+/// A full node endpoint. The JSON-RPC calls used to probe its health are made directly, while the [SuiClient] used
+/// for dry runs and signature verification is created on first use, so unreachable endpoints do not prevent startup.
+pub(crate) struct NodeClient {
+    url: String,
+    http: Client,
+    sui_client: OnceCell<SuiClient>,
+}
+
+impl NodeClient {
+    pub(crate) fn new(url: String, http: Client) -> Self {
+        Self {
+            url,
+            http,
+            sui_client: OnceCell::new(),
+        }
+    }
+
+    async fn sui_client(&self) -> Result<&SuiClient> {
+        self.sui_client
+            .get_or_try_init(|| async {
+                Ok::<_, anyhow::Error>(
+                    SuiClientBuilder::default()
+                        .request_timeout(ENDPOINT_TIMEOUT)
+                        .build(&self.url)
+                        .await?,
+                )
+            })
+            .await
+    }
+
+    pub(crate) async fn json_rpc(&self, method: &str, params: Value) -> Result<Value> {
+        let response = post_json(
+            &self.http,
+            &self.url,
+            &json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }),
+        )
+        .await?;
+        match response.get("error") {
+            Some(error) => bail!("{} failed: {}", method, error),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    async fn latest_checkpoint_timestamp(&self) -> Result<u64> {
+        let sequence_number = self
+            .json_rpc("sui_getLatestCheckpointSequenceNumber", json!([]))
+            .await?;
+        let checkpoint = self
+            .json_rpc("sui_getCheckpoint", json!([sequence_number]))
+            .await?;
+        parse_u64(&checkpoint["timestampMs"])
+    }
+
+    async fn reference_gas_price(&self) -> Result<u64> {
+        parse_u64(
+            &self
+                .json_rpc("suix_getReferenceGasPrice", json!([]))
+                .await?,
+        )
+    }
+}
+
+/// Parse an integer encoded as a string, as returned by the JSON-RPC API.
+fn parse_u64(value: &Value) -> Result<u64> {
+    value
+        .as_str()
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid integer: {}", value))
+}
+
+pub(crate) struct GraphQlClient {
+    pub(crate) url: String,
+    pub(crate) http: Client,
+}
+
+impl GraphQlClient {
+    pub(crate) fn new(url: String, http: Client) -> Self {
+        Self { url, http }
+    }
+
+    async fn latest_checkpoint_timestamp(&self) -> Result<u64> {
+        let response = post_json(
+            &self.http,
+            &self.url,
+            &json!({ "query": "query { checkpoint { timestamp } }" }),
+        )
+        .await?;
+        let timestamp = response["data"]["checkpoint"]["timestamp"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Invalid GraphQL response: {}", response))?;
+        Ok(DateTime::parse_from_rfc3339(timestamp)?.timestamp_millis() as u64)
+    }
+}
+
+/// A [ChainBackend] using full nodes, and GraphQL endpoints or the full nodes to resolve package versions, see
+/// [crate::package_resolver]. Requests go to the healthiest endpoint and fail over to the others, see
+/// [crate::endpoints].
+pub(crate) struct SuiChain {
+    nodes: Arc<EndpointPool<NodeClient>>,
+    graphql: Arc<EndpointPool<GraphQlClient>>,
+    resolvers: Vec<Box<dyn PackageResolver>>,
+    /// The first and latest versions of packages on this network, by package id.
+    package_cache: Cache<ObjectID, (ObjectID, ObjectID)>,
+}
+
+impl SuiChain {
+    /// Create a chain using the given full node and GraphQL endpoints, in order of preference, and caching package
+    /// versions in the given cache.
+    pub fn new(
+        node_urls: Vec<String>,
+        graphql_urls: Vec<String>,
+        package_resolver: PackageResolverKind,
+        package_cache: Cache<ObjectID, (ObjectID, ObjectID)>,
+        allowed_staleness: Duration,
+        clock: Arc<dyn Clock>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let http = Client::builder()
+            .timeout(ENDPOINT_TIMEOUT)
+            .build()
+            .expect("Client configuration is valid");
+        let nodes = node_urls
+            .into_iter()
+            .map(|url| (url.clone(), NodeClient::new(url, http.clone())))
+            .collect();
+        let graphql = graphql_urls
+            .into_iter()
+            .map(|url| (url.clone(), GraphQlClient::new(url, http.clone())))
+            .collect();
+        let nodes = Arc::new(EndpointPool::new(
+            nodes,
+            allowed_staleness,
+            clock.clone(),
+            metrics.clone(),
+        ));
+        let graphql = Arc::new(EndpointPool::new(
+            graphql,
+            allowed_staleness,
+            clock,
+            metrics,
+        ));
+        Self {
+            resolvers: resolvers(package_resolver, nodes.clone(), graphql.clone()),
+            nodes,
+            graphql,
+            package_cache,
+        }
+    }
+}
+
+#[async_trait]
+impl ChainBackend for SuiChain {
+    async fn dry_run(
+        &self,
+        sender: SuiAddress,
+        ptb: &ProgrammableTransaction,
+        gas_budget: u64,
+        gas_price: u64,
+    ) -> Result<bool> {
+        self.nodes
+            .call("Dry run", |node| async move {
+                let client = node.sui_client().await?;
+                let tx_data = client
+                    .transaction_builder()
+                    .tx_data_for_dry_run(
+                        sender,
+                        TransactionKind::ProgrammableTransaction(ptb.clone()),
+                        gas_budget,
+                        gas_price,
+                        None,
+                        None,
+                    )
+                    .await;
+                let response = client.read_api().dry_run_transaction_block(tx_data).await?;
+                debug!("Dry run response: {:?}", response);
+                Ok(response.effects.status().is_ok())
+            })
+            .await
+    }
+
+    async fn latest_checkpoint_timestamp(&self) -> Result<u64> {
+        // GraphQL endpoints are only probed if configured, to rank them for package lookups.
+        let probe_graphql = async {
+            if self.graphql.is_empty() {
+                return Ok(());
+            }
+            self.graphql
+                .probe(|graphql| graphql.latest_checkpoint_timestamp())
+                .await
+                .map(|_| ())
+        };
+        let (node_timestamp, graphql_result) = join(
+            self.nodes.probe(|node| node.latest_checkpoint_timestamp()),
+            probe_graphql,
+        )
+        .await;
+        if let Err(e) = graphql_result {
+            // Package lookups fall back to the full nodes, so this only needs attention if they are not fresh either.
+            if node_timestamp.is_ok() && self.nodes.has_healthy_endpoint() {
+                debug!("No GraphQL endpoint is available: {:?}", e);
+            } else {
+                warn!("No GraphQL endpoint is available: {:?}", e);
+            }
+        }
+        node_timestamp
+    }
+
+    async fn reference_gas_price(&self) -> Result<u64> {
+        self.nodes
+            .call("Getting the reference gas price", |node| {
+                node.reference_gas_price()
+            })
+            .await
+    }
+
+    async fn first_and_last_pkg_id(
+        &self,
+        pkg_id: &ObjectID,
+    ) -> Result<(ObjectID, ObjectID), InternalError> {
+        cached_first_and_last_pkg_id(&self.package_cache, pkg_id, || {
+            resolve_with_fallback(&self.resolvers, pkg_id)
+        })
+        .await
+    }
+
+    async fn verify_personal_message_signature(
+        &self,
+        signature: GenericSignature,
+        message: &[u8],
+        signer: SuiAddress,
+    ) -> Result<()> {
+        // Only zkLogin signatures, also as part of multisigs, are verified against the chain state.
+        let client = match signature {
+            GenericSignature::Signature(_) => None,
+            _ => Some(
+                self.nodes
+                    .call("Connecting", |node| node.sui_client())
+                    .await?
+                    .clone(),
+            ),
+        };
+        verify_personal_message_signature(signature, message, signer, client)
+            .await
+            .map_err(|e| anyhow!("{:?}", e))
+    }
+
+    fn endpoints_status(&self) -> EndpointsStatus {
+        EndpointsStatus {
+            full_nodes: self.nodes.status(),
+            graphql: self.graphql.status(),
+        }
+    }
+
+    fn package_cache_status(&self) -> Option<CacheStatus> {
+        Some(self.package_cache.status())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::collections::HashMap;
+    use sui_sdk::types::transaction::Command;
+
+    /// Decides whether a dry run of the PTB by the given sender succeeds on a chain whose latest checkpoint has the
+    /// given timestamp, standing in for the `seal_approve` functions of a package.
+    type MockPolicy = Box<dyn Fn(SuiAddress, &ProgrammableTransaction, u64) -> bool + Send>;
+
+    #[derive(Default)]
+    struct State {
+        /// The first version of each known package version.
+        first_versions: HashMap<ObjectID, ObjectID>,
+        /// The latest version of each package, by its first version.
+        latest_versions: HashMap<ObjectID, ObjectID>,
+        /// Policies by the package of the first Move call of the PTB.
+        policies: HashMap<ObjectID, MockPolicy>,
+        dry_runs: usize,
+        checkpoint_timestamp: u64,
+        reference_gas_price: u64,
+        unavailable: bool,
+    }
+
+    /// An in-memory [ChainBackend]. PTBs calling packages without a policy are denied. Signatures are
+    /// verified without chain state, so zkLogin signatures are not supported.
+    #[derive(Default)]
+    pub(crate) struct MockChain(Mutex<State>);
+
+    impl MockChain {
+        pub fn new(checkpoint_timestamp: u64) -> Self {
+            let chain = Self::default();
+            chain.0.lock().checkpoint_timestamp = checkpoint_timestamp;
+            chain.0.lock().reference_gas_price = 1000;
+            chain
+        }
+
+        /// Add a new package.
+        pub fn publish(&self, pkg_id: ObjectID) {
+            let mut state = self.0.lock();
+            state.first_versions.insert(pkg_id, pkg_id);
+            state.latest_versions.insert(pkg_id, pkg_id);
+        }
+
+        /// Add a new version of the package with the given first version.
+        pub fn upgrade(&self, first: ObjectID, new: ObjectID) {
+            let mut state = self.0.lock();
+            assert!(
+                state.latest_versions.contains_key(&first),
+                "Package is not published"
+            );
+            state.first_versions.insert(new, first);
+            state.latest_versions.insert(first, new);
+        }
+
+        /// Set the outcome of dry runs of PTBs calling the given package.
+        pub fn set_dry_run_result(&self, pkg_id: ObjectID, allowed: bool) {
+            self.set_policy(pkg_id, move |_, _, _| allowed);
+        }
+
+        /// Decide the outcome of dry runs of PTBs calling the given package version with the given policy.
+        pub fn set_policy(
+            &self,
+            pkg_id: ObjectID,
+            policy: impl Fn(SuiAddress, &ProgrammableTransaction, u64) -> bool + Send + 'static,
+        ) {
+            self.0.lock().policies.insert(pkg_id, Box::new(policy));
+        }
+
+        /// The number of dry runs performed.
+        pub fn dry_runs(&self) -> usize {
+            self.0.lock().dry_runs
+        }
+
+        pub fn set_checkpoint_timestamp(&self, timestamp: u64) {
+            self.0.lock().checkpoint_timestamp = timestamp;
+        }
+
+        pub fn set_reference_gas_price(&self, reference_gas_price: u64) {
+            self.0.lock().reference_gas_price = reference_gas_price;
+        }
+
+        /// If set, all calls which would need a full node fail.
+        pub fn set_unavailable(&self, unavailable: bool) {
+            self.0.lock().unavailable = unavailable;
+        }
+
+        fn check_available(&self) -> Result<()> {
+            match self.0.lock().unavailable {
+                true => Err(anyhow!("Full node is unavailable")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ChainBackend for MockChain {
+        async fn dry_run(
+            &self,
+            sender: SuiAddress,
+            ptb: &ProgrammableTransaction,
+            _gas_budget: u64,
+            _gas_price: u64,
+        ) -> Result<bool> {
+            self.check_available()?;
+            let mut state = self.0.lock();
+            state.dry_runs += 1;
+            let pkg_id = ptb.commands.iter().find_map(|command| match command {
+                Command::MoveCall(call) => Some(call.package),
+                _ => None,
+            });
+            Ok(pkg_id
+                .and_then(|pkg_id| state.policies.get(&pkg_id))
+                .is_some_and(|policy| policy(sender, ptb, state.checkpoint_timestamp)))
+        }
+
+        async fn latest_checkpoint_timestamp(&self) -> Result<u64> {
+            self.check_available()?;
+            Ok(self.0.lock().checkpoint_timestamp)
+        }
+
+        async fn reference_gas_price(&self) -> Result<u64> {
+            self.check_available()?;
+            Ok(self.0.lock().reference_gas_price)
+        }
+
+        async fn first_and_last_pkg_id(
+            &self,
+            pkg_id: &ObjectID,
+        ) -> Result<(ObjectID, ObjectID), InternalError> {
+            self.check_available().map_err(|_| InternalError::Failure)?;
+            let state = self.0.lock();
+            let first = *state
+                .first_versions
+                .get(pkg_id)
+                .ok_or(InternalError::InvalidPackage)?;
+            Ok((first, state.latest_versions[&first]))
+        }
+
+        async fn verify_personal_message_signature(
+            &self,
+            signature: GenericSignature,
+            message: &[u8],
+            signer: SuiAddress,
+        ) -> Result<()> {
+            verify_personal_message_signature(signature, message, signer, None)
+                .await
+                .map_err(|e| anyhow!("{:?}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::{CACHE_SIZE, CACHE_TTL};
+    use crate::clock::MockClock;
+    use crate::endpoints::redact;
+    use crate::tests::stub::{stub_server, stub_unavailable};
+    use chrono::TimeZone;
+    use prometheus::Registry;
+    use tracing_test::traced_test;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    /// A full node whose latest checkpoint has the given timestamp.
+    async fn stub_node(checkpoint_timestamp: u64, reference_gas_price: u64) -> String {
+        stub_server(move |request| {
+            let result = match request["method"].as_str()? {
+                "sui_getLatestCheckpointSequenceNumber" => json!("1000"),
+                "sui_getCheckpoint" => json!({ "timestampMs": checkpoint_timestamp.to_string() }),
+                "suix_getReferenceGasPrice" => json!(reference_gas_price.to_string()),
+                _ => return None,
+            };
+            Some(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+        })
+        .await
+    }
+
+    /// A GraphQL endpoint knowing the given package versions.
+    async fn stub_graphql(packages: Vec<(ObjectID, ObjectID)>) -> String {
+        stub_server(move |request| {
+            let query = request["query"].as_str()?;
+            if query.contains("latestPackage") {
+                let package = packages
+                    .iter()
+                    .find(|(first, latest)| {
+                        query.contains(&first.to_string()) || query.contains(&latest.to_string())
+                    })
+                    .map(|(first, latest)| {
+                        json!({ "address": latest, "packageAtVersion": { "address": first } })
+                    });
+                Some(json!({ "data": { "latestPackage": package } }))
+            } else {
+                let timestamp = chrono::Utc.timestamp_millis_opt(NOW as i64).unwrap();
+                Some(json!({ "data": { "checkpoint": { "timestamp": timestamp.to_rfc3339() } } }))
+            }
+        })
+        .await
+    }
+
+    fn sui_chain(
+        node_urls: Vec<String>,
+        graphql_urls: Vec<String>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> SuiChain {
+        let clock = Arc::new(MockClock::new(NOW));
+        SuiChain::new(
+            node_urls,
+            graphql_urls,
+            PackageResolverKind::GraphQl,
+            Cache::new(CACHE_TTL, CACHE_SIZE, clock.clone()),
+            Duration::from_secs(10),
+            clock,
+            metrics,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stale_node_failover() {
+        let stale = stub_node(NOW - 60_000, 1000).await;
+        let fresh = stub_node(NOW - 1_000, 2000).await;
+        let chain = sui_chain(vec![stale, fresh], vec![], None);
+
+        // Before probing, the first node is used.
+        assert_eq!(chain.reference_gas_price().await.unwrap(), 1000);
+
+        // Once probed, the fresh node is preferred and its checkpoint is reported.
+        assert_eq!(
+            chain.latest_checkpoint_timestamp().await.unwrap(),
+            NOW - 1_000
+        );
+        assert_eq!(chain.reference_gas_price().await.unwrap(), 2000);
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_unavailable_node_failover() {
+        let unavailable = stub_unavailable().await;
+        let node = stub_node(NOW, 1000).await;
+        let metrics = Arc::new(Metrics::new(&Registry::new()));
+        let chain = sui_chain(
+            vec![unavailable.clone(), node.clone()],
+            vec![],
+            Some(metrics.clone()),
+        );
+
+        assert_eq!(chain.reference_gas_price().await.unwrap(), 1000);
+        assert_eq!(chain.latest_checkpoint_timestamp().await.unwrap(), NOW);
+        assert_eq!(chain.reference_gas_price().await.unwrap(), 1000);
+
+        let requests = |url: &str, status: &str| {
+            metrics
+                .endpoint_requests
+                .with_label_values(&[url, status])
+                .get()
+        };
+        // The unavailable node is only tried before it has been probed. Endpoints are labelled by host and port.
+        assert_eq!(requests(&redact(&unavailable), "failure"), 2);
+        assert_eq!(requests(&redact(&node), "success"), 3);
+        assert_eq!(metrics.endpoint_failovers.get(), 1);
+        assert_eq!(
+            metrics
+                .endpoint_healthy
+                .with_label_values(&[&redact(&unavailable)])
+                .get(),
+            0
+        );
+        // No GraphQL endpoint is configured, so none is probed.
+        assert!(!logs_contain("No GraphQL endpoint is available"));
+
+        // If no node is available, requests fail.
+        let chain = sui_chain(vec![stub_unavailable().await], vec![], None);
+        assert!(chain.reference_gas_price().await.is_err());
+        assert!(chain.latest_checkpoint_timestamp().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_package_lookup_failover() {
+        let (first, latest) = (ObjectID::random(), ObjectID::random());
+        let unavailable = stub_unavailable().await;
+        let graphql = stub_graphql(vec![(first, latest)]).await;
+        let chain = sui_chain(vec![], vec![unavailable, graphql], None);
+
+        assert_eq!(
+            chain.first_and_last_pkg_id(&latest).await.unwrap(),
+            (first, latest)
+        );
+        assert_eq!(
+            chain.first_and_last_pkg_id(&ObjectID::random()).await,
+            Err(InternalError::InvalidPackage)
+        );
+
+        // Failures to reach any endpoint are reported as such.
+        let chain = sui_chain(vec![], vec![stub_unavailable().await], None);
+        assert_eq!(
+            chain.first_and_last_pkg_id(&ObjectID::random()).await,
+            Err(InternalError::Failure)
+        );
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The source of the current time used when handling requests, so tests can control it.
+
+use crate::externals::current_epoch_time;
+
+pub(crate) trait Clock: Send + Sync {
+    /// The current Unix time in milliseconds.
+    fn now(&self) -> u64;
+}
+
+/// The system clock.
+pub(crate) struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        current_epoch_time()
+    }
+}
+
+/// A clock which only moves when told to.
+#[cfg(test)]
+pub(crate) struct MockClock(std::sync::atomic::AtomicU64);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn new(now: u64) -> Self {
+        Self(now.into())
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.store(now, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        self.0.fetch_add(
+            duration.as_millis() as u64,
+            std::sync::atomic::Ordering::SeqCst,
+        );
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
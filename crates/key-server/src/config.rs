@@ -0,0 +1,1575 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Configuration of the key server.
+//!
+//! The configuration is read from an optional YAML (`.yaml`/`.yml`) or TOML (`.toml`) file, after which environment
+//! variables override individual values, e.g. `NETWORK=mainnet` or `GAS_BUDGET=1000`. All values but the key server
+//! object id have defaults, so the server can still be configured using environment variables only. The master key
+//! itself is never part of the file, only where to load it from, see [MasterKeySource].
+//!
+//! Example YAML file:
+//! ```yaml
+//! network: custom
+//! node_url: http://localhost:9000
+//! graphql_url: http://localhost:9125/graphql
+//! key_server_object_id: "0x1234"
+//! listen_address: 127.0.0.1:2024
+//! allowed_staleness_secs: 60
+//! master_key:
+//!   provider: keystore
+//!   path: /etc/seal/keystore.json
+//!   passphrase_file: /run/secrets/passphrase
+//! cors:
+//!   allowed_origins: ["https://example.com"]
+//! logging:
+//!   level: key_server=debug,info
+//! rate_limits:
+//!   per_user: { requests_per_sec: 1.0, burst: 10 }
+//!   per_ip: { requests_per_sec: 10.0, burst: 50 }
+//! ```
+//!
+//! Requests to the full node and the GraphQL endpoint fail over to fallback endpoints, see [crate::endpoints]:
+//! ```yaml
+//! network: mainnet
+//! fallback_node_urls: ["https://fullnode.example.com:443"]
+//! fallback_graphql_urls: ["https://graphql.example.com/graphql"]
+//! ```
+//!
+//! Package versions are resolved using GraphQL by default. To run with a full node only, use its JSON-RPC API:
+//! ```yaml
+//! network: custom
+//! node_url: http://localhost:9000
+//! package_resolver: json_rpc
+//! ```
+//!
+//! Identities may use another network than the top-level one. An identity which sets `network` takes none of the
+//! other network settings from the top level:
+//! ```yaml
+//! network: mainnet
+//! identities:
+//!   - key_server_object_id: "0x1"
+//!     master_key: { provider: secret_file, path: /run/secrets/mainnet }
+//!   - key_server_object_id: "0x2"
+//!     master_key: { provider: secret_file, path: /run/secrets/testnet }
+//!     network: testnet
+//!     fallback_node_urls: ["https://fullnode.example.com:443"]
+//! ```
+//!
+//! Key releases can be recorded in a tamper-evident audit log, see [crate::audit_log]:
+//! ```yaml
+//! audit_log:
+//!   path: /var/lib/seal/audit.log
+//!   checkpoint_interval_secs: 60
+//! ```
+//!
+//! During a master key rotation, the previous keys are kept as retired keys until the end of their grace period:
+//! ```yaml
+//! key_version: 1
+//! master_key:
+//!   provider: secret_file
+//!   path: /run/secrets/master-key-v1
+//! retired_keys:
+//!   - version: 0
+//!     master_key:
+//!       provider: secret_file
+//!       path: /run/secrets/master-key-v0
+//!     retired_until: 2025-06-01T00:00:00Z
+//! ```
+
+use crate::cache::{CACHE_SIZE, CACHE_TTL};
+use crate::types::Network;
+use axum::http::HeaderValue;
+use serde::Deserialize;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use sui_sdk::types::base_types::ObjectID;
+
+/// The allowed staleness of the full node.
+/// When setting this duration, note a timestamp on Sui may be a bit late compared to
+/// the current time, but it shouldn't be more than a second.
+const ALLOWED_STALENESS: Duration = Duration::from_secs(120);
+
+/// The interval at which the latest checkpoint timestamp is updated.
+const CHECKPOINT_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The interval at which the reference gas price is updated.
+const RGP_UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The maximum time to live for a session key.
+const SESSION_KEY_TTL_MAX: u16 = 30;
+
+/// The 1% of the max budget.
+const GAS_BUDGET: u64 = 500_000_000;
+
+const LISTEN_ADDRESS: &str = "0.0.0.0:2024";
+
+/// The default number of cached policy results.
+const POLICY_CACHE_SIZE: usize = 10_000;
+
+/// The default interval at which the audit log is checkpointed.
+const AUDIT_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The options used when handling requests.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ServerOptions {
+    pub allowed_staleness: Duration,
+    pub session_key_ttl_max: u16,
+    pub gas_budget: u64,
+    /// If set, policy results are cached, see [crate::policy_cache].
+    pub policy_cache: Option<PolicyCacheConfig>,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            allowed_staleness: ALLOWED_STALENESS,
+            session_key_ttl_max: SESSION_KEY_TTL_MAX,
+            gas_budget: GAS_BUDGET,
+            policy_cache: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PolicyCacheConfig {
+    /// How long policy results are reused, in milliseconds.
+    pub ttl_ms: u64,
+    pub size: usize,
+    /// First package ids of the packages whose policies are always evaluated.
+    pub excluded_packages: Vec<ObjectID>,
+}
+
+/// The audit log of key releases, see [crate::audit_log].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AuditLogConfig {
+    pub path: PathBuf,
+    pub checkpoint_interval: Duration,
+    /// The identity whose current master key signs the checkpoints.
+    pub key_server_object_id: ObjectID,
+}
+
+/// How the first and the latest version of a package are resolved, see [crate::package_resolver]. The other
+/// resolver is used as a fallback.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum PackageResolverKind {
+    #[default]
+    GraphQl,
+    JsonRpc,
+}
+
+/// Where the master key is loaded from. Defaults to [MasterKeySource::Env].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum MasterKeySource {
+    /// The base64 encoded key in the `MASTER_KEY` environment variable.
+    #[default]
+    Env,
+    /// A passphrase-encrypted keystore file, see [crate::keystore]. The passphrase is read from `passphrase_file`
+    /// or, if not set, from the `MASTER_KEY_PASSPHRASE` environment variable.
+    Keystore {
+        path: PathBuf,
+        passphrase_file: Option<PathBuf>,
+    },
+    /// A file containing the base64 encoded key, e.g. a secret mounted on a tmpfs.
+    SecretFile { path: PathBuf },
+    /// An inherited file descriptor from which the base64 encoded key is read. Must not be stdin, stdout or stderr.
+    Fd { fd: i32 },
+    /// An external signer listening on a Unix socket, see [crate::external_signer].
+    ExternalSigner { socket: PathBuf },
+}
+
+/// A token bucket limit allowing bursts of up to `burst` requests, refilled at `requests_per_sec`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RateLimit {
+    pub requests_per_sec: f64,
+    pub burst: u32,
+}
+
+/// Rate limits of fetch key requests, see [crate::rate_limit]. Limits which are not set are not enforced.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RateLimitConfig {
+    /// Per user address of the certificate, applied once its signature is verified.
+    pub per_user: Option<RateLimit>,
+    /// Per package id of the PTB.
+    pub per_package: Option<RateLimit>,
+    /// Per client IP.
+    pub per_ip: Option<RateLimit>,
+    /// Take the client IP from the last address of the `X-Forwarded-For` header instead of the connection, which is
+    /// used if the header is missing. Only enable this behind a load balancer which sets the header.
+    #[serde(default)]
+    pub trust_forwarded_for: bool,
+}
+
+/// The network of an identity and the endpoints used to access it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct NetworkConfig {
+    pub network: Network,
+    /// Full nodes used if the one of the network is unhealthy, in order of preference.
+    pub fallback_node_urls: Vec<String>,
+    /// GraphQL endpoints used if the one of the network is unhealthy, in order of preference.
+    pub fallback_graphql_urls: Vec<String>,
+    pub package_resolver: PackageResolverKind,
+}
+
+/// A key identity hosted by the key server, see [crate::identity].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct IdentityConfig {
+    pub key_server_object_id: ObjectID,
+    pub network: NetworkConfig,
+    pub master_key: MasterKeySource,
+    /// The version of the current master key.
+    pub key_version: u32,
+    /// Previous master keys which are still served during their grace period.
+    pub retired_keys: Vec<RetiredKeyConfig>,
+    /// The ids of the first versions of the packages keys are served for. If `None`, all packages are allowed.
+    pub allowed_packages: Option<Vec<ObjectID>>,
+}
+
+/// A previous master key of an identity.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RetiredKeyConfig {
+    pub version: u32,
+    pub master_key: MasterKeySource,
+    /// Unix time in milliseconds until which keys are still served with this master key.
+    pub retired_until: u64,
+}
+
+/// The validated configuration of the key server.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ServerConfig {
+    pub identities: Vec<IdentityConfig>,
+    pub listen_address: SocketAddr,
+    pub options: ServerOptions,
+    pub checkpoint_update_interval: Duration,
+    pub rgp_update_interval: Duration,
+    /// TTL in milliseconds of the package id cache.
+    pub cache_ttl: u64,
+    pub cache_size: usize,
+    /// The origins allowed by CORS. If `None`, any origin is allowed.
+    pub cors_allowed_origins: Option<Vec<HeaderValue>>,
+    /// Log filter directives, e.g. "info" or "key_server=debug,info". If `None`, `RUST_LOG` is used.
+    pub log_level: Option<String>,
+    pub rate_limits: RateLimitConfig,
+    /// If set, key releases are recorded in an audit log.
+    pub audit_log: Option<AuditLogConfig>,
+}
+
+/// The configuration file. All fields are optional and may be overridden by environment variables.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// The network settings of identities which do not set their own network.
+    network: Option<String>,
+    node_url: Option<String>,
+    graphql_url: Option<String>,
+    fallback_node_urls: Option<Vec<String>>,
+    fallback_graphql_urls: Option<Vec<String>>,
+    /// "graphql" or "json_rpc".
+    package_resolver: Option<String>,
+    key_server_object_id: Option<String>,
+    #[serde(default)]
+    master_key: MasterKeySource,
+    key_version: Option<u32>,
+    retired_keys: Option<Vec<RetiredKeyFile>>,
+    allowed_packages: Option<Vec<String>>,
+    /// Several identities. If set, the single identity fields above must not be set.
+    identities: Option<Vec<IdentityFile>>,
+    listen_address: Option<String>,
+    allowed_staleness_secs: Option<u64>,
+    checkpoint_update_interval_secs: Option<u64>,
+    rgp_update_interval_secs: Option<u64>,
+    session_key_ttl_max_min: Option<u16>,
+    gas_budget: Option<u64>,
+    cache_ttl_ms: Option<u64>,
+    cache_size: Option<usize>,
+    #[serde(default)]
+    cors: CorsConfig,
+    #[serde(default)]
+    logging: LoggingConfig,
+    #[serde(default)]
+    rate_limits: RateLimitConfig,
+    policy_cache: Option<PolicyCacheFile>,
+    audit_log: Option<AuditLogFile>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyCacheFile {
+    ttl_ms: Option<u64>,
+    size: Option<usize>,
+    excluded_packages: Option<Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuditLogFile {
+    path: Option<PathBuf>,
+    checkpoint_interval_secs: Option<u64>,
+    /// Only needed if several identities are hosted.
+    key_server_object_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdentityFile {
+    key_server_object_id: Option<String>,
+    #[serde(default)]
+    master_key: MasterKeySource,
+    key_version: Option<u32>,
+    retired_keys: Option<Vec<RetiredKeyFile>>,
+    allowed_packages: Option<Vec<String>>,
+    /// If set, the network settings below are used instead of the top-level ones.
+    network: Option<String>,
+    node_url: Option<String>,
+    graphql_url: Option<String>,
+    fallback_node_urls: Option<Vec<String>>,
+    fallback_graphql_urls: Option<Vec<String>>,
+    package_resolver: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RetiredKeyFile {
+    version: u32,
+    master_key: MasterKeySource,
+    /// An RFC 3339 timestamp, e.g. "2025-06-01T00:00:00Z".
+    retired_until: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CorsConfig {
+    /// Use "*" to allow any origin. Defaults to any origin.
+    allowed_origins: Option<Vec<String>>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LoggingConfig {
+    level: Option<String>,
+}
+
+impl ServerConfig {
+    /// Load the configuration from the given file, if any, and the environment.
+    /// Returns all problems found instead of stopping at the first one.
+    pub fn load(path: Option<&Path>) -> Result<Self, Vec<String>> {
+        Self::load_with_env(path, |name| std::env::var(name).ok())
+    }
+
+    fn load_with_env(
+        path: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Vec<String>> {
+        let file = match path {
+            Some(path) => read_config_file(path).map_err(|e| vec![e])?,
+            None => ConfigFile::default(),
+        };
+        Self::from_file_and_env(file, env)
+    }
+
+    fn from_file_and_env(
+        mut file: ConfigFile,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Vec<String>> {
+        let mut problems = vec![];
+
+        // Environment overrides.
+        let set = |name: &str, value: &mut Option<String>| {
+            if let Some(v) = env(name) {
+                *value = Some(v);
+            }
+        };
+        set("NETWORK", &mut file.network);
+        set("NODE_URL", &mut file.node_url);
+        set("GRAPHQL_URL", &mut file.graphql_url);
+        set("PACKAGE_RESOLVER", &mut file.package_resolver);
+        set("KEY_SERVER_OBJECT_ID", &mut file.key_server_object_id);
+        set("LISTEN_ADDRESS", &mut file.listen_address);
+        set("LOG_LEVEL", &mut file.logging.level);
+        override_with(&env, "KEY_VERSION", &mut file.key_version, &mut problems);
+        let set_list = |name: &str, value: &mut Option<Vec<String>>| {
+            if let Some(v) = env(name) {
+                *value = Some(v.split(',').map(|s| s.trim().to_string()).collect());
+            }
+        };
+        set_list("CORS_ALLOWED_ORIGINS", &mut file.cors.allowed_origins);
+        set_list("FALLBACK_NODE_URLS", &mut file.fallback_node_urls);
+        set_list("FALLBACK_GRAPHQL_URLS", &mut file.fallback_graphql_urls);
+        override_with(
+            &env,
+            "ALLOWED_STALENESS_SECS",
+            &mut file.allowed_staleness_secs,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "CHECKPOINT_UPDATE_INTERVAL_SECS",
+            &mut file.checkpoint_update_interval_secs,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "RGP_UPDATE_INTERVAL_SECS",
+            &mut file.rgp_update_interval_secs,
+            &mut problems,
+        );
+        override_with(
+            &env,
+            "SESSION_KEY_TTL_MAX_MIN",
+            &mut file.session_key_ttl_max_min,
+            &mut problems,
+        );
+        override_with(&env, "GAS_BUDGET", &mut file.gas_budget, &mut problems);
+        override_with(&env, "CACHE_TTL_MS", &mut file.cache_ttl_ms, &mut problems);
+        override_with(&env, "CACHE_SIZE", &mut file.cache_size, &mut problems);
+        if let Some(path) = env("AUDIT_LOG_PATH") {
+            file.audit_log.get_or_insert_with(Default::default).path = Some(path.into());
+        }
+        if env("POLICY_CACHE_TTL_MS").is_some() {
+            override_with(
+                &env,
+                "POLICY_CACHE_TTL_MS",
+                &mut file
+                    .policy_cache
+                    .get_or_insert_with(Default::default)
+                    .ttl_ms,
+                &mut problems,
+            );
+        }
+
+        // Validation.
+        let default_network = check_network(
+            file.network.as_deref().unwrap_or("testnet"),
+            file.node_url,
+            file.graphql_url,
+            file.fallback_node_urls,
+            file.fallback_graphql_urls,
+            file.package_resolver,
+            &mut problems,
+        );
+
+        let identity_files = match file.identities {
+            None => vec![IdentityFile {
+                key_server_object_id: file.key_server_object_id,
+                master_key: file.master_key,
+                key_version: file.key_version,
+                retired_keys: file.retired_keys,
+                allowed_packages: file.allowed_packages,
+                network: None,
+                node_url: None,
+                graphql_url: None,
+                fallback_node_urls: None,
+                fallback_graphql_urls: None,
+                package_resolver: None,
+            }],
+            Some(identities) => {
+                if file.key_server_object_id.is_some()
+                    || file.master_key != MasterKeySource::Env
+                    || file.key_version.is_some()
+                    || file.retired_keys.is_some()
+                    || file.allowed_packages.is_some()
+                {
+                    problems.push(
+                        "key_server_object_id, master_key, key_version, retired_keys and allowed_packages must be set per identity if identities are given"
+                            .into(),
+                    );
+                }
+                if identities.is_empty() {
+                    problems.push("identities must not be empty".into());
+                }
+                identities
+            }
+        };
+        let identities = check_identities(identity_files, default_network.as_ref(), &mut problems);
+
+        let listen_address = file.listen_address.as_deref().unwrap_or(LISTEN_ADDRESS);
+        let listen_address = SocketAddr::from_str(listen_address)
+            .map_err(|e| problems.push(format!("Invalid listen_address {}: {}", listen_address, e)))
+            .ok();
+
+        let mut positive = |name: &str, value: Option<u64>, default: u64| -> u64 {
+            let value = value.unwrap_or(default);
+            if value == 0 {
+                problems.push(format!("{} must be greater than 0", name));
+            }
+            value
+        };
+        let allowed_staleness = positive(
+            "allowed_staleness_secs",
+            file.allowed_staleness_secs,
+            ALLOWED_STALENESS.as_secs(),
+        );
+        let checkpoint_update_interval = positive(
+            "checkpoint_update_interval_secs",
+            file.checkpoint_update_interval_secs,
+            CHECKPOINT_UPDATE_INTERVAL.as_secs(),
+        );
+        let rgp_update_interval = positive(
+            "rgp_update_interval_secs",
+            file.rgp_update_interval_secs,
+            RGP_UPDATE_INTERVAL.as_secs(),
+        );
+        let session_key_ttl_max = positive(
+            "session_key_ttl_max_min",
+            file.session_key_ttl_max_min.map(u64::from),
+            SESSION_KEY_TTL_MAX as u64,
+        ) as u16;
+        let gas_budget = positive("gas_budget", file.gas_budget, GAS_BUDGET);
+        let cache_ttl = positive("cache_ttl_ms", file.cache_ttl_ms, CACHE_TTL);
+        let cache_size = positive(
+            "cache_size",
+            file.cache_size.map(|s| s as u64),
+            CACHE_SIZE as u64,
+        ) as usize;
+
+        let cors_allowed_origins = match file.cors.allowed_origins {
+            None => None,
+            Some(origins) if origins.iter().any(|o| o == "*") => None,
+            Some(origins) if origins.is_empty() => {
+                problems.push("cors.allowed_origins must not be empty".into());
+                None
+            }
+            Some(origins) => Some(
+                origins
+                    .iter()
+                    .filter_map(|o| {
+                        HeaderValue::from_str(o)
+                            .map_err(|_| problems.push(format!("Invalid CORS origin: {}", o)))
+                            .ok()
+                    })
+                    .collect(),
+            ),
+        };
+
+        if let Some(level) = &file.logging.level {
+            if let Err(e) = check_log_level(level) {
+                problems.push(e);
+            }
+        }
+
+        let policy_cache = file.policy_cache.map(|policy_cache| PolicyCacheConfig {
+            ttl_ms: match policy_cache.ttl_ms {
+                Some(ttl_ms) if ttl_ms > 0 => ttl_ms,
+                _ => {
+                    problems.push(
+                        "policy_cache.ttl_ms (POLICY_CACHE_TTL_MS) must be greater than 0".into(),
+                    );
+                    0
+                }
+            },
+            size: match policy_cache.size {
+                Some(0) => {
+                    problems.push("policy_cache.size must be greater than 0".into());
+                    0
+                }
+                size => size.unwrap_or(POLICY_CACHE_SIZE),
+            },
+            excluded_packages: policy_cache
+                .excluded_packages
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|id| {
+                    ObjectID::from_hex_literal(id)
+                        .map_err(|e| {
+                            problems.push(format!("Invalid excluded package {}: {}", id, e))
+                        })
+                        .ok()
+                })
+                .collect(),
+        });
+
+        let audit_log = file.audit_log.and_then(|audit_log| {
+            let checkpoint_interval = audit_log
+                .checkpoint_interval_secs
+                .unwrap_or(AUDIT_CHECKPOINT_INTERVAL.as_secs());
+            if checkpoint_interval == 0 {
+                problems.push("audit_log.checkpoint_interval_secs must be greater than 0".into());
+            }
+            let key_server_object_id = match &audit_log.key_server_object_id {
+                Some(id) => match ObjectID::from_hex_literal(id) {
+                    Ok(id) if identities.iter().any(|i| i.key_server_object_id == id) => Some(id),
+                    Ok(id) => {
+                        problems.push(format!(
+                            "audit_log.key_server_object_id {} is not a hosted identity",
+                            id
+                        ));
+                        None
+                    }
+                    Err(e) => {
+                        problems.push(format!(
+                            "Invalid audit_log.key_server_object_id {}: {}",
+                            id, e
+                        ));
+                        None
+                    }
+                },
+                None if identities.len() == 1 => Some(identities[0].key_server_object_id),
+                // Missing identities are reported above.
+                None if identities.is_empty() => None,
+                None => {
+                    problems.push(
+                        "audit_log.key_server_object_id must be set if several identities are hosted"
+                            .into(),
+                    );
+                    None
+                }
+            };
+            let Some(path) = audit_log.path else {
+                problems.push("audit_log.path (AUDIT_LOG_PATH) must be set".into());
+                return None;
+            };
+            Some(AuditLogConfig {
+                path,
+                checkpoint_interval: Duration::from_secs(checkpoint_interval),
+                key_server_object_id: key_server_object_id?,
+            })
+        });
+
+        for (name, limit) in [
+            ("per_user", &file.rate_limits.per_user),
+            ("per_package", &file.rate_limits.per_package),
+            ("per_ip", &file.rate_limits.per_ip),
+        ] {
+            if let Some(limit) = limit {
+                if !(limit.requests_per_sec.is_finite() && limit.requests_per_sec > 0.0)
+                    || limit.burst == 0
+                {
+                    problems.push(format!(
+                        "rate_limits.{} must have positive requests_per_sec and burst",
+                        name
+                    ));
+                }
+            }
+        }
+
+        if !problems.is_empty() {
+            return Err(problems);
+        }
+        Ok(ServerConfig {
+            identities,
+            listen_address: listen_address.expect("checked above"),
+            options: ServerOptions {
+                allowed_staleness: Duration::from_secs(allowed_staleness),
+                session_key_ttl_max,
+                gas_budget,
+                policy_cache,
+            },
+            checkpoint_update_interval: Duration::from_secs(checkpoint_update_interval),
+            rgp_update_interval: Duration::from_secs(rgp_update_interval),
+            cache_ttl,
+            cache_size,
+            cors_allowed_origins,
+            log_level: file.logging.level,
+            rate_limits: file.rate_limits,
+            audit_log,
+        })
+    }
+
+    /// The distinct networks of the identities, in the order in which they are first used.
+    pub fn networks(&self) -> Vec<&NetworkConfig> {
+        let mut networks: Vec<&NetworkConfig> = vec![];
+        for identity in &self.identities {
+            if !networks.contains(&&identity.network) {
+                networks.push(&identity.network);
+            }
+        }
+        networks
+    }
+}
+
+impl NetworkConfig {
+    /// The full nodes to use, in order of preference.
+    pub fn node_urls(&self) -> Vec<String> {
+        std::iter::once(self.network.node_url())
+            .chain(self.fallback_node_urls.iter().cloned())
+            .collect()
+    }
+
+    /// The GraphQL endpoints to use, in order of preference.
+    pub fn graphql_urls(&self) -> Vec<String> {
+        self.network
+            .graphql_url()
+            .into_iter()
+            .chain(self.fallback_graphql_urls.iter().cloned())
+            .collect()
+    }
+}
+
+fn check_network(
+    name: &str,
+    node_url: Option<String>,
+    graphql_url: Option<String>,
+    fallback_node_urls: Option<Vec<String>>,
+    fallback_graphql_urls: Option<Vec<String>>,
+    package_resolver: Option<String>,
+    problems: &mut Vec<String>,
+) -> Option<NetworkConfig> {
+    let network = Network::from_name(name, node_url, graphql_url)
+        .map_err(|e| problems.push(e))
+        .ok();
+    let package_resolver = match package_resolver.as_deref() {
+        None | Some("graphql") => PackageResolverKind::GraphQl,
+        Some("json_rpc") => PackageResolverKind::JsonRpc,
+        Some(resolver) => {
+            problems.push(format!(
+                "Unknown package_resolver {}, must be graphql or json_rpc",
+                resolver
+            ));
+            PackageResolverKind::GraphQl
+        }
+    };
+    if let Some(network) = &network {
+        if network.graphql_url().is_none() && package_resolver == PackageResolverKind::GraphQl {
+            problems.push(
+                "graphql_url (GRAPHQL_URL) must be set for a custom network unless package_resolver is json_rpc"
+                    .into(),
+            );
+        }
+    }
+    let fallback_node_urls = check_fallback_urls(
+        "fallback_node_urls",
+        fallback_node_urls.unwrap_or_default(),
+        problems,
+    );
+    let fallback_graphql_urls = check_fallback_urls(
+        "fallback_graphql_urls",
+        fallback_graphql_urls.unwrap_or_default(),
+        problems,
+    );
+    Some(NetworkConfig {
+        network: network?,
+        fallback_node_urls,
+        fallback_graphql_urls,
+        package_resolver,
+    })
+}
+
+fn check_fallback_urls(name: &str, urls: Vec<String>, problems: &mut Vec<String>) -> Vec<String> {
+    for (i, url) in urls.iter().enumerate() {
+        if let Err(e) = reqwest::Url::parse(url) {
+            problems.push(format!("Invalid url in {} {}: {}", name, url, e));
+        }
+        if urls[..i].contains(url) {
+            problems.push(format!("Duplicate url in {}: {}", name, url));
+        }
+    }
+    urls
+}
+
+fn check_identities(
+    identities: Vec<IdentityFile>,
+    default_network: Option<&NetworkConfig>,
+    problems: &mut Vec<String>,
+) -> Vec<IdentityConfig> {
+    let parse_object_id = |name: &str, id: &str, problems: &mut Vec<String>| {
+        ObjectID::from_hex_literal(id)
+            .map_err(|e| problems.push(format!("Invalid {} {}: {}", name, id, e)))
+            .ok()
+    };
+
+    let mut result: Vec<IdentityConfig> = vec![];
+    for identity in identities {
+        let key_server_object_id = match identity.key_server_object_id {
+            None => {
+                problems.push("key_server_object_id (KEY_SERVER_OBJECT_ID) must be set".into());
+                None
+            }
+            Some(id) => parse_object_id("key_server_object_id", &id, problems),
+        };
+        let key_version = identity.key_version.unwrap_or_default();
+        let retired_keys = check_retired_keys(
+            key_version,
+            identity.retired_keys.unwrap_or_default(),
+            problems,
+        );
+        let sources: Vec<&MasterKeySource> = std::iter::once(&identity.master_key)
+            .chain(retired_keys.iter().map(|key| &key.master_key))
+            .collect();
+        for source in &sources {
+            if let MasterKeySource::Fd { fd } = source {
+                if *fd <= 2 {
+                    problems.push(format!(
+                        "Invalid master key file descriptor: {} (must not be stdin, stdout or stderr)",
+                        fd
+                    ));
+                }
+            }
+        }
+        let allowed_packages = identity.allowed_packages.map(|packages| {
+            packages
+                .iter()
+                .filter_map(|id| parse_object_id("allowed package", id, problems))
+                .collect()
+        });
+        // An invalid top-level network is reported by the caller.
+        let network = match identity.network {
+            Some(name) => check_network(
+                &name,
+                identity.node_url,
+                identity.graphql_url,
+                identity.fallback_node_urls,
+                identity.fallback_graphql_urls,
+                identity.package_resolver,
+                problems,
+            ),
+            None => {
+                if identity.node_url.is_some()
+                    || identity.graphql_url.is_some()
+                    || identity.fallback_node_urls.is_some()
+                    || identity.fallback_graphql_urls.is_some()
+                    || identity.package_resolver.is_some()
+                {
+                    problems.push(
+                        "node_url, graphql_url, fallback_node_urls, fallback_graphql_urls and package_resolver of an identity require its network to be set"
+                            .into(),
+                    );
+                }
+                default_network.cloned()
+            }
+        };
+
+        let (Some(key_server_object_id), Some(network)) = (key_server_object_id, network) else {
+            continue;
+        };
+        if result
+            .iter()
+            .any(|other| other.key_server_object_id == key_server_object_id)
+        {
+            problems.push(format!(
+                "Duplicate key_server_object_id: {}",
+                key_server_object_id
+            ));
+        }
+        // The environment variable and file descriptors can only hold a single key.
+        let used = result
+            .iter()
+            .flat_map(|other| {
+                std::iter::once(&other.master_key)
+                    .chain(other.retired_keys.iter().map(|key| &key.master_key))
+            })
+            .chain(sources.iter().copied());
+        let mut seen: Vec<&MasterKeySource> = vec![];
+        for source in used {
+            if matches!(source, MasterKeySource::Env | MasterKeySource::Fd { .. })
+                && seen.contains(&source)
+            {
+                problems.push(format!(
+                    "The master key of {} is also used by another identity or key version",
+                    key_server_object_id
+                ));
+                break;
+            }
+            seen.push(source);
+        }
+        result.push(IdentityConfig {
+            key_server_object_id,
+            network,
+            master_key: identity.master_key,
+            key_version,
+            retired_keys,
+            allowed_packages,
+        });
+    }
+    result
+}
+
+fn check_retired_keys(
+    key_version: u32,
+    retired_keys: Vec<RetiredKeyFile>,
+    problems: &mut Vec<String>,
+) -> Vec<RetiredKeyConfig> {
+    let mut result: Vec<RetiredKeyConfig> = vec![];
+    for key in retired_keys {
+        if key.version == key_version || result.iter().any(|other| other.version == key.version) {
+            problems.push(format!("Duplicate key version: {}", key.version));
+        }
+        let retired_until = chrono::DateTime::parse_from_rfc3339(&key.retired_until)
+            .map_err(|e| e.to_string())
+            .and_then(|t| u64::try_from(t.timestamp_millis()).map_err(|e| e.to_string()));
+        match retired_until {
+            Ok(retired_until) => result.push(RetiredKeyConfig {
+                version: key.version,
+                master_key: key.master_key,
+                retired_until,
+            }),
+            Err(e) => problems.push(format!(
+                "Invalid retired_until {} of key version {}: {}",
+                key.retired_until, key.version, e
+            )),
+        }
+    }
+    result
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigFile, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read config file {}: {}", path.display(), e))?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("yaml") | Some("yml") => serde_yml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+        Some("toml") => toml::from_str(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e)),
+        _ => Err(format!(
+            "Config file {} must have extension .yaml, .yml or .toml",
+            path.display()
+        )),
+    }
+}
+
+fn override_with<T: FromStr>(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    value: &mut Option<T>,
+    problems: &mut Vec<String>,
+) where
+    T::Err: Display,
+{
+    if let Some(v) = env(name) {
+        match T::from_str(&v) {
+            Ok(v) => *value = Some(v),
+            Err(e) => problems.push(format!("Invalid value for {}: {} ({})", name, v, e)),
+        }
+    }
+}
+
+/// Check that every directive of the form `[target=]level` has a valid level.
+fn check_log_level(directives: &str) -> Result<(), String> {
+    for directive in directives.split(',') {
+        let level = directive.rsplit('=').next().unwrap_or_default().trim();
+        if !level.eq_ignore_ascii_case("off") && tracing::Level::from_str(level).is_err() {
+            return Err(format!("Invalid log level: {}", directive));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn write_config(extension: &str, contents: &str) -> tempfile::TempPath {
+        let mut file = tempfile::Builder::new()
+            .suffix(extension)
+            .tempfile()
+            .unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.into_temp_path()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config =
+            ServerConfig::load_with_env(None, env(&[("KEY_SERVER_OBJECT_ID", "0x1")])).unwrap();
+        assert_eq!(config.identities[0].network.network, Network::Testnet);
+        assert_eq!(config.identities.len(), 1);
+        assert_eq!(
+            config.identities[0].key_server_object_id,
+            ObjectID::from_single_byte(1)
+        );
+        assert_eq!(config.identities[0].allowed_packages, None);
+        assert_eq!(
+            config.listen_address,
+            SocketAddr::from_str(LISTEN_ADDRESS).unwrap()
+        );
+        assert_eq!(config.identities[0].master_key, MasterKeySource::Env);
+        assert_eq!(config.options, ServerOptions::default());
+        assert_eq!(
+            config.checkpoint_update_interval,
+            CHECKPOINT_UPDATE_INTERVAL
+        );
+        assert_eq!(config.rgp_update_interval, RGP_UPDATE_INTERVAL);
+        assert_eq!(config.cache_ttl, CACHE_TTL);
+        assert_eq!(config.cache_size, CACHE_SIZE);
+        assert_eq!(config.cors_allowed_origins, None);
+        assert_eq!(config.log_level, None);
+        assert_eq!(config.rate_limits, RateLimitConfig::default());
+    }
+
+    #[test]
+    fn test_yaml_and_toml() {
+        let yaml = write_config(
+            ".yaml",
+            r#"
+network: custom
+node_url: http://localhost:9000
+graphql_url: http://localhost:9125/graphql
+key_server_object_id: "0x2"
+listen_address: 127.0.0.1:3000
+allowed_staleness_secs: 60
+session_key_ttl_max_min: 10
+cache_size: 5
+master_key:
+  provider: keystore
+  path: /etc/seal/keystore.json
+cors:
+  allowed_origins: ["https://example.com"]
+logging:
+  level: key_server=debug,info
+"#,
+        );
+        let toml = write_config(
+            ".toml",
+            r#"
+network = "custom"
+node_url = "http://localhost:9000"
+graphql_url = "http://localhost:9125/graphql"
+key_server_object_id = "0x2"
+listen_address = "127.0.0.1:3000"
+allowed_staleness_secs = 60
+session_key_ttl_max_min = 10
+cache_size = 5
+
+[master_key]
+provider = "keystore"
+path = "/etc/seal/keystore.json"
+
+[cors]
+allowed_origins = ["https://example.com"]
+
+[logging]
+level = "key_server=debug,info"
+"#,
+        );
+        let yaml = ServerConfig::load_with_env(Some(&yaml), env(&[])).unwrap();
+        let toml = ServerConfig::load_with_env(Some(&toml), env(&[])).unwrap();
+        assert_eq!(yaml, toml);
+        assert_eq!(
+            yaml.identities[0].network.network,
+            Network::Custom {
+                node_url: "http://localhost:9000".into(),
+                graphql_url: Some("http://localhost:9125/graphql".into()),
+            }
+        );
+        assert_eq!(yaml.options.allowed_staleness, Duration::from_secs(60));
+        assert_eq!(yaml.options.session_key_ttl_max, 10);
+        assert_eq!(yaml.options.gas_budget, GAS_BUDGET);
+        assert_eq!(yaml.cache_size, 5);
+        assert_eq!(
+            yaml.identities[0].master_key,
+            MasterKeySource::Keystore {
+                path: "/etc/seal/keystore.json".into(),
+                passphrase_file: None,
+            }
+        );
+        assert_eq!(
+            yaml.cors_allowed_origins,
+            Some(vec![HeaderValue::from_static("https://example.com")])
+        );
+        assert_eq!(yaml.log_level.as_deref(), Some("key_server=debug,info"));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let path = write_config(
+            ".yml",
+            "network: devnet\nkey_server_object_id: \"0x2\"\ngas_budget: 10\n",
+        );
+        let config = ServerConfig::load_with_env(
+            Some(&path),
+            env(&[
+                ("NETWORK", "mainnet"),
+                ("GAS_BUDGET", "20"),
+                ("CORS_ALLOWED_ORIGINS", "https://a.com, https://b.com"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.identities[0].network.network, Network::Mainnet);
+        assert_eq!(
+            config.identities[0].key_server_object_id,
+            ObjectID::from_single_byte(2)
+        );
+        assert_eq!(config.options.gas_budget, 20);
+        assert_eq!(config.cors_allowed_origins.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let path = write_config(
+            ".toml",
+            r#"
+network = "custom"
+node_url = "not a url"
+listen_address = "localhost"
+cache_ttl_ms = 0
+cache_size = 0
+
+[cors]
+allowed_origins = []
+
+[logging]
+level = "loud"
+"#,
+        );
+        let problems =
+            ServerConfig::load_with_env(Some(&path), env(&[("GAS_BUDGET", "a lot")])).unwrap_err();
+        assert_eq!(problems.len(), 8, "{:?}", problems);
+
+        // Unknown fields and extensions are rejected.
+        let path = write_config(".yaml", "gas_budgett: 1\n");
+        assert_eq!(
+            ServerConfig::load_with_env(Some(&path), env(&[]))
+                .unwrap_err()
+                .len(),
+            1
+        );
+        let path = write_config(".json", "{}");
+        assert!(ServerConfig::load_with_env(Some(&path), env(&[])).is_err());
+
+        // Unknown network.
+        assert!(ServerConfig::load_with_env(
+            None,
+            env(&[("NETWORK", "moonnet"), ("KEY_SERVER_OBJECT_ID", "0x1")])
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_identities() {
+        let path = write_config(
+            ".yaml",
+            r#"
+identities:
+  - key_server_object_id: "0x1"
+    master_key:
+      provider: secret_file
+      path: /run/secrets/first
+    allowed_packages: ["0xa", "0xb"]
+  - key_server_object_id: "0x2"
+    master_key:
+      provider: external_signer
+      socket: /run/signer.sock
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        let testnet = NetworkConfig {
+            network: Network::Testnet,
+            fallback_node_urls: vec![],
+            fallback_graphql_urls: vec![],
+            package_resolver: PackageResolverKind::GraphQl,
+        };
+        assert_eq!(
+            config.identities,
+            vec![
+                IdentityConfig {
+                    key_server_object_id: ObjectID::from_single_byte(1),
+                    network: testnet.clone(),
+                    master_key: MasterKeySource::SecretFile {
+                        path: "/run/secrets/first".into()
+                    },
+                    key_version: 0,
+                    retired_keys: vec![],
+                    allowed_packages: Some(vec![
+                        ObjectID::from_single_byte(0xa),
+                        ObjectID::from_single_byte(0xb)
+                    ]),
+                },
+                IdentityConfig {
+                    key_server_object_id: ObjectID::from_single_byte(2),
+                    network: testnet,
+                    master_key: MasterKeySource::ExternalSigner {
+                        socket: "/run/signer.sock".into()
+                    },
+                    key_version: 0,
+                    retired_keys: vec![],
+                    allowed_packages: None,
+                },
+            ]
+        );
+
+        // Single identity fields conflict with identities.
+        assert_eq!(
+            ServerConfig::load_with_env(Some(&path), env(&[("KEY_SERVER_OBJECT_ID", "0x3")]))
+                .unwrap_err()
+                .len(),
+            1
+        );
+
+        // Duplicate ids, shared master keys, invalid packages and missing ids are reported.
+        let path = write_config(
+            ".yaml",
+            r#"
+identities:
+  - key_server_object_id: "0x1"
+  - key_server_object_id: "0x1"
+    master_key:
+      provider: fd
+      fd: 3
+    allowed_packages: ["not an id"]
+  - master_key:
+      provider: fd
+      fd: 3
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+
+        // The standard streams cannot be used to pass the master key.
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+master_key:
+  provider: fd
+  fd: 0
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_identity_networks() {
+        let path = write_config(
+            ".yaml",
+            r#"
+network: mainnet
+fallback_node_urls: ["http://localhost:9000"]
+identities:
+  - key_server_object_id: "0x1"
+    master_key: { provider: secret_file, path: /run/secrets/first }
+  - key_server_object_id: "0x2"
+    master_key: { provider: secret_file, path: /run/secrets/second }
+    network: custom
+    node_url: http://localhost:9001
+    package_resolver: json_rpc
+  - key_server_object_id: "0x3"
+    master_key: { provider: secret_file, path: /run/secrets/third }
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        let networks = config.networks();
+        assert_eq!(networks.len(), 2);
+        assert_eq!(networks[0], &config.identities[0].network);
+        assert_eq!(networks[0], &config.identities[2].network);
+        assert_eq!(
+            networks[0].node_urls(),
+            vec![
+                Network::Mainnet.node_url(),
+                "http://localhost:9000".to_string()
+            ]
+        );
+        // Nothing but the network of the identity is used.
+        assert_eq!(
+            networks[1],
+            &NetworkConfig {
+                network: Network::Custom {
+                    node_url: "http://localhost:9001".into(),
+                    graphql_url: None,
+                },
+                fallback_node_urls: vec![],
+                fallback_graphql_urls: vec![],
+                package_resolver: PackageResolverKind::JsonRpc,
+            }
+        );
+
+        // Endpoints of an identity require its network, and invalid networks of identities are reported.
+        let path = write_config(
+            ".yaml",
+            r#"
+identities:
+  - key_server_object_id: "0x1"
+    master_key: { provider: secret_file, path: /run/secrets/first }
+    node_url: http://localhost:9001
+  - key_server_object_id: "0x2"
+    master_key: { provider: secret_file, path: /run/secrets/second }
+    network: moonnet
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_retired_keys() {
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+key_version: 2
+master_key:
+  provider: secret_file
+  path: /run/secrets/v2
+retired_keys:
+  - version: 1
+    master_key:
+      provider: secret_file
+      path: /run/secrets/v1
+    retired_until: 2025-06-01T00:00:00Z
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        assert_eq!(config.identities[0].key_version, 2);
+        assert_eq!(
+            config.identities[0].retired_keys,
+            vec![RetiredKeyConfig {
+                version: 1,
+                master_key: MasterKeySource::SecretFile {
+                    path: "/run/secrets/v1".into()
+                },
+                retired_until: 1_748_736_000_000,
+            }]
+        );
+
+        // The current version can be overridden, e.g. when rolling out a new key.
+        let config =
+            ServerConfig::load_with_env(Some(&path), env(&[("KEY_VERSION", "3")])).unwrap();
+        assert_eq!(config.identities[0].key_version, 3);
+
+        // Duplicate versions, invalid timestamps and master keys shared between versions are reported.
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+key_version: 1
+retired_keys:
+  - version: 1
+    master_key:
+      provider: secret_file
+      path: /run/secrets/v1
+    retired_until: 2025-06-01T00:00:00Z
+  - version: 0
+    master_key:
+      provider: env
+    retired_until: tomorrow
+  - version: 2
+    master_key:
+      provider: env
+    retired_until: 2025-06-01T00:00:00Z
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let path = write_config(
+            ".toml",
+            r#"
+key_server_object_id = "0x1"
+
+[rate_limits]
+per_user = { requests_per_sec = 0.5, burst = 10 }
+per_ip = { requests_per_sec = 20, burst = 100 }
+trust_forwarded_for = true
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        assert_eq!(
+            config.rate_limits,
+            RateLimitConfig {
+                per_user: Some(RateLimit {
+                    requests_per_sec: 0.5,
+                    burst: 10
+                }),
+                per_package: None,
+                per_ip: Some(RateLimit {
+                    requests_per_sec: 20.0,
+                    burst: 100
+                }),
+                trust_forwarded_for: true,
+            }
+        );
+
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+rate_limits:
+  per_user: { requests_per_sec: 0, burst: 10 }
+  per_package: { requests_per_sec: 1, burst: 0 }
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_policy_cache() {
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+policy_cache:
+  ttl_ms: 5000
+  excluded_packages: ["0xa"]
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        assert_eq!(
+            config.options.policy_cache,
+            Some(PolicyCacheConfig {
+                ttl_ms: 5000,
+                size: POLICY_CACHE_SIZE,
+                excluded_packages: vec![ObjectID::from_single_byte(0xa)],
+            })
+        );
+
+        // The cache can be enabled with an environment variable.
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[
+                ("KEY_SERVER_OBJECT_ID", "0x1"),
+                ("POLICY_CACHE_TTL_MS", "2000"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.options.policy_cache.unwrap().ttl_ms, 2000);
+
+        let path = write_config(
+            ".yaml",
+            "key_server_object_id: \"0x1\"\npolicy_cache:\n  size: 0\n",
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_fallback_urls() {
+        let path = write_config(
+            ".yaml",
+            r#"
+network: mainnet
+key_server_object_id: "0x1"
+fallback_node_urls: ["http://localhost:9000"]
+"#,
+        );
+        let config = ServerConfig::load_with_env(
+            Some(&path),
+            env(&[(
+                "FALLBACK_GRAPHQL_URLS",
+                "http://localhost:9125/graphql, http://localhost:9126/graphql",
+            )]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.identities[0].network.node_urls(),
+            vec![
+                Network::Mainnet.node_url(),
+                "http://localhost:9000".to_string()
+            ]
+        );
+        assert_eq!(
+            config.identities[0].network.graphql_urls(),
+            vec![
+                Network::Mainnet.graphql_url().unwrap(),
+                "http://localhost:9125/graphql".to_string(),
+                "http://localhost:9126/graphql".to_string(),
+            ]
+        );
+
+        let path = write_config(
+            ".yaml",
+            r#"
+key_server_object_id: "0x1"
+fallback_node_urls: ["not a url", "http://localhost:9000", "http://localhost:9000"]
+"#,
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_package_resolver() {
+        let config =
+            ServerConfig::load_with_env(None, env(&[("KEY_SERVER_OBJECT_ID", "0x1")])).unwrap();
+        assert_eq!(
+            config.identities[0].network.package_resolver,
+            PackageResolverKind::GraphQl
+        );
+
+        // A custom network needs no GraphQL endpoint if packages are resolved using the full node.
+        let path = write_config(
+            ".yaml",
+            r#"
+network: custom
+node_url: http://localhost:9000
+key_server_object_id: "0x1"
+package_resolver: json_rpc
+"#,
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        assert_eq!(
+            config.identities[0].network.package_resolver,
+            PackageResolverKind::JsonRpc
+        );
+        assert!(config.identities[0].network.graphql_urls().is_empty());
+
+        let problems =
+            ServerConfig::load_with_env(Some(&path), env(&[("PACKAGE_RESOLVER", "graphql")]))
+                .unwrap_err();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        let problems =
+            ServerConfig::load_with_env(Some(&path), env(&[("PACKAGE_RESOLVER", "indexer")]))
+                .unwrap_err();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let config = ServerConfig::load_with_env(
+            None,
+            env(&[
+                ("KEY_SERVER_OBJECT_ID", "0x1"),
+                ("AUDIT_LOG_PATH", "/var/lib/seal/audit.log"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.audit_log,
+            Some(AuditLogConfig {
+                path: "/var/lib/seal/audit.log".into(),
+                checkpoint_interval: AUDIT_CHECKPOINT_INTERVAL,
+                key_server_object_id: ObjectID::from_single_byte(1),
+            })
+        );
+
+        // With several identities, the one signing checkpoints must be given.
+        let identities = r#"
+identities:
+  - key_server_object_id: "0x1"
+  - key_server_object_id: "0x2"
+"#;
+        let path = write_config(
+            ".yaml",
+            &format!("{}audit_log:\n  path: audit.log\n", identities),
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        let path = write_config(
+            ".yaml",
+            &format!(
+                "{}audit_log:\n  path: audit.log\n  key_server_object_id: \"0x2\"\n",
+                identities
+            ),
+        );
+        let config = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap();
+        assert_eq!(
+            config.audit_log.unwrap().key_server_object_id,
+            ObjectID::from_single_byte(2)
+        );
+
+        let path = write_config(
+            ".yaml",
+            "key_server_object_id: \"0x1\"\naudit_log:\n  checkpoint_interval_secs: 0\n  key_server_object_id: \"0x3\"\n",
+        );
+        let problems = ServerConfig::load_with_env(Some(&path), env(&[])).unwrap_err();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+    }
+}
@@ -1,6 +1,6 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
@@ -14,12 +14,16 @@ pub enum InternalError {
     InvalidSignature,
     InvalidSessionSignature,
     InvalidCertificate,
+    UnknownKeyServer,
+    PackageNotAllowed,
+    InvalidKeyVersion,
+    TooManyRequests { retry_after_secs: u64 },
     Failure, // Internal error, try again later
 }
 
 #[derive(Debug, Serialize)]
 pub struct ErrorResponse {
-    error: InternalError,
+    error: &'static str,
     message: String,
 }
 
@@ -40,6 +44,22 @@ impl IntoResponse for InternalError {
             InternalError::InvalidSessionSignature => {
                 (StatusCode::FORBIDDEN, "Invalid session key signature")
             }
+            InternalError::UnknownKeyServer => (
+                StatusCode::NOT_FOUND,
+                "Unknown key server object id, or no id given to a server hosting several key servers",
+            ),
+            InternalError::PackageNotAllowed => (
+                StatusCode::FORBIDDEN,
+                "Package is not allowed by this key server",
+            ),
+            InternalError::InvalidKeyVersion => (
+                StatusCode::FORBIDDEN,
+                "Unknown master key version, or the key version has been retired",
+            ),
+            InternalError::TooManyRequests { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests, please try again later",
+            ),
             InternalError::Failure => (
                 StatusCode::SERVICE_UNAVAILABLE,
                 "Internal server error, please try again later",
@@ -47,11 +67,19 @@ impl IntoResponse for InternalError {
         };
 
         let error_response = ErrorResponse {
-            error: self,
+            error: self.as_str(),
             message: message.to_string(),
         };
 
-        (status, Json(error_response)).into_response()
+        match self {
+            InternalError::TooManyRequests { retry_after_secs } => (
+                status,
+                [(header::RETRY_AFTER, retry_after_secs.to_string())],
+                Json(error_response),
+            )
+                .into_response(),
+            _ => (status, Json(error_response)).into_response(),
+        }
     }
 }
 
@@ -65,6 +93,10 @@ impl InternalError {
             InternalError::OldPackageVersion => "OldPackageVersion",
             InternalError::InvalidSignature => "InvalidSignature",
             InternalError::InvalidSessionSignature => "InvalidSessionSignature",
+            InternalError::UnknownKeyServer => "UnknownKeyServer",
+            InternalError::PackageNotAllowed => "PackageNotAllowed",
+            InternalError::InvalidKeyVersion => "InvalidKeyVersion",
+            InternalError::TooManyRequests { .. } => "TooManyRequests",
             InternalError::Failure => "Failure",
         }
     }
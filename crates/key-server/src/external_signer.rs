@@ -0,0 +1,211 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client for an external signer, a separate process holding the master key which performs key extraction and
+//! creates proofs of possession on behalf of the key server, so the master key never enters the memory of the
+//! key server.
+//!
+//! The signer listens on a Unix socket. Each request is sent on a new connection and answered with a single
+//! response. Messages are BCS encoded [SignerRequest]s and [SignerResponse]s, prefixed by their length as a
+//! big-endian u32.
+//!
+//! Calls to the signer use blocking I/O with a timeout, so async code must make them on the blocking thread pool.
+
+use crate::master_key::MasterKeyProvider;
+use crate::types::{IbePublicKey, MasterKeyPOP};
+use anyhow::{anyhow, bail, Result};
+use crypto::ibe::UserSecretKey;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Timeout for reading from and writing to the signer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The maximum size of a message. A response with the keys for a full PTB is well below this.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum SignerRequest {
+    PublicKey,
+    /// Extract the user secret keys for the given full ids.
+    Extract {
+        ids: Vec<Vec<u8>>,
+    },
+    ProofOfPossession {
+        message: Vec<u8>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) enum SignerResponse {
+    PublicKey(IbePublicKey),
+    Extract(Vec<UserSecretKey>),
+    ProofOfPossession(MasterKeyPOP),
+    Error(String),
+}
+
+/// A [MasterKeyProvider] that forwards all operations to an external signer.
+pub(crate) struct ExternalSigner {
+    socket: PathBuf,
+    public_key: IbePublicKey,
+}
+
+impl ExternalSigner {
+    /// Connect to the signer listening on the given socket and fetch its public key.
+    pub fn connect(socket: &Path) -> Result<Self> {
+        match call(socket, &SignerRequest::PublicKey)? {
+            SignerResponse::PublicKey(public_key) => Ok(Self {
+                socket: socket.to_path_buf(),
+                public_key,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+impl MasterKeyProvider for ExternalSigner {
+    fn public_key(&self) -> IbePublicKey {
+        self.public_key
+    }
+
+    fn extract(&self, ids: &[Vec<u8>]) -> Result<Vec<UserSecretKey>> {
+        let request = SignerRequest::Extract { ids: ids.to_vec() };
+        match call(&self.socket, &request)? {
+            SignerResponse::Extract(keys) if keys.len() == ids.len() => Ok(keys),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    fn create_proof_of_possession(&self, message: &[u8]) -> Result<MasterKeyPOP> {
+        let request = SignerRequest::ProofOfPossession {
+            message: message.to_vec(),
+        };
+        match call(&self.socket, &request)? {
+            SignerResponse::ProofOfPossession(pop) => Ok(pop),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+fn call(socket: &Path, request: &SignerRequest) -> Result<SignerResponse> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write_message(&mut stream, request)?;
+    read_message(&mut stream)
+}
+
+fn unexpected(response: SignerResponse) -> anyhow::Error {
+    match response {
+        SignerResponse::Error(e) => anyhow!("External signer failed: {}", e),
+        _ => anyhow!("Unexpected response from external signer"),
+    }
+}
+
+pub(crate) fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<()> {
+    let bytes = bcs::to_bytes(message)?;
+    stream.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    stream.write_all(&bytes)?;
+    Ok(())
+}
+
+pub(crate) fn read_message<T: DeserializeOwned>(stream: &mut UnixStream) -> Result<T> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        bail!("Message too large: {} bytes", length);
+    }
+    let mut bytes = vec![0u8; length];
+    stream.read_exact(&mut bytes)?;
+    Ok(bcs::from_bytes(&bytes)?)
+}
+
+/// A stand-in for an external signer which serves requests from a thread in the current process.
+#[cfg(test)]
+pub(crate) mod local_signer {
+    use super::*;
+    use crate::master_key::LockedMasterKey;
+    use crate::types::IbeMasterKey;
+    use std::os::unix::net::UnixListener;
+    use tempfile::TempDir;
+
+    pub(crate) struct LocalSigner {
+        socket: PathBuf,
+        _dir: TempDir,
+    }
+
+    impl LocalSigner {
+        pub fn spawn(master_key: IbeMasterKey) -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let socket = dir.path().join("signer.sock");
+            let listener = UnixListener::bind(&socket).unwrap();
+            let master_key = LockedMasterKey::new(master_key);
+            std::thread::spawn(move || {
+                for mut stream in listener.incoming().flatten() {
+                    let response = match read_message(&mut stream) {
+                        Ok(request) => handle_request(&master_key, request),
+                        Err(e) => SignerResponse::Error(e.to_string()),
+                    };
+                    let _ = write_message(&mut stream, &response);
+                }
+            });
+            Self { socket, _dir: dir }
+        }
+
+        pub fn socket(&self) -> &Path {
+            &self.socket
+        }
+    }
+
+    fn handle_request(provider: &dyn MasterKeyProvider, request: SignerRequest) -> SignerResponse {
+        let result = match request {
+            SignerRequest::PublicKey => Ok(SignerResponse::PublicKey(provider.public_key())),
+            SignerRequest::Extract { ids } => provider.extract(&ids).map(SignerResponse::Extract),
+            SignerRequest::ProofOfPossession { message } => provider
+                .create_proof_of_possession(&message)
+                .map(SignerResponse::ProofOfPossession),
+        };
+        result.unwrap_or_else(|e| SignerResponse::Error(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::local_signer::LocalSigner;
+    use super::*;
+    use crypto::ibe;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_external_signer() {
+        let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let signer = LocalSigner::spawn(master_key.clone());
+        let client = ExternalSigner::connect(signer.socket()).unwrap();
+        assert_eq!(client.public_key(), public_key);
+
+        let ids = vec![b"first".to_vec(), b"second".to_vec()];
+        let keys = client.extract(&ids).unwrap();
+        assert_eq!(keys.len(), 2);
+        for (id, key) in ids.iter().zip(&keys) {
+            assert_eq!(key, &ibe::extract(&master_key, id));
+            ibe::verify_user_secret_key(key, id, &public_key).unwrap();
+        }
+        assert!(client.extract(&[]).unwrap().is_empty());
+
+        assert_eq!(
+            client.create_proof_of_possession(b"message").unwrap(),
+            ibe::create_proof_of_possession(&master_key, b"message")
+        );
+    }
+
+    #[test]
+    fn test_external_signer_unavailable() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(ExternalSigner::connect(&dir.path().join("missing.sock")).is_err());
+    }
+}
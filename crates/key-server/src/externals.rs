@@ -1,124 +1,76 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::cache::{Cache, CACHE_SIZE, CACHE_TTL};
+use crate::cache::Cache;
+use crate::endpoints::post_json;
 use crate::errors::InternalError;
-use crate::types::Network;
-use once_cell::sync::Lazy;
+use anyhow::bail;
 use reqwest::Client;
-use serde_json::Value;
+use std::future::Future;
 use std::str::FromStr;
-use sui_sdk::error::SuiRpcResult;
-use sui_sdk::rpc_types::CheckpointId;
-use sui_sdk::SuiClient;
 use sui_types::base_types::ObjectID;
-use tap::TapFallible;
-use tracing::{debug, warn};
+use tracing::debug;
 
-static CACHE: Lazy<Cache<ObjectID, (ObjectID, ObjectID)>> =
-    Lazy::new(|| Cache::new(CACHE_TTL, CACHE_SIZE));
-
-#[cfg(test)]
-pub(crate) fn add_latest(pkg_id: ObjectID, latest: ObjectID) {
-    match CACHE.get(&pkg_id) {
-        Some((first, old_latest)) => {
-            CACHE.insert(pkg_id, (first, latest));
-            CACHE.insert(latest, (first, latest));
-            CACHE.insert(old_latest, (first, latest));
-        }
-        None => panic!("Package is not in cache"),
-    }
-}
-
-#[cfg(test)]
-pub(crate) fn add_package(pkg_id: ObjectID) {
-    CACHE.insert(pkg_id, (pkg_id, pkg_id));
-}
-
-pub(crate) async fn fetch_first_and_last_pkg_id(
+/// Get the first and the latest version of a package from the given cache, or fetch and cache them.
+pub(crate) async fn cached_first_and_last_pkg_id<Fut>(
+    cache: &Cache<ObjectID, (ObjectID, ObjectID)>,
     pkg_id: &ObjectID,
-    network: &Network,
-) -> Result<(ObjectID, ObjectID), InternalError> {
-    match CACHE.get(pkg_id) {
+    fetch: impl FnOnce() -> Fut,
+) -> Result<(ObjectID, ObjectID), InternalError>
+where
+    Fut: Future<Output = Result<(ObjectID, ObjectID), InternalError>>,
+{
+    match cache.get(pkg_id) {
         Some((first, latest)) => Ok((first, latest)),
         None => {
-            let graphql_client = Client::new();
-            let url = network.graphql_url();
-            let query = serde_json::json!({
-                "query": format!(
-                    r#"
-                    query {{
-                        latestPackage(
-                            address: "{}"
-                        ) {{
-                            address
-                            packageAtVersion(version: 1) {{
-                                address
-                            }}
-                        }}
-                    }}
-                    "#,
-                    pkg_id
-                )
-            });
-            let response = graphql_client.post(url).json(&query).send().await;
-            debug!("Graphql response: {:?}", response);
-            let response = response
-                .map_err(|_| InternalError::Failure)?
-                .json::<Value>()
-                .await
-                .map_err(|_| InternalError::Failure)?;
-
-            let first = response["data"]["latestPackage"]["packageAtVersion"]["address"]
-                .as_str()
-                .ok_or(InternalError::InvalidPackage)?
-                .to_string();
-            let latest = response["data"]["latestPackage"]["address"]
-                .as_str()
-                .ok_or(InternalError::InvalidPackage)?
-                .to_string();
-            let (first, latest) = (
-                ObjectID::from_str(&first).map_err(|_| InternalError::Failure)?,
-                ObjectID::from_str(&latest).map_err(|_| InternalError::Failure)?,
-            );
-            CACHE.insert(*pkg_id, (first, latest));
+            let (first, latest) = fetch().await?;
+            cache.insert(*pkg_id, (first, latest));
             Ok((first, latest))
         }
     }
 }
 
-/// Returns the timestampe for the latest checkpoint.
-pub(crate) async fn get_latest_checkpoint_timestamp(client: SuiClient) -> SuiRpcResult<u64> {
-    let latest_checkpoint_sequence_number = client
-        .read_api()
-        .get_latest_checkpoint_sequence_number()
-        .await?;
-    let checkpoint = client
-        .read_api()
-        .get_checkpoint(CheckpointId::SequenceNumber(
-            latest_checkpoint_sequence_number,
-        ))
-        .await?;
-    Ok(checkpoint.timestamp_ms)
-}
-
-pub(crate) async fn get_reference_gas_price(client: SuiClient) -> SuiRpcResult<u64> {
-    let rgp = client
-        .read_api()
-        .get_reference_gas_price()
-        .await
-        .tap_err(|e| {
-            warn!("Failed retrieving RGP ({:?})", e);
-        })?;
-    Ok(rgp)
-}
-
-/// Compute the difference between the current time and the offset in milliseconds.
-/// The offset and the difference between the current time and the offset are cast to i64,
-/// so the caller should be aware of the potential overflow.
-pub(crate) fn duration_since(offset: u64) -> i64 {
-    let now = current_epoch_time() as i64;
-    now - offset as i64
+/// Query the first and the latest version of a package from a GraphQL endpoint. Fails if the endpoint could not be
+/// queried, and returns [InternalError::InvalidPackage] if the package does not exist.
+pub(crate) async fn query_first_and_last_pkg_id(
+    client: &Client,
+    url: &str,
+    pkg_id: &ObjectID,
+) -> anyhow::Result<Result<(ObjectID, ObjectID), InternalError>> {
+    let query = serde_json::json!({
+        "query": format!(
+            r#"
+            query {{
+                latestPackage(
+                    address: "{}"
+                ) {{
+                    address
+                    packageAtVersion(version: 1) {{
+                        address
+                    }}
+                }}
+            }}
+            "#,
+            pkg_id
+        )
+    });
+    let response = post_json(client, url, &query).await;
+    debug!("Graphql response: {:?}", response);
+    let response = response?;
+
+    let (Some(first), Some(latest)) = (
+        response["data"]["latestPackage"]["packageAtVersion"]["address"].as_str(),
+        response["data"]["latestPackage"]["address"].as_str(),
+    ) else {
+        if response["data"].is_null() {
+            bail!("Invalid GraphQL response: {}", response);
+        }
+        return Ok(Err(InternalError::InvalidPackage));
+    };
+    Ok(Ok((
+        ObjectID::from_str(first)?,
+        ObjectID::from_str(latest)?,
+    )))
 }
 
 pub(crate) fn current_epoch_time() -> u64 {
@@ -130,12 +82,14 @@ pub(crate) fn current_epoch_time() -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::externals::fetch_first_and_last_pkg_id;
-    use crate::types::Network;
+    use crate::externals::query_first_and_last_pkg_id;
+    use crate::tests::stub::stub_server;
     use crate::InternalError;
     use fastcrypto::ed25519::Ed25519KeyPair;
     use fastcrypto::secp256k1::Secp256k1KeyPair;
     use fastcrypto::secp256r1::Secp256r1KeyPair;
+    use reqwest::Client;
+    use serde_json::json;
     use shared_crypto::intent::{Intent, IntentMessage, PersonalMessage};
     use std::str::FromStr;
     use sui_sdk::types::crypto::{get_key_pair, Signature};
@@ -143,29 +97,41 @@ mod tests {
     use sui_sdk::verify_personal_message_signature::verify_personal_message_signature;
     use sui_types::base_types::ObjectID;
 
+    /// A GraphQL endpoint answering `latestPackage` queries as a full node would for an upgraded package with the
+    /// given first and latest versions, and with `null` for other addresses.
+    async fn stub_graphql(first: ObjectID, latest: ObjectID) -> String {
+        stub_server(move |request| {
+            let query = request["query"].as_str()?;
+            let package = (query.contains(&first.to_string())
+                || query.contains(&latest.to_string()))
+            .then(|| json!({ "address": latest, "packageAtVersion": { "address": first } }));
+            Some(json!({ "data": { "latestPackage": package } }))
+        })
+        .await
+    }
+
     #[tokio::test]
     async fn test_fetch_first_and_last_pkg_id() {
-        let address = ObjectID::from_str(
+        let first = ObjectID::from_str(
             "0xd92bc457b42d48924087ea3f22d35fd2fe9afdf5bdfe38cc51c0f14f3282f6d5",
         )
         .unwrap();
+        let latest = ObjectID::random();
+        let url = stub_graphql(first, latest).await;
 
-        match fetch_first_and_last_pkg_id(&address, &Network::Mainnet).await {
-            Ok((first, latest)) => {
-                assert!(!first.is_empty(), "First address should not be empty");
-                assert!(!latest.is_empty(), "Latest address should not be empty");
-                println!("First address: {:?}", first);
-                println!("Latest address: {:?}", latest);
+        for address in [first, latest] {
+            match query_first_and_last_pkg_id(&Client::new(), &url, &address).await {
+                Ok(Ok(versions)) => assert_eq!(versions, (first, latest)),
+                result => panic!("Test failed with result: {:?}", result),
             }
-            Err(e) => panic!("Test failed with error: {:?}", e),
         }
     }
 
     #[tokio::test]
     async fn test_fetch_first_and_last_pkg_id_with_invalid_id() {
-        let invalid_address = ObjectID::ZERO;
-        let result = fetch_first_and_last_pkg_id(&invalid_address, &Network::Mainnet).await;
-        assert!(matches!(result, Err(InternalError::InvalidPackage)));
+        let url = stub_graphql(ObjectID::random(), ObjectID::random()).await;
+        let result = query_first_and_last_pkg_id(&Client::new(), &url, &ObjectID::ZERO).await;
+        assert!(matches!(result, Ok(Err(InternalError::InvalidPackage))));
     }
 
     #[tokio::test]
@@ -175,14 +141,10 @@ mod tests {
         )
         .unwrap();
 
-        // Use a custom network with an invalid URL to emulate fetch failure
-        let invalid_network = Network::Custom {
-            graphql_url: "http://invalid-url".to_string(),
-            node_url: "http://invalid-url".to_string(),
-        };
-
-        let result = fetch_first_and_last_pkg_id(&address, &invalid_network).await;
-        assert!(matches!(result, Err(InternalError::Failure)));
+        // Use an invalid URL to emulate fetch failure
+        let result =
+            query_first_and_last_pkg_id(&Client::new(), "http://invalid-url", &address).await;
+        assert!(result.is_err());
     }
 
     #[tokio::test]
@@ -0,0 +1,454 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Key identities. A key server can host several identities, e.g. for different partners, each with its own master
+//! key, key server object and package allowlist. Requests are routed to an identity by its key server object id.
+//!
+//! Master keys are versioned. Besides its current key, an identity may hold retired keys which are still used for
+//! requests selecting their version until the end of their grace period, so objects encrypted under a previous key
+//! remain decryptable while a rotation is rolled out. The version of the current key and the public keys of previous
+//! versions are recorded in the key server object on chain, see `key_server::rotate_key`.
+
+use crate::config::IdentityConfig;
+use crate::errors::InternalError;
+use crate::master_key::MasterKeyProvider;
+use crate::types::{ElGamalPublicKey, MasterKeyPOP};
+use crate::{DecryptionKey, FetchKeyResponse, KeyId};
+use crypto::elgamal::encrypt;
+use rand::thread_rng;
+use std::collections::{HashMap, HashSet};
+use sui_sdk::types::base_types::ObjectID;
+use tracing::{debug, warn};
+
+pub(crate) struct KeyIdentity {
+    pub master_key: Box<dyn MasterKeyProvider>,
+    pub key_server_object_id: ObjectID,
+    pub key_server_object_id_sig: MasterKeyPOP,
+    /// The version of the current master key.
+    pub key_version: u32,
+    pub retired_keys: Vec<RetiredKey>,
+    /// The packages this identity serves keys for, given by the id of their first version. If `None`, keys are
+    /// served for all packages.
+    pub allowed_packages: Option<HashSet<ObjectID>>,
+}
+
+/// A previous master key of an identity.
+pub(crate) struct RetiredKey {
+    pub version: u32,
+    pub master_key: Box<dyn MasterKeyProvider>,
+    pub key_server_object_id_sig: MasterKeyPOP,
+    /// Unix time in milliseconds until which keys are still served with this master key.
+    pub retired_until: u64,
+}
+
+impl KeyIdentity {
+    pub fn new(
+        master_key: Box<dyn MasterKeyProvider>,
+        key_server_object_id: ObjectID,
+        key_version: u32,
+        allowed_packages: Option<Vec<ObjectID>>,
+    ) -> anyhow::Result<Self> {
+        let key_server_object_id_sig = master_key
+            .create_proof_of_possession(&pop_message(&key_server_object_id, key_version))?;
+        Ok(Self {
+            master_key,
+            key_server_object_id,
+            key_server_object_id_sig,
+            key_version,
+            retired_keys: vec![],
+            allowed_packages: allowed_packages.map(HashSet::from_iter),
+        })
+    }
+
+    /// Add a previous master key which is used until `retired_until` (Unix time in milliseconds).
+    pub fn with_retired_key(
+        mut self,
+        version: u32,
+        master_key: Box<dyn MasterKeyProvider>,
+        retired_until: u64,
+    ) -> anyhow::Result<Self> {
+        let key_server_object_id_sig = master_key
+            .create_proof_of_possession(&pop_message(&self.key_server_object_id, version))?;
+        self.retired_keys.push(RetiredKey {
+            version,
+            master_key,
+            key_server_object_id_sig,
+            retired_until,
+        });
+        Ok(self)
+    }
+
+    /// Get the master key of the given version at the given time (Unix time in milliseconds). If no version is
+    /// given, the current master key is returned.
+    pub fn master_key(
+        &self,
+        key_version: Option<u32>,
+        now: u64,
+    ) -> Result<&dyn MasterKeyProvider, InternalError> {
+        match key_version {
+            None => Ok(self.master_key.as_ref()),
+            Some(version) if version == self.key_version => Ok(self.master_key.as_ref()),
+            Some(version) => self
+                .retired_keys
+                .iter()
+                .find(|key| key.version == version && now <= key.retired_until)
+                .map(|key| key.master_key.as_ref())
+                .ok_or_else(|| {
+                    debug!(
+                        "Unknown or expired key version {} of key server {:?}",
+                        version, self.key_server_object_id
+                    );
+                    InternalError::InvalidKeyVersion
+                }),
+        }
+    }
+
+    /// Check that this identity serves keys for the package with the given first version.
+    pub fn check_package(
+        &self,
+        first_pkg_id: &ObjectID,
+        req_id: Option<&str>,
+    ) -> Result<(), InternalError> {
+        match &self.allowed_packages {
+            Some(allowed) if !allowed.contains(first_pkg_id) => {
+                debug!(
+                    "Package {:?} is not allowed by key server {:?} (req_id: {:?})",
+                    first_pkg_id, self.key_server_object_id, req_id
+                );
+                Err(InternalError::PackageNotAllowed)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn create_response(
+        &self,
+        ids: &[KeyId],
+        enc_key: &ElGamalPublicKey,
+        key_version: Option<u32>,
+        now: u64,
+    ) -> Result<FetchKeyResponse, InternalError> {
+        debug!("Checking response for ids: {:?}", ids);
+        let master_key = self.master_key(key_version, now)?;
+        // Requested keys
+        let keys = master_key.extract(ids).map_err(|e| {
+            warn!("Key extraction failed: {:?}", e);
+            InternalError::Failure
+        })?;
+        let decryption_keys = ids
+            .iter()
+            .zip(keys)
+            .map(|(id, key)| {
+                // ElGamal encryption of key under the user's public key
+                let encrypted_key = encrypt(&mut thread_rng(), key.expose(), enc_key);
+                DecryptionKey {
+                    id: id.to_owned(),
+                    encrypted_key,
+                }
+            })
+            .collect();
+        Ok(FetchKeyResponse { decryption_keys })
+    }
+}
+
+/// The message signed by the proof of possession of the master key with the given version. Version 0 signs the key
+/// server object id only, as before master keys were versioned, so existing clients can still verify it. Other
+/// versions append the little-endian version, so a proof for one version is not valid for another.
+pub(crate) fn pop_message(key_server_object_id: &ObjectID, key_version: u32) -> Vec<u8> {
+    let mut message = key_server_object_id.into_bytes().to_vec();
+    if key_version != 0 {
+        message.extend_from_slice(&key_version.to_le_bytes());
+    }
+    message
+}
+
+impl IdentityConfig {
+    /// Load the master key and create the identity.
+    pub fn load(&self) -> Result<KeyIdentity, String> {
+        let pop_error = |e: anyhow::Error| {
+            format!(
+                "Cannot create proof of possession for key server {}: {}",
+                self.key_server_object_id, e
+            )
+        };
+        let mut identity = KeyIdentity::new(
+            self.master_key.load()?,
+            self.key_server_object_id,
+            self.key_version,
+            self.allowed_packages.clone(),
+        )
+        .map_err(pop_error)?;
+        for key in &self.retired_keys {
+            identity = identity
+                .with_retired_key(key.version, key.master_key.load()?, key.retired_until)
+                .map_err(pop_error)?;
+        }
+        Ok(identity)
+    }
+}
+
+/// The identities hosted by a key server, indexed by their key server object id.
+pub(crate) struct KeyIdentities(HashMap<ObjectID, KeyIdentity>);
+
+impl KeyIdentities {
+    /// Panics if two identities have the same key server object id. This is checked when loading the config.
+    pub fn new(identities: Vec<KeyIdentity>) -> Self {
+        let count = identities.len();
+        let identities: HashMap<_, _> = identities
+            .into_iter()
+            .map(|identity| (identity.key_server_object_id, identity))
+            .collect();
+        assert_eq!(count, identities.len(), "Duplicate key server object id");
+        Self(identities)
+    }
+
+    /// Get the identity for the given key server object id. If no id is given, the server must host exactly one
+    /// identity, which is returned. This keeps requests from clients unaware of identities working.
+    pub fn get(
+        &self,
+        key_server_object_id: Option<&ObjectID>,
+    ) -> Result<&KeyIdentity, InternalError> {
+        match key_server_object_id {
+            Some(id) => self.0.get(id),
+            None if self.0.len() == 1 => self.0.values().next(),
+            None => None,
+        }
+        .ok_or(InternalError::UnknownKeyServer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &KeyIdentity> {
+        self.0.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use crate::master_key::LockedMasterKey;
+    use crate::types::ElgamalVerificationKey;
+    use crypto::{
+        create_full_id, elgamal, ibe, seal_decrypt, seal_encrypt_with_key_versions,
+        EncryptedObject, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys,
+    };
+    use fastcrypto::groups::bls12381::G1Element;
+    use std::time::Duration;
+
+    const NOW: u64 = 1_700_000_000_000;
+
+    fn new_identity(allowed_packages: Option<Vec<ObjectID>>) -> (KeyIdentity, ibe::PublicKey) {
+        let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let identity = KeyIdentity::new(
+            Box::new(LockedMasterKey::new(master_key)),
+            ObjectID::random(),
+            0,
+            allowed_packages,
+        )
+        .unwrap();
+        (identity, public_key)
+    }
+
+    #[test]
+    fn test_routing() {
+        let (first, _) = new_identity(None);
+        let first_id = first.key_server_object_id;
+
+        // A single identity is used if no id is given.
+        let identities = KeyIdentities::new(vec![first]);
+        assert_eq!(identities.get(None).unwrap().key_server_object_id, first_id);
+        assert_eq!(
+            identities
+                .get(Some(&first_id))
+                .unwrap()
+                .key_server_object_id,
+            first_id
+        );
+        assert_eq!(
+            identities.get(Some(&ObjectID::random())).err(),
+            Some(InternalError::UnknownKeyServer)
+        );
+
+        let (first, first_pk) = new_identity(None);
+        let (second, second_pk) = new_identity(None);
+        let (first_id, second_id) = (first.key_server_object_id, second.key_server_object_id);
+        let identities = KeyIdentities::new(vec![first, second]);
+        assert_eq!(
+            identities.get(None).err(),
+            Some(InternalError::UnknownKeyServer)
+        );
+
+        // Each identity uses its own master key.
+        let clock = MockClock::new(NOW);
+        let (sk, pk, _): (_, _, ElgamalVerificationKey) = elgamal::genkey(&mut thread_rng());
+        for (id, public_key) in [(first_id, first_pk), (second_id, second_pk)] {
+            let response = identities
+                .get(Some(&id))
+                .unwrap()
+                .create_response(&[b"id".to_vec()], &pk, None, clock.now())
+                .unwrap();
+            let key: G1Element = elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key);
+            ibe::verify_user_secret_key(&key.into(), b"id", &public_key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_package_allowlist() {
+        let allowed = ObjectID::random();
+        let (identity, _) = new_identity(Some(vec![allowed]));
+        assert!(identity.check_package(&allowed, None).is_ok());
+        assert_eq!(
+            identity.check_package(&ObjectID::random(), None),
+            Err(InternalError::PackageNotAllowed)
+        );
+
+        let (identity, _) = new_identity(None);
+        assert!(identity.check_package(&ObjectID::random(), None).is_ok());
+    }
+
+    #[test]
+    fn test_key_rotation_grace_period() {
+        let (old_master_key, old_public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let (new_master_key, new_public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let key_server_object_id = ObjectID::random();
+        let service = crypto::ObjectID::new(key_server_object_id.into_bytes());
+        let package_id = crypto::ObjectID::random();
+
+        // An object encrypted before the rotation, under version 0 of the master key.
+        let (object, _) = seal_encrypt_with_key_versions(
+            package_id,
+            b"id".to_vec(),
+            vec![service],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![old_public_key]),
+            1,
+            EncryptionInput::Aes256Gcm {
+                data: b"secret".to_vec(),
+                aad: None,
+            },
+            None,
+            vec![0],
+        )
+        .unwrap();
+        let object: EncryptedObject = bcs::from_bytes(&bcs::to_bytes(&object).unwrap()).unwrap();
+        assert_eq!(object.version, 2);
+        assert_eq!(object.key_version(0), 0);
+
+        // Rotate to version 1 and keep version 0 for an hour.
+        let clock = MockClock::new(NOW);
+        let retired_until = clock.now() + 3_600_000;
+        let identity = KeyIdentity::new(
+            Box::new(LockedMasterKey::new(new_master_key)),
+            key_server_object_id,
+            1,
+            None,
+        )
+        .unwrap()
+        .with_retired_key(
+            0,
+            Box::new(LockedMasterKey::new(old_master_key)),
+            retired_until,
+        )
+        .unwrap();
+        assert_eq!(
+            identity.master_key(None, 0).unwrap().public_key(),
+            new_public_key
+        );
+        assert_eq!(
+            identity.master_key(Some(1), 0).unwrap().public_key(),
+            new_public_key
+        );
+
+        // The proofs of possession are bound to the key version.
+        let retired_pop = identity.retired_keys[0].key_server_object_id_sig;
+        assert!(ibe::verify_proof_of_possession(
+            &old_public_key,
+            &key_server_object_id.into_bytes(),
+            &retired_pop
+        )
+        .is_ok());
+        let pop = identity.key_server_object_id_sig;
+        assert!(ibe::verify_proof_of_possession(
+            &new_public_key,
+            &pop_message(&key_server_object_id, 1),
+            &pop
+        )
+        .is_ok());
+        assert!(ibe::verify_proof_of_possession(
+            &new_public_key,
+            &key_server_object_id.into_bytes(),
+            &pop
+        )
+        .is_err());
+
+        // During the grace period, the old object can still be decrypted.
+        let full_id = create_full_id(&package_id.into_bytes(), &object.id);
+        let (sk, pk, _): (_, _, ElgamalVerificationKey) = elgamal::genkey(&mut thread_rng());
+        let response = identity
+            .create_response(
+                &[full_id.clone()],
+                &pk,
+                Some(object.key_version(0)),
+                clock.now(),
+            )
+            .unwrap();
+        let key: G1Element = elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key);
+        let plaintext = seal_decrypt(
+            &object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381([(service, key.into())].into()),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(vec![old_public_key])),
+        )
+        .unwrap();
+        assert_eq!(plaintext, b"secret");
+
+        // Keys from the current master key do not decrypt the old object.
+        let response = identity
+            .create_response(&[full_id.clone()], &pk, None, clock.now())
+            .unwrap();
+        let key: G1Element = elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key);
+        assert!(seal_decrypt(
+            &object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381([(service, key.into())].into()),
+            None,
+        )
+        .is_err());
+
+        // Objects encrypted after the rotation record version 1 and are decrypted with the current master key.
+        let (object, _) = seal_encrypt_with_key_versions(
+            package_id,
+            b"id".to_vec(),
+            vec![service],
+            &IBEPublicKeys::BonehFranklinBLS12381(vec![new_public_key]),
+            1,
+            EncryptionInput::Aes256Gcm {
+                data: b"new secret".to_vec(),
+                aad: None,
+            },
+            None,
+            vec![1],
+        )
+        .unwrap();
+        let response = identity
+            .create_response(&[full_id], &pk, Some(object.key_version(0)), clock.now())
+            .unwrap();
+        let key: G1Element = elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key);
+        let plaintext = seal_decrypt(
+            &object,
+            &IBEUserSecretKeys::BonehFranklinBLS12381([(service, key.into())].into()),
+            Some(&IBEPublicKeys::BonehFranklinBLS12381(vec![new_public_key])),
+        )
+        .unwrap();
+        assert_eq!(plaintext, b"new secret");
+
+        // After the grace period, the old version is rejected, as are unknown versions.
+        clock.set(retired_until);
+        assert!(identity.master_key(Some(0), clock.now()).is_ok());
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            identity.master_key(Some(0), clock.now()).err(),
+            Some(InternalError::InvalidKeyVersion)
+        );
+        assert_eq!(
+            identity.master_key(Some(2), 0).err(),
+            Some(InternalError::InvalidKeyVersion)
+        );
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Passphrase-encrypted keystore files for master keys.
+//!
+//! The passphrase is stretched to an AES-256 key with Argon2id using a random salt, and the master key is encrypted
+//! with AES-256-GCM. A fresh salt is used for every keystore, so a derived key is never used twice and the fixed IV
+//! of [Aes256Gcm] is safe. The version and KDF parameters are authenticated as associated data.
+//!
+//! Keystores are stored as JSON:
+//! ```json
+//! {
+//!   "version": 1,
+//!   "kdf": { "m_cost": 19456, "t_cost": 2, "p_cost": 1, "salt": "..." },
+//!   "ciphertext": "..."
+//! }
+//! ```
+
+use crate::types::IbeMasterKey;
+use argon2::{Algorithm, Argon2, Params, Version};
+use crypto::dem::Aes256Gcm;
+use crypto::KEY_SIZE;
+use fastcrypto::encoding::{Base64, Encoding};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+const KEYSTORE_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Keystore {
+    version: u8,
+    kdf: KdfParams,
+    /// Base64 encoded.
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// Base64 encoded.
+    salt: String,
+}
+
+/// Encrypt a master key under a passphrase using the default Argon2 parameters.
+/// Returns the keystore as a JSON string.
+pub(crate) fn encrypt_master_key<R: RngCore + CryptoRng>(
+    master_key: &IbeMasterKey,
+    passphrase: &[u8],
+    rng: &mut R,
+) -> Result<String, String> {
+    encrypt_master_key_with_params(master_key, passphrase, Params::default(), rng)
+}
+
+fn encrypt_master_key_with_params<R: RngCore + CryptoRng>(
+    master_key: &IbeMasterKey,
+    passphrase: &[u8],
+    params: Params,
+    rng: &mut R,
+) -> Result<String, String> {
+    let mut salt = [0u8; SALT_LENGTH];
+    rng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, &params)?;
+    let plaintext = Zeroizing::new(master_key.to_byte_array());
+    let ciphertext = Aes256Gcm::encrypt(
+        plaintext.as_slice(),
+        &aad(KEYSTORE_VERSION, &params, &salt),
+        &key,
+    );
+    let keystore = Keystore {
+        version: KEYSTORE_VERSION,
+        kdf: KdfParams {
+            m_cost: params.m_cost(),
+            t_cost: params.t_cost(),
+            p_cost: params.p_cost(),
+            salt: Base64::encode(salt),
+        },
+        ciphertext: Base64::encode(ciphertext),
+    };
+    serde_json::to_string_pretty(&keystore).map_err(|e| e.to_string())
+}
+
+/// Decrypt a master key from a keystore in the JSON format written by [encrypt_master_key].
+pub(crate) fn decrypt_master_key(
+    keystore: &str,
+    passphrase: &[u8],
+) -> Result<IbeMasterKey, String> {
+    let keystore: Keystore =
+        serde_json::from_str(keystore).map_err(|e| format!("Invalid keystore: {}", e))?;
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(format!(
+            "Unsupported keystore version: {}",
+            keystore.version
+        ));
+    }
+    let params = Params::new(
+        keystore.kdf.m_cost,
+        keystore.kdf.t_cost,
+        keystore.kdf.p_cost,
+        Some(KEY_SIZE),
+    )
+    .map_err(|e| format!("Invalid keystore KDF parameters: {}", e))?;
+    let salt = Base64::decode(&keystore.kdf.salt).map_err(|_| "Invalid keystore salt")?;
+    let ciphertext =
+        Base64::decode(&keystore.ciphertext).map_err(|_| "Invalid keystore ciphertext")?;
+
+    let key = derive_key(passphrase, &salt, &params)?;
+    let plaintext = Zeroizing::new(
+        Aes256Gcm::decrypt(&ciphertext, &aad(keystore.version, &params, &salt), &key)
+            .map_err(|_| "Wrong passphrase or corrupted keystore")?,
+    );
+    IbeMasterKey::from_byte_array(
+        plaintext
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Invalid master key length in keystore")?,
+    )
+    .map_err(|_| "Invalid master key in keystore".to_string())
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: &Params,
+) -> Result<Zeroizing<[u8; KEY_SIZE]>, String> {
+    let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password_into(passphrase, salt, key.as_mut())
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn aad(version: u8, params: &Params, salt: &[u8]) -> Vec<u8> {
+    bcs::to_bytes(&(
+        version,
+        params.m_cost(),
+        params.t_cost(),
+        params.p_cost(),
+        salt,
+    ))
+    .expect("Serialization of fixed types never fails")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::ibe;
+    use rand::thread_rng;
+
+    fn test_params() -> Params {
+        // Small parameters to keep the tests fast.
+        Params::new(Params::MIN_M_COST, 1, 1, Some(KEY_SIZE)).unwrap()
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let keystore = encrypt_master_key_with_params(
+            &master_key,
+            b"correct horse battery staple",
+            test_params(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+        assert_eq!(
+            decrypt_master_key(&keystore, b"correct horse battery staple").unwrap(),
+            master_key
+        );
+        assert!(decrypt_master_key(&keystore, b"wrong passphrase").is_err());
+
+        // The KDF parameters are authenticated.
+        let mut modified: serde_json::Value = serde_json::from_str(&keystore).unwrap();
+        modified["kdf"]["t_cost"] = 2.into();
+        assert!(
+            decrypt_master_key(&modified.to_string(), b"correct horse battery staple").is_err()
+        );
+
+        let mut modified: serde_json::Value = serde_json::from_str(&keystore).unwrap();
+        modified["version"] = 2.into();
+        assert!(
+            decrypt_master_key(&modified.to_string(), b"correct horse battery staple").is_err()
+        );
+    }
+
+    #[test]
+    fn test_keystore_salt_is_random() {
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let first = encrypt_master_key_with_params(
+            &master_key,
+            b"passphrase",
+            test_params(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+        let second = encrypt_master_key_with_params(
+            &master_key,
+            b"passphrase",
+            test_params(),
+            &mut thread_rng(),
+        )
+        .unwrap();
+        assert_ne!(first, second);
+    }
+}
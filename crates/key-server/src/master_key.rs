@@ -0,0 +1,286 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::config::MasterKeySource;
+use crate::external_signer::ExternalSigner;
+use crate::keystore::decrypt_master_key;
+use crate::types::{IbeMasterKey, IbePublicKey, MasterKeyPOP};
+use crypto::ibe;
+use crypto::ibe::UserSecretKey;
+use fastcrypto::encoding::{Base64, Encoding};
+use fastcrypto::serde_helpers::ToFromByteArray;
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::Read;
+use std::mem::size_of;
+use std::ops::Deref;
+use std::os::fd::FromRawFd;
+use std::path::Path;
+use tracing::warn;
+use zeroize::{Zeroize, Zeroizing};
+
+/// A backend performing the operations that need the master key. Implementations may keep the key outside of
+/// the key server process, in which case the operations may fail.
+pub(crate) trait MasterKeyProvider: Send + Sync {
+    fn public_key(&self) -> IbePublicKey;
+
+    /// Extract the user secret keys for the given full ids.
+    fn extract(&self, ids: &[Vec<u8>]) -> anyhow::Result<Vec<UserSecretKey>>;
+
+    fn create_proof_of_possession(&self, message: &[u8]) -> anyhow::Result<MasterKeyPOP>;
+}
+
+/// A master key kept on the heap in memory which is locked to prevent it from being swapped to disk.
+/// The key is zeroized when dropped, e.g., when the server shuts down.
+pub struct LockedMasterKey {
+    key: Box<IbeMasterKey>,
+    locked: bool,
+}
+
+impl LockedMasterKey {
+    pub fn new(master_key: IbeMasterKey) -> Self {
+        let key = Box::new(master_key);
+
+        // SAFETY: The pointer refers to a live heap allocation of the given size.
+        let locked = unsafe { libc::mlock(Self::ptr(&key), size_of::<IbeMasterKey>()) } == 0;
+        if !locked {
+            // This happens, e.g., if RLIMIT_MEMLOCK is too low. The key is still zeroized on drop.
+            warn!(
+                "Failed to lock the memory holding the master key: {:?}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Self { key, locked }
+    }
+
+    fn ptr(key: &IbeMasterKey) -> *const c_void {
+        key as *const IbeMasterKey as *const c_void
+    }
+}
+
+impl Deref for LockedMasterKey {
+    type Target = IbeMasterKey;
+
+    fn deref(&self) -> &Self::Target {
+        &self.key
+    }
+}
+
+impl Drop for LockedMasterKey {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        if self.locked {
+            // SAFETY: The memory was locked in `new` and is still allocated.
+            unsafe {
+                libc::munlock(Self::ptr(&self.key), size_of::<IbeMasterKey>());
+            }
+        }
+    }
+}
+
+impl MasterKeyProvider for LockedMasterKey {
+    fn public_key(&self) -> IbePublicKey {
+        ibe::public_key_from_master_key(self)
+    }
+
+    fn extract(&self, ids: &[Vec<u8>]) -> anyhow::Result<Vec<UserSecretKey>> {
+        Ok(ids
+            .iter()
+            .map(|id| ibe::extract_with_gid(self, &ibe::hash_id(id)))
+            .collect())
+    }
+
+    fn create_proof_of_possession(&self, message: &[u8]) -> anyhow::Result<MasterKeyPOP> {
+        Ok(ibe::create_proof_of_possession(self, message))
+    }
+}
+
+impl MasterKeySource {
+    /// Load the master key, or connect to the external signer, as configured.
+    pub fn load(&self) -> Result<Box<dyn MasterKeyProvider>, String> {
+        let encoded = match self {
+            MasterKeySource::Env => Zeroizing::new(
+                std::env::var("MASTER_KEY").map_err(|_| "MASTER_KEY must be set".to_string())?,
+            ),
+            MasterKeySource::SecretFile { path } => read_secret(path)?,
+            MasterKeySource::Fd { fd } => {
+                if *fd <= 2 {
+                    return Err(format!(
+                        "Cannot read master key from fd {}: stdin, stdout and stderr are not allowed",
+                        fd
+                    ));
+                }
+                // SAFETY: The descriptor is inherited from the parent process for the sole purpose of passing the
+                // master key, so we take ownership of it. It is closed when the file is dropped.
+                let mut file = unsafe { File::from_raw_fd(*fd) };
+                let mut contents = Zeroizing::new(String::new());
+                file.read_to_string(&mut contents)
+                    .map_err(|e| format!("Cannot read master key from fd {}: {}", fd, e))?;
+                contents
+            }
+            MasterKeySource::Keystore {
+                path,
+                passphrase_file,
+            } => {
+                let keystore = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read keystore {}: {}", path.display(), e))?;
+                let passphrase =
+                    match passphrase_file {
+                        Some(passphrase_file) => read_secret(passphrase_file)?,
+                        None => Zeroizing::new(std::env::var("MASTER_KEY_PASSPHRASE").map_err(
+                            |_| "passphrase_file or MASTER_KEY_PASSPHRASE must be set".to_string(),
+                        )?),
+                    };
+                let master_key =
+                    decrypt_master_key(&keystore, strip_newline(&passphrase).as_bytes())?;
+                return Ok(Box::new(LockedMasterKey::new(master_key)));
+            }
+            MasterKeySource::ExternalSigner { socket } => {
+                return ExternalSigner::connect(socket)
+                    .map(|signer| Box::new(signer) as Box<dyn MasterKeyProvider>)
+                    .map_err(|e| {
+                        format!(
+                            "Cannot connect to external signer at {}: {}",
+                            socket.display(),
+                            e
+                        )
+                    });
+            }
+        };
+        Ok(Box::new(LockedMasterKey::new(parse_master_key(
+            encoded.trim(),
+        )?)))
+    }
+}
+
+/// Parse a base64 encoded master key.
+pub(crate) fn parse_master_key(master_key: &str) -> Result<IbeMasterKey, String> {
+    let bytes = Zeroizing::new(
+        Base64::decode(master_key).map_err(|_| "Master key should be base64 encoded")?,
+    );
+    IbeMasterKey::from_byte_array(
+        bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Invalid master key length")?,
+    )
+    .map_err(|_| "Invalid master key value".to_string())
+}
+
+/// Remove a single trailing newline, e.g. from a file written by `echo`. Other whitespace is part of the passphrase.
+fn strip_newline(passphrase: &str) -> &str {
+    passphrase
+        .strip_suffix('\n')
+        .map(|p| p.strip_suffix('\r').unwrap_or(p))
+        .unwrap_or(passphrase)
+}
+
+fn read_secret(path: &Path) -> Result<Zeroizing<String>, String> {
+    std::fs::read_to_string(path)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Cannot read secret {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::ibe;
+    use rand::thread_rng;
+
+    #[test]
+    fn test_locked_master_key() {
+        let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let locked = LockedMasterKey::new(master_key);
+        assert_eq!(ibe::public_key_from_master_key(&locked), public_key);
+        assert_eq!(format!("{:?}", *locked), "MasterKey([REDACTED])");
+    }
+
+    #[test]
+    fn test_parse_master_key() {
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let encoded = Base64::encode(master_key.to_byte_array());
+        assert_eq!(parse_master_key(&encoded).unwrap(), master_key);
+        assert!(parse_master_key("not base64!").is_err());
+        assert!(parse_master_key(&Base64::encode([1u8; 31])).is_err());
+        assert!(parse_master_key(&Base64::encode([0xffu8; 32])).is_err());
+    }
+
+    #[test]
+    fn test_master_key_sources() {
+        use std::io::Write;
+        use std::os::fd::IntoRawFd;
+
+        let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let dir = tempfile::tempdir().unwrap();
+
+        // Secret file, e.g. on a tmpfs. Trailing newlines are ignored.
+        let path = dir.path().join("master_key");
+        std::fs::write(
+            &path,
+            format!("{}\n", Base64::encode(master_key.to_byte_array())),
+        )
+        .unwrap();
+        let source = MasterKeySource::SecretFile { path: path.clone() };
+        assert_eq!(source.load().unwrap().public_key(), public_key);
+
+        // File descriptor.
+        let fd = File::open(&path).unwrap().into_raw_fd();
+        let source = MasterKeySource::Fd { fd };
+        assert_eq!(source.load().unwrap().public_key(), public_key);
+
+        // Keystore with a passphrase file.
+        let keystore_path = dir.path().join("keystore.json");
+        let passphrase_path = dir.path().join("passphrase");
+        let keystore =
+            crate::keystore::encrypt_master_key(&master_key, b"passphrase", &mut thread_rng())
+                .unwrap();
+        std::fs::write(&keystore_path, keystore).unwrap();
+        let mut passphrase_file = File::create(&passphrase_path).unwrap();
+        writeln!(passphrase_file, "passphrase").unwrap();
+        let source = MasterKeySource::Keystore {
+            path: keystore_path,
+            passphrase_file: Some(passphrase_path.clone()),
+        };
+        let provider = source.load().unwrap();
+        assert_eq!(provider.public_key(), public_key);
+        assert_eq!(
+            provider.extract(&[b"id".to_vec()]).unwrap()[0],
+            ibe::extract(&master_key, b"id")
+        );
+
+        // Whitespace other than the trailing newline is part of the passphrase.
+        let keystore =
+            crate::keystore::encrypt_master_key(&master_key, b" passphrase ", &mut thread_rng())
+                .unwrap();
+        let keystore_path = dir.path().join("keystore-with-spaces.json");
+        std::fs::write(&keystore_path, keystore).unwrap();
+        std::fs::write(&passphrase_path, " passphrase \n").unwrap();
+        let source = MasterKeySource::Keystore {
+            path: keystore_path.clone(),
+            passphrase_file: Some(passphrase_path.clone()),
+        };
+        assert_eq!(source.load().unwrap().public_key(), public_key);
+        std::fs::write(&passphrase_path, "passphrase\n").unwrap();
+        let source = MasterKeySource::Keystore {
+            path: keystore_path,
+            passphrase_file: Some(passphrase_path),
+        };
+        assert!(source.load().is_err());
+
+        // The standard streams are rejected.
+        assert!(MasterKeySource::Fd { fd: 0 }.load().is_err());
+
+        // External signer.
+        let signer = crate::external_signer::local_signer::LocalSigner::spawn(master_key);
+        let source = MasterKeySource::ExternalSigner {
+            socket: signer.socket().to_path_buf(),
+        };
+        assert_eq!(source.load().unwrap().public_key(), public_key);
+
+        // Missing files are reported.
+        let source = MasterKeySource::SecretFile {
+            path: dir.path().join("missing"),
+        };
+        assert!(source.load().is_err());
+    }
+}
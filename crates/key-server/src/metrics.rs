@@ -2,8 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use prometheus::{
-    register_histogram_with_registry, register_int_counter_vec_with_registry,
-    register_int_counter_with_registry, Histogram, IntCounter, IntCounterVec, Registry,
+    register_histogram_vec_with_registry, register_histogram_with_registry,
+    register_int_counter_vec_with_registry, register_int_counter_with_registry,
+    register_int_gauge_vec_with_registry, Histogram, HistogramVec, IntCounter, IntCounterVec,
+    IntGaugeVec, Registry,
 };
 use std::time::Instant;
 
@@ -18,6 +20,9 @@ pub(crate) struct Metrics {
     /// Total number of internal errors by type
     errors: IntCounterVec,
 
+    /// Total number of throttled requests by the limit exceeded
+    pub throttled_requests: IntCounterVec,
+
     /// Delay of timestamp of the latest checkpoint
     pub checkpoint_timestamp_delay: Histogram,
 
@@ -30,14 +35,35 @@ pub(crate) struct Metrics {
     /// Status of requests of getting the reference gas price
     pub get_reference_gas_price_status: IntCounterVec,
 
+    /// Status of self-tests of the master keys
+    pub self_test_status: IntCounterVec,
+
     /// Duration of check_policy
     pub check_policy_duration: Histogram,
 
+    /// Policy cache lookups by result (hit or miss)
+    pub policy_cache_lookups: IntCounterVec,
+
     /// Duration of fetch_pkg_ids
     pub fetch_pkg_ids_duration: Histogram,
 
     /// Total number of requests per number of ids
     pub requests_per_number_of_ids: Histogram,
+
+    /// Requests to full node and GraphQL endpoints by endpoint and status
+    pub endpoint_requests: IntCounterVec,
+
+    /// Duration of requests to full node and GraphQL endpoints by endpoint
+    pub endpoint_request_duration: HistogramVec,
+
+    /// Delay of the latest checkpoint seen by each endpoint
+    pub endpoint_checkpoint_delay: IntGaugeVec,
+
+    /// Whether each endpoint is considered healthy (1) or not (0)
+    pub endpoint_healthy: IntGaugeVec,
+
+    /// Total number of requests retried on another endpoint
+    pub endpoint_failovers: IntCounter,
 }
 
 impl Metrics {
@@ -56,6 +82,13 @@ impl Metrics {
                 registry
             )
             .unwrap(),
+            throttled_requests: register_int_counter_vec_with_registry!(
+                "throttled_requests",
+                "Total number of throttled fetch_key requests by the limit exceeded",
+                &["limit"],
+                registry
+            )
+            .unwrap(),
             service_requests: register_int_counter_with_registry!(
                 "service_requests",
                 "Total number of service requests received",
@@ -97,6 +130,13 @@ impl Metrics {
                 registry
             )
             .unwrap(),
+            policy_cache_lookups: register_int_counter_vec_with_registry!(
+                "policy_cache_lookups",
+                "Policy cache lookups by result",
+                &["result"],
+                registry
+            )
+            .unwrap(),
             get_reference_gas_price_status: register_int_counter_vec_with_registry!(
                 "get_reference_gas_price_status",
                 "Status of requests of getting the reference gas price",
@@ -104,6 +144,13 @@ impl Metrics {
                 registry
             )
             .unwrap(),
+            self_test_status: register_int_counter_vec_with_registry!(
+                "self_test_status",
+                "Status of self-tests of the master keys",
+                &["status"],
+                registry
+            )
+            .unwrap(),
             requests_per_number_of_ids: register_histogram_with_registry!(
                 "requests_per_number_of_ids",
                 "Total number of requests per number of ids",
@@ -111,6 +158,41 @@ impl Metrics {
                 registry
             )
             .unwrap(),
+            endpoint_requests: register_int_counter_vec_with_registry!(
+                "endpoint_requests",
+                "Requests to full node and GraphQL endpoints by endpoint and status",
+                &["endpoint", "status"],
+                registry
+            )
+            .unwrap(),
+            endpoint_request_duration: register_histogram_vec_with_registry!(
+                "endpoint_request_duration",
+                "Duration of requests to full node and GraphQL endpoints",
+                &["endpoint"],
+                default_external_call_duration_buckets(),
+                registry
+            )
+            .unwrap(),
+            endpoint_checkpoint_delay: register_int_gauge_vec_with_registry!(
+                "endpoint_checkpoint_delay",
+                "Delay of the latest checkpoint seen by an endpoint",
+                &["endpoint"],
+                registry
+            )
+            .unwrap(),
+            endpoint_healthy: register_int_gauge_vec_with_registry!(
+                "endpoint_healthy",
+                "Whether an endpoint is considered healthy",
+                &["endpoint"],
+                registry
+            )
+            .unwrap(),
+            endpoint_failovers: register_int_counter_with_registry!(
+                "endpoint_failovers",
+                "Total number of requests retried on another endpoint",
+                registry
+            )
+            .unwrap(),
         }
     }
 
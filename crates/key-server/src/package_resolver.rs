@@ -0,0 +1,515 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Resolution of the first and the latest version of a package, either using GraphQL or the JSON-RPC API of a full
+//! node. The resolver selected in the config is tried first, and the other one is used if it fails or does not find
+//! the package.
+//!
+//! The JSON-RPC API has no direct lookup of package versions, so [JsonRpcResolver] follows the links between objects:
+//! - The first version is the address of the package's modules, which is that of the original package for all
+//!   versions.
+//! - The latest version is tracked by the upgrade cap of the package, which is found in the changes of the
+//!   transaction which published or upgraded the given version.
+//!
+//! Packages whose upgrade cap has been destroyed or wrapped in another object, e.g. by a custom upgrade policy, or
+//! which were published together with other packages, can therefore only be resolved using GraphQL.
+
+use crate::chain::{GraphQlClient, NodeClient};
+use crate::config::PackageResolverKind;
+use crate::endpoints::EndpointPool;
+use crate::errors::InternalError;
+use crate::externals::query_first_and_last_pkg_id;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use std::sync::Arc;
+use sui_sdk::types::base_types::ObjectID;
+use tracing::{debug, warn};
+
+const UPGRADE_CAP_TYPE: &str = "0x2::package::UpgradeCap";
+
+#[async_trait]
+pub(crate) trait PackageResolver: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The ids of the first and the latest version of the given package. Fails if the package could not be resolved,
+    /// and returns [InternalError::InvalidPackage] if it does not exist.
+    async fn resolve(
+        &self,
+        pkg_id: &ObjectID,
+    ) -> Result<Result<(ObjectID, ObjectID), InternalError>>;
+}
+
+pub(crate) struct GraphQlResolver(pub Arc<EndpointPool<GraphQlClient>>);
+
+#[async_trait]
+impl PackageResolver for GraphQlResolver {
+    fn name(&self) -> &'static str {
+        "GraphQL"
+    }
+
+    async fn resolve(
+        &self,
+        pkg_id: &ObjectID,
+    ) -> Result<Result<(ObjectID, ObjectID), InternalError>> {
+        self.0
+            .call("Resolving package using GraphQL", |graphql| {
+                query_first_and_last_pkg_id(&graphql.http, &graphql.url, pkg_id)
+            })
+            .await
+    }
+}
+
+pub(crate) struct JsonRpcResolver(pub Arc<EndpointPool<NodeClient>>);
+
+#[async_trait]
+impl PackageResolver for JsonRpcResolver {
+    fn name(&self) -> &'static str {
+        "JSON-RPC"
+    }
+
+    async fn resolve(
+        &self,
+        pkg_id: &ObjectID,
+    ) -> Result<Result<(ObjectID, ObjectID), InternalError>> {
+        self.0
+            .call("Resolving package using JSON-RPC", |node| {
+                query_first_and_last_pkg_id_json_rpc(node, pkg_id)
+            })
+            .await
+    }
+}
+
+async fn query_first_and_last_pkg_id_json_rpc(
+    node: &NodeClient,
+    pkg_id: &ObjectID,
+) -> Result<Result<(ObjectID, ObjectID), InternalError>> {
+    let object = node
+        .json_rpc(
+            "sui_getObject",
+            json!([pkg_id, { "showType": true, "showPreviousTransaction": true }]),
+        )
+        .await?;
+    let package = &object["data"];
+    if package.is_null() {
+        // Objects which do not exist or have been deleted are reported as errors in the result.
+        if object["error"]["code"].is_string() {
+            return Ok(Err(InternalError::InvalidPackage));
+        }
+        bail!("Invalid sui_getObject response: {}", object);
+    }
+    if package["type"] != "package" {
+        return Ok(Err(InternalError::InvalidPackage));
+    }
+
+    let first = if package["version"] == "1" {
+        *pkg_id
+    } else {
+        let modules = node
+            .json_rpc("sui_getNormalizedMoveModulesByPackage", json!([pkg_id]))
+            .await?;
+        let address = modules
+            .as_object()
+            .and_then(|modules| modules.values().next())
+            .and_then(|module| module["address"].as_str())
+            .ok_or_else(|| anyhow!("Invalid normalized modules of package {}", pkg_id))?;
+        ObjectID::from_hex_literal(address)?
+    };
+
+    let transaction = node
+        .json_rpc(
+            "sui_getTransactionBlock",
+            json!([package["previousTransaction"], { "showObjectChanges": true }]),
+        )
+        .await?;
+    let upgrade_caps: Vec<&str> = transaction["objectChanges"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|change| change["objectType"] == UPGRADE_CAP_TYPE)
+        .filter_map(|change| change["objectId"].as_str())
+        .collect();
+    let [upgrade_cap] = upgrade_caps[..] else {
+        bail!(
+            "Expected one upgrade cap in the transaction of package {}, found {}",
+            pkg_id,
+            upgrade_caps.len()
+        );
+    };
+
+    let upgrade_cap = node
+        .json_rpc(
+            "sui_getObject",
+            json!([upgrade_cap, { "showContent": true }]),
+        )
+        .await?;
+    let latest = upgrade_cap["data"]["content"]["fields"]["package"]
+        .as_str()
+        .ok_or_else(|| anyhow!("The upgrade cap of package {} is not available", pkg_id))?;
+    Ok(Ok((first, ObjectID::from_hex_literal(latest)?)))
+}
+
+/// The resolvers to use, with the preferred one first and the other one as fallback.
+pub(crate) fn resolvers(
+    preferred: PackageResolverKind,
+    nodes: Arc<EndpointPool<NodeClient>>,
+    graphql: Arc<EndpointPool<GraphQlClient>>,
+) -> Vec<Box<dyn PackageResolver>> {
+    let graphql: Box<dyn PackageResolver> = Box::new(GraphQlResolver(graphql));
+    let json_rpc: Box<dyn PackageResolver> = Box::new(JsonRpcResolver(nodes));
+    match preferred {
+        PackageResolverKind::GraphQl => vec![graphql, json_rpc],
+        PackageResolverKind::JsonRpc => vec![json_rpc, graphql],
+    }
+}
+
+/// Resolve a package using the first resolver which finds it. A package reported as not existing by one resolver, e.g.
+/// because the GraphQL indexer has not caught up with it yet, is looked up using the others as well. Returns
+/// [InternalError::InvalidPackage] if no resolver found the package and [InternalError::Failure] if all failed.
+pub(crate) async fn resolve_with_fallback(
+    resolvers: &[Box<dyn PackageResolver>],
+    pkg_id: &ObjectID,
+) -> Result<(ObjectID, ObjectID), InternalError> {
+    let mut result = Err(InternalError::Failure);
+    for resolver in resolvers {
+        match resolver.resolve(pkg_id).await {
+            Ok(Ok(versions)) => return Ok(versions),
+            Ok(Err(e)) => {
+                debug!(
+                    "Package {} not found using {}: {:?}",
+                    pkg_id,
+                    resolver.name(),
+                    e
+                );
+                result = Err(e);
+            }
+            Err(e) => warn!(
+                "Resolving package {} using {} failed: {:?}",
+                pkg_id,
+                resolver.name(),
+                e
+            ),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::tests::stub::{stub_server, stub_unavailable};
+    use reqwest::Client;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// A package with three versions whose upgrade cap is owned by an address, and an unrelated coin. The responses
+    /// of the stub endpoints are written by hand in the format of the APIs, with made up digests, rather than recorded
+    /// from a network.
+    struct StubPackage {
+        versions: [ObjectID; 3],
+        upgrade_cap: ObjectID,
+        coin: ObjectID,
+    }
+
+    impl StubPackage {
+        fn new() -> Self {
+            Self {
+                versions: [ObjectID::random(), ObjectID::random(), ObjectID::random()],
+                upgrade_cap: ObjectID::random(),
+                coin: ObjectID::random(),
+            }
+        }
+
+        /// Responses of a full node in the format of the JSON-RPC API, by method and first parameter.
+        fn json_rpc_responses(&self) -> HashMap<(String, String), Value> {
+            let [first, _, latest] = self.versions;
+            let mut responses = HashMap::new();
+            for (i, version) in self.versions.iter().enumerate() {
+                let digest = format!("Digest{}", i + 1);
+                responses.insert(
+                    ("sui_getObject".into(), version.to_string()),
+                    json!({
+                        "data": {
+                            "objectId": version,
+                            "version": (i + 1).to_string(),
+                            "digest": "4M9i3VTfYKFWLbBMyP3mgNzwm4nbA3XhGnHQB8dZ8QvW",
+                            "type": "package",
+                            "previousTransaction": digest,
+                        }
+                    }),
+                );
+                responses.insert(
+                    (
+                        "sui_getNormalizedMoveModulesByPackage".into(),
+                        version.to_string(),
+                    ),
+                    json!({
+                        "policy": {
+                            "fileFormatVersion": 6,
+                            "address": first,
+                            "name": "policy",
+                            "friends": [],
+                            "structs": {},
+                            "exposedFunctions": {},
+                        }
+                    }),
+                );
+                let cap_change = if i == 0 { "created" } else { "mutated" };
+                responses.insert(
+                    ("sui_getTransactionBlock".into(), digest),
+                    json!({
+                        "digest": format!("Digest{}", i + 1),
+                        "objectChanges": [
+                            {
+                                "type": "mutated",
+                                "sender": "0x1a2b",
+                                "owner": { "AddressOwner": "0x1a2b" },
+                                "objectType": "0x2::coin::Coin<0x2::sui::SUI>",
+                                "objectId": self.coin,
+                                "version": "10",
+                                "digest": "8ujrpy2B8XcRvNuVXbpNx6D8dBWEhwvfmrdbMeWBnGpj",
+                            },
+                            {
+                                "type": cap_change,
+                                "sender": "0x1a2b",
+                                "owner": { "AddressOwner": "0x1a2b" },
+                                "objectType": UPGRADE_CAP_TYPE,
+                                "objectId": self.upgrade_cap,
+                                "version": "10",
+                                "digest": "EV8hk2BWH6GjbgKE4Dut5y5cAv3X6ZrS9Ly4R4VQmkGd",
+                            },
+                            {
+                                "type": "published",
+                                "packageId": version,
+                                "version": (i + 1).to_string(),
+                                "digest": "4M9i3VTfYKFWLbBMyP3mgNzwm4nbA3XhGnHQB8dZ8QvW",
+                                "modules": ["policy"],
+                            },
+                        ]
+                    }),
+                );
+            }
+            responses.insert(
+                ("sui_getObject".into(), self.upgrade_cap.to_string()),
+                json!({
+                    "data": {
+                        "objectId": self.upgrade_cap,
+                        "version": "10",
+                        "digest": "EV8hk2BWH6GjbgKE4Dut5y5cAv3X6ZrS9Ly4R4VQmkGd",
+                        "content": {
+                            "dataType": "moveObject",
+                            "type": UPGRADE_CAP_TYPE,
+                            "hasPublicTransfer": true,
+                            "fields": {
+                                "id": { "id": self.upgrade_cap },
+                                "package": latest,
+                                "policy": 0,
+                                "version": "3",
+                            }
+                        }
+                    }
+                }),
+            );
+            responses.insert(
+                ("sui_getObject".into(), self.coin.to_string()),
+                json!({
+                    "data": {
+                        "objectId": self.coin,
+                        "version": "10",
+                        "digest": "8ujrpy2B8XcRvNuVXbpNx6D8dBWEhwvfmrdbMeWBnGpj",
+                        "type": "0x2::coin::Coin<0x2::sui::SUI>",
+                        "previousTransaction": "Digest3",
+                    }
+                }),
+            );
+            responses
+        }
+
+        /// A full node answering with the stub responses. Unknown objects do not exist.
+        async fn node(&self) -> String {
+            let responses = self.json_rpc_responses();
+            stub_server(move |request| {
+                let method = request["method"].as_str()?;
+                let param = request["params"][0].as_str()?;
+                let result = match responses.get(&(method.to_string(), param.to_string())) {
+                    Some(result) => result.clone(),
+                    None if method == "sui_getObject" => {
+                        json!({ "error": { "code": "notExists", "object_id": param } })
+                    }
+                    None => return None,
+                };
+                Some(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+            })
+            .await
+        }
+
+        /// A GraphQL endpoint knowing the versions of the package.
+        async fn graphql(&self) -> String {
+            let [first, _, latest] = self.versions;
+            let versions = self.versions;
+            stub_server(move |request| {
+                let query = request["query"].as_str()?;
+                let package = versions
+                    .iter()
+                    .any(|version| query.contains(&version.to_string()))
+                    .then(
+                        || json!({ "address": latest, "packageAtVersion": { "address": first } }),
+                    );
+                Some(json!({ "data": { "latestPackage": package } }))
+            })
+            .await
+        }
+    }
+
+    fn node_pool(urls: Vec<String>) -> Arc<EndpointPool<NodeClient>> {
+        let nodes = urls
+            .into_iter()
+            .map(|url| (url.clone(), NodeClient::new(url, Client::new())))
+            .collect();
+        Arc::new(EndpointPool::new(
+            nodes,
+            Duration::from_secs(10),
+            Arc::new(MockClock::new(0)),
+            None,
+        ))
+    }
+
+    fn graphql_pool(urls: Vec<String>) -> Arc<EndpointPool<GraphQlClient>> {
+        let endpoints = urls
+            .into_iter()
+            .map(|url| (url.clone(), GraphQlClient::new(url, Client::new())))
+            .collect();
+        Arc::new(EndpointPool::new(
+            endpoints,
+            Duration::from_secs(10),
+            Arc::new(MockClock::new(0)),
+            None,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_json_rpc_resolver_with_stub_node() {
+        let package = StubPackage::new();
+        let resolver = JsonRpcResolver(node_pool(vec![package.node().await]));
+        let [first, _, latest] = package.versions;
+
+        // All versions, including the first and the latest, resolve to the same ids.
+        for version in package.versions {
+            assert_eq!(
+                resolver.resolve(&version).await.unwrap(),
+                Ok((first, latest))
+            );
+        }
+
+        // Objects which are not packages, and objects which do not exist, are invalid packages.
+        for object in [package.coin, ObjectID::random()] {
+            assert_eq!(
+                resolver.resolve(&object).await.unwrap(),
+                Err(InternalError::InvalidPackage)
+            );
+        }
+
+        // If the full node is unavailable, resolving fails.
+        let resolver = JsonRpcResolver(node_pool(vec![stub_unavailable().await]));
+        assert!(resolver.resolve(&first).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_rpc_resolver_with_stub_node_without_upgrade_cap() {
+        // The upgrade cap has been destroyed to make the package immutable, or wrapped in another object. Either way
+        // the full node reports it as deleted.
+        let package = StubPackage::new();
+        let mut responses = package.json_rpc_responses();
+        responses.insert(
+            ("sui_getObject".into(), package.upgrade_cap.to_string()),
+            json!({ "error": { "code": "deleted", "object_id": package.upgrade_cap, "version": 11, "digest": "7gyGAp71YXQRoxmFBaHxofQXAipvgHyBKPyxmdSJxyvz" } }),
+        );
+        let url = stub_server(move |request| {
+            let method = request["method"].as_str()?;
+            let param = request["params"][0].as_str()?;
+            let result = responses
+                .get(&(method.to_string(), param.to_string()))?
+                .clone();
+            Some(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+        })
+        .await;
+        let resolver = JsonRpcResolver(node_pool(vec![url]));
+        assert!(resolver.resolve(&package.versions[1]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_graphql_resolver_with_stub_endpoint() {
+        let package = StubPackage::new();
+        let resolver = GraphQlResolver(graphql_pool(vec![package.graphql().await]));
+        let [first, _, latest] = package.versions;
+
+        for version in package.versions {
+            assert_eq!(
+                resolver.resolve(&version).await.unwrap(),
+                Ok((first, latest))
+            );
+        }
+        assert_eq!(
+            resolver.resolve(&ObjectID::random()).await.unwrap(),
+            Err(InternalError::InvalidPackage)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fallback_with_stub_endpoints() {
+        let package = StubPackage::new();
+        let [first, second, latest] = package.versions;
+
+        // GraphQL is preferred, but unavailable, so the full node is used.
+        let ordered = resolvers(
+            PackageResolverKind::GraphQl,
+            node_pool(vec![package.node().await]),
+            graphql_pool(vec![stub_unavailable().await]),
+        );
+        assert_eq!(ordered[0].name(), "GraphQL");
+        assert_eq!(
+            resolve_with_fallback(&ordered, &second).await,
+            Ok((first, latest))
+        );
+        assert_eq!(
+            resolve_with_fallback(&ordered, &ObjectID::random()).await,
+            Err(InternalError::InvalidPackage)
+        );
+
+        // GraphQL is preferred, but does not know the package yet, so the full node is used.
+        let ordered = resolvers(
+            PackageResolverKind::GraphQl,
+            node_pool(vec![package.node().await]),
+            graphql_pool(vec![StubPackage::new().graphql().await]),
+        );
+        assert_eq!(
+            resolve_with_fallback(&ordered, &second).await,
+            Ok((first, latest))
+        );
+
+        // The full node is preferred, but cannot be reached, so GraphQL is used.
+        let ordered = resolvers(
+            PackageResolverKind::JsonRpc,
+            node_pool(vec![]),
+            graphql_pool(vec![package.graphql().await]),
+        );
+        assert_eq!(ordered[0].name(), "JSON-RPC");
+        assert_eq!(
+            resolve_with_fallback(&ordered, &second).await,
+            Ok((first, latest))
+        );
+
+        // If both fail, so does resolving.
+        let ordered = resolvers(
+            PackageResolverKind::JsonRpc,
+            node_pool(vec![stub_unavailable().await]),
+            graphql_pool(vec![stub_unavailable().await]),
+        );
+        assert_eq!(
+            resolve_with_fallback(&ordered, &second).await,
+            Err(InternalError::Failure)
+        );
+    }
+}
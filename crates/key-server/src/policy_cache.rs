@@ -0,0 +1,135 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Short-lived cache of policy evaluation results, so retries and requests for several objects under the same policy
+//! do not each need a dry run on the full node.
+//!
+//! Results are keyed by the sender, the digest of the PTB and the bucket of length `ttl` containing the timestamp of the
+//! latest checkpoint when the request was evaluated. Policies observe time through the clock object, so a result is
+//! only reused while the full node serves checkpoints from the same bucket, and never once the chain has moved past
+//! it, regardless of the local time of the key server.
+
+use crate::cache::{Cache, CacheStatus};
+use crate::clock::Clock;
+use crate::config::PolicyCacheConfig;
+use fastcrypto::hash::{Blake2b256, HashFunction};
+use std::collections::HashSet;
+use std::sync::Arc;
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub(crate) struct PolicyCacheKey {
+    sender: SuiAddress,
+    ptb_digest: [u8; 32],
+    bucket: u64,
+}
+
+pub(crate) struct PolicyCache {
+    ttl: u64,
+    /// Whether the policy allowed access.
+    cache: Cache<PolicyCacheKey, bool>,
+    /// First package ids of the packages whose policies are always evaluated.
+    excluded_packages: HashSet<ObjectID>,
+}
+
+impl PolicyCache {
+    pub fn new(config: &PolicyCacheConfig, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            ttl: config.ttl_ms,
+            cache: Cache::new(config.ttl_ms, config.size, clock),
+            excluded_packages: config.excluded_packages.iter().copied().collect(),
+        }
+    }
+
+    /// The key for the result of evaluating the given BCS serialized PTB for the sender against a full node whose
+    /// latest checkpoint has the given timestamp (Unix time in milliseconds), or `None` if the package with the given
+    /// first version opted out of caching.
+    pub fn key(
+        &self,
+        first_pkg_id: &ObjectID,
+        sender: SuiAddress,
+        ptb: &[u8],
+        checkpoint_timestamp: u64,
+    ) -> Option<PolicyCacheKey> {
+        if self.excluded_packages.contains(first_pkg_id) {
+            return None;
+        }
+        Some(PolicyCacheKey {
+            sender,
+            ptb_digest: Blake2b256::digest(ptb).digest,
+            bucket: checkpoint_timestamp / self.ttl,
+        })
+    }
+
+    /// Get the cached result for the given key. Results from earlier checkpoint buckets are never returned since the
+    /// bucket is part of the key.
+    pub fn get(&self, key: &PolicyCacheKey) -> Option<bool> {
+        self.cache.get(key)
+    }
+
+    pub fn insert(&self, key: PolicyCacheKey, allowed: bool) {
+        self.cache.insert(key, allowed);
+    }
+
+    pub fn status(&self) -> CacheStatus {
+        self.cache.status()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    fn cache(excluded_packages: Vec<ObjectID>) -> PolicyCache {
+        PolicyCache::new(
+            &PolicyCacheConfig {
+                ttl_ms: 5_000,
+                size: 10,
+                excluded_packages,
+            },
+            Arc::new(MockClock::new(0)),
+        )
+    }
+
+    #[test]
+    fn test_policy_cache_key() {
+        let cache = cache(vec![]);
+        let package = ObjectID::random();
+        let sender = SuiAddress::random_for_testing_only();
+
+        let key = cache.key(&package, sender, b"ptb", 10_000).unwrap();
+        assert_eq!(cache.get(&key), None);
+        cache.insert(key, true);
+        assert_eq!(cache.get(&key), Some(true));
+
+        // Checkpoints in the same bucket.
+        assert_eq!(cache.key(&package, sender, b"ptb", 14_999), Some(key));
+        // Checkpoint in the next bucket, other PTB or other sender.
+        assert_ne!(cache.key(&package, sender, b"ptb", 15_000), Some(key));
+        assert_ne!(cache.key(&package, sender, b"other ptb", 10_000), Some(key));
+        assert_ne!(
+            cache.key(
+                &package,
+                SuiAddress::random_for_testing_only(),
+                b"ptb",
+                10_000
+            ),
+            Some(key)
+        );
+
+        // Denials are cached as well.
+        let key = cache.key(&package, sender, b"denied", 10_000).unwrap();
+        cache.insert(key, false);
+        assert_eq!(cache.get(&key), Some(false));
+    }
+
+    #[test]
+    fn test_policy_cache_opt_out() {
+        let excluded = ObjectID::random();
+        let cache = cache(vec![excluded]);
+        let sender = SuiAddress::random_for_testing_only();
+        assert!(cache.key(&excluded, sender, b"ptb", 0).is_none());
+        assert!(cache.key(&ObjectID::random(), sender, b"ptb", 0).is_some());
+    }
+}
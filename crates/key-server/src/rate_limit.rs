@@ -0,0 +1,300 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Token bucket rate limiting of fetch key requests by the user address of the certificate, the package id of the
+//! PTB and the client IP. Every key has a bucket holding up to `burst` tokens which is refilled at
+//! `requests_per_sec`, and a request takes one token from the bucket of each of its keys.
+//!
+//! The user of a request is only known once the signature of its certificate is verified, so the user limit is
+//! checked separately from and after the IP and package limits.
+
+use crate::config::{RateLimit, RateLimitConfig};
+use lru::LruCache;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::num::NonZero;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use sui_sdk::types::base_types::{ObjectID, SuiAddress};
+
+/// The number of buckets per kind of key. Once reached, the least recently used bucket is dropped, which resets its
+/// limit, so this should be well above the number of keys active within the time it takes to refill a bucket.
+const MAX_BUCKETS: usize = 100_000;
+
+/// A request was throttled by the given limit ("user", "package" or "ip").
+#[derive(Debug, PartialEq)]
+pub(crate) struct Throttled {
+    pub limit: &'static str,
+    pub retry_after: Duration,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.requests_per_sec).min(limit.burst as f64);
+        self.updated = now;
+    }
+
+    /// Check that a token is available, or return the time until one is.
+    fn check(&mut self, limit: &RateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - self.tokens) / limit.requests_per_sec,
+            ))
+        }
+    }
+}
+
+/// The buckets for one kind of key.
+struct Buckets<K: Hash + Eq> {
+    name: &'static str,
+    limit: RateLimit,
+    buckets: Mutex<LruCache<K, TokenBucket>>,
+}
+
+impl<K: Eq + Hash> Buckets<K> {
+    fn new(name: &'static str, limit: &Option<RateLimit>, capacity: usize) -> Option<Self> {
+        limit.clone().map(|limit| Self {
+            name,
+            limit,
+            buckets: Mutex::new(LruCache::new(
+                NonZero::new(capacity).expect("positive capacity"),
+            )),
+        })
+    }
+
+    /// Check that a token is available for the key without taking it. Keys without a bucket have a full one.
+    fn check(&self, key: &K, now: Instant) -> Result<(), Throttled> {
+        let mut buckets = self.buckets.lock().expect("lock is never poisoned");
+        match buckets.get_mut(key) {
+            Some(bucket) => bucket
+                .check(&self.limit, now)
+                .map_err(|retry_after| Throttled {
+                    limit: self.name,
+                    retry_after,
+                }),
+            None => Ok(()),
+        }
+    }
+
+    /// Take a token for the key. A concurrent request may have taken the last token since it was checked, which
+    /// leaves the bucket below zero until it is refilled.
+    fn take(&self, key: K, now: Instant) {
+        let mut buckets = self.buckets.lock().expect("lock is never poisoned");
+        let bucket = buckets.get_or_insert_mut(key, || TokenBucket::new(&self.limit, now));
+        bucket.refill(&self.limit, now);
+        bucket.tokens -= 1.0;
+    }
+
+    fn check_and_take(&self, key: K, now: Instant) -> Result<(), Throttled> {
+        self.check(&key, now)?;
+        self.take(key, now);
+        Ok(())
+    }
+}
+
+/// Rate limiter for fetch key requests. Limits which are not configured are not enforced.
+pub(crate) struct RateLimiter {
+    per_user: Option<Buckets<SuiAddress>>,
+    per_package: Option<Buckets<ObjectID>>,
+    per_ip: Option<Buckets<IpAddr>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self::with_capacity(config, MAX_BUCKETS)
+    }
+
+    fn with_capacity(config: &RateLimitConfig, capacity: usize) -> Self {
+        Self {
+            per_user: Buckets::new("user", &config.per_user, capacity),
+            per_package: Buckets::new("package", &config.per_package, capacity),
+            per_ip: Buckets::new("ip", &config.per_ip, capacity),
+        }
+    }
+
+    /// Take a token for the client IP and, if given, the package. Both limits are checked before a token is taken,
+    /// so a request throttled by one limit does not count against the other. The IP limit is returned if both are
+    /// exceeded.
+    pub fn check(
+        &self,
+        package_id: Option<ObjectID>,
+        ip: IpAddr,
+        now: Instant,
+    ) -> Result<(), Throttled> {
+        let per_ip = self.per_ip.as_ref().map(|buckets| (buckets, ip));
+        let per_package = self.per_package.as_ref().zip(package_id);
+        if let Some((buckets, ip)) = &per_ip {
+            buckets.check(ip, now)?;
+        }
+        if let Some((buckets, package_id)) = &per_package {
+            buckets.check(package_id, now)?;
+        }
+        if let Some((buckets, ip)) = per_ip {
+            buckets.take(ip, now);
+        }
+        if let Some((buckets, package_id)) = per_package {
+            buckets.take(package_id, now);
+        }
+        Ok(())
+    }
+
+    /// Take a token for the user. Must only be called once the user has signed the request, since anyone could
+    /// otherwise use up the limit of any address.
+    pub fn check_user(&self, user: SuiAddress, now: Instant) -> Result<(), Throttled> {
+        match &self.per_user {
+            Some(buckets) => buckets.check_and_take(user, now),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests_per_sec: f64, burst: u32) -> Option<RateLimit> {
+        Some(RateLimit {
+            requests_per_sec,
+            burst,
+        })
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_user: limit(2.0, 3),
+            ..Default::default()
+        });
+        let user = SuiAddress::random_for_testing_only();
+        let other = SuiAddress::random_for_testing_only();
+        let start = Instant::now();
+
+        // A burst is allowed, after which the user has to wait for the bucket to refill.
+        for _ in 0..3 {
+            assert!(limiter.check_user(user, start).is_ok());
+        }
+        assert_eq!(
+            limiter.check_user(user, start),
+            Err(Throttled {
+                limit: "user",
+                retry_after: Duration::from_millis(500),
+            })
+        );
+        assert!(limiter
+            .check_user(user, start + Duration::from_millis(400))
+            .is_err());
+        assert!(limiter
+            .check_user(user, start + Duration::from_millis(600))
+            .is_ok());
+
+        // Other users are not affected.
+        assert!(limiter.check_user(other, start).is_ok());
+
+        // The bucket never holds more than the burst.
+        let later = start + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert!(limiter.check_user(user, later).is_ok());
+        }
+        assert!(limiter.check_user(user, later).is_err());
+    }
+
+    #[test]
+    fn test_limits_by_key() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_user: limit(1.0, 1),
+            per_package: limit(1.0, 1),
+            per_ip: limit(1.0, 2),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        let package_id = ObjectID::random();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "10.0.0.2".parse().unwrap();
+
+        // The IP and package limits do not depend on the user.
+        assert!(limiter.check(Some(package_id), other_ip, now).is_ok());
+        assert_eq!(
+            limiter
+                .check(Some(package_id), other_ip, now)
+                .unwrap_err()
+                .limit,
+            "package"
+        );
+        assert!(limiter.check(Some(ObjectID::random()), ip, now).is_ok());
+        assert!(limiter.check(Some(ObjectID::random()), ip, now).is_ok());
+        assert_eq!(
+            limiter
+                .check(Some(ObjectID::random()), ip, now)
+                .unwrap_err()
+                .limit,
+            "ip"
+        );
+
+        // A request throttled by the package limit takes no token from the IP limit.
+        let third_ip: IpAddr = "10.0.0.3".parse().unwrap();
+        for _ in 0..3 {
+            assert!(limiter.check(Some(package_id), third_ip, now).is_err());
+        }
+        assert!(limiter.check(None, third_ip, now).is_ok());
+        assert!(limiter.check(None, third_ip, now).is_ok());
+
+        // Without limits, nothing is throttled.
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let user = SuiAddress::random_for_testing_only();
+        for _ in 0..100 {
+            assert!(limiter.check(Some(package_id), ip, now).is_ok());
+            assert!(limiter.check_user(user, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_bounded_buckets() {
+        let limiter = RateLimiter::with_capacity(
+            &RateLimitConfig {
+                per_user: limit(1.0, 1),
+                ..Default::default()
+            },
+            2,
+        );
+        let now = Instant::now();
+        let users: Vec<_> = (0..3)
+            .map(|_| SuiAddress::random_for_testing_only())
+            .collect();
+        for user in &users[..2] {
+            assert!(limiter.check_user(*user, now).is_ok());
+        }
+        assert!(limiter.check_user(users[0], now).is_err());
+
+        // The least recently used bucket is dropped once the capacity is reached.
+        assert!(limiter.check_user(users[2], now).is_ok());
+        assert_eq!(
+            limiter
+                .per_user
+                .as_ref()
+                .unwrap()
+                .buckets
+                .lock()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(limiter.check_user(users[1], now).is_ok());
+        assert!(limiter.check_user(users[2], now).is_err());
+    }
+}
@@ -1,53 +1,81 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
-use crate::externals::{current_epoch_time, duration_since, get_reference_gas_price};
+use crate::audit_log::{AuditEvent, AuditLog};
+use crate::cache::Cache;
+use crate::chain::{ChainBackend, SuiChain};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{ServerConfig, ServerOptions};
+use crate::identity::{KeyIdentities, KeyIdentity};
+use crate::keystore::encrypt_master_key;
+use crate::master_key::parse_master_key;
 use crate::metrics::{call_with_duration, observation_callback, status_callback, Metrics};
+use crate::policy_cache::PolicyCache;
+use crate::rate_limit::{RateLimiter, Throttled};
 use crate::signed_message::{signed_message, signed_request};
-use crate::types::MasterKeyPOP;
-use anyhow::Result;
-use axum::http::HeaderMap;
+use crate::status::{IdentityStatus, NetworkStatus, SelfTestResult, Status};
+use crate::types::{IbePublicKey, MasterKeyPOP};
+use anyhow::{anyhow, bail, Result};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::http::{HeaderMap, StatusCode};
 use axum::routing::{get, post};
-use axum::{extract::State, Json};
+use axum::Json;
+use clap::{Parser, Subcommand};
 use core::time::Duration;
-use crypto::elgamal::encrypt;
-use crypto::ibe;
-use crypto::ibe::create_proof_of_possession;
 use errors::InternalError;
-use externals::get_latest_checkpoint_timestamp;
 use fastcrypto::ed25519::{Ed25519PublicKey, Ed25519Signature};
 use fastcrypto::encoding::{Base64, Encoding};
-use fastcrypto::serde_helpers::ToFromByteArray;
 use fastcrypto::traits::VerifyingKey;
 use mysten_service::get_mysten_service;
 use mysten_service::metrics::start_basic_prometheus_server;
 use mysten_service::package_name;
 use mysten_service::package_version;
-use mysten_service::serve;
+use parking_lot::Mutex;
 use rand::thread_rng;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::env;
+use std::fs::{File, OpenOptions};
 use std::future::Future;
+use std::io::{BufReader, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path as FilePath, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
-use sui_sdk::error::SuiRpcResult;
-use sui_sdk::rpc_types::SuiTransactionBlockEffectsAPI;
 use sui_sdk::types::base_types::{ObjectID, SuiAddress};
 use sui_sdk::types::signature::GenericSignature;
-use sui_sdk::types::transaction::{ProgrammableTransaction, TransactionKind};
-use sui_sdk::verify_personal_message_signature::verify_personal_message_signature;
-use sui_sdk::{SuiClient, SuiClientBuilder};
+use sui_sdk::types::transaction::ProgrammableTransaction;
 use tap::tap::TapFallible;
+use tokio::net::TcpListener;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::oneshot;
 use tokio::sync::watch::{channel, Receiver};
-use tower_http::cors::{Any, CorsLayer};
+use tokio::task::JoinHandle;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tracing::{debug, info, warn};
-use types::{ElGamalPublicKey, ElgamalEncryption, ElgamalVerificationKey, IbeMasterKey, Network};
+use tracing_subscriber::EnvFilter;
+use types::{ElGamalPublicKey, ElgamalEncryption, ElgamalVerificationKey};
 use valid_ptb::ValidPtb;
+use zeroize::Zeroizing;
 
+mod audit_log;
 mod cache;
+mod chain;
+mod clock;
+mod config;
+mod endpoints;
 mod errors;
+mod external_signer;
 mod externals;
+mod identity;
+mod keystore;
+mod master_key;
+mod package_resolver;
+mod policy_cache;
+mod rate_limit;
 mod signed_message;
+mod status;
 mod types;
 mod valid_ptb;
 
@@ -55,24 +83,17 @@ mod metrics;
 #[cfg(test)]
 pub mod tests;
 
-/// The allowed staleness of the full node.
-/// When setting this duration, note a timestamp on Sui may be a bit late compared to
-/// the current time, but it shouldn't be more than a second.
-const ALLOWED_STALENESS: Duration = Duration::from_secs(120);
-
-/// The interval at which the latest checkpoint timestamp is updated.
-const CHECKPOINT_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
-
-/// The interval at which the reference gas price is updated.
-const RGP_UPDATE_INTERVAL: Duration = Duration::from_secs(60);
+const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// The maximum time to live for a session key.
-const SESSION_KEY_TTL_MAX: u16 = 30;
+/// Names of the values updated by [Server::spawn_periodic_updater].
+const CHECKPOINT_TIMESTAMP: &str = "latest checkpoint timestamp";
+const REFERENCE_GAS_PRICE: &str = "RGP";
 
-/// The 1% of the max budget.
-const GAS_BUDGET: u64 = 500_000_000;
+/// The reference gas price is considered outdated after this many update intervals without a successful update.
+const MAX_MISSED_RGP_UPDATES: u32 = 3;
 
-const PACKAGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How often the master keys are self-tested, see [status::self_test].
+const SELF_TEST_INTERVAL: Duration = Duration::from_secs(60);
 
 // The "session" certificate, signed by the user
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -97,6 +118,15 @@ struct FetchKeyRequest {
     request_signature: Ed25519Signature,
 
     certificate: Certificate,
+
+    // The key server to fetch keys from if the server hosts several. May also be given in the path.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_server_object_id: Option<ObjectID>,
+
+    // The version of the master key the object was encrypted to, see key_server::key_version. Defaults to the current
+    // version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key_version: Option<u32>,
 }
 
 type KeyId = Vec<u8>;
@@ -114,45 +144,77 @@ struct FetchKeyResponse {
     decryption_keys: Vec<DecryptionKey>,
 }
 
-#[derive(Clone)]
 struct Server {
-    sui_client: SuiClient,
-    network: Network,
-    master_key: IbeMasterKey,
-    key_server_object_id: ObjectID,
-    key_server_object_id_sig: MasterKeyPOP,
+    chain: Arc<dyn ChainBackend>,
+    clock: Arc<dyn Clock>,
+    identities: KeyIdentities,
+    options: ServerOptions,
+    policy_cache: Option<PolicyCache>,
+    /// Applies the user limit once the signature of a request is verified. The other limits are applied by the
+    /// handlers, see [MyState::check_rate_limits].
+    rate_limiter: Option<Arc<RateLimiter>>,
+    /// The latest value of each periodically updated value and when it was last updated successfully.
+    last_updates: Arc<Mutex<HashMap<&'static str, (u64, Timestamp)>>>,
 }
 
 impl Server {
-    async fn new(
-        master_key: IbeMasterKey,
-        network: Network,
-        key_server_object_id: ObjectID,
+    fn new(
+        identities: Vec<KeyIdentity>,
+        chain: Arc<dyn ChainBackend>,
+        clock: Arc<dyn Clock>,
+        options: ServerOptions,
     ) -> Self {
-        let sui_client = SuiClientBuilder::default()
-            .build(&network.node_url())
-            .await
-            .expect("SuiClientBuilder should not failed unless provided with invalid network url");
-        info!(
-            "Server started with public key: {:?} and network: {:?}",
-            Base64::encode(
-                bcs::to_bytes(&ibe::public_key_from_master_key(&master_key)).expect("valid pk")
-            ),
-            network
-        );
-
-        let key_server_object_id_sig =
-            create_proof_of_possession(&master_key, &key_server_object_id.into_bytes());
+        for identity in &identities {
+            info!(
+                "Serving key server {:?} with public key: {:?} (key version {})",
+                identity.key_server_object_id,
+                Base64::encode(bcs::to_bytes(&identity.master_key.public_key()).expect("valid pk")),
+                identity.key_version,
+            );
+            for key in &identity.retired_keys {
+                info!(
+                    "Serving retired key version {} of key server {:?} until {}",
+                    key.version, identity.key_server_object_id, key.retired_until,
+                );
+            }
+        }
 
         Server {
-            sui_client,
-            network,
-            master_key,
-            key_server_object_id,
-            key_server_object_id_sig,
+            chain,
+            identities: KeyIdentities::new(identities),
+            policy_cache: options
+                .policy_cache
+                .as_ref()
+                .map(|config| PolicyCache::new(config, clock.clone())),
+            clock,
+            options,
+            rate_limiter: None,
+            last_updates: Default::default(),
         }
     }
 
+    /// Limit the requests of each user with the given rate limiter, which may be shared with other servers.
+    fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// When the value with the given name was last updated successfully by [Server::spawn_periodic_updater].
+    fn last_update(&self, value_name: &str) -> Option<Timestamp> {
+        self.last_updates
+            .lock()
+            .get(value_name)
+            .map(|(_, updated)| *updated)
+    }
+
+    /// The latest value with the given name fetched by [Server::spawn_periodic_updater].
+    fn latest_value(&self, value_name: &str) -> Option<u64> {
+        self.last_updates
+            .lock()
+            .get(value_name)
+            .map(|(value, _)| *value)
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn check_signature(
         &self,
@@ -165,10 +227,11 @@ impl Server {
         req_id: Option<&str>,
     ) -> Result<(), InternalError> {
         // Check certificate.
-        if cert.ttl_min > SESSION_KEY_TTL_MAX
-            || cert.creation_time > current_epoch_time()
-            || current_epoch_time() < 60_000 * (cert.ttl_min as u64) // checks for overflow
-            || current_epoch_time() - 60_000 * (cert.ttl_min as u64) > cert.creation_time
+        let now = self.clock.now();
+        if cert.ttl_min > self.options.session_key_ttl_max
+            || cert.creation_time > now
+            || now < 60_000 * (cert.ttl_min as u64) // checks for overflow
+            || now - 60_000 * (cert.ttl_min as u64) > cert.creation_time
         {
             debug!(
                 "Certificate has invalid expiration time (req_id: {:?})",
@@ -182,20 +245,16 @@ impl Server {
             "Checking signature on message: {:?} (req_id: {:?})",
             msg, req_id
         );
-        verify_personal_message_signature(
-            cert.signature.clone(),
-            msg.as_bytes(),
-            cert.user,
-            Some(self.sui_client.clone()),
-        )
-        .await
-        .tap_err(|e| {
-            debug!(
-                "Signature verification failed: {:?} (req_id: {:?})",
-                e, req_id
-            );
-        })
-        .map_err(|_| InternalError::InvalidSignature)?;
+        self.chain
+            .verify_personal_message_signature(cert.signature.clone(), msg.as_bytes(), cert.user)
+            .await
+            .tap_err(|e| {
+                debug!(
+                    "Signature verification failed: {:?} (req_id: {:?})",
+                    e, req_id
+                );
+            })
+            .map_err(|_| InternalError::InvalidSignature)?;
 
         // Check session signature
         let signed_msg = signed_request(ptb, enc_key, enc_verification_key);
@@ -223,29 +282,15 @@ impl Server {
             req_id
         );
         // Evaluate the `seal_approve*` function
-        let tx_data = self
-            .sui_client
-            .transaction_builder()
-            .tx_data_for_dry_run(
-                sender,
-                TransactionKind::ProgrammableTransaction(vptb.ptb().clone()),
-                GAS_BUDGET,
-                gas_price,
-                None,
-                None,
-            )
-            .await;
-        let dry_run_res = self
-            .sui_client
-            .read_api()
-            .dry_run_transaction_block(tx_data)
+        let allowed = self
+            .chain
+            .dry_run(sender, vptb.ptb(), self.options.gas_budget, gas_price)
             .await
             .map_err(|e| {
                 warn!("Dry run execution failed ({:?}) (req_id: {:?})", e, req_id);
                 InternalError::Failure
             })?;
-        debug!("Dry run response: {:?} (req_id: {:?})", dry_run_res, req_id);
-        if dry_run_res.effects.status().is_err() {
+        if !allowed {
             debug!("Dry run execution asserted (req_id: {:?})", req_id);
             // TODO: Should we return a different error per status, e.g., InsufficientGas?
             return Err(InternalError::NoAccess);
@@ -258,6 +303,7 @@ impl Server {
     #[allow(clippy::too_many_arguments)]
     async fn check_request(
         &self,
+        key_server_object_id: Option<&ObjectID>,
         ptb_str: &str,
         enc_key: &ElGamalPublicKey,
         enc_verification_key: &ElgamalVerificationKey,
@@ -271,6 +317,7 @@ impl Server {
             "Checking request for ptb_str: {:?}, cert {:?} (req_id: {:?})",
             ptb_str, certificate, req_id
         );
+        let identity = self.identities.get(key_server_object_id)?;
         let ptb_b64 = Base64::decode(ptb_str).map_err(|_| InternalError::InvalidPTB)?;
         let ptb: ProgrammableTransaction =
             bcs::from_bytes(&ptb_b64).map_err(|_| InternalError::InvalidPTB)?;
@@ -285,7 +332,7 @@ impl Server {
         // Handle package upgrades: only call the latest version but use the first as the namespace
         let (first_pkg_id, last_pkg_id) =
             call_with_duration(metrics.map(|m| &m.fetch_pkg_ids_duration), || async {
-                externals::fetch_first_and_last_pkg_id(&valid_ptb.pkg_id(), &self.network).await
+                self.chain.first_and_last_pkg_id(&valid_ptb.pkg_id()).await
             })
             .await?;
 
@@ -298,6 +345,7 @@ impl Server {
             );
             return Err(InternalError::OldPackageVersion);
         }
+        identity.check_package(&first_pkg_id, req_id)?;
 
         // Check all conditions
         self.check_signature(
@@ -311,11 +359,53 @@ impl Server {
         )
         .await?;
 
-        call_with_duration(metrics.map(|m| &m.check_policy_duration), || async {
-            self.check_policy(certificate.user, &valid_ptb, gas_price, req_id)
-                .await
-        })
-        .await?;
+        // Only now is the user known to have signed the request, so others cannot use up its limit.
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter
+                .check_user(certificate.user, Instant::now())
+                .map_err(|throttled| throttled_error(throttled, metrics))?;
+        }
+
+        // Reuse a result for the same sender and PTB evaluated against a recent checkpoint if the policy cache is
+        // enabled. Nothing is cached before the first checkpoint timestamp is known.
+        let checkpoint_timestamp = self.latest_value(CHECKPOINT_TIMESTAMP);
+        let cache_key = self
+            .policy_cache
+            .as_ref()
+            .zip(checkpoint_timestamp)
+            .and_then(|(cache, timestamp)| {
+                cache.key(&first_pkg_id, certificate.user, &ptb_b64, timestamp)
+            });
+        let cached = cache_key.and_then(|key| self.policy_cache.as_ref()?.get(&key));
+        if let (Some(m), Some(_)) = (metrics, cache_key) {
+            m.policy_cache_lookups
+                .with_label_values(&[if cached.is_some() { "hit" } else { "miss" }])
+                .inc();
+        }
+        match cached {
+            Some(true) => debug!("Policy allowed by cached result (req_id: {:?})", req_id),
+            Some(false) => {
+                debug!("Policy denied by cached result (req_id: {:?})", req_id);
+                return Err(InternalError::NoAccess);
+            }
+            None => {
+                let result =
+                    call_with_duration(metrics.map(|m| &m.check_policy_duration), || async {
+                        self.check_policy(certificate.user, &valid_ptb, gas_price, req_id)
+                            .await
+                    })
+                    .await;
+                // Only definite results are cached, not failures to evaluate the policy.
+                if let (Some(cache), Some(key)) = (&self.policy_cache, cache_key) {
+                    match result {
+                        Ok(()) => cache.insert(key, true),
+                        Err(InternalError::NoAccess) => cache.insert(key, false),
+                        Err(_) => {}
+                    }
+                }
+                result?;
+            }
+        }
 
         info!(
             "Valid request: {}",
@@ -326,22 +416,19 @@ impl Server {
         Ok(valid_ptb.full_ids(&first_pkg_id))
     }
 
-    fn create_response(&self, ids: &[KeyId], enc_key: &ElGamalPublicKey) -> FetchKeyResponse {
-        debug!("Checking response for ids: {:?}", ids);
-        let decryption_keys = ids
-            .iter()
-            .map(|id| {
-                // Requested key
-                let key = ibe::extract(&self.master_key, id);
-                // ElGamal encryption of key under the user's public key
-                let encrypted_key = encrypt(&mut thread_rng(), &key, enc_key);
-                DecryptionKey {
-                    id: id.to_owned(),
-                    encrypted_key,
-                }
-            })
-            .collect();
-        FetchKeyResponse { decryption_keys }
+    fn create_response(
+        &self,
+        key_server_object_id: Option<&ObjectID>,
+        ids: &[KeyId],
+        enc_key: &ElGamalPublicKey,
+        key_version: Option<u32>,
+    ) -> Result<FetchKeyResponse, InternalError> {
+        self.identities.get(key_server_object_id)?.create_response(
+            ids,
+            enc_key,
+            key_version,
+            self.clock.now(),
+        )
     }
 
     /// Helper function to spawn a thread that periodically fetches a value and sends it to a [Receiver].
@@ -358,14 +445,16 @@ impl Server {
         success_callback: Option<I>,
     ) -> Receiver<u64>
     where
-        F: Fn(SuiClient) -> Fut + Send + 'static,
-        Fut: Future<Output = SuiRpcResult<u64>> + Send,
+        F: Fn(Arc<dyn ChainBackend>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<u64>> + Send,
         G: Fn(u64) + Send + 'static,
         H: Fn(Duration) + Send + 'static,
         I: Fn(bool) + Send + 'static,
     {
         let (sender, mut receiver) = channel(0);
-        let local_client = self.sui_client.clone();
+        let chain = self.chain.clone();
+        let clock = self.clock.clone();
+        let last_updates = self.last_updates.clone();
         let mut interval = tokio::time::interval(update_interval);
 
         // In case of a missed tick due to a slow responding full node, we don't need to
@@ -375,7 +464,7 @@ impl Server {
         tokio::task::spawn(async move {
             loop {
                 let now = Instant::now();
-                let result = fetch_fn(local_client.clone()).await;
+                let result = fetch_fn(chain.clone()).await;
                 if let Some(dcb) = &duration_callback {
                     dcb(now.elapsed());
                 }
@@ -384,6 +473,10 @@ impl Server {
                 }
                 match result {
                     Ok(new_value) => {
+                        // Recorded before sending, so receivers woken by the update observe it in the server too.
+                        last_updates
+                            .lock()
+                            .insert(value_name, (new_value, clock.now()));
                         sender
                             .send(new_value)
                             .expect("Channel closed, this should never happen");
@@ -415,13 +508,14 @@ impl Server {
         update_interval: Duration,
         metrics: Option<&Metrics>,
     ) -> Receiver<Timestamp> {
+        let clock = self.clock.clone();
         self.spawn_periodic_updater(
             update_interval,
-            get_latest_checkpoint_timestamp,
-            "latest checkpoint timestamp",
+            |chain| async move { chain.latest_checkpoint_timestamp().await },
+            CHECKPOINT_TIMESTAMP,
             metrics.map(|m| {
-                observation_callback(&m.checkpoint_timestamp_delay, |ts| {
-                    duration_since(ts) as f64
+                observation_callback(&m.checkpoint_timestamp_delay, move |ts| {
+                    clock.now() as f64 - ts as f64
                 })
             }),
             metrics.map(|m| {
@@ -443,8 +537,8 @@ impl Server {
     ) -> Receiver<u64> {
         self.spawn_periodic_updater(
             update_interval,
-            get_reference_gas_price,
-            "RGP",
+            |chain| async move { chain.reference_gas_price().await },
+            REFERENCE_GAS_PRICE,
             None::<fn(u64)>,
             None::<fn(Duration)>,
             metrics.map(|m| status_callback(&m.get_reference_gas_price_status)),
@@ -455,8 +549,36 @@ impl Server {
 
 async fn handle_fetch_key(
     State(app_state): State<MyState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
     headers: HeaderMap,
     Json(payload): Json<FetchKeyRequest>,
+) -> Result<Json<FetchKeyResponse>, InternalError> {
+    fetch_key(app_state, remote_address, headers, payload, None).await
+}
+
+async fn handle_fetch_key_for(
+    State(app_state): State<MyState>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(key_server_object_id): Path<ObjectID>,
+    headers: HeaderMap,
+    Json(payload): Json<FetchKeyRequest>,
+) -> Result<Json<FetchKeyResponse>, InternalError> {
+    fetch_key(
+        app_state,
+        remote_address,
+        headers,
+        payload,
+        Some(key_server_object_id),
+    )
+    .await
+}
+
+async fn fetch_key(
+    app_state: MyState,
+    remote_address: SocketAddr,
+    headers: HeaderMap,
+    payload: FetchKeyRequest,
+    path_key_server_object_id: Option<ObjectID>,
 ) -> Result<Json<FetchKeyResponse>, InternalError> {
     let req_id = headers
         .get("Request-Id")
@@ -470,56 +592,230 @@ async fn handle_fetch_key(
     );
 
     app_state.metrics.requests.inc();
-    app_state.check_full_node_is_fresh(ALLOWED_STALENESS)?;
-
     app_state
-        .server
-        .check_request(
-            &payload.ptb,
-            &payload.enc_key,
-            &payload.enc_verification_key,
-            &payload.request_signature,
-            &payload.certificate,
-            app_state.reference_gas_price(),
-            Some(&app_state.metrics),
-            req_id,
+        .check_rate_limits(&payload, remote_address.ip(), &headers)
+        .tap_err(|e| app_state.metrics.observe_error(e.as_str()))?;
+
+    // The key server may be given in the path, in the body, or in both if they agree.
+    let key_server_object_id = match (path_key_server_object_id, payload.key_server_object_id) {
+        (Some(path_id), Some(body_id)) if path_id != body_id => {
+            return Err(InternalError::UnknownKeyServer)
+        }
+        (path_id, body_id) => path_id.or(body_id),
+    };
+    let network = app_state
+        .network(key_server_object_id.as_ref())
+        .tap_err(|e| app_state.metrics.observe_error(e.as_str()))?;
+    network.check_full_node_is_fresh(network.server.options.allowed_staleness)?;
+
+    let result = async {
+        let full_id = network
+            .server
+            .check_request(
+                key_server_object_id.as_ref(),
+                &payload.ptb,
+                &payload.enc_key,
+                &payload.enc_verification_key,
+                &payload.request_signature,
+                &payload.certificate,
+                network.reference_gas_price(),
+                Some(&app_state.metrics),
+                req_id,
+            )
+            .await?;
+        let response = create_response_blocking(
+            network.server.clone(),
+            key_server_object_id,
+            full_id.clone(),
+            payload.enc_key.clone(),
+            payload.key_version,
         )
-        .await
-        .map(|full_id| Json(app_state.server.create_response(&full_id, &payload.enc_key)))
+        .await?;
+        app_state
+            .audit_release(key_server_object_id.as_ref(), &payload, &full_id, req_id)
+            .await?;
+        Ok(response)
+    }
+    .await;
+    result
+        .map(Json)
         .tap_err(|e| app_state.metrics.observe_error(e.as_str()))
 }
 
+/// Create the response on the blocking thread pool, since the master key provider may be an external signer which is
+/// called using blocking I/O.
+async fn create_response_blocking(
+    server: Arc<Server>,
+    key_server_object_id: Option<ObjectID>,
+    ids: Vec<KeyId>,
+    enc_key: ElGamalPublicKey,
+    key_version: Option<u32>,
+) -> Result<FetchKeyResponse, InternalError> {
+    tokio::task::spawn_blocking(move || {
+        server.create_response(key_server_object_id.as_ref(), &ids, &enc_key, key_version)
+    })
+    .await
+    .map_err(|_| InternalError::Failure)?
+}
+
 #[derive(Serialize, Deserialize)]
 struct GetServiceResponse {
     service_id: ObjectID,
     pop: MasterKeyPOP,
+    key_version: u32,
+    public_key: IbePublicKey,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    retired_keys: Vec<RetiredKeyResponse>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RetiredKeyResponse {
+    version: u32,
+    public_key: IbePublicKey,
+    pop: MasterKeyPOP,
+    retired_until: u64,
 }
 
 async fn handle_get_service(
     State(app_state): State<MyState>,
+) -> Result<Json<GetServiceResponse>, InternalError> {
+    get_service(app_state, None)
+}
+
+async fn handle_get_service_for(
+    State(app_state): State<MyState>,
+    Path(key_server_object_id): Path<ObjectID>,
+) -> Result<Json<GetServiceResponse>, InternalError> {
+    get_service(app_state, Some(key_server_object_id))
+}
+
+fn get_service(
+    app_state: MyState,
+    key_server_object_id: Option<ObjectID>,
 ) -> Result<Json<GetServiceResponse>, InternalError> {
     app_state.metrics.service_requests.inc();
+    let identity = app_state
+        .network(key_server_object_id.as_ref())?
+        .server
+        .identities
+        .get(key_server_object_id.as_ref())?;
     Ok(Json(GetServiceResponse {
-        service_id: app_state.server.key_server_object_id,
-        pop: app_state.server.key_server_object_id_sig,
+        service_id: identity.key_server_object_id,
+        pop: identity.key_server_object_id_sig,
+        key_version: identity.key_version,
+        public_key: identity.master_key.public_key(),
+        retired_keys: identity
+            .retired_keys
+            .iter()
+            .map(|key| RetiredKeyResponse {
+                version: key.version,
+                public_key: key.master_key.public_key(),
+                pop: key.key_server_object_id_sig,
+                retired_until: key.retired_until,
+            })
+            .collect(),
     }))
 }
 
 #[derive(Clone)]
 struct MyState {
     metrics: Arc<Metrics>,
+    clock: Arc<dyn Clock>,
+    /// The networks of the hosted identities, each with the server of the identities using it.
+    networks: Vec<NetworkState>,
+    rate_limiter: Arc<RateLimiter>,
+    trust_forwarded_for: bool,
+    audit_log: Option<Arc<AuditLog>>,
+    /// The result of the latest self-test of the master keys, if any has run.
+    self_test: Arc<Mutex<Option<SelfTestResult>>>,
+    max_rgp_age: Duration,
+}
+
+/// The IP of the client. If `trust_forwarded_for` is set, this is the last address of the `X-Forwarded-For` header,
+/// falling back to the address of the connection if the header is missing or invalid.
+fn client_ip(remote_ip: IpAddr, headers: &HeaderMap, trust_forwarded_for: bool) -> IpAddr {
+    let forwarded_for = || {
+        headers
+            .get("X-Forwarded-For")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+    };
+    match trust_forwarded_for {
+        true => forwarded_for().unwrap_or(remote_ip),
+        false => remote_ip,
+    }
+}
+
+/// Log and count a throttled request.
+fn throttled_error(throttled: Throttled, metrics: Option<&Metrics>) -> InternalError {
+    debug!(
+        "Request throttled by {} limit, retry after {:?}",
+        throttled.limit, throttled.retry_after
+    );
+    if let Some(m) = metrics {
+        m.throttled_requests
+            .with_label_values(&[throttled.limit])
+            .inc();
+    }
+    InternalError::TooManyRequests {
+        retry_after_secs: throttled.retry_after.as_secs_f64().ceil() as u64,
+    }
+}
+
+/// The package called by a PTB, if it is valid.
+fn ptb_package_id(ptb: &str) -> Option<ObjectID> {
+    Base64::decode(ptb)
+        .ok()
+        .and_then(|ptb| bcs::from_bytes::<ProgrammableTransaction>(&ptb).ok())
+        .and_then(|ptb| ValidPtb::try_from(ptb).ok())
+        .map(|ptb| ptb.pkg_id())
+}
+
+/// A server for the identities on one network, and the chain state of the network.
+#[derive(Clone)]
+struct NetworkState {
+    /// The name of the network, e.g. "mainnet".
+    name: &'static str,
     server: Arc<Server>,
     latest_checkpoint_timestamp_receiver: Receiver<Timestamp>,
     reference_gas_price: Receiver<u64>,
 }
 
-impl MyState {
+impl NetworkState {
+    /// Spawn the tasks which update the chain state of the network used by the given server.
+    async fn spawn(
+        name: &'static str,
+        server: Arc<Server>,
+        checkpoint_update_interval: Duration,
+        rgp_update_interval: Duration,
+        metrics: Option<&Metrics>,
+    ) -> Self {
+        let latest_checkpoint_timestamp_receiver = server
+            .spawn_latest_checkpoint_timestamp_updater(checkpoint_update_interval, metrics)
+            .await;
+        let reference_gas_price = server
+            .spawn_reference_gas_price_updater(rgp_update_interval, metrics)
+            .await;
+        NetworkState {
+            name,
+            server,
+            latest_checkpoint_timestamp_receiver,
+            reference_gas_price,
+        }
+    }
+
+    /// Time since the latest checkpoint seen by the server, in milliseconds.
+    fn checkpoint_lag(&self) -> i64 {
+        self.server.clock.now() as i64 - *self.latest_checkpoint_timestamp_receiver.borrow() as i64
+    }
+
     fn check_full_node_is_fresh(&self, allowed_staleness: Duration) -> Result<(), InternalError> {
-        let staleness = duration_since(*self.latest_checkpoint_timestamp_receiver.borrow());
+        let staleness = self.checkpoint_lag();
         if staleness > allowed_staleness.as_millis() as i64 {
             warn!(
-                "Full node is stale. Latest checkpoint is {} ms old.",
-                staleness
+                "Full node of {} is stale. Latest checkpoint is {} ms old.",
+                self.name, staleness
             );
             return Err(InternalError::Failure);
         }
@@ -529,17 +825,290 @@ impl MyState {
     fn reference_gas_price(&self) -> u64 {
         *self.reference_gas_price.borrow()
     }
+
+    /// The status of the network, adding why it is not ready to `problems`.
+    fn status(&self, max_rgp_age: Duration, problems: &mut Vec<String>) -> NetworkStatus {
+        let now = self.server.clock.now();
+
+        let checkpoint_lag = self.checkpoint_lag();
+        if checkpoint_lag > self.server.options.allowed_staleness.as_millis() as i64 {
+            problems.push(format!(
+                "Latest checkpoint of {} is {} ms old",
+                self.name, checkpoint_lag
+            ));
+        }
+
+        let reference_gas_price_age = self
+            .server
+            .last_update(REFERENCE_GAS_PRICE)
+            .map(|updated| now.saturating_sub(updated));
+        match reference_gas_price_age {
+            Some(age) if age <= max_rgp_age.as_millis() as u64 => {}
+            Some(age) => problems.push(format!(
+                "Reference gas price of {} is {} ms old",
+                self.name, age
+            )),
+            None => problems.push(format!(
+                "Reference gas price of {} has not been fetched",
+                self.name
+            )),
+        }
+
+        let endpoints = self.server.chain.endpoints_status();
+        NetworkStatus {
+            network: self.name,
+            checkpoint_timestamp: *self.latest_checkpoint_timestamp_receiver.borrow(),
+            checkpoint_lag_ms: checkpoint_lag,
+            reference_gas_price: self.reference_gas_price(),
+            reference_gas_price_age_ms: reference_gas_price_age,
+            graphql_reachable: endpoints.graphql.iter().any(|endpoint| endpoint.healthy),
+            endpoints,
+            package_cache: self.server.chain.package_cache_status(),
+            policy_cache: self.server.policy_cache.as_ref().map(PolicyCache::status),
+            identities: self
+                .server
+                .identities
+                .iter()
+                .map(IdentityStatus::from)
+                .collect(),
+        }
+    }
+}
+
+impl MyState {
+    /// The network of the identity with the given key server object id. If no id is given, the server must use a
+    /// single network, whose server then selects the identity.
+    fn network(
+        &self,
+        key_server_object_id: Option<&ObjectID>,
+    ) -> Result<&NetworkState, InternalError> {
+        match key_server_object_id {
+            Some(id) => self
+                .networks
+                .iter()
+                .find(|network| network.server.identities.get(Some(id)).is_ok()),
+            None if self.networks.len() == 1 => self.networks.first(),
+            None => None,
+        }
+        .ok_or(InternalError::UnknownKeyServer)
+    }
+
+    /// Check the rate limits of the client IP and the package of the PTB. Requests with an invalid PTB are only
+    /// limited by IP, and are rejected later. The user limit is checked once the signature is verified, see
+    /// [Server::check_request].
+    fn check_rate_limits(
+        &self,
+        payload: &FetchKeyRequest,
+        remote_ip: IpAddr,
+        headers: &HeaderMap,
+    ) -> Result<(), InternalError> {
+        let ip = client_ip(remote_ip, headers, self.trust_forwarded_for);
+        let package_id = ptb_package_id(&payload.ptb);
+        self.rate_limiter
+            .check(package_id, ip, Instant::now())
+            .map_err(|throttled| throttled_error(throttled, Some(&self.metrics)))
+    }
+
+    /// Self-test the master keys and keep the result for the status endpoints.
+    fn run_self_test(&self) -> SelfTestResult {
+        let identities = self
+            .networks
+            .iter()
+            .flat_map(|network| network.server.identities.iter());
+        let result = status::self_test(identities, self.clock.now());
+        if let Some(error) = &result.error {
+            warn!("{}", error);
+        }
+        status_callback(&self.metrics.self_test_status)(result.passed);
+        *self.self_test.lock() = Some(result.clone());
+        result
+    }
+
+    fn status(&self) -> Status {
+        let mut problems = vec![];
+        let networks = self
+            .networks
+            .iter()
+            .map(|network| network.status(self.max_rgp_age, &mut problems))
+            .collect();
+
+        let self_test = self.self_test.lock().clone();
+        match &self_test {
+            Some(result) if result.passed => {}
+            Some(result) => problems.push(result.error.clone().unwrap_or_default()),
+            None => problems.push("The master keys have not been self-tested yet".to_string()),
+        }
+
+        Status {
+            version: PACKAGE_VERSION,
+            problems,
+            networks,
+            self_test,
+        }
+    }
+
+    /// Record released keys in the audit log, if enabled. The keys must not be returned if this fails.
+    async fn audit_release(
+        &self,
+        key_server_object_id: Option<&ObjectID>,
+        payload: &FetchKeyRequest,
+        ids: &[KeyId],
+        req_id: Option<&str>,
+    ) -> Result<(), InternalError> {
+        let Some(audit_log) = &self.audit_log else {
+            return Ok(());
+        };
+        let identity = self
+            .network(key_server_object_id)?
+            .server
+            .identities
+            .get(key_server_object_id)?;
+        audit_log
+            .record(AuditEvent::Release {
+                key_server_object_id: identity.key_server_object_id,
+                key_version: payload.key_version,
+                user: payload.certificate.user,
+                package_id: ptb_package_id(&payload.ptb).ok_or(InternalError::InvalidPTB)?,
+                ids: ids.iter().map(hex::encode).collect(),
+                session_vk: payload.certificate.session_vk.clone(),
+                req_id: req_id.map(String::from),
+            })
+            .await
+            .map_err(|e| {
+                warn!("Failed to write to the audit log: {:?}", e);
+                InternalError::Failure
+            })
+    }
+}
+
+async fn handle_status(State(app_state): State<MyState>) -> Json<Status> {
+    Json(app_state.status())
+}
+
+/// Ready if the chain state is fresh and the master keys passed the latest self-test. The reasons are only reported by
+/// the status endpoint.
+async fn handle_ready(State(app_state): State<MyState>) -> StatusCode {
+    match app_state.status().problems.is_empty() {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Live unless the master keys failed the latest self-test, since restarting does not help with stale chain state.
+async fn handle_live(State(app_state): State<MyState>) -> StatusCode {
+    match app_state.self_test.lock().as_ref() {
+        Some(result) if !result.passed => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "key-server")]
+struct Arguments {
+    /// Path to a YAML or TOML config file. Defaults to the CONFIG_PATH environment variable, if set.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check the configuration and the master key, report all problems found and exit.
+    CheckConfig,
+    /// Encrypt a master key under a passphrase and write it to a new keystore file, readable only by the owner. The
+    /// base64 encoded master key and the passphrase are read from the first and second line of stdin.
+    CreateKeystore {
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Inspect an audit log of key releases.
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommand {
+    /// Verify the hash chain and the checkpoint signatures of an audit log, detecting modified, removed or
+    /// reordered entries.
+    Verify {
+        #[arg(long)]
+        log: PathBuf,
+        /// Public key checkpoints must be signed with, as logged on startup. May be given several times, e.g. for
+        /// the versions of a rotated key.
+        #[arg(long, value_parser = parse_public_key, required = true)]
+        public_key: Vec<IbePublicKey>,
+        /// An entry the log must contain, given as <SEQ>:<HASH>, e.g. a checkpoint from the server log. This
+        /// detects entries removed from the end of the log, which are not detected otherwise.
+        #[arg(long, value_parser = parse_head)]
+        head: Option<(u64, String)>,
+    },
+    /// Export the key releases in a time range as JSON lines, after verifying the hash chain of the log.
+    Export {
+        #[arg(long)]
+        log: PathBuf,
+        /// Start of the range (inclusive) as an RFC 3339 timestamp. Defaults to the start of the log.
+        #[arg(long, value_parser = parse_timestamp)]
+        from: Option<u64>,
+        /// End of the range (exclusive) as an RFC 3339 timestamp. Defaults to the end of the log.
+        #[arg(long, value_parser = parse_timestamp)]
+        to: Option<u64>,
+        /// Write to this file instead of stdout.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let master_key = env::var("MASTER_KEY").expect("MASTER_KEY must be set");
-    let object_id = env::var("KEY_SERVER_OBJECT_ID").expect("KEY_SERVER_OBJECT_ID must be set");
-    let network = env::var("NETWORK")
-        .map(|n| Network::from_str(&n))
-        .unwrap_or(Network::Testnet);
+    let args = Arguments::parse();
+    match &args.command {
+        Some(Command::CreateKeystore { output }) => return create_keystore(output),
+        Some(Command::Audit { command }) => return audit(command),
+        _ => {}
+    }
 
-    let _guard = mysten_service::logging::init();
+    let config_path = args
+        .config
+        .or_else(|| env::var("CONFIG_PATH").ok().map(PathBuf::from));
+    let loaded = ServerConfig::load(config_path.as_deref()).and_then(|config| {
+        let mut problems = vec![];
+        let identities: Vec<KeyIdentity> = config
+            .identities
+            .iter()
+            .filter_map(|identity| identity.load().map_err(|e| problems.push(e)).ok())
+            .collect();
+        match problems.is_empty() {
+            true => Ok((config, identities)),
+            false => Err(problems),
+        }
+    });
+    let (config, identities) = match loaded {
+        Ok(loaded) => loaded,
+        Err(problems) => {
+            for problem in problems {
+                eprintln!("{}", problem);
+            }
+            bail!("Invalid configuration");
+        }
+    };
+    if let Some(Command::CheckConfig) = args.command {
+        println!("Configuration is valid:\n{:#?}", config);
+        return Ok(());
+    }
+
+    // The configured filter is used directly since the environment must not be modified once the runtime is running.
+    let _guard = match &config.log_level {
+        Some(level) => {
+            tracing_subscriber::fmt()
+                .with_env_filter(EnvFilter::try_new(level)?)
+                .init();
+            None
+        }
+        None => Some(mysten_service::logging::init()),
+    };
     info!("Logging set up, setting up metrics");
 
     // initialize metrics
@@ -550,45 +1119,315 @@ async fn main() -> Result<()> {
 
     info!("Starting server, version {}", PACKAGE_VERSION);
 
-    let s = Server::new(
-        IbeMasterKey::from_byte_array(
-            &Base64::decode(&master_key)
-                .expect("MASTER_KEY should be base64 encoded")
-                .try_into()
-                .expect("Invalid MASTER_KEY length"),
-        )
-        .expect("Invalid MASTER_KEY value"),
-        network,
-        ObjectID::from_hex_literal(&object_id).expect("Invalid KEY_SERVER_OBJECT_ID"),
-    )
-    .await;
-    let server = Arc::new(s);
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let rate_limiter = Arc::new(RateLimiter::new(&config.rate_limits));
 
-    // Spawn tasks that update the state of the server.
-    let latest_checkpoint_timestamp_receiver = server
-        .spawn_latest_checkpoint_timestamp_updater(CHECKPOINT_UPDATE_INTERVAL, Some(&metrics))
-        .await;
-    let reference_gas_price = server
-        .spawn_reference_gas_price_updater(RGP_UPDATE_INTERVAL, Some(&metrics))
-        .await;
+    // Each network has its own server for the identities using it. The identities are loaded in config order.
+    let mut identities: Vec<_> = config
+        .identities
+        .iter()
+        .map(|identity| &identity.network)
+        .zip(identities)
+        .collect();
+    let mut networks = vec![];
+    for network in config.networks() {
+        info!(
+            "Using network: {:?}, full nodes: {:?}, GraphQL endpoints: {:?}",
+            network.network,
+            network.node_urls(),
+            network.graphql_urls()
+        );
+        let chain = Arc::new(SuiChain::new(
+            network.node_urls(),
+            network.graphql_urls(),
+            network.package_resolver,
+            Cache::new(config.cache_ttl, config.cache_size, clock.clone()),
+            config.options.allowed_staleness,
+            clock.clone(),
+            Some(metrics.clone()),
+        ));
+        let (on_network, others): (Vec<_>, Vec<_>) = identities
+            .into_iter()
+            .partition(|(identity_network, _)| *identity_network == network);
+        identities = others;
+        let server = Arc::new(
+            Server::new(
+                on_network
+                    .into_iter()
+                    .map(|(_, identity)| identity)
+                    .collect(),
+                chain,
+                clock.clone(),
+                config.options.clone(),
+            )
+            .with_rate_limiter(rate_limiter.clone()),
+        );
+        // Spawn tasks that update the state of the network.
+        networks.push(
+            NetworkState::spawn(
+                network.network.name(),
+                server,
+                config.checkpoint_update_interval,
+                config.rgp_update_interval,
+                Some(&metrics),
+            )
+            .await,
+        );
+    }
 
+    let audit_log = match &config.audit_log {
+        Some(audit_config) => Some(Arc::new(AuditLog::open(&audit_config.path, clock.clone())?)),
+        None => None,
+    };
     let state = MyState {
         metrics,
-        server,
-        latest_checkpoint_timestamp_receiver,
-        reference_gas_price,
+        clock,
+        networks,
+        rate_limiter,
+        trust_forwarded_for: config.rate_limits.trust_forwarded_for,
+        audit_log: audit_log.clone(),
+        self_test: Default::default(),
+        max_rgp_age: config.rgp_update_interval * MAX_MISSED_RGP_UPDATES,
+    };
+    let audit_checkpoints = match (&config.audit_log, audit_log) {
+        (Some(audit_config), Some(audit_log)) => {
+            let server = state
+                .network(Some(&audit_config.key_server_object_id))
+                .expect("checked in the config")
+                .server
+                .clone();
+            let (stop, stopped) = oneshot::channel();
+            let task = spawn_audit_checkpoints(
+                audit_log,
+                server,
+                audit_config.key_server_object_id,
+                audit_config.checkpoint_interval,
+                stopped,
+            );
+            Some((stop, task))
+        }
+        _ => None,
     };
+    let (stop_self_test, self_test_stopped) = oneshot::channel();
+    let self_test = spawn_self_test(state.clone(), SELF_TEST_INTERVAL, self_test_stopped);
 
+    let allowed_origins = match config.cors_allowed_origins {
+        Some(origins) => AllowOrigin::list(origins),
+        None => AllowOrigin::any(),
+    };
     let cors = CorsLayer::new()
         .allow_methods(Any)
-        .allow_origin(Any)
+        .allow_origin(allowed_origins)
         .allow_headers(Any);
 
     let app = get_mysten_service(package_name!(), package_version!())
         .route("/v1/fetch_key", post(handle_fetch_key))
+        .route(
+            "/v1/fetch_key/:key_server_object_id",
+            post(handle_fetch_key_for),
+        )
         .route("/v1/service", get(handle_get_service))
+        .route(
+            "/v1/service/:key_server_object_id",
+            get(handle_get_service_for),
+        )
+        .route("/v1/status", get(handle_status))
+        .route("/v1/ready", get(handle_ready))
+        .route("/v1/live", get(handle_live))
         .with_state(state)
         .layer(cors);
 
-    serve(app).await
+    let listener = TcpListener::bind(config.listen_address).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+
+    let _ = stop_self_test.send(());
+    self_test.await?;
+
+    // Checkpoint the entries since the last checkpoint before stopping.
+    if let Some((stop, task)) = audit_checkpoints {
+        let _ = stop.send(());
+        task.await?;
+    }
+
+    info!("Server stopped");
+    Ok(())
+}
+
+fn create_keystore(output: &std::path::Path) -> Result<()> {
+    let mut lines = std::io::stdin().lines();
+    let mut next_line = || -> Result<Zeroizing<String>> {
+        Ok(Zeroizing::new(
+            lines.next().ok_or(anyhow!("Unexpected end of input"))??,
+        ))
+    };
+    let master_key = parse_master_key(next_line()?.trim()).map_err(|e| anyhow!(e))?;
+    // The line ending has already been removed, and any other whitespace is part of the passphrase.
+    let passphrase = next_line()?;
+    let keystore = encrypt_master_key(&master_key, passphrase.as_bytes(), &mut thread_rng())
+        .map_err(|e| anyhow!(e))?;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(output)
+        .and_then(|mut file| file.write_all(keystore.as_bytes()))
+        .map_err(|e| anyhow!("Cannot write keystore {}: {}", output.display(), e))?;
+    println!("Keystore written to {}", output.display());
+    Ok(())
+}
+
+/// Spawn a task which checkpoints the audit log with the master key of the given identity once per interval, and
+/// once more when `stop` resolves.
+fn spawn_audit_checkpoints(
+    audit_log: Arc<AuditLog>,
+    server: Arc<Server>,
+    key_server_object_id: ObjectID,
+    interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let key_version = server
+            .identities
+            .get(Some(&key_server_object_id))
+            .expect("checked in the config")
+            .key_version;
+        let mut ticks = tokio::time::interval(interval);
+        // The first tick completes immediately.
+        ticks.tick().await;
+        loop {
+            let stopping = tokio::select! {
+                _ = ticks.tick() => false,
+                _ = &mut stop => true,
+            };
+            // The checkpoint is signed by the writer thread of the audit log, since the master key provider may be an
+            // external signer which is called using blocking I/O.
+            let server = server.clone();
+            let sign = Box::new(move |message: &[u8]| {
+                server
+                    .identities
+                    .get(Some(&key_server_object_id))
+                    .expect("checked in the config")
+                    .master_key
+                    .create_proof_of_possession(message)
+            });
+            if let Err(e) = audit_log
+                .checkpoint(key_server_object_id, key_version, sign)
+                .await
+            {
+                warn!("Failed to checkpoint the audit log: {:?}", e);
+            }
+            if stopping {
+                break;
+            }
+        }
+    })
+}
+
+/// Spawn a task which self-tests the master keys once per interval until `stop` resolves.
+fn spawn_self_test(
+    state: MyState,
+    interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = &mut stop => break,
+            }
+            // Extracting keys may call an external signer using blocking I/O.
+            let state = state.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || state.run_self_test()).await {
+                warn!("Self-test of the master keys did not complete: {:?}", e);
+            }
+        }
+    })
+}
+
+fn audit(command: &AuditCommand) -> Result<()> {
+    let open = |log: &FilePath| -> Result<BufReader<File>> {
+        Ok(BufReader::new(File::open(log).map_err(|e| {
+            anyhow!("Cannot open audit log {}: {}", log.display(), e)
+        })?))
+    };
+    match command {
+        AuditCommand::Verify {
+            log,
+            public_key,
+            head,
+        } => {
+            if head.is_none() {
+                eprintln!(
+                    "Warning: no --head was given, so entries removed from the end of the log are not detected"
+                );
+            }
+            let report = audit_log::verify(
+                open(log)?,
+                public_key,
+                head.as_ref().map(|(seq, hash)| (*seq, hash.as_str())),
+            )?;
+            println!(
+                "Audit log is valid: {} entries, {} key releases, {} checkpoints",
+                report.entries, report.releases, report.checkpoints
+            );
+            if report.unsigned > 0 {
+                println!(
+                    "The last {} entries are not covered by a checkpoint",
+                    report.unsigned
+                );
+            }
+            if let Some(hash) = report.head {
+                println!("Head: {}:{}", report.entries - 1, hash);
+            }
+        }
+        AuditCommand::Export {
+            log,
+            from,
+            to,
+            output,
+        } => {
+            let (from, to) = (from.unwrap_or(0), to.unwrap_or(u64::MAX));
+            let exported = match output {
+                Some(output) => audit_log::export(open(log)?, from, to, File::create(output)?)?,
+                None => audit_log::export(open(log)?, from, to, std::io::stdout().lock())?,
+            };
+            eprintln!("Exported {} key releases", exported);
+        }
+    }
+    Ok(())
+}
+
+fn parse_public_key(public_key: &str) -> Result<IbePublicKey, String> {
+    Base64::decode(public_key)
+        .ok()
+        .and_then(|bytes| bcs::from_bytes(&bytes).ok())
+        .ok_or_else(|| "Expected a base64 encoded public key".to_string())
+}
+
+fn parse_head(head: &str) -> Result<(u64, String), String> {
+    head.split_once(':')
+        .and_then(|(seq, hash)| Some((seq.parse().ok()?, hash.to_string())))
+        .ok_or_else(|| "Expected <SEQ>:<HASH>".to_string())
+}
+
+fn parse_timestamp(timestamp: &str) -> Result<u64, String> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.timestamp_millis() as u64)
+        .map_err(|e| format!("Invalid timestamp {}: {}", timestamp, e))
+}
+
+/// Resolves when the process receives SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = sigterm.recv() => {},
+    }
+    info!("Shutdown signal received, stopping server");
 }
@@ -0,0 +1,196 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Status of the key server and its dependencies, as reported by the `/v1/status`, `/v1/ready` and `/v1/live`
+//! endpoints, and the self-test of the master keys.
+
+use crate::cache::CacheStatus;
+use crate::chain::EndpointsStatus;
+use crate::identity::KeyIdentity;
+use crate::types::ElgamalVerificationKey;
+use crate::Timestamp;
+use anyhow::{anyhow, ensure};
+use crypto::{create_full_id, elgamal, ibe};
+use fastcrypto::encoding::{Base64, Encoding};
+use fastcrypto::hash::{Blake2b256, HashFunction};
+use rand::thread_rng;
+use serde::Serialize;
+use sui_sdk::types::base_types::ObjectID;
+
+/// Domain separation tag from which the package id of the synthetic id extracted by the self-test is derived.
+const SELF_TEST_PACKAGE_DST: &[u8] = b"SEAL-KEY-SERVER-SELF-TEST-PACKAGE";
+const SELF_TEST_ID: &[u8] = b"key-server-self-test";
+
+/// The package id of the synthetic id extracted by the self-test. Ids of packages are hashes prefixed by a hashing
+/// intent byte, which this hash of [SELF_TEST_PACKAGE_DST] does not share, so no package has this id and the extracted
+/// key does not decrypt anything.
+fn self_test_package_id() -> [u8; 32] {
+    Blake2b256::digest(SELF_TEST_PACKAGE_DST).digest
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Status {
+    pub version: &'static str,
+    /// Why the server is not ready to serve requests. Empty if it is ready.
+    pub problems: Vec<String>,
+    /// The networks of the hosted identities.
+    pub networks: Vec<NetworkStatus>,
+    pub self_test: Option<SelfTestResult>,
+}
+
+/// The status of a network and of the identities using it.
+#[derive(Debug, Serialize)]
+pub(crate) struct NetworkStatus {
+    /// The name of the network, e.g. "mainnet".
+    pub network: &'static str,
+    pub checkpoint_timestamp: Timestamp,
+    /// Time since the latest checkpoint seen by the server.
+    pub checkpoint_lag_ms: i64,
+    pub reference_gas_price: u64,
+    /// Time since the reference gas price was last updated.
+    pub reference_gas_price_age_ms: Option<u64>,
+    /// Whether any GraphQL endpoint is healthy.
+    pub graphql_reachable: bool,
+    pub endpoints: EndpointsStatus,
+    pub package_cache: Option<CacheStatus>,
+    pub policy_cache: Option<CacheStatus>,
+    pub identities: Vec<IdentityStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct IdentityStatus {
+    pub key_server_object_id: ObjectID,
+    pub key_version: u32,
+    /// Base64 encoding of the BCS serialized public key of the current master key.
+    pub public_key: String,
+    pub retired_key_versions: Vec<u32>,
+}
+
+impl From<&KeyIdentity> for IdentityStatus {
+    fn from(identity: &KeyIdentity) -> Self {
+        IdentityStatus {
+            key_server_object_id: identity.key_server_object_id,
+            key_version: identity.key_version,
+            public_key: Base64::encode(
+                bcs::to_bytes(&identity.master_key.public_key()).expect("valid pk"),
+            ),
+            retired_key_versions: identity
+                .retired_keys
+                .iter()
+                .map(|key| key.version)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct SelfTestResult {
+    pub timestamp: Timestamp,
+    pub passed: bool,
+    /// Why the self-test failed for the first failing identity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Extract the key of a synthetic id with the current master key of every identity, encrypt it to a fresh ElGamal
+/// key as for a request, and check that the decrypted key is valid for the public key of the identity.
+pub(crate) fn self_test<'a>(
+    identities: impl IntoIterator<Item = &'a KeyIdentity>,
+    now: Timestamp,
+) -> SelfTestResult {
+    let error = identities.into_iter().find_map(|identity| {
+        test_identity(identity, now).err().map(|e| {
+            format!(
+                "Self-test of key server {} failed: {}",
+                identity.key_server_object_id, e
+            )
+        })
+    });
+    SelfTestResult {
+        timestamp: now,
+        passed: error.is_none(),
+        error,
+    }
+}
+
+fn test_identity(identity: &KeyIdentity, now: Timestamp) -> anyhow::Result<()> {
+    let id = create_full_id(&self_test_package_id(), SELF_TEST_ID);
+    let (sk, pk, _): (_, _, ElgamalVerificationKey) = elgamal::genkey(&mut thread_rng());
+    let response = identity
+        .create_response(&[id.clone()], &pk, None, now)
+        .map_err(|e| anyhow!("Key extraction failed: {:?}", e))?;
+    ensure!(
+        response.decryption_keys.len() == 1,
+        "Expected one key, got {}",
+        response.decryption_keys.len()
+    );
+    let key = elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key);
+    ibe::verify_user_secret_key(&key.into(), &id, &identity.master_key.public_key())
+        .map_err(|_| anyhow!("Extracted key does not match the public key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::master_key::{LockedMasterKey, MasterKeyProvider};
+    use crate::types::{IbePublicKey, MasterKeyPOP};
+    use crypto::ibe::UserSecretKey;
+
+    /// Extracts keys with one master key but reports the public key of another.
+    struct MismatchedMasterKey {
+        master_key: LockedMasterKey,
+        public_key: IbePublicKey,
+    }
+
+    impl MasterKeyProvider for MismatchedMasterKey {
+        fn public_key(&self) -> IbePublicKey {
+            self.public_key
+        }
+
+        fn extract(&self, ids: &[Vec<u8>]) -> anyhow::Result<Vec<UserSecretKey>> {
+            self.master_key.extract(ids)
+        }
+
+        fn create_proof_of_possession(&self, message: &[u8]) -> anyhow::Result<MasterKeyPOP> {
+            self.master_key.create_proof_of_possession(message)
+        }
+    }
+
+    #[test]
+    fn test_self_test() {
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let identity = KeyIdentity::new(
+            Box::new(LockedMasterKey::new(master_key)),
+            ObjectID::random(),
+            0,
+            None,
+        )
+        .unwrap();
+        let result = self_test([&identity], 1000);
+        assert_eq!(
+            result,
+            SelfTestResult {
+                timestamp: 1000,
+                passed: true,
+                error: None
+            }
+        );
+
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let (_, other_public_key) = ibe::generate_key_pair(&mut thread_rng());
+        let faulty = KeyIdentity::new(
+            Box::new(MismatchedMasterKey {
+                master_key: LockedMasterKey::new(master_key),
+                public_key: other_public_key,
+            }),
+            ObjectID::random(),
+            0,
+            None,
+        )
+        .unwrap();
+        let faulty_id = faulty.key_server_object_id;
+        let result = self_test([&faulty], 1000);
+        assert!(!result.passed);
+        assert!(result.error.unwrap().contains(&faulty_id.to_string()));
+    }
+}
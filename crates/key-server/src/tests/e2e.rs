@@ -2,31 +2,25 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::tests::externals::get_key;
-use crate::tests::whitelist::{add_user_to_whitelist, create_whitelist, whitelist_create_ptb};
-use crate::tests::SealTestCluster;
+use crate::tests::whitelist::{whitelist_create_ptb, whitelist_policy};
+use crate::tests::SealTestSetup;
 use crypto::{seal_decrypt, seal_encrypt, EncryptionInput, IBEPublicKeys, IBEUserSecretKeys};
+use sui_types::base_types::ObjectID;
 use tracing_test::traced_test;
 
 #[traced_test]
 #[tokio::test]
 async fn test_e2e() {
-    let mut tc = SealTestCluster::new(3, 1).await;
-    let (examples_package_id, _) = tc.publish("patterns").await;
+    let tc = SealTestSetup::new(3, 1);
+    let examples_package_id = tc.publish();
 
-    let (whitelist, cap) = create_whitelist(tc.get_mut(), examples_package_id).await;
-
-    // Create test users
-    let user_address = tc.users[0].address;
-    add_user_to_whitelist(
-        tc.get_mut(),
+    let whitelist = ObjectID::random();
+    tc.chain.set_policy(
         examples_package_id,
-        whitelist,
-        cap,
-        user_address,
-    )
-    .await;
+        whitelist_policy(whitelist, vec![tc.users[0].address]),
+    );
 
-    // We know the version at this point
+    // The mock chain does not check object versions
     let initial_shared_version = 3;
 
     // Get keys from two key servers
@@ -50,35 +44,21 @@ async fn test_e2e() {
     .await
     .unwrap();
 
-    // Register the three services on-chain
-    let (package_id, _) = tc.publish("seal").await;
+    // The key servers registered on-chain.
+    let services = tc.servers.iter().map(|s| s.object_id).collect::<Vec<_>>();
+    let pks =
+        IBEPublicKeys::BonehFranklinBLS12381(tc.servers.iter().map(|s| s.public_key).collect());
 
-    let mut services = vec![];
-    for i in 0..3 {
-        services.push(
-            tc.register_key_server(
-                package_id,
-                &format!("Test server {}", i),
-                &format!("https:://testserver{}.com", i),
-                tc.servers[i].public_key,
-            )
-            .await,
-        );
-    }
-
-    // Read the public keys from the service objects
-    let pks = tc.get_public_keys(&services).await;
-    assert_eq!(
-        pks,
-        tc.servers.iter().map(|s| s.public_key).collect::<Vec<_>>()
-    );
-    let pks = IBEPublicKeys::BonehFranklinBLS12381(pks);
+    // The crypto crate uses its own object id type
+    let services = services
+        .iter()
+        .map(|s| crypto::ObjectID::new(s.into_bytes()))
+        .collect::<Vec<_>>();
 
     // Encrypt a message
     let message = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.";
-    let services = services.to_vec();
     let encryption = seal_encrypt(
-        examples_package_id,
+        crypto::ObjectID::new(examples_package_id.into_bytes()),
         whitelist.to_vec(),
         services.clone(),
         &pks,
@@ -8,11 +8,11 @@ use crate::{
     types::{ElGamalPublicKey, ElgamalVerificationKey},
     Certificate, Server,
 };
-use crypto::elgamal;
+use crypto::{elgamal, ibe};
 use fastcrypto::ed25519::Ed25519Signature;
 use fastcrypto::encoding::{Base64, Encoding};
 use fastcrypto::traits::{KeyPair, Signer};
-use fastcrypto::{ed25519::Ed25519KeyPair, error::FastCryptoResult, groups::bls12381::G1Element};
+use fastcrypto::{ed25519::Ed25519KeyPair, error::FastCryptoResult};
 use rand::thread_rng;
 use shared_crypto::intent::{Intent, IntentMessage, PersonalMessage};
 use sui_types::{
@@ -60,11 +60,12 @@ pub(crate) async fn get_key(
     pkg_id: &ObjectID,
     ptb: ProgrammableTransaction,
     kp: &Ed25519KeyPair,
-) -> FastCryptoResult<G1Element> {
+) -> FastCryptoResult<ibe::UserSecretKey> {
     let (sk, pk, vk) = elgamal::genkey(&mut thread_rng());
     let (cert, req_sig) = sign(pkg_id, &ptb, &pk, &vk, kp, current_epoch_time(), 1);
     server
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -75,11 +76,7 @@ pub(crate) async fn get_key(
             None,
         )
         .await
-        .map(|ids| {
-            elgamal::decrypt(
-                &sk,
-                &server.create_response(&ids, &pk).decryption_keys[0].encrypted_key,
-            )
-        })
+        .and_then(|ids| server.create_response(None, &ids, &pk, None))
+        .map(|response| elgamal::decrypt(&sk, &response.decryption_keys[0].encrypted_key).into())
         .map_err(|_| fastcrypto::error::FastCryptoError::GeneralOpaqueError)
 }
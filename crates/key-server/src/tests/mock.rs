@@ -0,0 +1,445 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests of request handling against the in-memory chain, which need neither a test cluster nor network access.
+
+use super::externals::{ptb_to_base64, sign};
+use crate::chain::mock::MockChain;
+use crate::clock::{Clock, MockClock};
+use crate::config::{PolicyCacheConfig, RateLimit, RateLimitConfig, ServerOptions};
+use crate::identity::KeyIdentity;
+use crate::master_key::LockedMasterKey;
+use crate::metrics::Metrics;
+use crate::rate_limit::RateLimiter;
+use crate::{InternalError, KeyId, MyState, NetworkState, Server};
+use crypto::{elgamal, ibe};
+use fastcrypto::ed25519::Ed25519KeyPair;
+use fastcrypto::traits::KeyPair;
+use prometheus::Registry;
+use rand::thread_rng;
+use std::sync::Arc;
+use std::time::Duration;
+use sui_types::base_types::ObjectID;
+use sui_types::programmable_transaction_builder::ProgrammableTransactionBuilder;
+use sui_types::transaction::ProgrammableTransaction;
+use sui_types::Identifier;
+
+const START_TIME: u64 = 1_700_000_000_000;
+
+struct MockSetup {
+    server: Arc<Server>,
+    chain: Arc<MockChain>,
+    clock: Arc<MockClock>,
+    keypair: Ed25519KeyPair,
+}
+
+impl MockSetup {
+    fn new(options: ServerOptions) -> Self {
+        Self::with_rate_limits(options, &RateLimitConfig::default())
+    }
+
+    fn with_rate_limits(options: ServerOptions, rate_limits: &RateLimitConfig) -> Self {
+        let chain = Arc::new(MockChain::new(START_TIME));
+        let clock = Arc::new(MockClock::new(START_TIME));
+        let (master_key, _) = ibe::generate_key_pair(&mut thread_rng());
+        let identity = KeyIdentity::new(
+            Box::new(LockedMasterKey::new(master_key)),
+            ObjectID::random(),
+            0,
+            None,
+        )
+        .unwrap();
+        Self {
+            server: Arc::new(
+                Server::new(vec![identity], chain.clone(), clock.clone(), options)
+                    .with_rate_limiter(Arc::new(RateLimiter::new(rate_limits))),
+            ),
+            chain,
+            clock,
+            keypair: Ed25519KeyPair::generate(&mut thread_rng()),
+        }
+    }
+
+    /// Check a request for the given PTB with a certificate created at the given time.
+    async fn check_request_at(
+        &self,
+        first_pkg_id: &ObjectID,
+        ptb: &ProgrammableTransaction,
+        creation_time: u64,
+    ) -> Result<Vec<KeyId>, InternalError> {
+        let (_, pk, vk) = elgamal::genkey(&mut thread_rng());
+        let (cert, req_sig) = sign(first_pkg_id, ptb, &pk, &vk, &self.keypair, creation_time, 1);
+        self.server
+            .check_request(
+                None,
+                &ptb_to_base64(ptb),
+                &pk,
+                &vk,
+                &req_sig,
+                &cert,
+                1000,
+                None,
+                None,
+            )
+            .await
+    }
+
+    /// The state of the handlers, with the chain state updated every `update_interval`.
+    async fn state(&self, update_interval: Duration) -> MyState {
+        MyState {
+            metrics: Arc::new(Metrics::new(&Registry::new())),
+            clock: self.clock.clone(),
+            networks: vec![self.network(update_interval).await],
+            rate_limiter: Arc::new(RateLimiter::new(&RateLimitConfig::default())),
+            trust_forwarded_for: false,
+            audit_log: None,
+            self_test: Default::default(),
+            max_rgp_age: update_interval * 3,
+        }
+    }
+
+    async fn network(&self, update_interval: Duration) -> NetworkState {
+        NetworkState::spawn(
+            "custom",
+            self.server.clone(),
+            update_interval,
+            update_interval,
+            None,
+        )
+        .await
+    }
+
+    async fn check_request(
+        &self,
+        first_pkg_id: &ObjectID,
+        ptb: &ProgrammableTransaction,
+    ) -> Result<Vec<KeyId>, InternalError> {
+        self.check_request_at(first_pkg_id, ptb, self.clock.now())
+            .await
+    }
+}
+
+fn approve_ptb(package_id: ObjectID, id: &[u8]) -> ProgrammableTransaction {
+    let mut builder = ProgrammableTransactionBuilder::new();
+    let id = builder.pure(id.to_vec()).unwrap();
+    builder.programmable_move_call(
+        package_id,
+        Identifier::new("policy").unwrap(),
+        Identifier::new("seal_approve").unwrap(),
+        vec![],
+        vec![id],
+    );
+    builder.finish()
+}
+
+#[tokio::test]
+async fn test_policy_outcome() {
+    let setup = MockSetup::new(ServerOptions::default());
+    let package_id = ObjectID::random();
+    setup.chain.publish(package_id);
+
+    // Packages without a scripted outcome are denied.
+    let ptb = approve_ptb(package_id, b"id");
+    assert_eq!(
+        setup.check_request(&package_id, &ptb).await,
+        Err(InternalError::NoAccess)
+    );
+
+    setup.chain.set_dry_run_result(package_id, true);
+    let ids = setup.check_request(&package_id, &ptb).await.unwrap();
+    assert_eq!(
+        ids,
+        vec![crypto::create_full_id(&package_id.into_bytes(), b"id")]
+    );
+
+    // Unknown packages are rejected before evaluating the policy.
+    let unknown = ObjectID::random();
+    assert_eq!(
+        setup
+            .check_request(&unknown, &approve_ptb(unknown, b"id"))
+            .await,
+        Err(InternalError::InvalidPackage)
+    );
+
+    // So are requests while the full node is unavailable.
+    setup.chain.set_unavailable(true);
+    assert_eq!(
+        setup.check_request(&package_id, &ptb).await,
+        Err(InternalError::Failure)
+    );
+    assert_eq!(setup.chain.dry_runs(), 2);
+}
+
+#[tokio::test]
+async fn test_package_upgrade() {
+    let setup = MockSetup::new(ServerOptions::default());
+    let first = ObjectID::random();
+    let second = ObjectID::random();
+    setup.chain.publish(first);
+    setup.chain.set_dry_run_result(first, true);
+    setup.chain.set_dry_run_result(second, true);
+    assert!(setup
+        .check_request(&first, &approve_ptb(first, b"id"))
+        .await
+        .is_ok());
+
+    // After an upgrade, only the latest version may be called, but ids are still prefixed by the first version.
+    setup.chain.upgrade(first, second);
+    assert_eq!(
+        setup
+            .check_request(&first, &approve_ptb(first, b"id"))
+            .await,
+        Err(InternalError::OldPackageVersion)
+    );
+    let ids = setup
+        .check_request(&first, &approve_ptb(second, b"id"))
+        .await
+        .unwrap();
+    assert_eq!(
+        ids,
+        vec![crypto::create_full_id(&first.into_bytes(), b"id")]
+    );
+}
+
+#[tokio::test]
+async fn test_certificate_expiry() {
+    let setup = MockSetup::new(ServerOptions::default());
+    let package_id = ObjectID::random();
+    setup.chain.publish(package_id);
+    setup.chain.set_dry_run_result(package_id, true);
+    let ptb = approve_ptb(package_id, b"id");
+
+    // Certificates with a ttl of one minute are valid for one minute after their creation time.
+    let created = setup.clock.now();
+    setup.clock.advance(Duration::from_secs(60));
+    assert!(setup
+        .check_request_at(&package_id, &ptb, created)
+        .await
+        .is_ok());
+    setup.clock.advance(Duration::from_millis(1));
+    assert_eq!(
+        setup.check_request_at(&package_id, &ptb, created).await,
+        Err(InternalError::InvalidCertificate)
+    );
+
+    // Certificates created in the future are rejected.
+    assert_eq!(
+        setup
+            .check_request_at(&package_id, &ptb, setup.clock.now() + 1)
+            .await,
+        Err(InternalError::InvalidCertificate)
+    );
+}
+
+#[tokio::test]
+async fn test_policy_cache() {
+    let setup = MockSetup::new(ServerOptions {
+        policy_cache: Some(PolicyCacheConfig {
+            ttl_ms: 5_000,
+            size: 10,
+            excluded_packages: vec![],
+        }),
+        ..Default::default()
+    });
+    let package_id = ObjectID::random();
+    setup.chain.publish(package_id);
+    setup.chain.set_dry_run_result(package_id, true);
+    let ptb = approve_ptb(package_id, b"id");
+
+    // Nothing is cached before the first checkpoint timestamp is known.
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    assert_eq!(setup.chain.dry_runs(), 2);
+
+    // Start with a checkpoint at the start of a cache bucket.
+    let checkpoint = START_TIME / 5_000 * 5_000;
+    setup.chain.set_checkpoint_timestamp(checkpoint);
+    let mut timestamps = setup
+        .server
+        .spawn_latest_checkpoint_timestamp_updater(Duration::from_millis(10), None)
+        .await;
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    assert_eq!(setup.chain.dry_runs(), 3);
+
+    // A changed policy outcome is only observed once the full node serves a checkpoint from the next bucket while the
+    // cached result has not expired locally.
+    setup.chain.set_dry_run_result(package_id, false);
+    setup.clock.advance(Duration::from_millis(4_000));
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    for (timestamp, allowed) in [(checkpoint + 4_999, true), (checkpoint + 5_000, false)] {
+        setup.chain.set_checkpoint_timestamp(timestamp);
+        while *timestamps.borrow_and_update() != timestamp {
+            timestamps.changed().await.unwrap();
+        }
+        assert_eq!(
+            setup.check_request(&package_id, &ptb).await.is_ok(),
+            allowed
+        );
+    }
+    assert_eq!(
+        setup.check_request(&package_id, &ptb).await,
+        Err(InternalError::NoAccess)
+    );
+    assert_eq!(setup.chain.dry_runs(), 4);
+
+    // Once the TTL has passed locally, the policy is evaluated again.
+    setup.clock.advance(Duration::from_millis(5_001));
+    assert!(setup.check_request(&package_id, &ptb).await.is_err());
+    assert_eq!(setup.chain.dry_runs(), 5);
+}
+
+#[tokio::test]
+async fn test_periodic_updaters() {
+    let setup = MockSetup::new(ServerOptions::default());
+    setup.chain.set_reference_gas_price(500);
+
+    let mut timestamps = setup
+        .server
+        .spawn_latest_checkpoint_timestamp_updater(Duration::from_millis(10), None)
+        .await;
+    assert_eq!(*timestamps.borrow_and_update(), START_TIME);
+    setup.chain.set_checkpoint_timestamp(START_TIME + 1_000);
+    while *timestamps.borrow_and_update() != START_TIME + 1_000 {
+        timestamps.changed().await.unwrap();
+    }
+
+    let prices = setup
+        .server
+        .spawn_reference_gas_price_updater(Duration::from_millis(10), None)
+        .await;
+    assert_eq!(*prices.borrow(), 500);
+}
+
+#[tokio::test]
+async fn test_status() {
+    let setup = MockSetup::new(ServerOptions::default());
+    let state = setup.state(Duration::from_millis(10)).await;
+
+    let status = state.status();
+    assert_eq!(
+        status.problems,
+        vec!["The master keys have not been self-tested yet"]
+    );
+    assert_eq!(status.networks.len(), 1);
+    assert_eq!(status.networks[0].checkpoint_lag_ms, 0);
+    assert_eq!(status.networks[0].reference_gas_price_age_ms, Some(0));
+    assert_eq!(status.networks[0].identities.len(), 1);
+    assert!(!status.networks[0].graphql_reachable);
+
+    assert!(state.run_self_test().passed);
+    assert!(state.status().problems.is_empty());
+
+    // Once the chain is unreachable, neither the checkpoint nor the reference gas price are updated.
+    setup.chain.set_unavailable(true);
+    let allowed_staleness = ServerOptions::default().allowed_staleness;
+    setup
+        .clock
+        .advance(allowed_staleness + Duration::from_secs(1));
+    let status = state.status();
+    assert_eq!(status.problems.len(), 2);
+    assert!(status.networks[0].checkpoint_lag_ms > allowed_staleness.as_millis() as i64);
+    assert!(status.self_test.unwrap().passed);
+}
+
+#[tokio::test]
+async fn test_networks() {
+    let first = MockSetup::new(ServerOptions::default());
+    let second = MockSetup::new(ServerOptions::default());
+    let first_id = first
+        .server
+        .identities
+        .iter()
+        .next()
+        .unwrap()
+        .key_server_object_id;
+    let second_id = second
+        .server
+        .identities
+        .iter()
+        .next()
+        .unwrap()
+        .key_server_object_id;
+    let update_interval = Duration::from_millis(10);
+    let mut state = first.state(update_interval).await;
+    state.networks.push(second.network(update_interval).await);
+
+    // Requests are routed to the network of the identity.
+    assert!(Arc::ptr_eq(
+        &state.network(Some(&first_id)).unwrap().server,
+        &first.server
+    ));
+    assert!(Arc::ptr_eq(
+        &state.network(Some(&second_id)).unwrap().server,
+        &second.server
+    ));
+    assert_eq!(
+        state.network(Some(&ObjectID::random())).err(),
+        Some(InternalError::UnknownKeyServer)
+    );
+    assert_eq!(
+        state.network(None).err(),
+        Some(InternalError::UnknownKeyServer)
+    );
+
+    // The self-test covers the identities of all networks, and a stale network is reported.
+    assert!(state.run_self_test().passed);
+    assert!(state.status().problems.is_empty());
+    second.chain.set_unavailable(true);
+    second
+        .clock
+        .advance(ServerOptions::default().allowed_staleness + Duration::from_secs(1));
+    let status = state.status();
+    assert_eq!(status.networks.len(), 2);
+    assert_eq!(status.problems.len(), 2, "{:?}", status.problems);
+    assert_eq!(status.networks[0].checkpoint_lag_ms, 0);
+}
+
+#[tokio::test]
+async fn test_user_rate_limit() {
+    let setup = MockSetup::with_rate_limits(
+        ServerOptions::default(),
+        &RateLimitConfig {
+            per_user: Some(RateLimit {
+                requests_per_sec: 0.001,
+                burst: 1,
+            }),
+            ..Default::default()
+        },
+    );
+    let package_id = ObjectID::random();
+    setup.chain.publish(package_id);
+    setup.chain.set_dry_run_result(package_id, true);
+    let ptb = approve_ptb(package_id, b"id");
+
+    // Requests claiming to be from the user but signed by someone else do not count against the user's limit.
+    let (_, pk, vk) = elgamal::genkey(&mut thread_rng());
+    let other = Ed25519KeyPair::generate(&mut thread_rng());
+    let (mut cert, req_sig) = sign(&package_id, &ptb, &pk, &vk, &other, setup.clock.now(), 1);
+    cert.user = setup.keypair.public().into();
+    for _ in 0..3 {
+        assert_eq!(
+            setup
+                .server
+                .check_request(
+                    None,
+                    &ptb_to_base64(&ptb),
+                    &pk,
+                    &vk,
+                    &req_sig,
+                    &cert,
+                    1000,
+                    None,
+                    None
+                )
+                .await,
+            Err(InternalError::InvalidSignature)
+        );
+    }
+
+    assert!(setup.check_request(&package_id, &ptb).await.is_ok());
+    assert!(matches!(
+        setup.check_request(&package_id, &ptb).await,
+        Err(InternalError::TooManyRequests { .. })
+    ));
+}
@@ -2,8 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::tests::externals::get_key;
-use crate::tests::SealTestCluster;
-use sui_sdk::{json::SuiJsonValue, rpc_types::ObjectChange};
+use crate::tests::{approves, object_id, pure_id, SealTestSetup};
 use sui_types::base_types::{ObjectDigest, SequenceNumber};
 use sui_types::{
     base_types::{ObjectID, SuiAddress},
@@ -11,22 +10,25 @@ use sui_types::{
     transaction::{ObjectArg, ProgrammableTransaction},
     Identifier,
 };
-use test_cluster::TestCluster;
 use tracing_test::traced_test;
 
 #[traced_test]
 #[tokio::test]
 async fn test_pd() {
-    let mut tc = SealTestCluster::new(1, 2).await;
+    let tc = SealTestSetup::new(1, 2);
 
-    let (package_id, _) = tc.publish("patterns").await;
+    let package_id = tc.publish();
 
     // create PrivateData with nonce=package_id, owned by addr1
-    let (pd, version, digest) =
-        create_private_data(tc.users[0].address, tc.get_mut(), package_id).await;
+    let creator = SuiAddress::random_for_testing_only();
+    let pd = ObjectID::random();
+    tc.chain.set_policy(
+        package_id,
+        pd_policy(pd, tc.users[0].address, pd_key_id(creator, package_id)),
+    );
 
     // addr1 should have access
-    let ptb = pd_create_ptb(tc.get_mut(), package_id, package_id, pd, version, digest).await;
+    let ptb = pd_create_ptb(package_id, creator, package_id, pd);
     assert!(
         get_key(tc.server(), &package_id, ptb.clone(), &tc.users[0].keypair)
             .await
@@ -38,15 +40,7 @@ async fn test_pd() {
         .is_err());
 
     // addr1 should not have access to a different nonce
-    let ptb = pd_create_ptb(
-        &mut tc.cluster,
-        package_id,
-        ObjectID::random(),
-        pd,
-        version,
-        digest,
-    )
-    .await;
+    let ptb = pd_create_ptb(package_id, creator, ObjectID::random(), pd);
     assert!(
         get_key(tc.server(), &package_id, ptb.clone(), &tc.users[0].keypair)
             .await
@@ -54,88 +48,47 @@ async fn test_pd() {
     );
 }
 
-pub(crate) async fn create_private_data(
-    user: SuiAddress,
-    cluster: &mut TestCluster,
-    package_id: ObjectID,
-) -> (ObjectID, SequenceNumber, ObjectDigest) {
-    let builder = cluster.sui_client().transaction_builder();
-    let tx = builder
-        .move_call(
-            cluster.get_address_0(),
-            package_id,
-            "private_data",
-            "store_entry",
-            vec![],
-            vec![
-                SuiJsonValue::from_object_id(package_id),
-                SuiJsonValue::from_object_id(package_id),
-            ],
-            None,
-            50_000_000,
-            None,
-        )
-        .await
-        .unwrap();
-    let response = cluster.sign_and_execute_transaction(&tx).await;
-
-    let mut pd: Option<ObjectID> = None;
-    for created in response.object_changes.unwrap() {
-        if let ObjectChange::Created {
-            object_type,
-            object_id,
-            ..
-        } = created
-        {
-            if object_type.name.as_str() == "PrivateData" {
-                pd.replace(object_id);
-            };
-        }
-    }
+/// The id of the key of private data stored by the given creator with the given nonce.
+fn pd_key_id(creator: SuiAddress, nonce: ObjectID) -> Vec<u8> {
+    [
+        bcs::to_bytes(&creator).unwrap(),
+        bcs::to_bytes(&nonce).unwrap(),
+    ]
+    .concat()
+}
 
-    let builder = cluster.sui_client().transaction_builder();
-    let tx = builder
-        .transfer_object(cluster.get_address_0(), pd.unwrap(), None, 50_000_000, user)
-        .await
-        .unwrap();
-    let response = cluster.sign_and_execute_transaction(&tx).await;
-    assert!(response.status_ok().unwrap());
-    for modified in response.object_changes.unwrap() {
-        if let ObjectChange::Mutated {
-            object_type,
-            object_id,
-            version,
-            digest,
-            ..
-        } = modified
-        {
-            if object_type.name.as_str() == "PrivateData" {
-                return (object_id, version, digest);
+/// Models `private_data::seal_approve` for the given private data object: only its owner can pass it, and the id must
+/// be computed from its creator and nonce.
+fn pd_policy(
+    pd: ObjectID,
+    owner: SuiAddress,
+    key_id: Vec<u8>,
+) -> impl Fn(SuiAddress, &ProgrammableTransaction, u64) -> bool + Send + 'static {
+    move |sender, ptb, _| {
+        approves(ptb, "private_data", |args| match args {
+            [id, e] => {
+                object_id(e) == Some(pd) && sender == owner && pure_id(id) == Some(key_id.clone())
             }
-        }
+            _ => false,
+        })
     }
-
-    panic!("should have found the pd object");
 }
 
-async fn pd_create_ptb(
-    cluster: &mut TestCluster,
+fn pd_create_ptb(
     package_id: ObjectID,
+    creator: SuiAddress,
     nonce: ObjectID,
     pd: ObjectID,
-    version: SequenceNumber,
-    digest: ObjectDigest,
 ) -> ProgrammableTransaction {
     let mut builder = ProgrammableTransactionBuilder::new();
-    // the id = creator || nonce which in this case is the package_id
-    let id = [
-        bcs::to_bytes(&cluster.get_address_0()).unwrap(),
-        bcs::to_bytes(&nonce).unwrap(),
-    ]
-    .concat();
-    let id = builder.pure(id).unwrap();
+    // the id = creator || nonce
+    let id = builder.pure(pd_key_id(creator, nonce)).unwrap();
     let pd = builder
-        .obj(ObjectArg::ImmOrOwnedObject((pd, version, digest)))
+        .obj(ObjectArg::ImmOrOwnedObject((
+            pd,
+            SequenceNumber::from(2),
+            ObjectDigest::random(),
+        )))
         .unwrap();
 
     builder.programmable_move_call(
@@ -5,15 +5,17 @@ use core::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing_test::traced_test;
 
-use crate::externals::get_latest_checkpoint_timestamp;
-use crate::tests::SealTestCluster;
+use crate::tests::SealTestSetup;
 
 #[tokio::test]
 async fn test_get_latest_checkpoint_timestamp() {
-    let tc = SealTestCluster::new(0, 0).await;
+    let tc = SealTestSetup::new(1, 0);
 
     let tolerance = 20000;
-    let timestamp: u64 = get_latest_checkpoint_timestamp(tc.cluster.sui_client().clone())
+    let timestamp: u64 = tc
+        .server()
+        .chain
+        .latest_checkpoint_timestamp()
         .await
         .unwrap();
 
@@ -28,7 +30,7 @@ async fn test_get_latest_checkpoint_timestamp() {
 
 #[tokio::test]
 async fn test_timestamp_updater() {
-    let tc = SealTestCluster::new(1, 0).await;
+    let tc = SealTestSetup::new(1, 0);
 
     let update_interval = Duration::from_secs(1);
 
@@ -49,18 +51,19 @@ async fn test_timestamp_updater() {
     assert!(diff < tolerance);
 
     // Get a new timestamp
-    receiver
-        .changed()
-        .await
-        .expect("Failed to get latest timestamp");
-    let new_timestamp = *receiver.borrow_and_update();
-    assert!(new_timestamp >= timestamp);
+    tc.chain.set_checkpoint_timestamp(timestamp + 1_000);
+    while *receiver.borrow_and_update() != timestamp + 1_000 {
+        receiver
+            .changed()
+            .await
+            .expect("Failed to get latest timestamp");
+    }
 }
 
 #[traced_test]
 #[tokio::test]
 async fn test_rgp_updater() {
-    let tc = SealTestCluster::new(1, 0).await;
+    let tc = SealTestSetup::new(1, 0);
 
     let update_interval = Duration::from_secs(1);
 
@@ -70,7 +73,10 @@ async fn test_rgp_updater() {
         .await;
 
     let price = *receiver.borrow_and_update();
-    assert_eq!(price, tc.cluster.get_reference_gas_price().await);
+    assert_eq!(price, 1000);
+    tc.chain.set_reference_gas_price(750);
 
-    receiver.changed().await.expect("Failed to get latest rgp");
+    while *receiver.borrow_and_update() != 750 {
+        receiver.changed().await.expect("Failed to get latest rgp");
+    }
 }
@@ -0,0 +1,32 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTTP servers standing in for full nodes and GraphQL endpoints.
+
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::Value;
+use tokio::net::TcpListener;
+
+/// Start an HTTP server answering POST requests with the given handler, or with 503 if it returns `None`.
+pub(crate) async fn stub_server(
+    handler: impl Fn(Value) -> Option<Value> + Clone + Send + Sync + 'static,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let app = Router::new().route(
+        "/",
+        post(move |Json(request): Json<Value>| {
+            let response = handler(request);
+            async move { response.map(Json).ok_or(StatusCode::SERVICE_UNAVAILABLE) }
+        }),
+    );
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// A server failing all requests.
+pub(crate) async fn stub_unavailable() -> String {
+    stub_server(|_| None).await
+}
@@ -1,15 +1,16 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::externals::current_epoch_time;
 use crate::tests::externals::{ptb_to_base64, sign};
-use crate::tests::SealTestCluster;
-use crate::{current_epoch_time, InternalError};
+use crate::tests::{approves, pure_id, SealTestSetup};
+use crate::InternalError;
 use crypto::elgamal;
 use fastcrypto::ed25519::Ed25519KeyPair;
 use fastcrypto::traits::KeyPair;
 use rand::thread_rng;
 use sui_types::{
-    base_types::ObjectID,
+    base_types::{ObjectID, SuiAddress},
     programmable_transaction_builder::ProgrammableTransactionBuilder,
     transaction::{ObjectArg, ProgrammableTransaction},
     Identifier, SUI_CLOCK_OBJECT_ID,
@@ -19,8 +20,9 @@ use tracing_test::traced_test;
 #[traced_test]
 #[tokio::test]
 async fn test_tle_policy() {
-    let mut tc = SealTestCluster::new(1, 1).await;
-    let (package_id, _) = tc.publish("patterns").await;
+    let tc = SealTestSetup::new(1, 1);
+    let package_id = tc.publish();
+    tc.chain.set_policy(package_id, tle_policy);
 
     {
         // old time
@@ -39,6 +41,7 @@ async fn test_tle_policy() {
         let result = tc
             .server()
             .check_request(
+                None,
                 &ptb_to_base64(&ptb),
                 &pk,
                 &vk,
@@ -71,6 +74,7 @@ async fn test_tle_policy() {
         let result = tc
             .server()
             .check_request(
+                None,
                 &ptb_to_base64(&ptb),
                 &pk,
                 &vk,
@@ -88,8 +92,9 @@ async fn test_tle_policy() {
 #[traced_test]
 #[tokio::test]
 async fn test_tle_certificate() {
-    let mut tc = SealTestCluster::new(1, 1).await;
-    let (package_id, _) = tc.publish("patterns").await;
+    let tc = SealTestSetup::new(1, 1);
+    let package_id = tc.publish();
+    tc.chain.set_policy(package_id, tle_policy);
 
     let ptb = tle_create_ptb(package_id, 1);
     let (_, pk, vk) = elgamal::genkey(&mut thread_rng());
@@ -107,6 +112,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -125,6 +131,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -142,6 +149,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -159,6 +167,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -177,6 +186,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -203,6 +213,7 @@ async fn test_tle_certificate() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -219,8 +230,9 @@ async fn test_tle_certificate() {
 #[traced_test]
 #[tokio::test]
 async fn test_tle_signed_request() {
-    let mut tc = SealTestCluster::new(1, 1).await;
-    let (package_id, _) = tc.publish("patterns").await;
+    let tc = SealTestSetup::new(1, 1);
+    let package_id = tc.publish();
+    tc.chain.set_policy(package_id, tle_policy);
 
     let ptb = tle_create_ptb(package_id, 1);
     let (_, pk, vk) = elgamal::genkey(&mut thread_rng());
@@ -237,6 +249,7 @@ async fn test_tle_signed_request() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk,
             &vk,
@@ -253,6 +266,7 @@ async fn test_tle_signed_request() {
     let result = tc
         .server()
         .check_request(
+            None,
             &ptb_to_base64(&ptb),
             &pk2,
             &vk2,
@@ -266,6 +280,20 @@ async fn test_tle_signed_request() {
     assert_eq!(result.err(), Some(InternalError::InvalidSessionSignature));
 }
 
+/// Models `tle::seal_approve`: the id is a time which has passed at the latest checkpoint.
+fn tle_policy(
+    _sender: SuiAddress,
+    ptb: &ProgrammableTransaction,
+    checkpoint_timestamp: u64,
+) -> bool {
+    approves(ptb, "tle", |args| match args {
+        [id, _clock] => pure_id(id)
+            .and_then(|id| bcs::from_bytes::<u64>(&id).ok())
+            .is_some_and(|time| time <= checkpoint_timestamp),
+        _ => false,
+    })
+}
+
 fn get_tle_id(time: u64) -> Vec<u8> {
     bcs::to_bytes(&time).unwrap()
 }
@@ -2,31 +2,29 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::externals::get_key;
-use crate::tests::SealTestCluster;
-use serde_json::json;
-use sui_sdk::{json::SuiJsonValue, rpc_types::ObjectChange};
+use crate::tests::{approves, object_id, pure_id, SealTestSetup};
 use sui_types::{
     base_types::{ObjectID, SuiAddress},
     programmable_transaction_builder::ProgrammableTransactionBuilder,
     transaction::{ObjectArg, ProgrammableTransaction},
     Identifier,
 };
-use test_cluster::TestCluster;
 use tracing_test::traced_test;
 
 #[traced_test]
 #[tokio::test]
 async fn test_whitelist() {
-    let mut tc = SealTestCluster::new(1, 2).await;
+    let tc = SealTestSetup::new(1, 2);
 
-    let (package_id, _) = tc.publish("patterns").await;
+    let package_id = tc.publish();
 
-    let (whitelist, cap) = create_whitelist(tc.get_mut(), package_id).await;
-
-    let user_address = tc.users[0].address;
-    add_user_to_whitelist(tc.get_mut(), package_id, whitelist, cap, user_address).await;
+    let whitelist = ObjectID::random();
+    tc.chain.set_policy(
+        package_id,
+        whitelist_policy(whitelist, vec![tc.users[0].address]),
+    );
 
-    // We know the version at this point
+    // The mock chain does not check object versions
     let initial_shared_version = 3;
 
     let ptb = whitelist_create_ptb(package_id, whitelist, initial_shared_version);
@@ -39,8 +37,9 @@ async fn test_whitelist() {
         .await
         .is_err());
 
-    let ptb = whitelist_create_ptb(package_id, whitelist, initial_shared_version);
-    assert!(get_key(tc.server(), &package_id, ptb, &tc.users[1].keypair)
+    // Another whitelist does not give access.
+    let ptb = whitelist_create_ptb(package_id, ObjectID::random(), initial_shared_version);
+    assert!(get_key(tc.server(), &package_id, ptb, &tc.users[0].keypair)
         .await
         .is_err());
 }
@@ -48,16 +47,17 @@ async fn test_whitelist() {
 #[traced_test]
 #[tokio::test]
 async fn test_whitelist_with_upgrade() {
-    let mut tc = SealTestCluster::new(1, 1).await;
+    let tc = SealTestSetup::new(1, 1);
 
-    let (package_id_1, upgrade_cap) = tc.publish("patterns").await;
+    let package_id_1 = tc.publish();
     println!("Old pkg: {}", package_id_1);
 
-    let (whitelist, cap) = create_whitelist(tc.get_mut(), package_id_1).await;
-    let user_address = tc.users[0].address;
-    add_user_to_whitelist(tc.get_mut(), package_id_1, whitelist, cap, user_address).await;
+    let whitelist = ObjectID::random();
+    let users = vec![tc.users[0].address];
+    tc.chain
+        .set_policy(package_id_1, whitelist_policy(whitelist, users.clone()));
 
-    // We know the version at this point
+    // The mock chain does not check object versions
     let initial_shared_version = 3;
 
     // Succeeds with initial version
@@ -71,7 +71,9 @@ async fn test_whitelist_with_upgrade() {
     .await
     .is_ok());
 
-    let package_id_2 = tc.upgrade(package_id_1, upgrade_cap, "patterns").await;
+    let package_id_2 = tc.upgrade(package_id_1);
+    tc.chain
+        .set_policy(package_id_2, whitelist_policy(whitelist, users.clone()));
 
     // Succeeds with new package id
     let ptb = whitelist_create_ptb(package_id_2, whitelist, initial_shared_version);
@@ -95,7 +97,9 @@ async fn test_whitelist_with_upgrade() {
     .await
     .is_err());
 
-    let package_id_3 = tc.upgrade(package_id_2, upgrade_cap, "patterns").await;
+    let package_id_3 = tc.upgrade(package_id_1);
+    tc.chain
+        .set_policy(package_id_3, whitelist_policy(whitelist, users));
 
     // Succeeds with last package id
     let ptb = whitelist_create_ptb(package_id_3, whitelist, initial_shared_version);
@@ -184,81 +188,19 @@ pub fn whitelist_create_ptb(
     builder.finish()
 }
 
-pub(crate) async fn create_whitelist(
-    cluster: &mut TestCluster,
-    package_id: ObjectID,
-) -> (ObjectID, ObjectID) {
-    // Create new whitelist
-    let tx = cluster
-        .sui_client()
-        .transaction_builder()
-        .move_call(
-            cluster.get_address_0(),
-            package_id,
-            "whitelist",
-            "create_whitelist_entry",
-            vec![],
-            vec![],
-            None,
-            50_000_000,
-            None,
-        )
-        .await
-        .unwrap();
-    let response = cluster.sign_and_execute_transaction(&tx).await;
-
-    // Read the id of the Whitelist and Cap objects
-    let mut whitelist: Option<ObjectID> = None;
-    let mut cap: Option<ObjectID> = None;
-    for created in response.object_changes.unwrap() {
-        if let ObjectChange::Created {
-            object_type,
-            object_id,
-            ..
-        } = created
-        {
-            match object_type.name.as_str() {
-                "Whitelist" => whitelist.replace(object_id),
-                "Cap" => cap.replace(object_id),
-                _ => None,
-            };
-        }
-    }
-    assert!(whitelist.is_some() && cap.is_some());
-    let whitelist = whitelist.unwrap();
-    let cap = cap.unwrap();
-
-    (whitelist, cap)
-}
-
-pub(crate) async fn add_user_to_whitelist(
-    cluster: &mut TestCluster,
-    package_id: ObjectID,
+/// Models `whitelist::seal_approve`: the id is prefixed by the id of the whitelist, which contains the sender.
+pub(crate) fn whitelist_policy(
     whitelist: ObjectID,
-    cap: ObjectID,
-    user: SuiAddress,
-) {
-    // Add the first user to the whitelist
-    let tx = cluster
-        .sui_client()
-        .transaction_builder()
-        .move_call(
-            cluster.get_address_0(),
-            package_id,
-            "whitelist",
-            "add",
-            vec![],
-            vec![
-                SuiJsonValue::from_object_id(whitelist),
-                SuiJsonValue::from_object_id(cap),
-                SuiJsonValue::new(json!(user)).unwrap(),
-            ],
-            None,
-            50_000_000,
-            None,
-        )
-        .await
-        .unwrap();
-    let response = cluster.sign_and_execute_transaction(&tx).await;
-    assert!(response.status_ok().unwrap());
+    users: Vec<SuiAddress>,
+) -> impl Fn(SuiAddress, &ProgrammableTransaction, u64) -> bool + Send + 'static {
+    move |sender, ptb, _| {
+        approves(ptb, "whitelist", |args| match args {
+            [id, list] => {
+                object_id(list) == Some(whitelist)
+                    && pure_id(id).is_some_and(|id| id.starts_with(&whitelist.to_vec()))
+                    && users.contains(&sender)
+            }
+            _ => false,
+        })
+    }
 }
@@ -3,11 +3,14 @@
 
 use crypto::elgamal;
 use crypto::ibe;
+use fastcrypto::groups::bls12381::G1Element;
 
 /// The Identity-based encryption types.
 pub type IbeMasterKey = ibe::MasterKey;
-type IbeDerivedKey = ibe::UserSecretKey;
-type IbePublicKey = ibe::PublicKey;
+pub type IbePublicKey = ibe::PublicKey;
+
+/// The group element of a derived key, which is what is encrypted to the user using ElGamal.
+type IbeDerivedKey = G1Element;
 
 /// ElGamal related types.
 pub type ElGamalPublicKey = elgamal::PublicKey<IbeDerivedKey>;
@@ -24,45 +27,67 @@ pub enum Network {
     Mainnet,
     Custom {
         node_url: String,
-        graphql_url: String,
+        /// Not needed if packages are resolved using the full node.
+        graphql_url: Option<String>,
     },
-    #[cfg(test)]
-    TestCluster,
 }
 
 impl Network {
+    /// The name of the network as used in the configuration, e.g. "mainnet".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Devnet => "devnet",
+            Network::Testnet => "testnet",
+            Network::Mainnet => "mainnet",
+            Network::Custom { .. } => "custom",
+        }
+    }
+
     pub fn node_url(&self) -> String {
         match self {
             Network::Devnet => "https://fullnode.devnet.sui.io:443".into(),
             Network::Testnet => "https://fullnode.testnet.sui.io:443".into(),
             Network::Mainnet => "https://fullnode.mainnet.sui.io:443".into(),
             Network::Custom { node_url, .. } => node_url.clone(),
-            #[cfg(test)]
-            Network::TestCluster => panic!(), // Currently not used, but can be found from cluster.rpc_url() if needed
         }
     }
 
-    pub fn graphql_url(&self) -> String {
+    pub fn graphql_url(&self) -> Option<String> {
         match self {
-            Network::Devnet => "https://sui-devnet.mystenlabs.com/graphql".into(),
-            Network::Testnet => "https://sui-testnet.mystenlabs.com/graphql".into(),
-            Network::Mainnet => "https://sui-mainnet.mystenlabs.com/graphql".into(),
+            Network::Devnet => Some("https://sui-devnet.mystenlabs.com/graphql".into()),
+            Network::Testnet => Some("https://sui-testnet.mystenlabs.com/graphql".into()),
+            Network::Mainnet => Some("https://sui-mainnet.mystenlabs.com/graphql".into()),
             Network::Custom { graphql_url, .. } => graphql_url.clone(),
-            #[cfg(test)]
-            Network::TestCluster => panic!("GraphQL is not available on test cluster"),
         }
     }
 
-    pub fn from_str(str: &str) -> Self {
-        match str.to_ascii_lowercase().as_str() {
-            "devnet" => Network::Devnet,
-            "testnet" => Network::Testnet,
-            "mainnet" => Network::Mainnet,
-            "custom" => Network::Custom {
-                node_url: std::env::var("NODE_URL").expect("NODE_URL must be set"),
-                graphql_url: std::env::var("GRAPHQL_URL").expect("GRAPHQL_URL must be set"),
-            },
-            _ => panic!("Unknown network: {}", str),
+    /// Create a network from its name. A custom network requires a node url, and optionally a GraphQL url.
+    pub fn from_name(
+        name: &str,
+        node_url: Option<String>,
+        graphql_url: Option<String>,
+    ) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "devnet" => Ok(Network::Devnet),
+            "testnet" => Ok(Network::Testnet),
+            "mainnet" => Ok(Network::Mainnet),
+            "custom" => {
+                let node_url = check_url("node_url (NODE_URL)", node_url)?;
+                let graphql_url = graphql_url
+                    .map(|url| check_url("graphql_url (GRAPHQL_URL)", Some(url)))
+                    .transpose()?;
+                Ok(Network::Custom {
+                    node_url,
+                    graphql_url,
+                })
+            }
+            _ => Err(format!("Unknown network: {}", name)),
         }
     }
 }
+
+fn check_url(name: &str, url: Option<String>) -> Result<String, String> {
+    let url = url.ok_or(format!("{} must be set for a custom network", name))?;
+    reqwest::Url::parse(&url).map_err(|e| format!("Invalid {} {}: {}", name, url, e))?;
+    Ok(url)
+}
@@ -1,35 +1,52 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use clap::{Parser, Subcommand};
-use crypto::dem::{Aes256Gcm, Hmac256Ctr};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use crypto::EncryptionInput::Plain;
 use crypto::{
-    create_full_id, ibe, seal_decrypt, seal_encrypt, Ciphertext, EncryptedObject, EncryptionInput,
-    IBEEncryptions, IBEPublicKeys, IBEUserSecretKeys, ObjectID,
+    create_full_id, decrypt_with_dem, ibe,
+    onchain::{prepare_onchain_decryption, OnchainDecryptionArguments},
+    patterns::PatternId,
+    seal_decrypt, seal_decrypt_metadata, seal_encrypt, Ciphertext, Compression, DemHeader,
+    EncryptedObject, EncryptionInput, IBEEncryptions, IBEPublicKeys, IBEUserSecretKeys, Metadata,
+    ObjectID, Padding, SymmetricKey, KEY_SIZE,
 };
 use fastcrypto::encoding::Encoding;
-use fastcrypto::encoding::Hex;
+use fastcrypto::encoding::{Base64, Hex};
 use fastcrypto::error::{FastCryptoError, FastCryptoResult};
-use fastcrypto::groups::bls12381::{G1Element, G2Element, Scalar};
+use fastcrypto::groups::bls12381::G2Element;
 use rand::thread_rng;
 use serde::Deserialize;
 use serde::Serialize;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-const KEY_LENGTH: usize = 32;
-
 /// Default encoding for serializing and deserializing values.
 type DefaultEncoding = Hex;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Arguments {
+    /// The encoding of encrypted objects given as input or printed as output.
+    #[arg(long, global = true, value_enum, default_value_t = Format::Hex)]
+    format: Format,
     #[command(subcommand)]
     command: Command,
 }
 
+/// Encodings of encrypted objects.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    /// Hex-encoded BCS serialization.
+    Hex,
+    /// Base64-encoded BCS serialization.
+    Base64,
+    /// ASCII armor with a base64 body and a checksum.
+    Armor,
+    /// Human-readable JSON with hex and base64 encoded fields.
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 #[allow(clippy::large_enum_variant)]
 enum Command {
@@ -40,38 +57,35 @@ enum Command {
         /// The Sui address of the Move package that handles the KMS for this key
         #[arg(long)]
         package_id: ObjectID,
-        /// The ID of the key that should be derived.
-        #[arg(long)]
-        id: EncodedBytes,
+        #[command(flatten)]
+        id: IdArgs,
         /// Master key. Base64 encoding of a BLS12-381 scalar.
-        #[arg(long, value_parser = parse_serializable::<Scalar, DefaultEncoding>)]
-        master_key: Scalar,
+        #[arg(long, value_parser = parse_serializable::<ibe::MasterKey, DefaultEncoding>)]
+        master_key: ibe::MasterKey,
     },
     /// Verify a user secret key against a public key.
     Verify {
         /// The Sui address of the Move package that handles the KMS for this key
         #[arg(long)]
         package_id: ObjectID,
-        /// The ID of the key that should be derived.
-        #[arg(long)]
-        id: EncodedBytes,
+        #[command(flatten)]
+        id: IdArgs,
         /// User secret key. Base64 encoding of a compressed BLS12-381 G1Element.
-        #[arg(long, value_parser = parse_serializable::<G1Element, DefaultEncoding>)]
-        user_secret_key: G1Element,
+        #[arg(long, value_parser = parse_serializable::<ibe::UserSecretKey, DefaultEncoding>)]
+        user_secret_key: ibe::UserSecretKey,
         /// Public key. Base64 encoding of a compressed BLS12-381 G2Element.
         #[arg(long, value_parser = parse_serializable::<G2Element, DefaultEncoding>)]
         public_key: G2Element,
     },
     /// Derive a key using Seal.
     /// The key is derived from the ID using an IBKEM, Boneh-Franklin over BLS12381.
-    /// This outputs both the encrypted object as a hex-encoded BCS serialization, which can be shared publicly, and the derived symmetric key which should be kept privately.
+    /// This outputs both the encrypted object in the encoding given by --format, which can be shared publicly, and the derived symmetric key which should be kept privately.
     Plain {
         /// The Sui address of the Move package that handles the KMS for this key
         #[arg(long)]
         package_id: ObjectID,
-        /// The ID of the key that should be derived.
-        #[arg(long)]
-        id: EncodedBytes,
+        #[command(flatten)]
+        id: IdArgs,
         /// The number of key servers that need to be present for decryption
         #[arg(long)]
         threshold: u8,
@@ -84,7 +98,7 @@ enum Command {
     },
     /// Encrypt a message using Seal.
     /// The key is derived from the ID using an IBKEM, Boneh-Franklin over BLS12381, and the message is encrypted using AES-256-GCM.
-    /// This outputs both the encrypted object as a hex-encoded BCS serialization, which can be shared publicly, and the derived symmetric key which should be kept privately.
+    /// This outputs both the encrypted object in the encoding given by --format, which can be shared publicly, and the derived symmetric key which should be kept privately.
     EncryptAes {
         /// The message to encrypt as hex-encoded bytes
         #[arg(long)]
@@ -92,12 +106,17 @@ enum Command {
         /// Optional additional authenticated data as hex-encoded bytes
         #[arg(long)]
         aad: Option<EncodedBytes>,
+        /// Optional padding scheme to hide the length of the message: "pow2", "padme" or "block:<size>"
+        #[arg(long)]
+        pad: Option<Padding>,
+        /// Optional compression codec applied before encryption: "zstd" or "deflate"
+        #[arg(long)]
+        compress: Option<Compression>,
         /// The Sui address of the Move package that handles the KMS for this encryption
         #[arg(long)]
         package_id: ObjectID,
-        /// The ID of the key that should be used for this encryption
-        #[arg(long)]
-        id: EncodedBytes,
+        #[command(flatten)]
+        id: IdArgs,
         /// The number of key servers that need to be present for decryption
         #[arg(long)]
         threshold: u8,
@@ -110,7 +129,7 @@ enum Command {
     },
     /// Encrypt a message using Seal.
     /// The key is derived from the ID using an IBKEM, Boneh-Franklin over BLS12381, and the message is encrypted using counter-mode with hmac-sha3-256 as a PRF.
-    /// This outputs both the encrypted object as a hex-encoded BCS serialization, which can be shared publicly, and the derived symmetric key which should be kept privately.
+    /// This outputs both the encrypted object in the encoding given by --format, which can be shared publicly, and the derived symmetric key which should be kept privately.
     EncryptHmac {
         /// The message to encrypt as hex-encoded bytes
         #[arg(long)]
@@ -118,12 +137,39 @@ enum Command {
         /// Optional additional authenticated data as hex-encoded bytes
         #[arg(long)]
         aad: Option<EncodedBytes>,
+        /// Optional padding scheme to hide the length of the message: "pow2", "padme" or "block:<size>"
+        #[arg(long)]
+        pad: Option<Padding>,
+        /// Optional compression codec applied before encryption: "zstd" or "deflate"
+        #[arg(long)]
+        compress: Option<Compression>,
         /// The Sui address of the Move package that handles the KMS for this encryption
         #[arg(long)]
         package_id: ObjectID,
-        /// The ID of the key that should be used for this encryption
+        #[command(flatten)]
+        id: IdArgs,
+        /// The number of key servers that need to be present for decryption
         #[arg(long)]
-        id: EncodedBytes,
+        threshold: u8,
+        /// The hex-encoded public keys for the key servers
+        #[arg(value_parser = parse_serializable::<G2Element, DefaultEncoding>, num_args = 1..)]
+        public_keys: Vec<G2Element>,
+        /// The address for the Move objects representing the key servers
+        #[arg(num_args = 1.., last = true)]
+        object_ids: Vec<ObjectID>,
+    },
+    /// Protect an existing 256-bit key using Seal.
+    /// The key is wrapped with AES key wrap (RFC 3394) under a key derived from the ID using an IBKEM, Boneh-Franklin over BLS12381.
+    /// This outputs only the encrypted object in the encoding given by --format, which can be shared publicly. The derived symmetric key is not printed since decryption returns the wrapped key.
+    KeyWrap {
+        /// The hex-encoded key to protect
+        #[arg(long)]
+        key: EncodedBytes,
+        /// The Sui address of the Move package that handles the KMS for this encryption
+        #[arg(long)]
+        package_id: ObjectID,
+        #[command(flatten)]
+        id: IdArgs,
         /// The number of key servers that need to be present for decryption
         #[arg(long)]
         threshold: u8,
@@ -137,44 +183,75 @@ enum Command {
     /// Decrypt a Seal encrypted object.
     /// In case the encrypted object holds a message, this is returned.
     /// If Plain was used, the derived encryption key is returned.
+    /// If KeyWrap was used, the wrapped key is returned.
     Decrypt {
-        /// An encrypted object as hex-encoded bytes
-        #[arg(value_parser = parse_serializable::<EncryptedObject, DefaultEncoding>)]
-        encrypted_object: EncryptedObject,
+        /// An encrypted object in the encoding given by --format
+        encrypted_object: String,
         /// The secret keys for the key servers. The order of the keys must match the order of the key servers in the object_ids field.
-        #[arg(value_parser = parse_serializable::<G1Element, DefaultEncoding>, num_args = 1..)]
-        secret_keys: Vec<G1Element>,
+        #[arg(value_parser = parse_serializable::<ibe::UserSecretKey, DefaultEncoding>, num_args = 1..)]
+        secret_keys: Vec<ibe::UserSecretKey>,
         /// The address for the Move objects representing the key servers used for this decryption.
         #[arg(num_args = 1.., last = true)]
         object_ids: Vec<ObjectID>,
     },
     /// Parse a Seal encrypted object.
     /// This outputs the parts of the parsed encrypted object as a hex-encoded BCS serialization.
+    /// If user secret keys are given, the encrypted metadata is also decrypted and shown.
     Parse {
-        /// The encrypted object as hex-encoded bytes
-        #[arg(value_parser = parse_serializable::<EncryptedObject, DefaultEncoding>)]
-        encrypted_object: EncryptedObject,
+        /// The encrypted object in the encoding given by --format
+        encrypted_object: String,
+        /// Optional secret keys for the key servers used to decrypt the metadata. The order of the keys must match the order of the key servers in the object_ids field.
+        #[arg(value_parser = parse_serializable::<ibe::UserSecretKey, DefaultEncoding>, num_args = 0..)]
+        secret_keys: Vec<ibe::UserSecretKey>,
+        /// The address for the Move objects representing the key servers used to decrypt the metadata.
+        #[arg(num_args = 0.., last = true)]
+        object_ids: Vec<ObjectID>,
+    },
+    /// Prepare the arguments for decrypting a Hmac256Ctr encrypted object on chain using the seal::bf_hmac_encryption Move module.
+    /// This outputs each pure argument and the bundle of all arguments as hex-encoded BCS serializations.
+    OnchainDecryptionArgs {
+        /// An encrypted object in the encoding given by --format
+        encrypted_object: String,
+        /// The hex-encoded public keys for all key servers of the encrypted object in the order they appear in it.
+        #[arg(long, value_parser = parse_serializable::<G2Element, DefaultEncoding>, num_args = 1..)]
+        public_keys: Vec<G2Element>,
+        /// The secret keys for the key servers. The order of the keys must match the order of the key servers in the object_ids field.
+        #[arg(value_parser = parse_serializable::<ibe::UserSecretKey, DefaultEncoding>, num_args = 1..)]
+        secret_keys: Vec<ibe::UserSecretKey>,
+        /// The address for the Move objects representing the key servers used for this decryption.
+        #[arg(num_args = 1.., last = true)]
+        object_ids: Vec<ObjectID>,
+    },
+    /// Convert an encrypted object from the encoding given by --format to another encoding.
+    Convert {
+        /// The encrypted object in the encoding given by --format.
+        encrypted_object: String,
+        /// The encoding of the output.
+        #[arg(long, value_enum)]
+        to: Format,
     },
     SymmetricDecrypt {
-        /// An encrypted object as hex-encoded bytes.
-        #[arg(value_parser = parse_serializable::<EncryptedObject, DefaultEncoding>)]
-        encrypted_object: EncryptedObject,
+        /// An encrypted object in the encoding given by --format.
+        encrypted_object: String,
         /// The derived symmetric key from the encryption.
         #[arg(long)]
         key: EncodedBytes,
     },
 }
 
-struct GenkeyOutput((Scalar, G2Element));
-struct ExtractOutput(G1Element);
+struct GenkeyOutput((ibe::MasterKey, G2Element));
+struct ExtractOutput(ibe::UserSecretKey);
 struct VerifyOutput(FastCryptoResult<()>);
-struct EncryptionOutput((EncryptedObject, [u8; KEY_LENGTH]));
+struct EncryptionOutput((EncryptedObject, SymmetricKey), Format);
+struct KeyWrapOutput(EncryptedObject, Format);
 struct DecryptionOutput(Vec<u8>);
-struct ParseOutput(EncryptedObject);
+struct ParseOutput(EncryptedObject, Option<Metadata>);
+struct OnchainDecryptionArgsOutput(OnchainDecryptionArguments);
 struct SymmetricDecryptOutput(Vec<u8>);
 
 fn main() -> FastCryptoResult<()> {
     let args = Arguments::parse();
+    let format = args.format;
 
     let output = match args.command {
         Command::Genkey => GenkeyOutput(ibe::generate_key_pair(&mut thread_rng())).to_string(),
@@ -184,7 +261,7 @@ fn main() -> FastCryptoResult<()> {
             master_key,
         } => ExtractOutput(ibe::extract(
             &master_key,
-            &create_full_id(&package_id, &id.0),
+            &create_full_id(&package_id, &id.inner_id()?),
         ))
         .to_string(),
         Command::Verify {
@@ -194,7 +271,7 @@ fn main() -> FastCryptoResult<()> {
             public_key,
         } => VerifyOutput(ibe::verify_user_secret_key(
             &user_secret_key,
-            &create_full_id(&package_id, &id.0),
+            &create_full_id(&package_id, &id.inner_id()?),
             &public_key,
         ))
         .to_string(),
@@ -204,68 +281,155 @@ fn main() -> FastCryptoResult<()> {
             threshold,
             public_keys,
             object_ids,
-        } => EncryptionOutput(seal_encrypt(
-            package_id,
-            id.0,
-            object_ids,
-            &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
-            threshold,
-            Plain,
-        )?)
+        } => EncryptionOutput(
+            seal_encrypt(
+                package_id,
+                id.inner_id()?,
+                object_ids,
+                &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+                threshold,
+                Plain,
+            )?,
+            format,
+        )
         .to_string(),
         Command::EncryptAes {
             message,
             aad,
+            pad,
+            compress,
             package_id,
             id,
             threshold,
             public_keys,
             object_ids,
-        } => EncryptionOutput(seal_encrypt(
-            package_id,
-            id.0,
-            object_ids,
-            &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
-            threshold,
-            EncryptionInput::Aes256Gcm {
-                data: message.0,
-                aad: aad.map(|a| a.0),
-            },
-        )?)
+        } => EncryptionOutput(
+            seal_encrypt(
+                package_id,
+                id.inner_id()?,
+                object_ids,
+                &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+                threshold,
+                match (pad, compress) {
+                    (None, None) => EncryptionInput::Aes256Gcm {
+                        data: message.0,
+                        aad: aad.map(|a| a.0),
+                    },
+                    (padding, compression) => EncryptionInput::Aes256GcmWithHeader {
+                        data: message.0,
+                        aad: aad.map(|a| a.0),
+                        header: DemHeader {
+                            padding,
+                            compression,
+                        },
+                    },
+                },
+            )?,
+            format,
+        )
         .to_string(),
         Command::EncryptHmac {
             message,
             aad,
+            pad,
+            compress,
             package_id,
             id,
             threshold,
             public_keys,
             object_ids,
-        } => EncryptionOutput(seal_encrypt(
+        } => EncryptionOutput(
+            seal_encrypt(
+                package_id,
+                id.inner_id()?,
+                object_ids,
+                &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+                threshold,
+                match (pad, compress) {
+                    (None, None) => EncryptionInput::Hmac256Ctr {
+                        data: message.0,
+                        aad: aad.map(|a| a.0),
+                    },
+                    (padding, compression) => EncryptionInput::Hmac256CtrWithHeader {
+                        data: message.0,
+                        aad: aad.map(|a| a.0),
+                        header: DemHeader {
+                            padding,
+                            compression,
+                        },
+                    },
+                },
+            )?,
+            format,
+        )
+        .to_string(),
+        Command::KeyWrap {
+            key,
             package_id,
-            id.0,
-            object_ids,
-            &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+            id,
             threshold,
-            EncryptionInput::Hmac256Ctr {
-                data: message.0,
-                aad: aad.map(|a| a.0),
-            },
-        )?)
+            public_keys,
+            object_ids,
+        } => KeyWrapOutput(
+            seal_encrypt(
+                package_id,
+                id.inner_id()?,
+                object_ids,
+                &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+                threshold,
+                EncryptionInput::KeyWrap {
+                    key: SymmetricKey::from(
+                        <[u8; KEY_SIZE]>::try_from(key.0)
+                            .map_err(|_| FastCryptoError::InvalidInput)?,
+                    ),
+                },
+            )?
+            .0,
+            format,
+        )
         .to_string(),
         Command::Decrypt {
             encrypted_object,
             secret_keys,
             object_ids,
         } => DecryptionOutput(seal_decrypt(
-            &encrypted_object, // TODO
-            &IBEUserSecretKeys::BonehFranklinBLS12381(
-                object_ids.into_iter().zip(secret_keys).collect(),
-            ),
+            &parse_encrypted_object(&encrypted_object, format)?, // TODO
+            &user_secret_keys(object_ids, secret_keys)?,
             None,
         )?)
         .to_string(),
-        Command::Parse { encrypted_object } => ParseOutput(encrypted_object).to_string(),
+        Command::Parse {
+            encrypted_object,
+            secret_keys,
+            object_ids,
+        } => {
+            let encrypted_object = parse_encrypted_object(&encrypted_object, format)?;
+            let metadata = if secret_keys.is_empty() {
+                None
+            } else {
+                seal_decrypt_metadata(
+                    &encrypted_object,
+                    &user_secret_keys(object_ids, secret_keys)?,
+                    None,
+                )?
+            };
+            ParseOutput(encrypted_object, metadata).to_string()
+        }
+        Command::OnchainDecryptionArgs {
+            encrypted_object,
+            public_keys,
+            secret_keys,
+            object_ids,
+        } => OnchainDecryptionArgsOutput(prepare_onchain_decryption(
+            &parse_encrypted_object(&encrypted_object, format)?,
+            &user_secret_keys(object_ids, secret_keys)?,
+            &IBEPublicKeys::BonehFranklinBLS12381(public_keys),
+        )?)
+        .to_string(),
+        Command::Convert {
+            encrypted_object,
+            to,
+        } => encrypted_object_to_string(&parse_encrypted_object(&encrypted_object, format)?, to),
         Command::SymmetricDecrypt {
             encrypted_object,
             key,
@@ -274,16 +438,12 @@ fn main() -> FastCryptoResult<()> {
                 .0
                 .try_into()
                 .map_err(|_| FastCryptoError::InvalidInput)?;
-            let EncryptedObject { ciphertext, .. } = encrypted_object;
+            let EncryptedObject { ciphertext, .. } =
+                parse_encrypted_object(&encrypted_object, format)?;
 
             match ciphertext {
-                Ciphertext::Aes256Gcm { blob, aad } => {
-                    Aes256Gcm::decrypt(&blob, &aad.unwrap_or(vec![]), &dem_key)
-                }
-                Ciphertext::Hmac256Ctr { blob, aad, mac } => {
-                    Hmac256Ctr::decrypt(&blob, &mac, &aad.unwrap_or(vec![]), &dem_key)
-                }
-                _ => Err(FastCryptoError::InvalidInput),
+                Ciphertext::Plain => Err(FastCryptoError::InvalidInput),
+                ciphertext => decrypt_with_dem(&ciphertext, &dem_key),
             }
             .map(SymmetricDecryptOutput)?
             .to_string()
@@ -293,6 +453,66 @@ fn main() -> FastCryptoResult<()> {
     Ok(())
 }
 
+/// The inner ID of a key, either given directly or built for one of the access patterns in `move/patterns`.
+#[derive(Args, Debug)]
+struct IdArgs {
+    /// The ID of the key as hex-encoded bytes. If a whitelist or subscription is given, this is the suffix after
+    /// the object id, and if a private data creator is given, this is the nonce.
+    #[arg(long)]
+    id: Option<EncodedBytes>,
+    /// Use the ID of the `tle` pattern for a key that can be fetched after the given time in milliseconds since the Unix epoch.
+    #[arg(long, group = "pattern", conflicts_with = "id")]
+    tle_unlock_at: Option<u64>,
+    /// Use the ID of the `whitelist` pattern for the given whitelist object.
+    #[arg(long, group = "pattern")]
+    whitelist: Option<ObjectID>,
+    /// Use the ID of the `subscription` pattern for the given service object.
+    #[arg(long, group = "pattern")]
+    subscription: Option<ObjectID>,
+    /// Use the ID of the `private_data` pattern for the given creator address.
+    #[arg(long, group = "pattern", requires = "id")]
+    private_data_creator: Option<ObjectID>,
+    /// Use the ID of the `account_based` pattern for the given account address.
+    #[arg(long, group = "pattern", conflicts_with = "id")]
+    account: Option<ObjectID>,
+    /// Use the ID of the `voting` pattern for the given vote object.
+    #[arg(long, group = "pattern", conflicts_with = "id")]
+    vote: Option<ObjectID>,
+}
+
+impl IdArgs {
+    fn inner_id(self) -> FastCryptoResult<Vec<u8>> {
+        let id = self.id.map(|id| id.0);
+        let pattern_id = if let Some(unlock_at) = self.tle_unlock_at {
+            PatternId::Tle { unlock_at }
+        } else if let Some(whitelist) = self.whitelist {
+            PatternId::Whitelist {
+                whitelist,
+                suffix: id.unwrap_or_default(),
+            }
+        } else if let Some(service) = self.subscription {
+            PatternId::Subscription {
+                service,
+                suffix: id.unwrap_or_default(),
+            }
+        } else if let Some(creator) = self.private_data_creator {
+            PatternId::PrivateData {
+                creator,
+                nonce: id.unwrap_or_default(),
+            }
+        } else if let Some(account) = self.account {
+            PatternId::AccountBased { account }
+        } else if let Some(vote) = self.vote {
+            PatternId::Voting { vote }
+        } else {
+            return id.ok_or(FastCryptoError::GeneralError(
+                "Either --id or a pattern must be given".to_string(),
+            ));
+        };
+        Ok(pattern_id.to_inner_id())
+    }
+}
+
 /// Type used for binary inputs to the CLI.
 #[derive(Debug, Clone)]
 struct EncodedBytes(Vec<u8>);
@@ -312,6 +532,47 @@ fn serializable_to_string<T: Serialize>(t: &T) -> String {
     DefaultEncoding::encode(bcs::to_bytes(t).expect("serialization failed"))
 }
 
+/// Parse an encrypted object in the given encoding.
+/// Pair each key server with its secret key. Fails if the number of keys and key servers differ.
+fn user_secret_keys(
+    object_ids: Vec<ObjectID>,
+    secret_keys: Vec<ibe::UserSecretKey>,
+) -> FastCryptoResult<IBEUserSecretKeys> {
+    if object_ids.len() != secret_keys.len() {
+        return Err(FastCryptoError::GeneralError(format!(
+            "Got {} secret keys for {} key servers",
+            secret_keys.len(),
+            object_ids.len()
+        )));
+    }
+    Ok(IBEUserSecretKeys::BonehFranklinBLS12381(
+        object_ids.into_iter().zip(secret_keys).collect(),
+    ))
+}
+
+fn parse_encrypted_object(s: &str, format: Format) -> FastCryptoResult<EncryptedObject> {
+    match format {
+        Format::Hex => {
+            bcs::from_bytes(&Hex::decode(s.trim())?).map_err(|_| FastCryptoError::InvalidInput)
+        }
+        Format::Base64 => {
+            bcs::from_bytes(&Base64::decode(s.trim())?).map_err(|_| FastCryptoError::InvalidInput)
+        }
+        Format::Armor => EncryptedObject::from_armor(s),
+        Format::Json => EncryptedObject::from_json(s),
+    }
+}
+
+/// Encode an encrypted object in the given encoding.
+fn encrypted_object_to_string(object: &EncryptedObject, format: Format) -> String {
+    match format {
+        Format::Hex => Hex::encode(bcs::to_bytes(object).expect("serialization failed")),
+        Format::Base64 => Base64::encode(bcs::to_bytes(object).expect("serialization failed")),
+        Format::Armor => object.to_armor().expect("serialization failed"),
+        Format::Json => object.to_json().expect("serialization failed"),
+    }
+}
+
 pub fn parse_serializable<T: for<'a> Deserialize<'a>, E: Encoding>(s: &str) -> Result<T, String> {
     let bytes = E::decode(s).map_err(|e| format!("{}", e))?;
     bcs::from_bytes(&bytes).map_err(|e| format!("{}", e))
@@ -350,12 +611,26 @@ impl Display for VerifyOutput {
 
 impl Display for EncryptionOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Encrypted object (bcs): {}\nSymmetric key: {}",
-            DefaultEncoding::encode(bcs::to_bytes(&self.0 .0).unwrap()),
-            Hex::encode(self.0 .1)
-        )
+        let encrypted_object = encrypted_object_to_string(&self.0 .0, self.1);
+        match self.1 {
+            Format::Hex | Format::Base64 => {
+                write!(f, "Encrypted object (bcs): {}", encrypted_object)?
+            }
+            Format::Armor | Format::Json => write!(f, "Encrypted object:\n{}", encrypted_object)?,
+        }
+        write!(f, "\nSymmetric key: {}", Hex::encode(self.0 .1.as_bytes()))
+    }
+}
+
+impl Display for KeyWrapOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let encrypted_object = encrypted_object_to_string(&self.0, self.1);
+        match self.1 {
+            Format::Hex | Format::Base64 => {
+                write!(f, "Encrypted object (bcs): {}", encrypted_object)
+            }
+            Format::Armor | Format::Json => write!(f, "Encrypted object:\n{}", encrypted_object),
+        }
     }
 }
 
@@ -371,8 +646,17 @@ impl Display for ParseOutput {
         writeln!(f, "Package ID: {}", self.0.package_id)?;
         writeln!(f, "ID: {}", DefaultEncoding::encode(&self.0.id))?;
         writeln!(f, "Services: share index:")?;
-        for (id, index) in &self.0.services {
-            writeln!(f, "  {}: {}", id, index)?;
+        for (i, (id, index)) in self.0.services.iter().enumerate() {
+            match &self.0.key_versions {
+                Some(_) => writeln!(
+                    f,
+                    "  {}: {} (key version {})",
+                    id,
+                    index,
+                    self.0.key_version(i)
+                )?,
+                None => writeln!(f, "  {}: {}", id, index)?,
+            }
         }
         writeln!(f, "Threshold: {}", self.0.threshold)?;
         writeln!(f, "Ciphertext:")?;
@@ -401,6 +685,38 @@ impl Display for ParseOutput {
             Ciphertext::Plain => {
                 writeln!(f, "  Type: Plain")?;
             }
+            Ciphertext::KeyWrap { wrapped_key } => {
+                writeln!(f, "  Type: AES Key Wrap")?;
+                writeln!(f, "  Wrapped key: {}", DefaultEncoding::encode(wrapped_key))?;
+            }
+            Ciphertext::Aes256GcmWithHeader { header, blob, aad } => {
+                writeln!(f, "  Type: AES-256-GCM")?;
+                write_header(f, header)?;
+                writeln!(f, "  Blob: {}", DefaultEncoding::encode(blob))?;
+                writeln!(
+                    f,
+                    "  AAD: {}",
+                    aad.as_ref()
+                        .map_or("None".to_string(), DefaultEncoding::encode)
+                )?;
+            }
+            Ciphertext::Hmac256CtrWithHeader {
+                header,
+                blob,
+                mac,
+                aad,
+            } => {
+                writeln!(f, "  Type: HMAC-256-CTR")?;
+                write_header(f, header)?;
+                writeln!(f, "  Blob: {}", DefaultEncoding::encode(blob))?;
+                writeln!(
+                    f,
+                    "  AAD: {}",
+                    aad.as_ref()
+                        .map_or("None".to_string(), DefaultEncoding::encode)
+                )?;
+                writeln!(f, "  MAC: {}", DefaultEncoding::encode(mac))?;
+            }
         }
         writeln!(f, "Encrypted shares:")?;
         match &self.0.encrypted_shares {
@@ -421,10 +737,91 @@ impl Display for ParseOutput {
                 )?;
             }
         };
+        match (&self.0.metadata, &self.1) {
+            (None, _) => {}
+            (Some(encrypted), None) => {
+                write!(
+                    f,
+                    "\nEncrypted metadata: {}",
+                    DefaultEncoding::encode(encrypted)
+                )?;
+            }
+            (Some(_), Some(metadata)) => {
+                write!(f, "\nMetadata:")?;
+                let optional = |value: &Option<String>| value.clone().unwrap_or("None".to_string());
+                write!(f, "\n  Filename: {}", optional(&metadata.filename))?;
+                write!(f, "\n  MIME type: {}", optional(&metadata.mime_type))?;
+                write!(
+                    f,
+                    "\n  Created at: {}",
+                    optional(&metadata.created_at.map(|t| t.to_string()))
+                )?;
+                write!(f, "\n  Tags:")?;
+                for (key, value) in &metadata.tags {
+                    write!(f, "\n    {}: {}", key, value)?;
+                }
+            }
+        }
         Ok(())
     }
 }
 
+impl Display for OnchainDecryptionArgsOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let arguments = &self.0;
+        writeln!(
+            f,
+            "Encrypted object (parse_encrypted_object): {}",
+            serializable_to_string(&arguments.encrypted_object)
+        )?;
+        writeln!(f, "Derived keys (g1_from_bytes):")?;
+        for (derived_key, key_server) in arguments
+            .derived_keys
+            .iter()
+            .zip(&arguments.derived_key_servers)
+        {
+            writeln!(
+                f,
+                "  {}: {}",
+                key_server,
+                serializable_to_string(derived_key)
+            )?;
+        }
+        writeln!(
+            f,
+            "Package ID (verify_derived_keys): {}",
+            serializable_to_string(&arguments.package_id)
+        )?;
+        writeln!(
+            f,
+            "ID (verify_derived_keys): {}",
+            serializable_to_string(&arguments.id)
+        )?;
+        writeln!(f, "Key servers (decrypt):")?;
+        for key_server in &arguments.key_servers {
+            writeln!(f, "  {}", key_server)?;
+        }
+        write!(f, "Bundle (bcs): {}", serializable_to_string(arguments))
+    }
+}
+
+fn write_header(f: &mut Formatter<'_>, header: &DemHeader) -> std::fmt::Result {
+    writeln!(
+        f,
+        "  Padding: {}",
+        header
+            .padding
+            .map_or("None".to_string(), |padding| padding.to_string())
+    )?;
+    writeln!(
+        f,
+        "  Compression: {}",
+        header
+            .compression
+            .map_or("None".to_string(), |compression| compression.to_string())
+    )
+}
+
 impl Display for SymmetricDecryptOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Decrypted message: {}", DefaultEncoding::encode(&self.0))
@@ -0,0 +1,134 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+#ifndef SEAL_H
+#define SEAL_H
+
+/* This file is generated by cbindgen. Do not edit it manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Use AES-256-GCM as DEM.
+#define SEAL_DEM_AES_256_GCM 0
+
+// Use counter mode with HMAC-SHA3-256 as DEM.
+#define SEAL_DEM_HMAC_256_CTR 1
+
+// Do not encrypt a message but only derive a key.
+#define SEAL_DEM_PLAIN 2
+
+// Status codes returned by all functions.
+typedef enum SealStatus {
+  SEAL_STATUS_OK = 0,
+  // A required pointer was null.
+  SEAL_STATUS_NULL_POINTER = 1,
+  // An input could not be parsed or is invalid, e.g. the threshold is larger than the number of key servers.
+  SEAL_STATUS_INVALID_INPUT = 2,
+  // Decryption failed, e.g. because of an invalid MAC or inconsistent shares.
+  SEAL_STATUS_DECRYPTION_FAILED = 3,
+  // A user secret key is not valid for the given public key and id.
+  SEAL_STATUS_VERIFICATION_FAILED = 4,
+  // Encryption failed, e.g. because a public key is invalid.
+  SEAL_STATUS_ENCRYPTION_FAILED = 5,
+  // An unexpected internal error, e.g. a panic, occurred.
+  SEAL_STATUS_INTERNAL_ERROR = 6,
+} SealStatus;
+
+// A byte buffer owned by this library. Must be freed using [seal_buffer_free].
+typedef struct SealBuffer {
+  uint8_t *data;
+  uintptr_t len;
+} SealBuffer;
+
+// A borrowed byte slice given as input.
+typedef struct SealSlice {
+  const uint8_t *data;
+  uintptr_t len;
+} SealSlice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Free a buffer returned by this library. The contents are zeroized before the memory is released.
+// Freeing an empty buffer (null data) is a no-op.
+//
+// # Safety
+// The buffer must have been returned by this library and must not be freed more than once.
+void seal_buffer_free(struct SealBuffer buffer);
+
+// Generate a new master key and public key, both as BCS serializations.
+//
+// # Safety
+// The output pointers must be valid.
+enum SealStatus seal_generate_key_pair(struct SealBuffer *master_key_out,
+                                       struct SealBuffer *public_key_out);
+
+// Extract a user secret key from a master key for the given 32 byte package id and inner id.
+//
+// # Safety
+// All pointers must be valid for the given lengths.
+enum SealStatus seal_extract(struct SealSlice master_key,
+                             struct SealSlice package_id,
+                             struct SealSlice id,
+                             struct SealBuffer *user_secret_key_out);
+
+// Verify a user secret key for the given 32 byte package id and inner id against a public key.
+// Returns [SealStatus::Ok] if the key is valid and [SealStatus::VerificationFailed] if not.
+//
+// # Safety
+// All pointers must be valid for the given lengths.
+enum SealStatus seal_verify_user_secret_key(struct SealSlice user_secret_key,
+                                            struct SealSlice package_id,
+                                            struct SealSlice id,
+                                            struct SealSlice public_key);
+
+// Encrypt a message. The key servers are 32 byte object ids and the public keys are BCS
+// serializations, one per key server. `dem` is one of the `SEAL_DEM_*` constants. `aad` may have
+// null data to indicate no additional authenticated data. `key_out` may be null if the derived
+// symmetric key is not needed.
+//
+// # Safety
+// All pointers must be valid for the given lengths.
+enum SealStatus seal_encrypt(struct SealSlice package_id,
+                             struct SealSlice id,
+                             const struct SealSlice *key_servers,
+                             const struct SealSlice *public_keys,
+                             uintptr_t number_of_key_servers,
+                             uint8_t threshold,
+                             uint8_t dem,
+                             struct SealSlice data,
+                             struct SealSlice aad,
+                             struct SealBuffer *encrypted_object_out,
+                             struct SealBuffer *key_out);
+
+// Decrypt an encrypted object given user secret keys for some of its key servers. The key servers
+// are 32 byte object ids and the user secret keys are BCS serializations, one per key server. If
+// `public_keys` is not null, it must contain the public keys for all `number_of_public_keys` key
+// servers in the object and the shares are checked for consistency.
+//
+// # Safety
+// All pointers must be valid for the given lengths.
+enum SealStatus seal_decrypt(struct SealSlice encrypted_object,
+                             const struct SealSlice *key_servers,
+                             const struct SealSlice *user_secret_keys,
+                             uintptr_t number_of_keys,
+                             const struct SealSlice *public_keys,
+                             uintptr_t number_of_public_keys,
+                             struct SealBuffer *plaintext_out);
+
+// Parse an encrypted object and return a JSON representation as UTF-8 bytes (not null-terminated).
+//
+// # Safety
+// All pointers must be valid for the given lengths.
+enum SealStatus seal_parse(struct SealSlice encrypted_object, struct SealBuffer *json_out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SEAL_H */
@@ -0,0 +1,167 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Safe functions shared by the C and Python bindings.
+//! Keys and encrypted objects are BCS serializations and object ids are 32 raw bytes.
+
+use crate::SealStatus;
+use crypto::{
+    create_full_id, ibe, seal_decrypt, seal_encrypt, EncryptedObject, EncryptionInput,
+    IBEPublicKeys, IBEUserSecretKeys, ObjectID, SymmetricKey,
+};
+use fastcrypto::error::FastCryptoError;
+use rand::thread_rng;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+/// Use AES-256-GCM as DEM.
+pub const SEAL_DEM_AES_256_GCM: u8 = 0;
+
+/// Use counter mode with HMAC-SHA3-256 as DEM.
+pub const SEAL_DEM_HMAC_256_CTR: u8 = 1;
+
+/// Do not encrypt a message but only derive a key.
+pub const SEAL_DEM_PLAIN: u8 = 2;
+
+pub type ApiResult<T> = Result<T, SealStatus>;
+
+/// Generate a master key and a public key. The serialized master key is zeroized when dropped.
+pub fn generate_key_pair() -> ApiResult<(Zeroizing<Vec<u8>>, Vec<u8>)> {
+    let (master_key, public_key) = ibe::generate_key_pair(&mut thread_rng());
+    Ok((
+        Zeroizing::new(to_bytes(&master_key)?),
+        to_bytes(&public_key)?,
+    ))
+}
+
+/// Extract a user secret key for the given package id and inner id.
+pub fn extract(master_key: &[u8], package_id: &[u8], id: &[u8]) -> ApiResult<Vec<u8>> {
+    let master_key: ibe::MasterKey = from_bytes(master_key)?;
+    let package_id = ObjectID::from_bytes(package_id).map_err(|_| SealStatus::InvalidInput)?;
+    to_bytes(&ibe::extract(&master_key, &create_full_id(&package_id, id)))
+}
+
+/// Verify a user secret key for the given package id and inner id against a public key.
+pub fn verify_user_secret_key(
+    user_secret_key: &[u8],
+    package_id: &[u8],
+    id: &[u8],
+    public_key: &[u8],
+) -> ApiResult<()> {
+    let user_secret_key: ibe::UserSecretKey = from_bytes(user_secret_key)?;
+    let public_key: ibe::PublicKey = from_bytes(public_key)?;
+    let package_id = ObjectID::from_bytes(package_id).map_err(|_| SealStatus::InvalidInput)?;
+    ibe::verify_user_secret_key(
+        &user_secret_key,
+        &create_full_id(&package_id, id),
+        &public_key,
+    )
+    .map_err(|_| SealStatus::VerificationFailed)
+}
+
+/// Encrypt a message. Returns the serialized encrypted object and the derived symmetric key.
+#[allow(clippy::too_many_arguments)]
+pub fn encrypt<K: AsRef<[u8]>, P: AsRef<[u8]>>(
+    package_id: &[u8],
+    id: &[u8],
+    key_servers: &[K],
+    public_keys: &[P],
+    threshold: u8,
+    dem: u8,
+    data: &[u8],
+    aad: Option<&[u8]>,
+) -> ApiResult<(Vec<u8>, SymmetricKey)> {
+    let package_id = ObjectID::from_bytes(package_id).map_err(|_| SealStatus::InvalidInput)?;
+    let aad = aad.map(<[u8]>::to_vec);
+    let encryption_input = match dem {
+        SEAL_DEM_AES_256_GCM => EncryptionInput::Aes256Gcm {
+            data: data.to_vec(),
+            aad,
+        },
+        SEAL_DEM_HMAC_256_CTR => EncryptionInput::Hmac256Ctr {
+            data: data.to_vec(),
+            aad,
+        },
+        SEAL_DEM_PLAIN => EncryptionInput::Plain,
+        _ => return Err(SealStatus::InvalidInput),
+    };
+    let (encrypted_object, key) = seal_encrypt(
+        package_id,
+        id.to_vec(),
+        object_ids(key_servers)?,
+        &IBEPublicKeys::BonehFranklinBLS12381(all_from_bytes(public_keys)?),
+        threshold,
+        encryption_input,
+    )
+    .map_err(to_encryption_status)?;
+    Ok((to_bytes(&encrypted_object)?, key))
+}
+
+/// Decrypt an encrypted object using user secret keys for the given key servers.
+/// If public keys for all key servers in the object are given, the shares are checked for consistency.
+pub fn decrypt<K: AsRef<[u8]>, U: AsRef<[u8]>, P: AsRef<[u8]>>(
+    encrypted_object: &[u8],
+    key_servers: &[K],
+    user_secret_keys: &[U],
+    public_keys: Option<&[P]>,
+) -> ApiResult<Vec<u8>> {
+    if key_servers.len() != user_secret_keys.len() {
+        return Err(SealStatus::InvalidInput);
+    }
+    let encrypted_object: EncryptedObject = from_bytes(encrypted_object)?;
+    let user_secret_keys = IBEUserSecretKeys::BonehFranklinBLS12381(
+        object_ids(key_servers)?
+            .into_iter()
+            .zip(all_from_bytes::<ibe::UserSecretKey, _>(user_secret_keys)?)
+            .collect(),
+    );
+    let public_keys = public_keys
+        .map(|pks| all_from_bytes(pks).map(IBEPublicKeys::BonehFranklinBLS12381))
+        .transpose()?;
+    seal_decrypt(&encrypted_object, &user_secret_keys, public_keys.as_ref())
+        .map_err(to_decryption_status)
+}
+
+/// Parse an encrypted object and return it as JSON.
+pub fn parse(encrypted_object: &[u8]) -> ApiResult<String> {
+    let encrypted_object: EncryptedObject = from_bytes(encrypted_object)?;
+    serde_json::to_string(&encrypted_object).map_err(|_| SealStatus::InvalidInput)
+}
+
+fn to_encryption_status(e: FastCryptoError) -> SealStatus {
+    match e {
+        FastCryptoError::InvalidInput => SealStatus::InvalidInput,
+        _ => SealStatus::EncryptionFailed,
+    }
+}
+
+fn to_decryption_status(e: FastCryptoError) -> SealStatus {
+    match e {
+        FastCryptoError::InvalidInput => SealStatus::InvalidInput,
+        _ => SealStatus::DecryptionFailed,
+    }
+}
+
+/// Serialize into a buffer of the exact size, so no reallocation leaves copies of secret keys behind.
+fn to_bytes<T: Serialize>(value: &T) -> ApiResult<Vec<u8>> {
+    let size = bcs::serialized_size(value).map_err(|_| SealStatus::InternalError)?;
+    let mut bytes = Vec::with_capacity(size);
+    bcs::serialize_into(&mut bytes, value).map_err(|_| SealStatus::InternalError)?;
+    Ok(bytes)
+}
+
+fn from_bytes<T: DeserializeOwned>(bytes: &[u8]) -> ApiResult<T> {
+    bcs::from_bytes(bytes).map_err(|_| SealStatus::InvalidInput)
+}
+
+fn all_from_bytes<T: DeserializeOwned, B: AsRef<[u8]>>(values: &[B]) -> ApiResult<Vec<T>> {
+    values.iter().map(|v| from_bytes(v.as_ref())).collect()
+}
+
+fn object_ids<B: AsRef<[u8]>>(values: &[B]) -> ApiResult<Vec<ObjectID>> {
+    values
+        .iter()
+        .map(|v| ObjectID::from_bytes(v).map_err(|_| SealStatus::InvalidInput))
+        .collect()
+}
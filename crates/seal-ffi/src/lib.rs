@@ -0,0 +1,470 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! C ABI for the Seal crypto crate. The header is in `include/seal.h` and can be regenerated using
+//! `cbindgen --config cbindgen.toml --output include/seal.h`.
+//!
+//! Ownership: All input pointers are borrowed for the duration of the call. Output buffers are
+//! allocated by this library and must be released by the caller using [seal_buffer_free] which
+//! zeroizes the contents before freeing the memory. On error, output buffers are left untouched.
+//!
+//! Panics never unwind into the caller. All functions catch them and return
+//! [SealStatus::InternalError] instead. Note that the release profile of the workspace sets
+//! `panic = 'abort'`, in which case a panic aborts the process.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+use std::slice;
+use zeroize::Zeroize;
+
+pub mod api;
+
+pub use api::{SEAL_DEM_AES_256_GCM, SEAL_DEM_HMAC_256_CTR, SEAL_DEM_PLAIN};
+
+/// Status codes returned by all functions.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SealStatus {
+    Ok = 0,
+    /// A required pointer was null.
+    NullPointer = 1,
+    /// An input could not be parsed or is invalid, e.g. the threshold is larger than the number of key servers.
+    InvalidInput = 2,
+    /// Decryption failed, e.g. because of an invalid MAC or inconsistent shares.
+    DecryptionFailed = 3,
+    /// A user secret key is not valid for the given public key and id.
+    VerificationFailed = 4,
+    /// Encryption failed, e.g. because a public key is invalid.
+    EncryptionFailed = 5,
+    /// An unexpected internal error, e.g. a panic, occurred.
+    InternalError = 6,
+}
+
+/// A byte buffer owned by this library. Must be freed using [seal_buffer_free].
+#[repr(C)]
+#[derive(Debug)]
+pub struct SealBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// A borrowed byte slice given as input.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SealSlice {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+impl SealBuffer {
+    /// Copy the bytes into a buffer of the exact size and zeroize the original, since shrinking the
+    /// vector in place may leave a copy of the contents in the released memory.
+    fn from_vec(mut bytes: Vec<u8>) -> Self {
+        let buffer: Box<[u8]> = bytes.as_slice().into();
+        bytes.zeroize();
+        let len = buffer.len();
+        let data = Box::into_raw(buffer) as *mut u8;
+        SealBuffer { data, len }
+    }
+}
+
+/// Run the body of an exported function, returning [SealStatus::InternalError] if it panics.
+fn guard(f: impl FnOnce() -> SealStatus) -> SealStatus {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(SealStatus::InternalError)
+}
+
+/// Free a buffer returned by this library. The contents are zeroized before the memory is released.
+/// Freeing an empty buffer (null data) is a no-op.
+///
+/// # Safety
+/// The buffer must have been returned by this library and must not be freed more than once.
+#[no_mangle]
+pub unsafe extern "C" fn seal_buffer_free(buffer: SealBuffer) {
+    guard(|| {
+        if !buffer.data.is_null() {
+            let mut bytes = Box::from_raw(slice::from_raw_parts_mut(buffer.data, buffer.len));
+            bytes.zeroize();
+        }
+        SealStatus::Ok
+    });
+}
+
+/// Generate a new master key and public key, both as BCS serializations.
+///
+/// # Safety
+/// The output pointers must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn seal_generate_key_pair(
+    master_key_out: *mut SealBuffer,
+    public_key_out: *mut SealBuffer,
+) -> SealStatus {
+    guard(|| {
+        if master_key_out.is_null() || public_key_out.is_null() {
+            return SealStatus::NullPointer;
+        }
+        match api::generate_key_pair() {
+            Ok((mut master_key, public_key)) => {
+                *master_key_out = SealBuffer::from_vec(std::mem::take(&mut master_key));
+                *public_key_out = SealBuffer::from_vec(public_key);
+                SealStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+/// Extract a user secret key from a master key for the given 32 byte package id and inner id.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn seal_extract(
+    master_key: SealSlice,
+    package_id: SealSlice,
+    id: SealSlice,
+    user_secret_key_out: *mut SealBuffer,
+) -> SealStatus {
+    guard(|| {
+        if user_secret_key_out.is_null() {
+            return SealStatus::NullPointer;
+        }
+        let result =
+            (|| api::extract(as_slice(master_key)?, as_slice(package_id)?, as_slice(id)?))();
+        write_output(result, user_secret_key_out)
+    })
+}
+
+/// Verify a user secret key for the given 32 byte package id and inner id against a public key.
+/// Returns [SealStatus::Ok] if the key is valid and [SealStatus::VerificationFailed] if not.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn seal_verify_user_secret_key(
+    user_secret_key: SealSlice,
+    package_id: SealSlice,
+    id: SealSlice,
+    public_key: SealSlice,
+) -> SealStatus {
+    guard(|| {
+        let result = (|| {
+            api::verify_user_secret_key(
+                as_slice(user_secret_key)?,
+                as_slice(package_id)?,
+                as_slice(id)?,
+                as_slice(public_key)?,
+            )
+        })();
+        match result {
+            Ok(()) => SealStatus::Ok,
+            Err(status) => status,
+        }
+    })
+}
+
+/// Encrypt a message. The key servers are 32 byte object ids and the public keys are BCS
+/// serializations, one per key server. `dem` is one of the `SEAL_DEM_*` constants. `aad` may have
+/// null data to indicate no additional authenticated data. `key_out` may be null if the derived
+/// symmetric key is not needed.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub unsafe extern "C" fn seal_encrypt(
+    package_id: SealSlice,
+    id: SealSlice,
+    key_servers: *const SealSlice,
+    public_keys: *const SealSlice,
+    number_of_key_servers: usize,
+    threshold: u8,
+    dem: u8,
+    data: SealSlice,
+    aad: SealSlice,
+    encrypted_object_out: *mut SealBuffer,
+    key_out: *mut SealBuffer,
+) -> SealStatus {
+    guard(|| {
+        if encrypted_object_out.is_null() {
+            return SealStatus::NullPointer;
+        }
+        let result = (|| {
+            let aad = match aad.data.is_null() {
+                true => None,
+                false => Some(as_slice(aad)?),
+            };
+            api::encrypt(
+                as_slice(package_id)?,
+                as_slice(id)?,
+                &as_slices(key_servers, number_of_key_servers)?,
+                &as_slices(public_keys, number_of_key_servers)?,
+                threshold,
+                dem,
+                as_slice(data)?,
+                aad,
+            )
+        })();
+        match result {
+            Ok((encrypted_object, key)) => {
+                *encrypted_object_out = SealBuffer::from_vec(encrypted_object);
+                if !key_out.is_null() {
+                    *key_out = SealBuffer::from_vec(key.to_vec());
+                }
+                SealStatus::Ok
+            }
+            Err(status) => status,
+        }
+    })
+}
+
+/// Decrypt an encrypted object given user secret keys for some of its key servers. The key servers
+/// are 32 byte object ids and the user secret keys are BCS serializations, one per key server. If
+/// `public_keys` is not null, it must contain the public keys for all `number_of_public_keys` key
+/// servers in the object and the shares are checked for consistency.
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn seal_decrypt(
+    encrypted_object: SealSlice,
+    key_servers: *const SealSlice,
+    user_secret_keys: *const SealSlice,
+    number_of_keys: usize,
+    public_keys: *const SealSlice,
+    number_of_public_keys: usize,
+    plaintext_out: *mut SealBuffer,
+) -> SealStatus {
+    guard(|| {
+        if plaintext_out.is_null() {
+            return SealStatus::NullPointer;
+        }
+        let result = (|| {
+            let public_keys = match public_keys.is_null() {
+                true => None,
+                false => Some(as_slices(public_keys, number_of_public_keys)?),
+            };
+            api::decrypt(
+                as_slice(encrypted_object)?,
+                &as_slices(key_servers, number_of_keys)?,
+                &as_slices(user_secret_keys, number_of_keys)?,
+                public_keys.as_deref(),
+            )
+        })();
+        write_output(result, plaintext_out)
+    })
+}
+
+/// Parse an encrypted object and return a JSON representation as UTF-8 bytes (not null-terminated).
+///
+/// # Safety
+/// All pointers must be valid for the given lengths.
+#[no_mangle]
+pub unsafe extern "C" fn seal_parse(
+    encrypted_object: SealSlice,
+    json_out: *mut SealBuffer,
+) -> SealStatus {
+    guard(|| {
+        if json_out.is_null() {
+            return SealStatus::NullPointer;
+        }
+        let result = (|| api::parse(as_slice(encrypted_object)?).map(String::into_bytes))();
+        write_output(result, json_out)
+    })
+}
+
+unsafe fn write_output(result: api::ApiResult<Vec<u8>>, out: *mut SealBuffer) -> SealStatus {
+    match result {
+        Ok(bytes) => {
+            *out = SealBuffer::from_vec(bytes);
+            SealStatus::Ok
+        }
+        Err(status) => status,
+    }
+}
+
+unsafe fn as_slice<'a>(s: SealSlice) -> api::ApiResult<&'a [u8]> {
+    if s.len == 0 {
+        return Ok(&[]);
+    }
+    if s.data.is_null() {
+        return Err(SealStatus::NullPointer);
+    }
+    Ok(slice::from_raw_parts(s.data, s.len))
+}
+
+unsafe fn as_slices<'a>(slices: *const SealSlice, len: usize) -> api::ApiResult<Vec<&'a [u8]>> {
+    if len == 0 {
+        return Ok(vec![]);
+    }
+    if slices.is_null() {
+        return Err(SealStatus::NullPointer);
+    }
+    slice::from_raw_parts(slices, len)
+        .iter()
+        .map(|s| as_slice(*s))
+        .collect()
+}
+
+impl Default for SealBuffer {
+    fn default() -> Self {
+        SealBuffer {
+            data: null_mut(),
+            len: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::ObjectID;
+
+    fn input(bytes: &[u8]) -> SealSlice {
+        SealSlice {
+            data: bytes.as_ptr(),
+            len: bytes.len(),
+        }
+    }
+
+    unsafe fn take(buffer: SealBuffer) -> Vec<u8> {
+        let bytes = slice::from_raw_parts(buffer.data, buffer.len).to_vec();
+        seal_buffer_free(buffer);
+        bytes
+    }
+
+    #[test]
+    fn test_c_abi_round_trip() {
+        unsafe {
+            let package_id = ObjectID::random();
+            let id = [1u8, 2, 3, 4];
+
+            let mut master_keys = vec![];
+            let mut public_keys = vec![];
+            for _ in 0..3 {
+                let (mut mk, mut pk) = (SealBuffer::default(), SealBuffer::default());
+                assert_eq!(seal_generate_key_pair(&mut mk, &mut pk), SealStatus::Ok);
+                master_keys.push(take(mk));
+                public_keys.push(take(pk));
+            }
+            let services = (0..3)
+                .map(|_| ObjectID::random().into_bytes())
+                .collect::<Vec<_>>();
+
+            let service_slices = services.iter().map(|s| input(s)).collect::<Vec<_>>();
+            let pk_slices = public_keys.iter().map(|pk| input(pk)).collect::<Vec<_>>();
+
+            let message = b"Hello, World!";
+            let mut encrypted_object = SealBuffer::default();
+            assert_eq!(
+                seal_encrypt(
+                    input(package_id.as_ref()),
+                    input(&id),
+                    service_slices.as_ptr(),
+                    pk_slices.as_ptr(),
+                    3,
+                    2,
+                    SEAL_DEM_HMAC_256_CTR,
+                    input(message),
+                    input(b"aad"),
+                    &mut encrypted_object,
+                    null_mut(),
+                ),
+                SealStatus::Ok
+            );
+            let encrypted_object = take(encrypted_object);
+
+            // Extract and verify keys for the last two key servers
+            let usks = (1..3)
+                .map(|i| {
+                    let mut usk = SealBuffer::default();
+                    assert_eq!(
+                        seal_extract(
+                            input(&master_keys[i]),
+                            input(package_id.as_ref()),
+                            input(&id),
+                            &mut usk
+                        ),
+                        SealStatus::Ok
+                    );
+                    let usk = take(usk);
+                    assert_eq!(
+                        seal_verify_user_secret_key(
+                            input(&usk),
+                            input(package_id.as_ref()),
+                            input(&id),
+                            input(&public_keys[i]),
+                        ),
+                        SealStatus::Ok
+                    );
+                    assert_eq!(
+                        seal_verify_user_secret_key(
+                            input(&usk),
+                            input(package_id.as_ref()),
+                            input(&[0]),
+                            input(&public_keys[i]),
+                        ),
+                        SealStatus::VerificationFailed
+                    );
+                    usk
+                })
+                .collect::<Vec<_>>();
+            let usk_slices = usks.iter().map(|usk| input(usk)).collect::<Vec<_>>();
+
+            let mut plaintext = SealBuffer::default();
+            assert_eq!(
+                seal_decrypt(
+                    input(&encrypted_object),
+                    service_slices[1..].as_ptr(),
+                    usk_slices.as_ptr(),
+                    2,
+                    pk_slices.as_ptr(),
+                    3,
+                    &mut plaintext,
+                ),
+                SealStatus::Ok
+            );
+            assert_eq!(take(plaintext), message);
+
+            let mut json = SealBuffer::default();
+            assert_eq!(
+                seal_parse(input(&encrypted_object), &mut json),
+                SealStatus::Ok
+            );
+            assert!(String::from_utf8(take(json))
+                .unwrap()
+                .contains(&package_id.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_guard_catches_panics() {
+        assert_eq!(guard(|| SealStatus::Ok), SealStatus::Ok);
+        assert_eq!(guard(|| panic!("panic")), SealStatus::InternalError);
+    }
+
+    #[test]
+    fn test_c_abi_errors() {
+        unsafe {
+            let mut out = SealBuffer::default();
+            assert_eq!(
+                seal_parse(input(&[1, 2, 3]), &mut out),
+                SealStatus::InvalidInput
+            );
+            assert!(out.data.is_null());
+            assert_eq!(
+                seal_parse(input(&[1, 2, 3]), null_mut()),
+                SealStatus::NullPointer
+            );
+            assert_eq!(
+                seal_parse(
+                    SealSlice {
+                        data: std::ptr::null(),
+                        len: 10
+                    },
+                    &mut out
+                ),
+                SealStatus::NullPointer
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Python bindings for Seal, built on the same functions as the C ABI in `seal-ffi`.
+//! Keys and encrypted objects are BCS serializations and object ids are 32 raw bytes.
+//! Build and install the module using `maturin develop`.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use seal_ffi::api;
+use seal_ffi::SealStatus;
+
+create_exception!(
+    seal,
+    SealError,
+    PyException,
+    "Raised when a Seal operation fails."
+);
+
+fn to_py_err(status: SealStatus) -> PyErr {
+    SealError::new_err((status as i32, format!("{:?}", status)))
+}
+
+/// Generate a new master key and public key.
+#[pyfunction]
+fn generate_key_pair(py: Python<'_>) -> PyResult<(Bound<'_, PyBytes>, Bound<'_, PyBytes>)> {
+    let (master_key, public_key) = api::generate_key_pair().map_err(to_py_err)?;
+    Ok((
+        PyBytes::new_bound(py, &master_key),
+        PyBytes::new_bound(py, &public_key),
+    ))
+}
+
+/// Extract a user secret key for the given package id and inner id.
+#[pyfunction]
+fn extract<'py>(
+    py: Python<'py>,
+    master_key: &[u8],
+    package_id: &[u8],
+    id: &[u8],
+) -> PyResult<Bound<'py, PyBytes>> {
+    api::extract(master_key, package_id, id)
+        .map(|usk| PyBytes::new_bound(py, &usk))
+        .map_err(to_py_err)
+}
+
+/// Return whether a user secret key is valid for the given package id, inner id and public key.
+#[pyfunction]
+fn verify_user_secret_key(
+    user_secret_key: &[u8],
+    package_id: &[u8],
+    id: &[u8],
+    public_key: &[u8],
+) -> PyResult<bool> {
+    match api::verify_user_secret_key(user_secret_key, package_id, id, public_key) {
+        Ok(()) => Ok(true),
+        Err(SealStatus::VerificationFailed) => Ok(false),
+        Err(status) => Err(to_py_err(status)),
+    }
+}
+
+/// Encrypt a message. Returns the encrypted object and the derived symmetric key.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction]
+#[pyo3(signature = (package_id, id, key_servers, public_keys, threshold, data, aad=None, dem=api::SEAL_DEM_AES_256_GCM))]
+fn encrypt<'py>(
+    py: Python<'py>,
+    package_id: &[u8],
+    id: &[u8],
+    key_servers: Vec<Vec<u8>>,
+    public_keys: Vec<Vec<u8>>,
+    threshold: u8,
+    data: &[u8],
+    aad: Option<&[u8]>,
+    dem: u8,
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let (encrypted_object, key) = api::encrypt(
+        package_id,
+        id,
+        &key_servers,
+        &public_keys,
+        threshold,
+        dem,
+        data,
+        aad,
+    )
+    .map_err(to_py_err)?;
+    Ok((
+        PyBytes::new_bound(py, &encrypted_object),
+        PyBytes::new_bound(py, key.as_bytes()),
+    ))
+}
+
+/// Decrypt an encrypted object using user secret keys for the given key servers.
+/// If public keys for all key servers in the object are given, the shares are checked for consistency.
+#[pyfunction]
+#[pyo3(signature = (encrypted_object, key_servers, user_secret_keys, public_keys=None))]
+fn decrypt<'py>(
+    py: Python<'py>,
+    encrypted_object: &[u8],
+    key_servers: Vec<Vec<u8>>,
+    user_secret_keys: Vec<Vec<u8>>,
+    public_keys: Option<Vec<Vec<u8>>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    api::decrypt(
+        encrypted_object,
+        &key_servers,
+        &user_secret_keys,
+        public_keys.as_deref(),
+    )
+    .map(|plaintext| PyBytes::new_bound(py, &plaintext))
+    .map_err(to_py_err)
+}
+
+/// Parse an encrypted object and return it as JSON.
+#[pyfunction]
+fn parse(encrypted_object: &[u8]) -> PyResult<String> {
+    api::parse(encrypted_object).map_err(to_py_err)
+}
+
+#[pymodule]
+fn seal(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("SealError", m.py().get_type_bound::<SealError>())?;
+    m.add("DEM_AES_256_GCM", api::SEAL_DEM_AES_256_GCM)?;
+    m.add("DEM_HMAC_256_CTR", api::SEAL_DEM_HMAC_256_CTR)?;
+    m.add("DEM_PLAIN", api::SEAL_DEM_PLAIN)?;
+    m.add_function(wrap_pyfunction!(generate_key_pair, m)?)?;
+    m.add_function(wrap_pyfunction!(extract, m)?)?;
+    m.add_function(wrap_pyfunction!(verify_user_secret_key, m)?)?;
+    m.add_function(wrap_pyfunction!(encrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(decrypt, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,176 @@
+# Copyright (c), Mysten Labs, Inc.
+# SPDX-License-Identifier: Apache-2.0
+
+"""Round-trip tests between the Python module and the C ABI.
+
+Build the Python module with `maturin develop` and the C library with
+`cargo build -p seal-ffi`, then run `pytest crates/seal-python/tests`.
+The path to the C library can be set using the SEAL_FFI_LIB environment variable.
+"""
+
+import ctypes
+import json
+import os
+import pathlib
+import sys
+
+import pytest
+import seal
+
+SEAL_STATUS_OK = 0
+SEAL_STATUS_DECRYPTION_FAILED = 3
+
+
+class SealBuffer(ctypes.Structure):
+    _fields_ = [("data", ctypes.POINTER(ctypes.c_uint8)), ("len", ctypes.c_size_t)]
+
+
+class SealSlice(ctypes.Structure):
+    _fields_ = [("data", ctypes.POINTER(ctypes.c_uint8)), ("len", ctypes.c_size_t)]
+
+
+def _default_lib_path():
+    root = pathlib.Path(__file__).resolve().parents[3]
+    name = {"darwin": "libseal_ffi.dylib", "win32": "seal_ffi.dll"}.get(sys.platform, "libseal_ffi.so")
+    return root / "target" / "debug" / name
+
+
+@pytest.fixture(scope="module")
+def lib():
+    lib = ctypes.CDLL(os.environ.get("SEAL_FFI_LIB", str(_default_lib_path())))
+    lib.seal_buffer_free.argtypes = [SealBuffer]
+    lib.seal_buffer_free.restype = None
+    lib.seal_encrypt.argtypes = [
+        SealSlice,
+        SealSlice,
+        ctypes.POINTER(SealSlice),
+        ctypes.POINTER(SealSlice),
+        ctypes.c_size_t,
+        ctypes.c_uint8,
+        ctypes.c_uint8,
+        SealSlice,
+        SealSlice,
+        ctypes.POINTER(SealBuffer),
+        ctypes.POINTER(SealBuffer),
+    ]
+    lib.seal_decrypt.argtypes = [
+        SealSlice,
+        ctypes.POINTER(SealSlice),
+        ctypes.POINTER(SealSlice),
+        ctypes.c_size_t,
+        ctypes.POINTER(SealSlice),
+        ctypes.c_size_t,
+        ctypes.POINTER(SealBuffer),
+    ]
+    lib.seal_encrypt.restype = ctypes.c_int
+    lib.seal_decrypt.restype = ctypes.c_int
+    return lib
+
+
+def _slice(data):
+    buffer = (ctypes.c_uint8 * len(data)).from_buffer_copy(data)
+    return SealSlice(ctypes.cast(buffer, ctypes.POINTER(ctypes.c_uint8)), len(data))
+
+
+def _slices(values):
+    return (SealSlice * len(values))(*[_slice(v) for v in values])
+
+
+def _take(lib, buffer):
+    data = bytes(buffer.data[: buffer.len])
+    lib.seal_buffer_free(buffer)
+    return data
+
+
+@pytest.fixture(scope="module")
+def setup():
+    package_id = os.urandom(32)
+    inner_id = b"\x01\x02\x03\x04"
+    key_pairs = [seal.generate_key_pair() for _ in range(3)]
+    key_servers = [os.urandom(32) for _ in range(3)]
+    public_keys = [pk for (_, pk) in key_pairs]
+    user_secret_keys = [seal.extract(mk, package_id, inner_id) for (mk, _) in key_pairs]
+    return package_id, inner_id, key_servers, public_keys, user_secret_keys
+
+
+def test_python_round_trip(setup):
+    package_id, inner_id, key_servers, public_keys, usks = setup
+    for (usk, pk) in zip(usks, public_keys):
+        assert seal.verify_user_secret_key(usk, package_id, inner_id, pk)
+    assert not seal.verify_user_secret_key(usks[0], package_id, inner_id, public_keys[1])
+
+    encrypted, _ = seal.encrypt(
+        package_id, inner_id, key_servers, public_keys, 2, b"Hello, World!", aad=b"aad"
+    )
+    assert seal.decrypt(encrypted, key_servers[:2], usks[:2], public_keys) == b"Hello, World!"
+
+    parsed = json.loads(seal.parse(encrypted))
+    assert parsed["threshold"] == 2
+
+    with pytest.raises(seal.SealError):
+        seal.parse(b"\x01\x02\x03")
+
+
+def test_encrypt_in_python_decrypt_in_c(setup, lib):
+    package_id, inner_id, key_servers, public_keys, usks = setup
+    encrypted, _ = seal.encrypt(
+        package_id,
+        inner_id,
+        key_servers,
+        public_keys,
+        2,
+        b"From Python",
+        dem=seal.DEM_HMAC_256_CTR,
+    )
+
+    plaintext = SealBuffer()
+    status = lib.seal_decrypt(
+        _slice(encrypted),
+        _slices(key_servers[1:]),
+        _slices(usks[1:]),
+        2,
+        _slices(public_keys),
+        3,
+        ctypes.byref(plaintext),
+    )
+    assert status == SEAL_STATUS_OK
+    assert _take(lib, plaintext) == b"From Python"
+
+
+def test_encrypt_in_c_decrypt_in_python(setup, lib):
+    package_id, inner_id, key_servers, public_keys, usks = setup
+
+    encrypted = SealBuffer()
+    key = SealBuffer()
+    status = lib.seal_encrypt(
+        _slice(package_id),
+        _slice(inner_id),
+        _slices(key_servers),
+        _slices(public_keys),
+        3,
+        2,
+        seal.DEM_AES_256_GCM,
+        _slice(b"From C"),
+        SealSlice(None, 0),
+        ctypes.byref(encrypted),
+        ctypes.byref(key),
+    )
+    assert status == SEAL_STATUS_OK
+    encrypted = _take(lib, encrypted)
+    assert len(_take(lib, key)) == 32
+
+    assert seal.decrypt(encrypted, [key_servers[0], key_servers[2]], [usks[0], usks[2]]) == b"From C"
+
+    # Decryption fails with a modified object
+    tampered = encrypted[:-2] + bytes([encrypted[-2] ^ 1]) + encrypted[-1:]
+    plaintext = SealBuffer()
+    status = lib.seal_decrypt(
+        _slice(tampered),
+        _slices(key_servers[:2]),
+        _slices(usks[:2]),
+        2,
+        None,
+        0,
+        ctypes.byref(plaintext),
+    )
+    assert status == SEAL_STATUS_DECRYPTION_FAILED
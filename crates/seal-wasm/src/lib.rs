@@ -0,0 +1,168 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! WebAssembly bindings for the Seal crypto crate.
+//! Keys are given as hex-encoded BCS serializations and object ids as hex strings, like in seal-cli.
+//! Encrypted objects are given and returned as BCS serializations.
+
+use crypto::{
+    ibe, seal_decrypt, seal_encrypt, EncryptedObject, EncryptionInput, IBEPublicKeys,
+    IBEUserSecretKeys, ObjectID,
+};
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::error::FastCryptoError::InvalidInput;
+use fastcrypto::error::FastCryptoResult;
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// The DEM used to encrypt the message.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dem {
+    Aes256Gcm,
+    Hmac256Ctr,
+}
+
+/// Encrypt a message using Seal. Returns the BCS serialization of the encrypted object.
+///
+/// @param package_id The package id as a hex string.
+/// @param id The inner id.
+/// @param key_servers The object ids of the key servers as hex strings.
+/// @param public_keys The hex-encoded public keys of the key servers. Must be in the same order as the key servers.
+/// @param threshold The number of key servers needed for decryption.
+/// @param dem The DEM to use.
+/// @param data The message to encrypt.
+/// @param aad Optional additional authenticated data.
+#[allow(clippy::too_many_arguments)]
+#[wasm_bindgen]
+pub fn encrypt(
+    package_id: &str,
+    id: &[u8],
+    key_servers: Vec<String>,
+    public_keys: Vec<String>,
+    threshold: u8,
+    dem: Dem,
+    data: &[u8],
+    aad: Option<Vec<u8>>,
+) -> Result<Vec<u8>, JsError> {
+    encrypt_internal(
+        package_id,
+        id,
+        &key_servers,
+        &public_keys,
+        threshold,
+        dem,
+        data,
+        aad,
+    )
+    .map_err(to_js_error)
+}
+
+/// Decrypt an encrypted object using Seal. Returns the decrypted message.
+///
+/// @param encrypted_object The BCS serialization of the encrypted object.
+/// @param key_servers The object ids of the key servers for which user secret keys are given, as hex strings.
+/// @param user_secret_keys The hex-encoded user secret keys. Must be in the same order as the key servers.
+/// @param public_keys The hex-encoded public keys of all key servers in the encrypted object. If non-empty, the shares are checked for consistency.
+#[wasm_bindgen]
+pub fn decrypt(
+    encrypted_object: &[u8],
+    key_servers: Vec<String>,
+    user_secret_keys: Vec<String>,
+    public_keys: Vec<String>,
+) -> Result<Vec<u8>, JsError> {
+    decrypt_internal(
+        encrypted_object,
+        &key_servers,
+        &user_secret_keys,
+        &public_keys,
+    )
+    .map_err(to_js_error)
+}
+
+/// Parse an encrypted object. Returns a JSON representation of the object.
+#[wasm_bindgen]
+pub fn parse(encrypted_object: &[u8]) -> Result<String, JsError> {
+    let encrypted_object: EncryptedObject =
+        bcs::from_bytes(encrypted_object).map_err(|_| to_js_error(InvalidInput))?;
+    serde_json::to_string(&encrypted_object).map_err(|e| JsError::new(&e.to_string()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn encrypt_internal(
+    package_id: &str,
+    id: &[u8],
+    key_servers: &[String],
+    public_keys: &[String],
+    threshold: u8,
+    dem: Dem,
+    data: &[u8],
+    aad: Option<Vec<u8>>,
+) -> FastCryptoResult<Vec<u8>> {
+    let encryption_input = match dem {
+        Dem::Aes256Gcm => EncryptionInput::Aes256Gcm {
+            data: data.to_vec(),
+            aad,
+        },
+        Dem::Hmac256Ctr => EncryptionInput::Hmac256Ctr {
+            data: data.to_vec(),
+            aad,
+        },
+    };
+    let (encrypted_object, _) = seal_encrypt(
+        ObjectID::from_str(package_id)?,
+        id.to_vec(),
+        parse_object_ids(key_servers)?,
+        &IBEPublicKeys::BonehFranklinBLS12381(parse_all(public_keys)?),
+        threshold,
+        encryption_input,
+    )?;
+    bcs::to_bytes(&encrypted_object).map_err(|_| InvalidInput)
+}
+
+fn decrypt_internal(
+    encrypted_object: &[u8],
+    key_servers: &[String],
+    user_secret_keys: &[String],
+    public_keys: &[String],
+) -> FastCryptoResult<Vec<u8>> {
+    if key_servers.len() != user_secret_keys.len() {
+        return Err(InvalidInput);
+    }
+    let encrypted_object: EncryptedObject =
+        bcs::from_bytes(encrypted_object).map_err(|_| InvalidInput)?;
+    let user_secret_keys = parse_all::<ibe::UserSecretKey>(user_secret_keys)?;
+    let public_keys = match public_keys.is_empty() {
+        true => None,
+        false => Some(IBEPublicKeys::BonehFranklinBLS12381(parse_all(
+            public_keys,
+        )?)),
+    };
+    seal_decrypt(
+        &encrypted_object,
+        &IBEUserSecretKeys::BonehFranklinBLS12381(
+            parse_object_ids(key_servers)?
+                .into_iter()
+                .zip(user_secret_keys)
+                .collect(),
+        ),
+        public_keys.as_ref(),
+    )
+}
+
+fn parse_object_ids(object_ids: &[String]) -> FastCryptoResult<Vec<ObjectID>> {
+    object_ids.iter().map(|s| ObjectID::from_str(s)).collect()
+}
+
+/// Parse hex-encoded BCS serializations.
+fn parse_all<T: DeserializeOwned>(values: &[String]) -> FastCryptoResult<Vec<T>> {
+    values
+        .iter()
+        .map(|s| Hex::decode(s).and_then(|b| bcs::from_bytes(&b).map_err(|_| InvalidInput)))
+        .collect()
+}
+
+fn to_js_error(e: fastcrypto::error::FastCryptoError) -> JsError {
+    JsError::new(&e.to_string())
+}
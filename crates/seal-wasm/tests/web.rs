@@ -0,0 +1,84 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the WebAssembly bindings. Run with `wasm-pack test --headless --chrome crates/seal-wasm`.
+#![cfg(target_arch = "wasm32")]
+
+use crypto::{create_full_id, ibe, ObjectID};
+use fastcrypto::encoding::{Encoding, Hex};
+use seal_wasm::{decrypt, encrypt, parse, Dem};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn to_hex<T: serde::Serialize>(value: &T) -> String {
+    Hex::encode(bcs::to_bytes(value).unwrap())
+}
+
+#[wasm_bindgen_test]
+fn test_encrypt_decrypt_round_trip() {
+    let package_id = ObjectID::random();
+    let id = vec![1, 2, 3, 4];
+    let full_id = create_full_id(&package_id, &id);
+
+    let mut rng = rand::thread_rng();
+    let keypairs = (0..3)
+        .map(|_| ibe::generate_key_pair(&mut rng))
+        .collect::<Vec<_>>();
+    let services = keypairs
+        .iter()
+        .map(|_| ObjectID::random().to_string())
+        .collect::<Vec<_>>();
+    let public_keys = keypairs
+        .iter()
+        .map(|(_, pk)| to_hex(pk))
+        .collect::<Vec<_>>();
+
+    for dem in [Dem::Aes256Gcm, Dem::Hmac256Ctr] {
+        let encrypted = encrypt(
+            &package_id.to_string(),
+            &id,
+            services.clone(),
+            public_keys.clone(),
+            2,
+            dem,
+            b"Hello, World!",
+            Some(b"aad".to_vec()),
+        )
+        .unwrap();
+
+        // Decrypt using the first two key servers only
+        let user_secret_keys = keypairs[..2]
+            .iter()
+            .map(|(mk, _)| to_hex(&ibe::extract(mk, &full_id)))
+            .collect::<Vec<_>>();
+        let decrypted = decrypt(
+            &encrypted,
+            services[..2].to_vec(),
+            user_secret_keys,
+            public_keys.clone(),
+        )
+        .unwrap();
+        assert_eq!(decrypted, b"Hello, World!");
+
+        let json = parse(&encrypted).unwrap();
+        assert!(json.contains(&package_id.to_string()));
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_invalid_inputs() {
+    assert!(parse(&[1, 2, 3]).is_err());
+    assert!(encrypt(
+        "not an id",
+        &[],
+        vec![],
+        vec![],
+        1,
+        Dem::Aes256Gcm,
+        &[],
+        None
+    )
+    .is_err());
+    assert!(decrypt(&[], vec!["0x1".to_string()], vec![], vec![]).is_err());
+}
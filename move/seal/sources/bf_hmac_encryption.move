@@ -456,3 +456,72 @@ fun test_seal_decrypt() {
     destroy_cap(cap2);
     test_scenario::end(scenario);
 }
+
+#[test_only]
+fun public_key_of(servers: &vector<KeyServer>, address: address): PublicKey {
+    let index = servers.find_index!(|s| object::id(s).to_address() == address).extract();
+    get_public_key(&servers[index])
+}
+
+#[test]
+fun test_decrypt_with_onchain_decryption_arguments() {
+    use sui::bls12381::g1_from_bytes;
+    use sui::test_scenario::{Self, next_tx, ctx};
+    use seal::key_server::{register, destroy_cap};
+    use std::string;
+
+    let addr1 = @0xA;
+    let mut scenario = test_scenario::begin(addr1);
+
+    let pks = vector[
+        x"aeb258b9fb9a2f29f74eb0a1a895860bb1c6ba3f9ea7075366de159e4764413e9ec0597ac9c0dad409723935440a45f40eee4728630ae3ea40a68a819375bba1d78d7810f901d8a469d785d00cfed6bd28f01d41e49c5652d924e9d19fddcf62",
+        x"b1076a26f4f82f39d0e767fcd2118659362afe40bce4e8d553258c86756bb74f888bca79f2d6b71edf6e25af89efa83713a223b48a19d2e551897ac92ac7458336cd489be3be025e348ca93f4c94d22594f96f0e08990e51a7de9da8ff29c98f",
+        x"95fcb465af3791f31d53d80db6c8dcf9f83a419b2570614ecfbb068f47613da17cb9ffc66bb052b9546f17196929538f0bd2d38e1f515d9916e2db13dc43e0ccbd4cb3d7cbb13ffecc0b68b37481ebaaaa17cad18096a9c2c27a797f17d78623",
+    ];
+    let mut caps = vector[];
+    let mut servers: vector<KeyServer> = vector[];
+    pks.do!(|pk| {
+        caps.push_back(
+            register(
+                string::utf8(b"mysten"),
+                string::utf8(b"https://mysten-labs.com"),
+                0,
+                pk,
+                ctx(&mut scenario),
+            ),
+        );
+        next_tx(&mut scenario, addr1);
+        servers.push_back(test_scenario::take_shared(&scenario));
+    });
+
+    // The BCS serialization of the OnchainDecryptionArguments for the object in test_seal_decrypt, created in Rust by
+    // crypto::onchain::prepare_onchain_decryption with the user secret keys of the first two key servers. See the test
+    // test_arguments_match_move_test in crates/crypto/src/onchain.rs.
+    let arguments =
+        x"da0300000000000000000000000000000000000000000000000000000000000000000020381dd9078c322a4663c392761a0211b527c127b29583851217f948d62131f40903034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab9601d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d302dba72804cc9504a82bbaa13ed4a83a0e2c6219d7e45125cf57fd10cbab957a97030200b7f57f44e5302b684737612ebf4561ce4b4c5fea496731914f78d402db1c3c712fae396125a150e8eb1582e05a1f98140afc3214db2060c80471d6d97a173407c41fa4ca58396f6f879826e4f78b7f58282c8e48c664c9f8c953ab2e7a727125030fbf02ffa94172ae1a5c5b1be1b8bddb20ea698d49150aa361ed56504daa3c8f6f7bc1e58f024dff40892db134da0b61e58fa82317afa6884ae14f5d739b5e95fc1b56d645b75d60302775aac94d1bf52a103eefbad9cecd61fbbad37c9dbccceeb9007861ee3f34e4a546b7fe6b5b195ef1fee6ba8080d5d228bd721904b0d5010dab6e4eca9b82653721946aac8401200000000000000000000000000000000000000000000000000000000000000001a5fb3bfe499a0fa285e7129a88962e278fc65e821851d4234ada909ac72a77e502308244fcbe49870a4d4aa947b7034a873e168580e18b5834ea34940dc9f492eda03a9b20c3c3c120b1a462f1642575e0cc30a0f04b759ed2ff477f0fe5b672992235205d2af502f659d4bbb484b745e35fd7a9ff11e37e12111023a891c3fa98a2d3000000000000000000000000000000000000000000000000000000000000000020381dd9078c322a4663c392761a0211b527c127b29583851217f948d62131f40902034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab96d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d303034401905bebdf8c04f3cd5f04f442a39372c8dc321c29edfb4f9cb30b23ab96d726ecf6f7036ee3557cd6c7b93a49b231070e8eecada9cfa157e40e3f02e5d3dba72804cc9504a82bbaa13ed4a83a0e2c6219d7e45125cf57fd10cbab957a97";
+    let mut arguments = sui::bcs::new(arguments);
+    let encrypted_object = arguments.peel_vec_u8();
+    let derived_keys = arguments.peel_vec!(|key| g1_from_bytes(&key.peel_vec_u8()));
+    let package_id = arguments.peel_address();
+    let id = arguments.peel_vec_u8();
+    let derived_key_servers = arguments.peel_vec!(|server| server.peel_address());
+    let key_servers = arguments.peel_vec!(|server| server.peel_address());
+    assert!(arguments.into_remainder_bytes().is_empty());
+
+    // Look up the key server objects by the ids given in the arguments.
+    let vdks = verify_derived_keys(
+        &derived_keys,
+        package_id,
+        id,
+        &derived_key_servers.map_ref!(|address| public_key_of(&servers, *address)),
+    );
+    let all_pks = key_servers.map_ref!(|address| public_key_of(&servers, *address));
+
+    let encrypted_object = parse_encrypted_object(encrypted_object);
+    let decrypted = decrypt(&encrypted_object, &vdks, &all_pks);
+    assert!(decrypted.borrow() == b"Hello, world!");
+
+    servers.destroy!(|s| test_scenario::return_shared(s));
+    caps.destroy!(|c| destroy_cap(c));
+    test_scenario::end(scenario);
+}
@@ -5,14 +5,17 @@
 // - Key server should expose an endpoint /service that returns the official object id of its key server (to prevent
 //   impersonation) and a PoP(key=IBE key, m=[key_server_id | IBE public key]).
 // - Key server should expose an endpoint /fetch_key that allows users to request a key from the key server.
+// - Master keys are versioned. Rotating a key records the public key of the previous version, so clients can encrypt
+//   to and request keys for a specific version. Keys registered before versioning have version 0.
 
 module seal::key_server;
 
 use std::string::String;
-use sui::{bls12381::{G2, g2_from_bytes}, group_ops::Element};
+use sui::{bls12381::{G2, g2_from_bytes}, dynamic_field as df, group_ops::Element};
 
 const EInvalidCap: u64 = 0;
 const EInvalidKeyType: u64 = 1;
+const EInvalidKeyVersion: u64 = 2;
 
 const KeyTypeBonehFranklinBLS12381: u8 = 0;
 
@@ -29,6 +32,14 @@ public struct Cap has key {
     key_server_id: ID,
 }
 
+// Dynamic field of a KeyServer holding the version of its current key `pk`. Not set for version 0.
+public struct KeyVersionKey has copy, drop, store {}
+
+// Dynamic field of a KeyServer holding the public key of a previous version.
+public struct PreviousKey has copy, drop, store {
+    version: u32,
+}
+
 public fun register(
     name: String,
     url: String,
@@ -99,6 +110,71 @@ public fun update(s: &mut KeyServer, cap: &Cap, url: String) {
     s.url = url;
 }
 
+// The version of the current key `pk`.
+public fun key_version(s: &KeyServer): u32 {
+    if (df::exists_(&s.id, KeyVersionKey {})) {
+        *df::borrow(&s.id, KeyVersionKey {})
+    } else {
+        0
+    }
+}
+
+// The public key of the given version, which is either the current or a previous version.
+public fun pk_of_version(s: &KeyServer, version: u32): &vector<u8> {
+    if (version == key_version(s)) {
+        return &s.pk
+    };
+    assert!(df::exists_(&s.id, PreviousKey { version }), EInvalidKeyVersion);
+    df::borrow(&s.id, PreviousKey { version })
+}
+
+// Rotate the master key: keep the current key as a previous version and make `pk` the current key with the given
+// version, which must be larger than the current one. The key server must serve the new key with the same version,
+// and the previous key as a retired key during a grace period.
+public fun rotate_key(s: &mut KeyServer, cap: &Cap, version: u32, pk: vector<u8>) {
+    assert!(object::id(s) == cap.key_server_id, EInvalidCap);
+    let current = key_version(s);
+    assert!(version > current, EInvalidKeyVersion);
+    let _ = g2_from_bytes(&pk);
+
+    let previous = s.pk;
+    df::add(&mut s.id, PreviousKey { version: current }, previous);
+    s.pk = pk;
+    if (current == 0) {
+        df::add(&mut s.id, KeyVersionKey {}, version);
+    } else {
+        *df::borrow_mut(&mut s.id, KeyVersionKey {}) = version;
+    };
+}
+
+#[test, expected_failure(abort_code = EInvalidKeyVersion)]
+fun test_rotate_key_to_older_version() {
+    use sui::test_scenario::{Self, next_tx, ctx};
+    use sui::bls12381::{g2_generator};
+    use std::string;
+
+    let addr1 = @0xA;
+    let mut scenario = test_scenario::begin(addr1);
+
+    let pk = *g2_generator().bytes();
+    let cap = register(
+        string::utf8(b"mysten"),
+        string::utf8(b"https::/mysten-labs.com"),
+        0,
+        pk,
+        ctx(&mut scenario),
+    );
+    next_tx(&mut scenario, addr1);
+
+    let mut s: KeyServer = test_scenario::take_shared(&scenario);
+    s.rotate_key(&cap, 1, pk);
+    s.rotate_key(&cap, 1, pk);
+
+    test_scenario::return_shared(s);
+    destroy_cap(cap);
+    test_scenario::end(scenario);
+}
+
 #[test_only]
 public fun destroy_cap(c: Cap) {
     let Cap { id, .. } = c;
@@ -131,6 +207,43 @@ fun test_flow() {
     assert!(pk(&s) == pk.bytes(), 0);
     s.update(&cap, string::utf8(b"https::/mysten-labs2.com"));
     assert!(url(&s) == string::utf8(b"https::/mysten-labs2.com"), 0);
+    assert!(key_version(&s) == 0, 0);
+    assert!(pk_of_version(&s, 0) == pk.bytes(), 0);
+
+    test_scenario::return_shared(s);
+    destroy_cap(cap);
+    test_scenario::end(scenario);
+}
+
+#[test]
+fun test_rotate_key() {
+    use sui::test_scenario::{Self, next_tx, ctx};
+    use sui::bls12381::{g2_generator, g2_mul, scalar_from_u64};
+    use std::string;
+
+    let addr1 = @0xA;
+    let mut scenario = test_scenario::begin(addr1);
+
+    let pk0 = *g2_generator().bytes();
+    let pk1 = *g2_mul(&scalar_from_u64(2), &g2_generator()).bytes();
+    let pk3 = *g2_mul(&scalar_from_u64(3), &g2_generator()).bytes();
+    let cap = register(
+        string::utf8(b"mysten"),
+        string::utf8(b"https::/mysten-labs.com"),
+        0,
+        pk0,
+        ctx(&mut scenario),
+    );
+    next_tx(&mut scenario, addr1);
+
+    let mut s: KeyServer = test_scenario::take_shared(&scenario);
+    s.rotate_key(&cap, 1, pk1);
+    s.rotate_key(&cap, 3, pk3);
+    assert!(key_version(&s) == 3, 0);
+    assert!(pk(&s) == &pk3, 0);
+    assert!(pk_of_version(&s, 0) == &pk0, 0);
+    assert!(pk_of_version(&s, 1) == &pk1, 0);
+    assert!(pk_of_version(&s, 3) == &pk3, 0);
 
     test_scenario::return_shared(s);
     destroy_cap(cap);